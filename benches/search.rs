@@ -0,0 +1,92 @@
+// Compares the search speed with and without prefetching TT buckets. A
+// fixed depth search visits the same nodes either way, so the difference
+// in time is the difference in speed. The table is large, so most of its
+// buckets are not in the CPU cache.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use crossbeam_channel::unbounded;
+use rustic_sharp::{
+    board::Board,
+    engine::defs::{Information, SearchData, TT},
+    movegen::MoveGenerator,
+    search::{
+        defs::{SearchControl, SearchInfo, SearchMode, SearchParams, SearchRefs, ThreadLocalData},
+        Search,
+    },
+};
+use std::{
+    sync::{Arc, RwLock},
+    time::Instant,
+};
+
+const TT_MEGABYTES: usize = 256;
+const SEARCH_DEPTH: i8 = 8;
+
+const POSITIONS: &[(&str, &str)] = &[
+    ("r2q1rk1/ppp2ppp/2np1n2/2b1p1B1/2B1P3/3P1N2/PPP2PPP/RN1Q1RK1 w - - 0 9", "Complex Middlegame"),
+    ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", "Kiwipete"),
+];
+
+// Searches the position to SEARCH_DEPTH on a single thread, and returns
+// the number of nodes searched.
+fn search(fen: &str, prefetch: bool, mg: &Arc<MoveGenerator>, tt: &Arc<RwLock<TT<SearchData>>>) -> usize {
+    let mut board = Board::new();
+    board.fen_read(Some(fen)).expect("Valid FEN");
+    let (_control_tx, control_rx) = unbounded::<SearchControl>();
+    let (report_tx, _report_rx) = unbounded::<Information>();
+    let mut search_params = SearchParams::new();
+    let mut search_info = SearchInfo::new();
+    let mut thread_local_data = ThreadLocalData::new(0);
+
+    search_params.search_mode = SearchMode::Depth;
+    search_params.depth = SEARCH_DEPTH;
+    search_params.quiet = true;
+    search_params.tt_prefetch = prefetch;
+
+    let mut refs = SearchRefs {
+        board: &mut board,
+        mg,
+        tt,
+        tt_enabled: true,
+        search_params: &mut search_params,
+        search_info: &mut search_info,
+        control_rx: &control_rx,
+        report_tx: &report_tx,
+        thread_local_data: &mut thread_local_data,
+    };
+
+    black_box(Search::iterative_deepening(&mut refs));
+    refs.search_info.nodes
+}
+
+fn bench_tt_prefetch(c: &mut Criterion) {
+    let mg = Arc::new(MoveGenerator::new());
+    let tt: Arc<RwLock<TT<SearchData>>> = Arc::new(RwLock::new(TT::new(TT_MEGABYTES)));
+    let mut group = c.benchmark_group("tt_prefetch");
+    group.sample_size(10);
+
+    for (fen, name) in POSITIONS {
+        for prefetch in [false, true] {
+            // Report the speed of a single search, as a quick comparison.
+            tt.write().unwrap().clear();
+            let now = Instant::now();
+            let nodes = search(fen, prefetch, &mg, &tt);
+            let nps = nodes as f64 / now.elapsed().as_secs_f64();
+            println!("{name}, prefetch {prefetch}: {nodes} nodes, {nps:.0} nps");
+
+            let id = if prefetch { "prefetch" } else { "no_prefetch" };
+            group.bench_with_input(BenchmarkId::new(id, name), fen, |b, fen| {
+                b.iter_batched(
+                    || tt.write().unwrap().clear(),
+                    |_| search(fen, prefetch, &mg, &tt),
+                    BatchSize::PerIteration,
+                );
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_tt_prefetch);
+criterion_main!(benches);
@@ -26,6 +26,8 @@ mod fen;
 mod gamestate;
 mod history;
 mod playmove;
+mod san;
+mod see;
 mod utils;
 mod zobrist;
 
@@ -36,11 +38,17 @@ use self::{
     zobrist::{ZobristKey, ZobristRandoms},
 };
 use crate::{
-    defs::{Bitboard, NrOf, Piece, Side, Sides, Square, EMPTY},
-    evaluation::{pawn, mobility, psqt::{self, FLIP, PSQT_MG}},
+    defs::{Bitboard, NrOf, Piece, Side, Sides, Square, EMPTY, MAX_MOVE_RULE},
+    engine::defs::ErrFatal,
+    evaluation::{
+        mobility,
+        params::EvalParams,
+        pawn::{self, PawnHashTable},
+        psqt::{self, FLIP, PSQT_EG, PSQT_MG},
+    },
     misc::bits,
 };
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 // This file implements the engine's board representation; it is bit-board
 // based, with the least significant bit being A1.
@@ -50,6 +58,8 @@ pub struct Board {
     pub game_state: GameState,
     pub history: History,
     pub piece_list: [Piece; NrOf::SQUARES],
+    pub pawn_table: Arc<RwLock<PawnHashTable>>,
+    pub eval_params: Arc<EvalParams>,
     zr: Arc<ZobristRandoms>,
 }
 
@@ -63,6 +73,8 @@ impl Board {
             game_state: GameState::new(),
             history: History::new(),
             piece_list: [Pieces::NONE; NrOf::SQUARES],
+            pawn_table: Arc::new(RwLock::new(PawnHashTable::new(0))),
+            eval_params: Arc::new(EvalParams::default()),
             zr: Arc::new(ZobristRandoms::new()),
         }
     }
@@ -104,6 +116,7 @@ impl Board {
         let flip = side == Sides::WHITE;
         let s = if flip { FLIP[square] } else { square };
         self.game_state.psqt[side] -= PSQT_MG[piece][s];
+        self.game_state.psqt_eg[side] -= PSQT_EG[piece][s];
     }
 
     // Put a piece onto the board, for the given side, piece, and square.
@@ -118,6 +131,7 @@ impl Board {
         let flip = side == Sides::WHITE;
         let s = if flip { FLIP[square] } else { square };
         self.game_state.psqt[side] += PSQT_MG[piece][s];
+        self.game_state.psqt_eg[side] += PSQT_EG[piece][s];
     }
 
     // Remove a piece from the from-square, and put it onto the to-square.
@@ -177,97 +191,15 @@ impl Board {
     }
 
     // Check if the current side is in check
-    pub fn in_check(&self) -> bool {
+    pub fn in_check(&self, move_gen: &crate::movegen::MoveGenerator) -> bool {
         let king_square = self.king_square(self.us());
-        let opponent = self.opponent();
-        
-        // Check if opponent's pieces attack our king
-        for piece in 0..NrOf::PIECE_TYPES {
-            let piece_bitboard = self.bb_pieces[opponent][piece];
-            if piece_bitboard == 0 {
-                continue;
-            }
-            
-            let mut pieces = piece_bitboard;
-            while pieces > 0 {
-                let from = bits::next(&mut pieces);
-                let attacks = self.get_attacks_from(piece, from);
-                if (attacks & (1u64 << king_square)) > 0 {
-                    return true;
-                }
-            }
-        }
-        false
-    }
-
-    // Get attacks from a piece on a given square
-    fn get_attacks_from(&self, piece: Piece, square: Square) -> Bitboard {
-        match piece {
-            Pieces::KING => {
-                let king_attacks = [
-                    (1, 0), (1, 1), (0, 1), (-1, 1),
-                    (-1, 0), (-1, -1), (0, -1), (1, -1)
-                ];
-                let mut attacks = 0u64;
-                let file = square % 8;
-                let rank = square / 8;
-                
-                for (df, dr) in king_attacks.iter() {
-                    let new_file = file as i8 + df;
-                    let new_rank = rank as i8 + dr;
-                    if new_file >= 0 && new_file < 8 && new_rank >= 0 && new_rank < 8 {
-                        attacks |= 1u64 << (new_rank * 8 + new_file);
-                    }
-                }
-                attacks
-            }
-            Pieces::KNIGHT => {
-                let knight_attacks = [
-                    (-2, -1), (-2, 1), (-1, -2), (-1, 2),
-                    (1, -2), (1, 2), (2, -1), (2, 1)
-                ];
-                let mut attacks = 0u64;
-                let file = square % 8;
-                let rank = square / 8;
-                
-                for (df, dr) in knight_attacks.iter() {
-                    let new_file = file as i8 + df;
-                    let new_rank = rank as i8 + dr;
-                    if new_file >= 0 && new_file < 8 && new_rank >= 0 && new_rank < 8 {
-                        attacks |= 1u64 << (new_rank * 8 + new_file);
-                    }
-                }
-                attacks
-            }
-            Pieces::PAWN => {
-                let side = if self.us() == Sides::WHITE { Sides::WHITE } else { Sides::BLACK };
-                let direction = if side == Sides::WHITE { -1 } else { 1 };
-                let mut attacks = 0u64;
-                let file = square % 8;
-                let rank = square / 8;
-                
-                // Pawn captures
-                for df in [-1, 1].iter() {
-                    let new_file = file as i8 + df;
-                    let new_rank = rank as i8 + direction;
-                    if new_file >= 0 && new_file < 8 && new_rank >= 0 && new_rank < 8 {
-                        attacks |= 1u64 << (new_rank * 8 + new_file);
-                    }
-                }
-                attacks
-            }
-            _ => {
-                // For sliding pieces, we'll use a simplified approach
-                // In a real implementation, you'd use the magic bitboard tables
-                0u64
-            }
-        }
+        self.attackers_to(king_square, self.opponent(), move_gen) > 0
     }
 
     // === Cache Management Functions ===
 
     /// Compute pawn hash for cache invalidation
-    fn compute_pawn_hash(&self) -> u64 {
+    pub fn compute_pawn_hash(&self) -> u64 {
         let white_pawns = self.bb_pieces[Sides::WHITE][Pieces::PAWN];
         let black_pawns = self.bb_pieces[Sides::BLACK][Pieces::PAWN];
         
@@ -280,14 +212,33 @@ impl Board {
         let current_hash = self.compute_pawn_hash();
         
         // Only recompute if pawn structure changed
-        if current_hash != self.game_state.pawn_hash {
-            self.game_state.pawn_structure_score = pawn::evaluate_pawn_structure(self);
-            self.game_state.pawn_hash = current_hash;
+        if self.game_state.pawn_hash != Some(current_hash) {
+            // Try the shared pawn hash table before evaluating from scratch.
+            let stored = self.pawn_table.read().expect(ErrFatal::LOCK).probe(current_hash);
+            self.game_state.pawn_structure_score = match stored {
+                Some(score) => score,
+                None => {
+                    let score = pawn::evaluate_pawn_structure(self);
+                    self.pawn_table.write().expect(ErrFatal::LOCK).store(current_hash, score);
+                    score
+                }
+            };
+            self.game_state.pawn_hash = Some(current_hash);
         }
     }
 
 
 
+    /// Replace the evaluation weights. The cached scores were computed with
+    /// the old weights, so these are invalidated, and the pawn hash table
+    /// shared with the other boards is cleared.
+    pub fn set_eval_params(&mut self, params: EvalParams) {
+        self.eval_params = Arc::new(params);
+        self.pawn_table.write().expect(ErrFatal::LOCK).clear();
+        self.invalidate_pawn_structure_cache();
+        self.invalidate_mobility_cache();
+    }
+
     /// Get cached pawn structure score (update if needed)
     pub fn get_cached_pawn_structure_score(&mut self) -> i16 {
         self.update_pawn_structure_cache();
@@ -302,7 +253,7 @@ impl Board {
 
     /// Initialize all caches (called after board setup)
     pub fn init_evaluation_caches(&mut self, move_gen: &crate::movegen::MoveGenerator) {
-        self.game_state.pawn_hash = self.compute_pawn_hash();
+        self.game_state.pawn_hash = Some(self.compute_pawn_hash());
         self.game_state.pawn_structure_score = pawn::evaluate_pawn_structure(self);
         self.game_state.game_phase = self.calculate_game_phase();
         self.game_state.mobility_score = mobility::evaluate_mobility(self, move_gen);
@@ -356,6 +307,12 @@ impl Board {
         self.game_state.mobility_score = 0;
     }
 
+    /// Drop the cached pawn structure score, so it is evaluated again even
+    /// if the pawns haven't moved
+    pub fn invalidate_pawn_structure_cache(&mut self) {
+        self.game_state.pawn_hash = None;
+    }
+
     /// Check if mobility cache is valid
     pub fn is_mobility_cache_valid(&self) -> bool {
         // Simple check - in practice you'd have a more sophisticated validation
@@ -399,6 +356,10 @@ impl Board {
         let psqt = psqt::apply(self);
         self.game_state.psqt[Sides::WHITE] = psqt.0;
         self.game_state.psqt[Sides::BLACK] = psqt.1;
+
+        let psqt_eg = psqt::apply_eg(self);
+        self.game_state.psqt_eg[Sides::WHITE] = psqt_eg.0;
+        self.game_state.psqt_eg[Sides::BLACK] = psqt_eg.1;
     }
 
     // Gather the pieces for each side into their own bitboard.
@@ -508,6 +469,8 @@ impl Clone for Board {
             // This avoids copying the entire history array and saves memory
             history: History::new_for_search(),
             piece_list: self.piece_list,
+            pawn_table: Arc::clone(&self.pawn_table),
+            eval_params: Arc::clone(&self.eval_params),
             zr: Arc::clone(&self.zr), // Reuse the ZobristRandoms
         }
     }
@@ -523,18 +486,30 @@ impl Board {
             game_state: self.game_state,
             history: self.history.clone(), // Full history clone
             piece_list: self.piece_list,
+            pawn_table: Arc::clone(&self.pawn_table),
+            eval_params: Arc::clone(&self.eval_params),
             zr: Arc::clone(&self.zr),
         }
     }
 
-    /// Clone for search thread (fresh history with smaller capacity)
+    /// Clone for search thread (fresh history with smaller capacity). The
+    /// positions since the last capture or pawn move are kept, so the
+    /// search can detect repetitions of positions played in the game.
     pub fn clone_for_search(&self) -> Self {
+        let mut history = History::new_for_search();
+        let reversible = (self.game_state.halfmove_clock as usize).min(MAX_MOVE_RULE as usize);
+        for i in self.history.len().saturating_sub(reversible)..self.history.len() {
+            history.push(*self.history.get_ref(i));
+        }
+
         Self {
             bb_pieces: self.bb_pieces,
             bb_side: self.bb_side,
             game_state: self.game_state,
-            history: History::new_for_search(), // Fresh history, smaller capacity
+            history,
             piece_list: self.piece_list,
+            pawn_table: Arc::clone(&self.pawn_table),
+            eval_params: Arc::clone(&self.eval_params),
             zr: Arc::clone(&self.zr),
         }
     }
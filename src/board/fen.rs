@@ -21,19 +21,20 @@ You should have received a copy of the GNU General Public License along
 with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
-// fen.rs reads an FEN-string and converts it into a board position.
-// If the procedure fails, the original position is not changed. Note that
+// fen.rs reads an FEN-string and converts it into a board position, and
+// writes the current position back out as an FEN-string.
+// If reading fails, the original position is not changed. Note that
 // checking position legality is not the responsibility of this module. It
 // is perfectly possible to set up a position with two white kings, both
 // kings in check at the same time, or with black in check but white to
 // move.
 
 use super::{
-    defs::{Files, Pieces, Ranks, Squares, BB_SQUARES},
+    defs::{Files, Pieces, Ranks, Squares, BB_SQUARES, SQUARE_NAME},
     Board,
 };
 use crate::{
-    defs::{Castling, Sides, Square, FEN_START_POSITION, MAX_GAME_MOVES, MAX_MOVE_RULE},
+    defs::{Castling, Side, Sides, Square, FEN_START_POSITION, MAX_GAME_MOVES, MAX_MOVE_RULE},
     misc::parse,
 };
 use if_chain::if_chain;
@@ -46,7 +47,6 @@ const LIST_OF_PIECES: &str = "kqrbnpKQRBNP";
 const EP_SQUARES_WHITE: RangeInclusive<Square> = Squares::A3..=Squares::H3;
 const EP_SQUARES_BLACK: RangeInclusive<Square> = Squares::A6..=Squares::H6;
 const WHITE_OR_BLACK: &str = "wb";
-const CASTLING_RIGHTS: &str = "KQkq-";
 const SPLITTER: char = '/';
 const DASH: char = '-';
 const EM_DASH: char = '–';
@@ -105,6 +105,55 @@ impl Board {
 
         result
     }
+
+    // This function writes the current position as an FEN-string, with
+    // all six parts.
+    pub fn to_fen(&self) -> String {
+        let mut placement = String::new();
+
+        for rank in (Ranks::R1..=Ranks::R8).rev() {
+            let mut empty = 0;
+
+            for file in Files::A..=Files::H {
+                let square = rank * 8 + file;
+                let piece = self.piece_list[square];
+
+                if piece == Pieces::NONE {
+                    empty += 1;
+                    continue;
+                }
+
+                if empty > 0 {
+                    placement.push_str(&empty.to_string());
+                    empty = 0;
+                }
+
+                let is_white = self.bb_side[Sides::WHITE] & BB_SQUARES[square] > 0;
+                let c = LIST_OF_PIECES.as_bytes()[piece] as char;
+                placement.push(if is_white { c.to_ascii_uppercase() } else { c });
+            }
+
+            if empty > 0 {
+                placement.push_str(&empty.to_string());
+            }
+
+            if rank != Ranks::R1 {
+                placement.push(SPLITTER);
+            }
+        }
+
+        let color = if self.us() == Sides::WHITE { 'w' } else { 'b' };
+        let castling = castling_as_string(self);
+        let ep = match self.game_state.en_passant {
+            Some(square) => SQUARE_NAME[square as usize],
+            None => "-",
+        };
+
+        format!(
+            "{placement} {color} {castling} {ep} {} {}",
+            self.game_state.halfmove_clock, self.game_state.fullmove_number
+        )
+    }
 }
 
 // ===== Private functions =====
@@ -186,7 +235,9 @@ fn color(board: &mut Board, part: &str) -> bool {
     result
 }
 
-// Part 3: Parse castling rights.
+// Part 3: Parse castling rights. Next to the normal "KQkq", this accepts
+// the rook files used by Shredder-FEN ("HAha") and X-FEN ("KQkq", or a
+// file if the castling rook is not the outermost one) for Chess960.
 fn castling(board: &mut Board, part: &str) -> bool {
     let length = part.len();
     let mut char_ok = 0;
@@ -196,16 +247,37 @@ fn castling(board: &mut Board, part: &str) -> bool {
     if (1..=4).contains(&length) {
         // Accepts "-" for no castling rights in addition to leaving out letters.
         for c in part.chars() {
-            if CASTLING_RIGHTS.contains(c) {
-                // Count correct characters
-                char_ok += 1;
-                match c {
-                    'K' => board.game_state.castling |= Castling::WK,
-                    'Q' => board.game_state.castling |= Castling::WQ,
-                    'k' => board.game_state.castling |= Castling::BK,
-                    'q' => board.game_state.castling |= Castling::BQ,
-                    _ => (),
+            let side = if c.is_ascii_uppercase() { Sides::WHITE } else { Sides::BLACK };
+            let has_king = board.bb_pieces[side][Pieces::KING] > 0;
+            let king_file = board.bb_pieces[side][Pieces::KING].trailing_zeros() as usize % 8;
+
+            // Find the rook file and the side of the king it's on.
+            let rook = match c {
+                'K' | 'k' => Some((outermost_rook_file(board, side, true).unwrap_or(Files::H), true)),
+                'Q' | 'q' => Some((outermost_rook_file(board, side, false).unwrap_or(Files::A), false)),
+                'A'..='H' | 'a'..='h' if has_king => {
+                    let file = (c.to_ascii_lowercase() as u8 - b'a') as usize;
+                    Some((file, file > king_file))
                 }
+                _ => None,
+            };
+
+            if let Some((file, is_kingside)) = rook {
+                let right = match (side, is_kingside) {
+                    (Sides::WHITE, true) => Castling::WK,
+                    (Sides::WHITE, false) => Castling::WQ,
+                    (_, true) => Castling::BK,
+                    (_, false) => Castling::BQ,
+                };
+                let index = right.trailing_zeros() as usize;
+
+                board.game_state.castling |= right;
+                board.game_state.castling_rook_files[index] = file as u8;
+            }
+
+            // Count correct characters
+            if rook.is_some() || c == DASH {
+                char_ok += 1;
             }
         }
     }
@@ -215,6 +287,60 @@ fn castling(board: &mut Board, part: &str) -> bool {
     (length >= 1) && (char_ok == length)
 }
 
+// Finds the file of the rook furthest away from the king on the given
+// side of the king, on the back rank. Returns None if the king is not on
+// the back rank, or there is no such rook.
+fn outermost_rook_file(board: &Board, side: Side, kingside: bool) -> Option<usize> {
+    let back_rank = if side == Sides::WHITE { Ranks::R1 } else { Ranks::R8 };
+    let king = board.bb_pieces[side][Pieces::KING];
+    let rooks = board.bb_pieces[side][Pieces::ROOK];
+    let king_square = king.trailing_zeros() as usize;
+
+    if king == 0 || king_square / 8 != back_rank {
+        return None;
+    }
+
+    let king_file = king_square % 8;
+    let is_rook = |file: &usize| rooks & BB_SQUARES[back_rank * 8 + file] > 0;
+
+    if kingside {
+        ((king_file + 1)..=Files::H).rev().find(is_rook)
+    } else {
+        (Files::A..king_file).find(is_rook)
+    }
+}
+
+// Writes the castling rights in X-FEN: "KQkq" if the castling rook is the
+// outermost one on its side of the king, and the rook's file otherwise.
+// For normal chess, this is the same as plain "KQkq".
+fn castling_as_string(board: &Board) -> String {
+    let mut castling = String::new();
+
+    for right in Castling::RIGHTS {
+        if board.game_state.castling & right == 0 {
+            continue;
+        }
+
+        let side = if right & (Castling::WK | Castling::WQ) > 0 { Sides::WHITE } else { Sides::BLACK };
+        let is_kingside = right & (Castling::WK | Castling::BK) > 0;
+        let file = board.castling_rook_square(right) % 8;
+
+        let c = if outermost_rook_file(board, side, is_kingside) == Some(file) {
+            if is_kingside { 'k' } else { 'q' }
+        } else {
+            (b'a' + file as u8) as char
+        };
+
+        castling.push(if side == Sides::WHITE { c.to_ascii_uppercase() } else { c });
+    }
+
+    if castling.is_empty() {
+        castling.push(DASH);
+    }
+
+    castling
+}
+
 // Part 4: Parse the en passant square
 fn ep(board: &mut Board, part: &str) -> bool {
     let length = part.len();
@@ -283,3 +409,53 @@ fn fmn(board: &mut Board, part: &str) -> bool {
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_fen_round_trip() {
+        let fens = [
+            FEN_START_POSITION,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "r3k3/8/8/8/3pP3/8/8/4K2R b Kq e3 0 12",
+            "8/8/4k3/8/8/8/8/4K3 b - - 37 80",
+        ];
+
+        for fen in fens {
+            let mut board = Board::new();
+            board.fen_read(Some(fen)).unwrap();
+            let written = board.to_fen();
+            assert_eq!(written, fen);
+
+            let mut other = Board::new();
+            other.fen_read(Some(&written)).unwrap();
+            assert_eq!(other.game_state.zobrist_key, board.game_state.zobrist_key);
+        }
+    }
+
+    #[test]
+    fn test_chess960_castling_rights() {
+        // Shredder-FEN rook files are written back as X-FEN.
+        let mut board = Board::new();
+        board
+            .fen_read(Some("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9"))
+            .unwrap();
+        assert_eq!(SQUARE_NAME[board.castling_rook_square(Castling::WK)], "h1");
+        assert_eq!(SQUARE_NAME[board.castling_rook_square(Castling::WQ)], "f1");
+        assert_eq!(SQUARE_NAME[board.castling_rook_square(Castling::BQ)], "f8");
+        assert_eq!(
+            board.to_fen(),
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9"
+        );
+
+        // The rook on f1 is not the outermost one, so its file is written.
+        let fen = "1r1k1rr1/8/8/8/8/8/8/1R1K1RR1 w Fb - 0 1";
+        board.fen_read(Some(fen)).unwrap();
+        assert_eq!(SQUARE_NAME[board.castling_rook_square(Castling::WK)], "f1");
+        assert_eq!(SQUARE_NAME[board.castling_rook_square(Castling::BQ)], "b8");
+        assert_eq!(board.to_fen(), "1r1k1rr1/8/8/8/8/8/8/1R1K1RR1 w Fq - 0 1");
+    }
+}
@@ -22,7 +22,7 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 use crate::{
-    board::defs::{Pieces, PIECE_NAME, SQUARE_NAME},
+    board::defs::{Files, Pieces, PIECE_NAME, SQUARE_NAME},
     defs::Sides,
     misc::print,
     movegen::defs::Move,
@@ -37,17 +37,19 @@ use crate::{
 pub struct GameState {
     pub active_color: u8,
     pub castling: u8,
+    pub castling_rook_files: [u8; 4], // Per right, in Castling::RIGHTS order
     pub halfmove_clock: u8,
     pub en_passant: Option<u8>,
     pub fullmove_number: u16,
     pub zobrist_key: u64,
     pub psqt: [i16; Sides::BOTH],
+    pub psqt_eg: [i16; Sides::BOTH],
     pub next_move: Move,
     
     // Cached evaluation values for performance
     pub pawn_structure_score: i16,
     pub mobility_score: i16,
-    pub pawn_hash: u64, // Hash of pawn positions to detect when cache is invalid
+    pub pawn_hash: Option<u64>, // Hash of the cached pawn structure, if any
     pub game_phase: i16, // Cached game phase to avoid recalculating
 }
 
@@ -56,15 +58,17 @@ impl GameState {
         Self {
             active_color: 0,
             castling: 0,
+            castling_rook_files: [Files::H as u8, Files::A as u8, Files::H as u8, Files::A as u8],
             en_passant: None,
             halfmove_clock: 0,
             fullmove_number: 0,
             zobrist_key: 0,
             psqt: [0; Sides::BOTH],
+            psqt_eg: [0; Sides::BOTH],
             next_move: Move::new(0),
             pawn_structure_score: 0,
             mobility_score: 0,
-            pawn_hash: 0,
+            pawn_hash: None,
             game_phase: 0,
         }
     }
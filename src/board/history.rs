@@ -22,7 +22,7 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 use super::gamestate::GameState;
-use crate::defs::MAX_GAME_MOVES;
+use crate::defs::{MAX_GAME_MOVES, MAX_MOVE_RULE, MAX_PLY};
 
 // The history struct holds the game states for each move. It uses a boxed array
 // for performance (direct indexing like the original) while allowing different
@@ -49,10 +49,12 @@ impl History {
 
     // Create a new history for search thread (smaller capacity)
     pub fn new_for_search() -> Self {
-        // Search threads typically need much less capacity than the main game
-        // Use a smaller capacity to save memory (128 vs 2048)
+        // Search threads need room for the game's positions since the last
+        // capture or pawn move, plus the deepest search line. This is much
+        // less than the main game needs, which saves memory.
         Self {
-            list: vec![GameState::new(); 128].into_boxed_slice(),
+            list: vec![GameState::new(); MAX_MOVE_RULE as usize + MAX_PLY as usize + 1]
+                .into_boxed_slice(),
             count: 0,
         }
     }
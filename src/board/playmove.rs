@@ -24,34 +24,37 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 // playmove.rs contains make() and unamke() for move execution and reversal.
 
 use super::{
-    defs::{Pieces, Squares, BB_SQUARES},
+    defs::{Pieces, BB_SQUARES},
     Board,
 };
 use crate::{
-    defs::{Castling, NrOf, Piece, Side, Sides, Square},
+    defs::{Castling, Piece, Side, Sides, Square},
     movegen::{defs::Move, MoveGenerator},
 };
 
-// Castling Permissions Per Square
-type CPSquare = [u8; NrOf::SQUARES];
-const CASTLING_PERMS: CPSquare = castling_permissions_per_square();
-const fn castling_permissions_per_square() -> CPSquare {
-    // First set all squares grant all castling permissions. This means
-    // moving a piece on such square doesn't have any effect on castling
-    // permissions.
-    let mut cp: CPSquare = [Castling::ALL; NrOf::SQUARES];
-
-    // Now disable castling permissions when moving pieces on certain
-    // squares. For example, when the piece (rook) on A1 moves, disable
-    // white castling to the queenside.
-    cp[Squares::A1] &= !Castling::WQ;
-    cp[Squares::E1] &= !Castling::WK & !Castling::WQ;
-    cp[Squares::H1] &= !Castling::WK;
-    cp[Squares::A8] &= !Castling::BQ;
-    cp[Squares::E8] &= !Castling::BK & !Castling::BQ;
-    cp[Squares::H8] &= !Castling::BK;
-
-    cp
+// Castling permissions that remain when the given piece leaves the given
+// square, or is captured on it. Because the rooks can start on any file
+// in Chess960, this can't be a fixed table per square. Moving the king
+// disables both of its side's permissions; moving or losing a rook on its
+// start square disables the permission that belongs to that rook.
+fn castling_permissions_after(board: &Board, side: Side, piece: Piece, square: Square) -> u8 {
+    let mut permissions = board.game_state.castling;
+
+    if piece == Pieces::KING {
+        permissions &= if side == Sides::WHITE {
+            !(Castling::WK | Castling::WQ)
+        } else {
+            !(Castling::BK | Castling::BQ)
+        };
+    }
+
+    for right in Castling::RIGHTS {
+        if board.castling_rook_square(right) == square {
+            permissions &= !right;
+        }
+    }
+
+    permissions
 }
 
 /*** ================================================================================ ***/
@@ -101,10 +104,23 @@ impl Board {
             self.game_state.halfmove_clock = 0;
             // Change castling permissions on rook capture in the corner.
             if captured == Pieces::ROOK && has_permissions {
-                self.update_castling_permissions(self.game_state.castling & CASTLING_PERMS[to]);
+                let permissions = castling_permissions_after(self, opponent, captured, to);
+                self.update_castling_permissions(permissions);
             }
         }
 
+        // When castling, take the rook off the board before moving the
+        // king. In Chess960, the king may land on the rook's start square.
+        let rook_move = if castling {
+            Some(self.castling_rook_move(to))
+        } else {
+            None
+        };
+
+        if let Some((rook_from, _)) = rook_move {
+            self.remove_piece(us, Pieces::ROOK, rook_from);
+        }
+
         // Make the move. Just move the piece if it's not a pawn.
         if piece != Pieces::PAWN {
             self.move_piece(us, piece, from, to);
@@ -128,18 +144,13 @@ impl Board {
         // Remove castling permissions if king/rook leaves from starting square.
         // (This will also adjust permissions when castling, because the king moves.)
         if (piece == Pieces::KING || piece == Pieces::ROOK) && has_permissions {
-            self.update_castling_permissions(self.game_state.castling & CASTLING_PERMS[from]);
+            let permissions = castling_permissions_after(self, us, piece, from);
+            self.update_castling_permissions(permissions);
         }
 
-        // If the king is castling, then also move the rook.
-        if castling {
-            match to {
-                Squares::G1 => self.move_piece(us, Pieces::ROOK, Squares::H1, Squares::F1),
-                Squares::C1 => self.move_piece(us, Pieces::ROOK, Squares::A1, Squares::D1),
-                Squares::G8 => self.move_piece(us, Pieces::ROOK, Squares::H8, Squares::F8),
-                Squares::C8 => self.move_piece(us, Pieces::ROOK, Squares::A8, Squares::D8),
-                _ => panic!("Error moving rook during castling."),
-            }
+        // If the king is castling, then put the rook onto its new square.
+        if let Some((_, rook_to)) = rook_move {
+            self.put_piece(us, Pieces::ROOK, rook_to);
         }
 
         // Swap the side to move.
@@ -198,26 +209,21 @@ impl Board {
         let castling = m.castling();
         let en_passant = m.en_passant();
 
-        // Moving backwards...
-        if promoted == Pieces::NONE {
+        // Moving backwards... When undoing castling, the rook is lifted
+        // first, because in Chess960 the king and rook can end up on each
+        // other's start squares.
+        if castling {
+            let (rook_from, rook_to) = self.castling_rook_move(to);
+            remove_piece(self, us, Pieces::ROOK, rook_to);
+            reverse_move(self, us, piece, to, from);
+            put_piece(self, us, Pieces::ROOK, rook_from);
+        } else if promoted == Pieces::NONE {
             reverse_move(self, us, piece, to, from);
         } else {
             remove_piece(self, us, promoted, to);
             put_piece(self, us, Pieces::PAWN, from);
         }
 
-        // The king's move was already undone as a normal move.
-        // Now undo the correct castling rook move.
-        if castling {
-            match to {
-                Squares::G1 => reverse_move(self, us, Pieces::ROOK, Squares::F1, Squares::H1),
-                Squares::C1 => reverse_move(self, us, Pieces::ROOK, Squares::D1, Squares::A1),
-                Squares::G8 => reverse_move(self, us, Pieces::ROOK, Squares::F8, Squares::H8),
-                Squares::C8 => reverse_move(self, us, Pieces::ROOK, Squares::D8, Squares::A8),
-                _ => panic!("Error: Reversing castling rook move."),
-            };
-        }
-
         // If a piece was captured, put it back onto the to-square
         if captured != Pieces::NONE {
             put_piece(self, opponent, captured, to);
@@ -303,6 +309,7 @@ fn reverse_move(board: &mut Board, side: Side, piece: Piece, remove: Square, put
 fn check_incrementals(board: &Board) -> bool {
     let from_scratch_key = board.init_zobrist_key();
     let from_scratch_psqt = crate::evaluation::psqt::apply(board);
+    let from_scratch_psqt_eg = crate::evaluation::psqt::apply_eg(board);
     let mut result = true;
 
     // Waterfall: only report first error encountered and skip any others.
@@ -321,5 +328,15 @@ fn check_incrementals(board: &Board) -> bool {
         result = false;
     };
 
+    if result && from_scratch_psqt_eg.0 != board.game_state.psqt_eg[Sides::WHITE] {
+        println!("Check Incrementals: Error in endgame PSQT for white.");
+        result = false;
+    };
+
+    if result && from_scratch_psqt_eg.1 != board.game_state.psqt_eg[Sides::BLACK] {
+        println!("Check Incrementals: Error in endgame PSQT for black.");
+        result = false;
+    };
+
     result
 }
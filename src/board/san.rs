@@ -0,0 +1,224 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// san.rs converts moves to Standard Algebraic Notation (SAN) as used in
+// PGN files, and parses SAN back into moves for the current position.
+
+use super::{
+    defs::{Files, Pieces, PIECE_CHAR_CAPS, SQUARE_NAME},
+    Board,
+};
+use crate::movegen::{
+    defs::{Move, MoveList, MoveType},
+    MoveGenerator,
+};
+
+const FILE_CHARS: &[u8] = b"abcdefgh";
+const RANK_CHARS: &[u8] = b"12345678";
+
+// Suffixes that may follow a SAN move, but don't change its meaning.
+const SAN_ANNOTATIONS: &[char] = &['+', '#', '!', '?'];
+const EN_PASSANT_SUFFIX: &str = "e.p.";
+
+impl Board {
+    // Converts a legal move in the current position to SAN, including
+    // disambiguation, captures, promotions, and check or mate.
+    pub fn move_to_san(&self, m: Move, mg: &MoveGenerator) -> String {
+        let mut san = if m.castling() {
+            if m.to() % 8 == Files::G {
+                String::from("O-O")
+            } else {
+                String::from("O-O-O")
+            }
+        } else {
+            self.san_without_check(m, mg)
+        };
+
+        // Play the move on a copy of the board to see if it gives check,
+        // and if the opponent has any moves left.
+        let mut board = self.clone();
+        if board.make(m, mg) && board.in_check(mg) {
+            san.push(if board.legal_moves(mg).is_empty() { '#' } else { '+' });
+        }
+
+        san
+    }
+
+    // Finds the legal move in the current position that matches the given
+    // SAN string. Check and mate signs, annotations, "e.p." and the "="
+    // before a promotion piece are optional.
+    pub fn san_to_move(&self, san: &str, mg: &MoveGenerator) -> Option<Move> {
+        let wanted = normalize_san(san);
+
+        if wanted.is_empty() {
+            return None;
+        }
+
+        self.legal_moves(mg)
+            .into_iter()
+            .find(|&m| normalize_san(&self.move_to_san(m, mg)) == wanted)
+    }
+
+    // All legal moves in the current position.
+    fn legal_moves(&self, mg: &MoveGenerator) -> Vec<Move> {
+        let mut ml = MoveList::new();
+        let mut board = self.clone();
+        let mut legal = Vec::new();
+
+        mg.generate_moves(&board, &mut ml, MoveType::All);
+        for i in 0..ml.len() {
+            let m = ml.get_move(i);
+            if board.make(m, mg) {
+                board.unmake();
+                legal.push(m);
+            }
+        }
+
+        legal
+    }
+
+    // SAN for a move that is not castling, without the check sign.
+    fn san_without_check(&self, m: Move, mg: &MoveGenerator) -> String {
+        let from = m.from();
+        let to = m.to();
+        let is_capture = m.captured() != Pieces::NONE || m.en_passant();
+        let mut san = String::new();
+
+        if m.piece() == Pieces::PAWN {
+            // Pawn captures always name the file the pawn came from.
+            if is_capture {
+                san.push(FILE_CHARS[from % 8] as char);
+            }
+        } else {
+            san.push_str(PIECE_CHAR_CAPS[m.piece()]);
+
+            // Other pieces of the same type that can go to the same square.
+            let others: Vec<Move> = self
+                .legal_moves(mg)
+                .into_iter()
+                .filter(|o| o.piece() == m.piece() && o.to() == to && o.from() != from)
+                .collect();
+
+            if !others.is_empty() {
+                let same_file = others.iter().any(|o| o.from() % 8 == from % 8);
+                let same_rank = others.iter().any(|o| o.from() / 8 == from / 8);
+
+                if !same_file {
+                    san.push(FILE_CHARS[from % 8] as char);
+                } else if !same_rank {
+                    san.push(RANK_CHARS[from / 8] as char);
+                } else {
+                    san.push(FILE_CHARS[from % 8] as char);
+                    san.push(RANK_CHARS[from / 8] as char);
+                }
+            }
+        }
+
+        if is_capture {
+            san.push('x');
+        }
+
+        san.push_str(SQUARE_NAME[to]);
+
+        if m.promoted() != Pieces::NONE {
+            san.push('=');
+            san.push_str(PIECE_CHAR_CAPS[m.promoted()]);
+        }
+
+        san
+    }
+}
+
+// Strips everything from a SAN string that is optional, so two ways of
+// writing the same move compare equal.
+fn normalize_san(san: &str) -> String {
+    san.trim()
+        .trim_end_matches(SAN_ANNOTATIONS)
+        .trim_end()
+        .trim_end_matches(EN_PASSANT_SUFFIX)
+        .trim_end()
+        .trim_end_matches(SAN_ANNOTATIONS)
+        .replace('0', "O")
+        .replace('=', "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(fen: &str) -> (Board, MoveGenerator) {
+        let mut board = Board::new();
+        board.fen_read(Some(fen)).unwrap();
+        (board, MoveGenerator::new())
+    }
+
+    // Parse the SAN, and check that writing it back gives the same SAN.
+    fn round_trip(board: &Board, mg: &MoveGenerator, san: &str) -> Move {
+        let m = board.san_to_move(san, mg).expect("SAN should parse");
+        assert_eq!(board.move_to_san(m, mg), san);
+        m
+    }
+
+    #[test]
+    fn test_san_file_disambiguation() {
+        // Knights on b1 and f1 can both go to d2.
+        let (board, mg) = setup("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1");
+
+        assert_eq!(round_trip(&board, &mg, "Nbd2").from(), 1);
+        assert_eq!(round_trip(&board, &mg, "Nfd2").from(), 5);
+        assert!(board.san_to_move("Nd2", &mg).is_none());
+    }
+
+    #[test]
+    fn test_san_rank_disambiguation() {
+        // Knights on g1 and g5 can both go to f3.
+        let (board, mg) = setup("4k3/8/8/6N1/8/8/8/4K1N1 w - - 0 1");
+
+        assert_eq!(round_trip(&board, &mg, "N1f3").from(), 6);
+        assert_eq!(round_trip(&board, &mg, "N5f3").from(), 38);
+    }
+
+    #[test]
+    fn test_san_en_passant() {
+        let (board, mg) = setup("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1");
+
+        let m = board.san_to_move("exd6 e.p.", &mg).expect("SAN should parse");
+        assert!(m.en_passant());
+        assert_eq!(board.move_to_san(m, &mg), "exd6");
+        assert!(board.san_to_move("exd6", &mg) == Some(m));
+    }
+
+    #[test]
+    fn test_san_castling_promotion_check_and_mate() {
+        let (board, mg) = setup("r3k3/1P6/8/8/8/8/8/R3K2R w KQq - 0 1");
+
+        round_trip(&board, &mg, "O-O");
+        round_trip(&board, &mg, "O-O-O");
+        round_trip(&board, &mg, "bxa8=Q+");
+        assert!(board.san_to_move("0-0", &mg).is_some());
+
+        // Back rank mate.
+        let (board, mg) = setup("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        round_trip(&board, &mg, "Ra8#");
+    }
+}
@@ -0,0 +1,197 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// Static Exchange Evaluation (SEE) on the board.
+
+use super::{
+    defs::{Pieces, BB_SQUARES},
+    Board,
+};
+use crate::{
+    defs::{Bitboard, NrOf, Piece, Side, Sides, Square},
+    movegen::MoveGenerator,
+    evaluation::defs::exchange_value,
+};
+
+// Order in which attackers are tried: least valuable first.
+const SEE_ATTACKER_ORDER: [Piece; NrOf::PIECE_TYPES] = [
+    Pieces::PAWN,
+    Pieces::KNIGHT,
+    Pieces::BISHOP,
+    Pieces::ROOK,
+    Pieces::QUEEN,
+    Pieces::KING,
+];
+
+// Maximum number of captures in one exchange sequence.
+const SEE_MAX_DEPTH: usize = 32;
+
+impl Board {
+    // Resolves the sequence of captures on the to-square, started by the
+    // piece on the from-square, with both sides always recapturing with
+    // their least valuable attacker and being allowed to stop when it
+    // doesn't pay off. Returns the material balance in centipawns for the
+    // side starting the exchange.
+    pub fn see(&self, from: Square, to: Square, move_gen: &MoveGenerator) -> i16 {
+        let gain = exchange_value(self.piece_list[to]);
+        let piece = self.piece_list[from];
+
+        self.see_exchange(from, to, gain, piece, self.occupancy(), move_gen)
+    }
+
+    // The exchange itself. The first capture has already been made: it
+    // gained first_gain, and left on_square on the to-square. Search uses
+    // this directly for en passant and promotions, which don't fit the
+    // plain from/to case.
+    pub fn see_exchange(
+        &self,
+        from: Square,
+        to: Square,
+        first_gain: i16,
+        on_square: Piece,
+        occupancy: Bitboard,
+        move_gen: &MoveGenerator,
+    ) -> i16 {
+        let mut gain = [0i32; SEE_MAX_DEPTH];
+        let mut depth = 0;
+        let mut from_bb = BB_SQUARES[from];
+        let mut side = if self.bb_side[Sides::WHITE] & from_bb > 0 {
+            Sides::WHITE
+        } else {
+            Sides::BLACK
+        };
+        let mut occupancy = occupancy;
+        let mut on_square = on_square;
+
+        gain[0] = first_gain as i32;
+
+        loop {
+            depth += 1;
+
+            // Speculative gain if the piece on the square is captured.
+            gain[depth] = exchange_value(on_square) as i32 - gain[depth - 1];
+
+            // Neither side can improve by continuing the exchange.
+            if (-gain[depth - 1]).max(gain[depth]) < 0 || depth == SEE_MAX_DEPTH - 1 {
+                break;
+            }
+
+            // Remove the piece that just captured. This uncovers any
+            // sliders behind it (x-rays), which join the exchange.
+            occupancy ^= from_bb;
+            side ^= 1;
+
+            let attackers = self.see_attackers(move_gen, to, occupancy);
+            match self.see_least_valuable(attackers, side) {
+                Some((piece, bb)) => {
+                    on_square = piece;
+                    from_bb = bb;
+                }
+                None => break,
+            }
+        }
+
+        // Negamax the speculative gains back to the first capture.
+        while depth > 1 {
+            depth -= 1;
+            gain[depth - 1] = -((-gain[depth - 1]).max(gain[depth]));
+        }
+
+        gain[0] as i16
+    }
+
+    // Returns all pieces of both sides attacking the square, considering
+    // only the pieces still present in the given occupancy.
+    fn see_attackers(&self, mg: &MoveGenerator, square: Square, occupancy: Bitboard) -> Bitboard {
+        let bb_w = &self.bb_pieces[Sides::WHITE];
+        let bb_b = &self.bb_pieces[Sides::BLACK];
+        let bb_king = mg.get_non_slider_attacks(Pieces::KING, square);
+        let bb_knight = mg.get_non_slider_attacks(Pieces::KNIGHT, square);
+        let bb_rook = mg.get_slider_attacks(Pieces::ROOK, square, occupancy);
+        let bb_bishop = mg.get_slider_attacks(Pieces::BISHOP, square, occupancy);
+        let rooks_queens = bb_w[Pieces::ROOK] | bb_w[Pieces::QUEEN] | bb_b[Pieces::ROOK] | bb_b[Pieces::QUEEN];
+        let bishops_queens = bb_w[Pieces::BISHOP] | bb_w[Pieces::QUEEN] | bb_b[Pieces::BISHOP] | bb_b[Pieces::QUEEN];
+
+        let attackers = (bb_king & (bb_w[Pieces::KING] | bb_b[Pieces::KING]))
+            | (bb_knight & (bb_w[Pieces::KNIGHT] | bb_b[Pieces::KNIGHT]))
+            | (bb_rook & rooks_queens)
+            | (bb_bishop & bishops_queens)
+            | (mg.get_pawn_attacks(Sides::BLACK, square) & bb_w[Pieces::PAWN])
+            | (mg.get_pawn_attacks(Sides::WHITE, square) & bb_b[Pieces::PAWN]);
+
+        attackers & occupancy
+    }
+
+    // Finds the least valuable piece of the given side among the attackers.
+    fn see_least_valuable(&self, attackers: Bitboard, side: Side) -> Option<(Piece, Bitboard)> {
+        SEE_ATTACKER_ORDER.iter().find_map(|&piece| {
+            let bb = attackers & self.bb_pieces[side][piece];
+            if bb > 0 {
+                // Isolate the lowest set bit: one single attacker.
+                Some((piece, bb & bb.wrapping_neg()))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluation::defs::PIECE_VALUES;
+
+    fn see_of(fen: &str, from: Square, to: Square) -> i16 {
+        let mut board = Board::new();
+        let mg = MoveGenerator::new();
+        board.fen_read(Some(fen)).unwrap();
+        board.see(from, to, &mg)
+    }
+
+    #[test]
+    fn see_pawn_takes_defended_pawn() {
+        // e4xd5, c6xd5.
+        let see = see_of("4k3/8/2p5/3p4/4P3/8/8/4K3 w - - 0 1", 28, 35);
+        assert_eq!(see, 0);
+    }
+
+    #[test]
+    fn see_rook_takes_hanging_pawn() {
+        let see = see_of("4k3/8/8/3p4/8/8/8/3RK3 w - - 0 1", 3, 35);
+        assert_eq!(see, PIECE_VALUES[Pieces::PAWN]);
+    }
+
+    #[test]
+    fn see_queen_behind_rook_recaptures() {
+        // Rxd5 Rxd5, and the queen on d1 recaptures through the rook.
+        let see = see_of("3rk3/8/8/3p4/8/8/3R4/3QK3 w - - 0 1", 11, 35);
+        assert_eq!(see, PIECE_VALUES[Pieces::PAWN]);
+    }
+
+    #[test]
+    fn see_for_black() {
+        // Black knight takes a pawn defended by a pawn.
+        let see = see_of("4k3/8/2n5/8/3P4/4P3/8/4K3 b - - 0 1", 42, 27);
+        assert_eq!(see, PIECE_VALUES[Pieces::PAWN] - PIECE_VALUES[Pieces::KNIGHT]);
+    }
+}
@@ -21,10 +21,13 @@ You should have received a copy of the GNU General Public License along
 with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
+#[cfg(test)]
+use super::gamestate::GameState;
 use super::{defs::Location, Board};
 use crate::{
-    board::defs::Ranks,
-    defs::{Side, Sides, Square},
+    board::defs::{Files, Pieces, Ranks, Squares, BB_DARK_SQUARES},
+    defs::{Bitboard, Castling, Side, Sides, Square},
+    movegen::{defs::Move, MoveGenerator},
 };
 
 impl Board {
@@ -35,6 +38,13 @@ impl Board {
         (file, rank)
     }
 
+    // Compute the distance between two squares in king moves.
+    pub fn square_distance(a: Square, b: Square) -> u8 {
+        let (file_a, rank_a) = Board::square_on_file_rank(a);
+        let (file_b, rank_b) = Board::square_on_file_rank(b);
+        file_a.abs_diff(file_b).max(rank_a.abs_diff(rank_b))
+    }
+
     // Compute if a given square is or isn't on the given rank.
     pub fn square_on_rank(square: Square, rank: Square) -> bool {
         let start = (rank) * 8;
@@ -57,4 +67,266 @@ impl Board {
             Ranks::R1
         }
     }
+
+    // Counts how often the current position occurred before. Only the
+    // positions since the last capture or pawn move can repeat; the
+    // halfmove clock counts them. Positions with the other side to move
+    // can't be the same, so every second one is skipped. Returns 1 for a
+    // twofold and 2 for a threefold repetition.
+    pub fn repetition_count(&self) -> u8 {
+        let len = self.history.len();
+        let reversible = (self.game_state.halfmove_clock as usize).min(len);
+        let mut count = 0;
+
+        // History entry len - 1 is the position one ply ago.
+        for plies_ago in (2..=reversible).step_by(2) {
+            let historic = self.history.get_ref(len - plies_ago);
+            if historic.zobrist_key == self.game_state.zobrist_key {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    // Returns true if the move repeats the position for the third time in
+    // the game, which ends it in a draw. Illegal moves don't.
+    pub fn would_be_threefold(&mut self, m: Move, mg: &MoveGenerator) -> bool {
+        if !self.make(m, mg) {
+            return false;
+        }
+        let is_threefold = self.repetition_count() >= 2;
+        self.unmake();
+        is_threefold
+    }
+
+    // Returns true if neither side can possibly checkmate: KvK, KNvK, KBvK,
+    // and KBvKB with both bishops on the same color.
+    pub fn is_insufficient_material(&self) -> bool {
+        let heavy_or_pawns = [Pieces::QUEEN, Pieces::ROOK, Pieces::PAWN]
+            .iter()
+            .any(|&p| self.get_pieces(p, Sides::WHITE) | self.get_pieces(p, Sides::BLACK) > 0);
+
+        if heavy_or_pawns {
+            return false;
+        }
+
+        let w_knights = self.get_pieces(Pieces::KNIGHT, Sides::WHITE).count_ones();
+        let b_knights = self.get_pieces(Pieces::KNIGHT, Sides::BLACK).count_ones();
+        let w_bishops = self.get_pieces(Pieces::BISHOP, Sides::WHITE);
+        let b_bishops = self.get_pieces(Pieces::BISHOP, Sides::BLACK);
+        let w_minors = w_knights + w_bishops.count_ones();
+        let b_minors = b_knights + b_bishops.count_ones();
+
+        match (w_minors, b_minors) {
+            // KvK, KNvK, KBvK
+            (0, 0) | (1, 0) | (0, 1) => true,
+            // KBvKB, bishops on the same color
+            (1, 1) if w_knights == 0 && b_knights == 0 => {
+                (w_bishops & BB_DARK_SQUARES == 0) == (b_bishops & BB_DARK_SQUARES == 0)
+            }
+            _ => false,
+        }
+    }
+
+    // Returns all pieces of the given side attacking the square. Like
+    // square_attacked(), this looks from the square outward with the
+    // moves of each piece type, and keeps those where such a piece of the
+    // side is actually standing.
+    pub fn attackers_to(&self, square: Square, side: Side, move_gen: &MoveGenerator) -> Bitboard {
+        let pieces = &self.bb_pieces[side];
+        let occupancy = self.occupancy();
+        let bb_king = move_gen.get_non_slider_attacks(Pieces::KING, square);
+        let bb_knight = move_gen.get_non_slider_attacks(Pieces::KNIGHT, square);
+        let bb_rook = move_gen.get_slider_attacks(Pieces::ROOK, square, occupancy);
+        let bb_bishop = move_gen.get_slider_attacks(Pieces::BISHOP, square, occupancy);
+
+        // A pawn attacks the square if a pawn of the other side, standing
+        // on the square, would attack the pawn.
+        let bb_pawns = move_gen.get_pawn_attacks(side ^ 1, square);
+
+        (bb_king & pieces[Pieces::KING])
+            | (bb_knight & pieces[Pieces::KNIGHT])
+            | (bb_rook & (pieces[Pieces::ROOK] | pieces[Pieces::QUEEN]))
+            | (bb_bishop & (pieces[Pieces::BISHOP] | pieces[Pieces::QUEEN]))
+            | (bb_pawns & pieces[Pieces::PAWN])
+    }
+
+    // Returns the start square of the rook belonging to the given castling
+    // right. In Chess960 this can be any square on the back rank.
+    pub fn castling_rook_square(&self, right: u8) -> Square {
+        let index = right.trailing_zeros() as usize;
+        let file = self.game_state.castling_rook_files[index] as Square;
+        let is_black = right & (Castling::BK | Castling::BQ) > 0;
+
+        if is_black {
+            Squares::A8 + file
+        } else {
+            Squares::A1 + file
+        }
+    }
+
+    // Returns the rook's from- and to-square when the king castles onto
+    // the given square. The king always ends up on the G- or C-file, and
+    // the rook next to it on the F- or D-file, whatever the start files.
+    pub fn castling_rook_move(&self, king_to: Square) -> (Square, Square) {
+        let right = match king_to {
+            Squares::G1 => Castling::WK,
+            Squares::C1 => Castling::WQ,
+            Squares::G8 => Castling::BK,
+            Squares::C8 => Castling::BQ,
+            _ => panic!("Error: castling to an invalid square."),
+        };
+        let rook_to = if king_to % 8 == Files::G {
+            king_to - (Files::G - Files::F)
+        } else {
+            king_to + (Files::D - Files::C)
+        };
+
+        (self.castling_rook_square(right), rook_to)
+    }
+
+    // Returns the position flipped top to bottom with the colors swapped:
+    // white's pieces become black's on the mirrored squares, and the other
+    // side is to move. Castling rights and the en-passant square are kept
+    // for the new owners. An evaluation that is symmetric scores both
+    // positions the same for the side to move.
+    #[cfg(test)]
+    pub fn mirror(&self) -> Board {
+        let mut board = self.clone();
+        board.reset();
+
+        // Flipping the ranks of a bitboard reverses its bytes.
+        for side in [Sides::WHITE, Sides::BLACK] {
+            for (piece, bb) in self.bb_pieces[side].iter().enumerate() {
+                board.bb_pieces[side ^ 1][piece] = bb.swap_bytes();
+            }
+        }
+
+        let gs = &self.game_state;
+        let files = gs.castling_rook_files;
+        board.game_state = GameState {
+            active_color: gs.active_color ^ 1,
+            castling: (gs.castling & (Castling::WK | Castling::WQ)) << 2
+                | (gs.castling & (Castling::BK | Castling::BQ)) >> 2,
+            castling_rook_files: [files[2], files[3], files[0], files[1]],
+            halfmove_clock: gs.halfmove_clock,
+            en_passant: gs.en_passant.map(|square| square ^ 56),
+            fullmove_number: gs.fullmove_number,
+            ..GameState::new()
+        };
+
+        board.init();
+        board
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insufficient(fen: &str) -> bool {
+        let mut board = Board::new();
+        board.fen_read(Some(fen)).unwrap();
+        board.is_insufficient_material()
+    }
+
+    #[test]
+    fn test_insufficient_material_draws() {
+        assert!(insufficient("4k3/8/8/8/8/8/8/4K3 w - - 0 1")); // KvK
+        assert!(insufficient("4k3/8/8/8/8/8/8/4KN2 w - - 0 1")); // KNvK
+        assert!(insufficient("4kn2/8/8/8/8/8/8/4K3 w - - 0 1")); // KvKN
+        assert!(insufficient("4k3/8/8/8/8/8/8/4KB2 w - - 0 1")); // KBvK
+        assert!(insufficient("2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1")); // KBvKB, light squares
+    }
+
+    #[test]
+    fn test_sufficient_material() {
+        assert!(!insufficient("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1")); // KPvK
+        assert!(!insufficient("4kb2/8/8/8/8/8/8/4KB2 w - - 0 1")); // KBvKB, opposite colors
+        assert!(!insufficient("4k3/8/8/8/8/8/8/3BKB2 w - - 0 1")); // KBBvK
+        assert!(!insufficient("4k3/8/8/8/8/8/8/4KBN1 w - - 0 1")); // KBNvK
+    }
+
+    #[test]
+    fn test_mirror_flips_position_and_rights() {
+        let mut board = Board::new();
+        board
+            .fen_read(Some("r3k2r/ppp1pppp/8/8/3pP3/8/PPPP1PPP/R3K2R b Kq e3 0 3"))
+            .unwrap();
+        let mirrored = board.mirror();
+
+        assert_eq!(
+            mirrored.to_fen(),
+            "r3k2r/pppp1ppp/8/3Pp3/8/8/PPP1PPPP/R3K2R w Qk e6 0 3"
+        );
+        assert_eq!(mirrored.mirror().game_state.zobrist_key, board.game_state.zobrist_key);
+    }
+
+    #[test]
+    fn test_attackers_to_contested_center() {
+        let mg = MoveGenerator::new();
+        let mut board = Board::new();
+        board
+            .fen_read(Some("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"))
+            .unwrap();
+
+        // The pawn on d5 is attacked by e6, b6 and f6, and defended by e4
+        // and c3. The queen on f3 is blocked by the pawn on e4.
+        let d5 = 35;
+        let black = board.attackers_to(d5, Sides::BLACK, &mg);
+        let white = board.attackers_to(d5, Sides::WHITE, &mg);
+        assert_eq!(black, (1u64 << 44) | (1u64 << 41) | (1u64 << 45));
+        assert_eq!(white, (1u64 << 28) | (1u64 << 18));
+
+        // After e4 is gone, the queen on f3 joins the defence.
+        board.remove_piece(Sides::WHITE, Pieces::PAWN, 28);
+        let white = board.attackers_to(d5, Sides::WHITE, &mg);
+        assert_eq!(white, (1u64 << 21) | (1u64 << 18));
+    }
+
+    #[test]
+    fn test_in_check_by_slider() {
+        let mg = MoveGenerator::new();
+        let mut board = Board::new();
+        board.fen_read(Some("4k3/8/8/8/8/8/8/4R1K1 b - - 0 1")).unwrap();
+        assert!(board.in_check(&mg));
+
+        board.fen_read(Some("4k3/8/8/8/8/8/4P3/4R1K1 b - - 0 1")).unwrap();
+        assert!(!board.in_check(&mg));
+    }
+
+    #[test]
+    fn test_in_check_by_bishop() {
+        let mg = MoveGenerator::new();
+        let mut board = Board::new();
+        board.fen_read(Some("4k3/8/8/1B6/8/8/8/6K1 b - - 0 1")).unwrap();
+        assert!(board.in_check(&mg));
+
+        board.fen_read(Some("4k3/3p4/8/1B6/8/8/8/6K1 b - - 0 1")).unwrap();
+        assert!(!board.in_check(&mg));
+    }
+
+    #[test]
+    fn test_would_be_threefold() {
+        let mg = MoveGenerator::new();
+        let mut board = Board::new();
+        board.fen_read(Some("k7/8/8/8/8/8/8/3QK3 w - - 0 1")).unwrap();
+
+        // After the first shuffle, Qd2 repeats the position a second time,
+        // and after the second shuffle a third time.
+        for (shuffles, expected) in [(1, false), (2, true)] {
+            while board.history.len() < 4 * shuffles {
+                for san in ["Qd2", "Kb8", "Qd1", "Ka8"] {
+                    let m = board.san_to_move(san, &mg).unwrap();
+                    assert!(board.make(m, &mg));
+                }
+            }
+
+            let repeating = board.san_to_move("Qd2", &mg).unwrap();
+            let other = board.san_to_move("Qd3", &mg).unwrap();
+            assert_eq!(board.would_be_threefold(repeating, &mg), expected);
+            assert!(!board.would_be_threefold(other, &mg));
+            assert_eq!(board.history.len(), 4 * shuffles);
+        }
+    }
 }
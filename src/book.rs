@@ -0,0 +1,288 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// Support for opening books in the Polyglot format. A book is a file of
+// 16-byte entries, sorted by the Polyglot key of the position. Polyglot
+// uses its own Zobrist numbers and rules, so the key has nothing to do
+// with the engine's internal Zobrist key.
+
+mod random;
+
+use crate::{
+    board::{
+        defs::{Pieces, BB_SQUARES},
+        Board,
+    },
+    defs::{Castling, Piece, Sides},
+    movegen::{
+        defs::{Move, MoveList, MoveType},
+        MoveGenerator,
+    },
+};
+use rand::Rng;
+use random::RANDOM64;
+use std::{fs, io, path::Path};
+
+const ENTRY_SIZE: usize = 16;
+const RANDOM_CASTLING: usize = 768;
+const RANDOM_EN_PASSANT: usize = 772;
+const RANDOM_TURN: usize = 780;
+
+// Polyglot orders the pieces as pawn, knight, bishop, rook, queen, king;
+// this maps the engine's piece numbers onto that order.
+const POLYGLOT_PIECE: [usize; 6] = [5, 4, 3, 2, 1, 0];
+
+// Promotion pieces as encoded in bits 12-14 of a book move.
+const POLYGLOT_PROMOTION: [Piece; 5] = [
+    Pieces::NONE,
+    Pieces::KNIGHT,
+    Pieces::BISHOP,
+    Pieces::ROOK,
+    Pieces::QUEEN,
+];
+
+#[derive(Copy, Clone)]
+pub struct BookEntry {
+    pub key: u64,
+    pub mv: u16,
+    pub weight: u16,
+}
+
+pub struct Book {
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    // Loads a Polyglot book from disk.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(Self::from_bytes(&fs::read(path)?))
+    }
+
+    // Reads book entries from raw bytes. All numbers are big-endian. The
+    // last four bytes of each entry hold "learn" data, which is ignored.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let entries = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|e| BookEntry {
+                key: u64::from_be_bytes([e[0], e[1], e[2], e[3], e[4], e[5], e[6], e[7]]),
+                mv: u16::from_be_bytes([e[8], e[9]]),
+                weight: u16::from_be_bytes([e[10], e[11]]),
+            })
+            .collect();
+
+        Self { entries }
+    }
+
+    // Computes the Polyglot key of the position on the board.
+    pub fn key(board: &Board) -> u64 {
+        let mut key = 0;
+
+        for side in [Sides::WHITE, Sides::BLACK] {
+            for (piece, polyglot_piece) in POLYGLOT_PIECE.iter().enumerate() {
+                // Polyglot puts black before white for each piece kind.
+                let kind = polyglot_piece * 2 + (side == Sides::WHITE) as usize;
+                let mut bitboard = board.get_pieces(piece, side);
+
+                while bitboard > 0 {
+                    let square = bitboard.trailing_zeros() as usize;
+                    bitboard &= bitboard - 1;
+                    key ^= RANDOM64[kind * 64 + square];
+                }
+            }
+        }
+
+        for (i, right) in Castling::RIGHTS.iter().enumerate() {
+            if board.game_state.castling & right > 0 {
+                key ^= RANDOM64[RANDOM_CASTLING + i];
+            }
+        }
+
+        if let Some(file) = Book::en_passant_file(board) {
+            key ^= RANDOM64[RANDOM_EN_PASSANT + file];
+        }
+
+        if board.us() == Sides::WHITE {
+            key ^= RANDOM64[RANDOM_TURN];
+        }
+
+        key
+    }
+
+    // Polyglot only hashes the en-passant file if a pawn of the side to
+    // move actually stands next to the pawn that just made a double step.
+    fn en_passant_file(board: &Board) -> Option<usize> {
+        let ep = board.game_state.en_passant? as usize;
+        let file = ep % 8;
+        let us = board.us();
+        let beside = if us == Sides::WHITE { ep - 8 } else { ep + 8 };
+
+        let mut capturers = 0;
+        if file > 0 {
+            capturers |= BB_SQUARES[beside - 1];
+        }
+        if file < 7 {
+            capturers |= BB_SQUARES[beside + 1];
+        }
+
+        (board.get_pieces(Pieces::PAWN, us) & capturers > 0).then_some(file)
+    }
+
+    // Returns all book entries for the given key.
+    pub fn entries(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|e| e.key < key);
+        let end = self.entries.partition_point(|e| e.key <= key);
+        &self.entries[start..end]
+    }
+
+    // Picks a book move at random, with chances according to the weights.
+    pub fn weighted_move(&self, board: &Board, mg: &MoveGenerator) -> Option<Move> {
+        let candidates: Vec<(u32, Move)> = self
+            .entries(Book::key(board))
+            .iter()
+            .filter_map(|e| Book::legal_move(e.mv, board, mg).map(|m| (e.weight as u32, m)))
+            .collect();
+
+        let total: u32 = candidates.iter().map(|(weight, _)| weight).sum();
+        if total == 0 {
+            return candidates.first().map(|(_, m)| *m);
+        }
+
+        let mut pick = rand::thread_rng().gen_range(0..total);
+        for (weight, m) in candidates {
+            if pick < weight {
+                return Some(m);
+            }
+            pick -= weight;
+        }
+
+        None
+    }
+
+    // Converts a book move into a legal move in this position. Book moves
+    // encode castling as the king taking its own rook. A move that isn't
+    // legal (which can happen on a key collision) returns None.
+    fn legal_move(book_move: u16, board: &Board, mg: &MoveGenerator) -> Option<Move> {
+        let bm = book_move as usize;
+        let to = bm & 0x3F;
+        let from = (bm >> 6) & 0x3F;
+        let promotion = POLYGLOT_PROMOTION.get((bm >> 12) & 0x7).copied()?;
+
+        let mut move_list = MoveList::new();
+        mg.generate_moves(board, &mut move_list, MoveType::All);
+
+        (0..move_list.len())
+            .map(|i| move_list.get_move(i))
+            .find(|m| {
+                let target = if m.castling() {
+                    board.castling_rook_move(m.to()).0
+                } else {
+                    m.to()
+                };
+                m.from() == from && target == to && m.promoted() == promotion
+            })
+            .filter(|m| board.clone().make(*m, mg))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_after(moves: &[&str]) -> u64 {
+        let mg = MoveGenerator::new();
+        let mut board = Board::new();
+        board.fen_read(None).unwrap();
+
+        for m in moves {
+            let mut move_list = MoveList::new();
+            mg.generate_moves(&board, &mut move_list, MoveType::All);
+            let mv = (0..move_list.len())
+                .map(|i| move_list.get_move(i))
+                .find(|x| x.as_string() == *m)
+                .unwrap();
+            assert!(board.make(mv, &mg));
+        }
+
+        Book::key(&board)
+    }
+
+    // Test positions and keys from the Polyglot book format specification.
+    #[test]
+    fn test_polyglot_keys() {
+        assert_eq!(key_after(&[]), 0x463B96181691FC9C);
+        assert_eq!(key_after(&["e2e4"]), 0x823C9B50FD114196);
+        assert_eq!(key_after(&["e2e4", "d7d5"]), 0x0756B94461C50FB0);
+        assert_eq!(key_after(&["e2e4", "d7d5", "e4e5"]), 0x662FAFB965DB29D4);
+        assert_eq!(key_after(&["e2e4", "d7d5", "e4e5", "f7f5"]), 0x22A48B5A8E47FF78);
+        assert_eq!(
+            key_after(&["e2e4", "d7d5", "e4e5", "f7f5", "e1e2"]),
+            0x652A607CA3F242C1
+        );
+        assert_eq!(
+            key_after(&["e2e4", "d7d5", "e4e5", "f7f5", "e1e2", "e8f7"]),
+            0x00FDD303C946BDD9
+        );
+        assert_eq!(
+            key_after(&["a2a4", "b7b5", "h2h4", "b5b4", "c2c4"]),
+            0x3C8123EA7B067637
+        );
+        assert_eq!(
+            key_after(&["a2a4", "b7b5", "h2h4", "b5b4", "c2c4", "b4c3", "a1a3"]),
+            0x5C3F9B829B279560
+        );
+    }
+
+    #[test]
+    fn test_book_probe() {
+        let mg = MoveGenerator::new();
+        let mut board = Board::new();
+        board.fen_read(None).unwrap();
+
+        // A tiny book with 1. e4 (weight 10) and 1. d4 (weight 5) for the
+        // start position, plus an entry for another position.
+        let entry = |key: u64, mv: u16, weight: u16| {
+            let mut bytes = key.to_be_bytes().to_vec();
+            bytes.extend(mv.to_be_bytes());
+            bytes.extend(weight.to_be_bytes());
+            bytes.extend([0; 4]);
+            bytes
+        };
+        let e2e4 = (12 << 6 | 28) as u16;
+        let d2d4 = (11 << 6 | 27) as u16;
+        let bytes = [
+            entry(0x463B96181691FC9C, e2e4, 10),
+            entry(0x463B96181691FC9C, d2d4, 5),
+            entry(0x823C9B50FD114196, d2d4, 1),
+        ]
+        .concat();
+        let book = Book::from_bytes(&bytes);
+
+        assert_eq!(book.entries(Book::key(&board)).len(), 2);
+
+        let weighted = book.weighted_move(&board, &mg).unwrap().as_string();
+        assert!(weighted == "e2e4" || weighted == "d2d4");
+
+        board.fen_read(Some("4k3/8/8/8/8/8/8/4K3 w - - 0 1")).unwrap();
+        assert!(book.weighted_move(&board, &mg).is_none());
+    }
+}
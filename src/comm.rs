@@ -22,17 +22,18 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 pub mod uci;
-// pub mod xboard;
+pub mod xboard;
 
 use crate::{
     board::Board,
     engine::defs::{EngineOption, Information},
     movegen::defs::Move,
-    search::defs::{SearchCurrentMove, SearchStats, SearchSummary},
+    search::defs::{SearchCurrentMove, SearchStats, SearchSummary, SharpInfo, TimeStats},
 };
 use crossbeam_channel::Sender;
 use std::sync::{Arc, Mutex};
 use uci::UciReport;
+use xboard::XboardReport;
 
 // These are the types of communication the engine is capable of.
 pub struct CommType;
@@ -65,12 +66,16 @@ pub enum CommControl {
     SearchCurrMove(SearchCurrentMove), // Transmit currently considered move.
     SearchStats(SearchStats),          // Transmit search Statistics.
     InfoString(String),                // Transmit general information.
-    BestMove(Move),                    // Transmit the engine's best move.
+    SharpInfo(SharpInfo),              // Transmit sharpness of a root move.
+    BestMove(Move, Option<Move>),      // Transmit the engine's best move and ponder move.
+    IllegalMove(String),               // Transmit that a received move was illegal.
 
     // Output to screen when running in a terminal window.
     PrintBoard,
     PrintHistory,
+    PrintPgn(String),
     PrintHelp,
+    PrintTimeStats(TimeStats),
 }
 
 // These are the commands a Comm module can create and send back to the
@@ -78,6 +83,7 @@ pub enum CommControl {
 #[derive(PartialEq, Clone)]
 pub enum CommReport {
     Uci(UciReport),
+    Xboard(XboardReport),
 }
 
 impl CommReport {
@@ -31,7 +31,8 @@ use crate::{
     misc::print,
     movegen::defs::Move,
     search::defs::{
-        GameTime, SearchCurrentMove, SearchStats, SearchSummary, CHECKMATE, CHECKMATE_THRESHOLD,
+        GameTime, ScoreBound, SearchCurrentMove, SearchStats, SearchSummary, SearchTunables,
+        TimeStats, CHECKMATE, CHECKMATE_THRESHOLD,
     },
 };
 use crossbeam_channel::{self, Sender};
@@ -57,6 +58,7 @@ pub enum UciReport {
     GoNodes(usize),
     GoGameTime(GameTime),
     GoPonder(GameTime),
+    GoSearchMoves(Vec<String>, Box<UciReport>),
     Stop,
     PonderHit,
     Quit,
@@ -64,13 +66,24 @@ pub enum UciReport {
     // Custom commands
     Board,
     History,
+    Pgn,
     Eval,
+    TimeStats,
+    SaveHash(String),
+    LoadHash(String),
     Help,
 
     // Empty or unknown command.
     Unknown,
 }
 
+// Keywords of the go-command. These end the list of moves following
+// "searchmoves".
+const GO_KEYWORDS: [&str; 11] = [
+    "ponder", "infinite", "depth", "movetime", "nodes", "mate", "wtime", "btime", "winc", "binc",
+    "movestogo",
+];
+
 // This struct is used to instantiate the Comm Console module.
 pub struct Uci {
     control_handle: Option<JoinHandle<()>>,
@@ -203,15 +216,18 @@ impl Uci {
                     CommControl::SearchCurrMove(current) => Uci::search_currmove(&current),
                     CommControl::SearchStats(stats) => Uci::search_stats(&stats),
                     CommControl::InfoString(msg) => Uci::info_string(&msg),
-                    CommControl::BestMove(bm) => Uci::best_move(&bm),
+                    CommControl::SharpInfo(info) => Uci::info_string(&info.as_string()),
+                    CommControl::BestMove(bm, ponder) => Uci::best_move(&bm, ponder),
 
                     // Custom prints for use in the console.
                     CommControl::PrintBoard => Uci::print_board(&t_board),
                     CommControl::PrintHistory => Uci::print_history(&t_board),
+                    CommControl::PrintPgn(pgn) => print!("{pgn}"),
                     CommControl::PrintHelp => Uci::print_help(),
+                    CommControl::PrintTimeStats(stats) => Uci::print_time_stats(&stats),
 
                     // Comm Control commands that are not (yet) used.
-                    CommControl::Update => (),
+                    CommControl::Update | CommControl::IllegalMove(_) => (),
                 }
             }
         });
@@ -246,7 +262,15 @@ impl Uci {
             // Custom commands
             cmd if cmd == "board" => CommReport::Uci(UciReport::Board),
             cmd if cmd == "history" => CommReport::Uci(UciReport::History),
+            cmd if cmd == "pgn" => CommReport::Uci(UciReport::Pgn),
             cmd if cmd == "eval" => CommReport::Uci(UciReport::Eval),
+            cmd if cmd == "timestats" => CommReport::Uci(UciReport::TimeStats),
+            cmd if cmd.starts_with("savehash ") => {
+                CommReport::Uci(UciReport::SaveHash(cmd["savehash".len()..].trim().to_string()))
+            }
+            cmd if cmd.starts_with("loadhash ") => {
+                CommReport::Uci(UciReport::LoadHash(cmd["loadhash".len()..].trim().to_string()))
+            }
             cmd if cmd == "help" => CommReport::Uci(UciReport::Help),
 
             // Everything else is ignored.
@@ -303,12 +327,24 @@ impl Uci {
             MovesToGo,
         }
 
-        let parts: Vec<String> = cmd.split_whitespace().map(|s| s.to_string()).collect();
+        let mut parts: Vec<String> = cmd.split_whitespace().map(|s| s.to_string()).collect();
         let mut report = CommReport::Uci(UciReport::Unknown);
         let mut token = Tokens::Nothing;
         let mut game_time = GameTime::new(0, 0, 0, 0, None);
         let mut ponder = false;
 
+        // "searchmoves" is followed by the moves to restrict the search
+        // to, up to the next keyword. Take these out first, and parse the
+        // rest of the command as usual.
+        let mut search_moves: Vec<String> = Vec::new();
+        if let Some(start) = parts.iter().position(|p| p == "searchmoves") {
+            let end = parts[start + 1..]
+                .iter()
+                .position(|p| GO_KEYWORDS.contains(&&p[..]))
+                .map_or(parts.len(), |n| start + 1 + n);
+            search_moves = parts.drain(start..end).skip(1).collect();
+        }
+
         for p in parts {
             match p {
                 t if t == "go" => report = CommReport::Uci(UciReport::GoInfinite),
@@ -369,6 +405,14 @@ impl Uci {
             }
         }
 
+        // Wrap the go-command if the search is restricted to certain moves.
+        if let CommReport::Uci(go) = &report {
+            if !search_moves.is_empty() && *go != UciReport::Unknown {
+                let go = Box::new(go.clone());
+                report = CommReport::Uci(UciReport::GoSearchMoves(search_moves, go));
+            }
+        }
+
         report
     } // end parse_go()
 
@@ -383,6 +427,7 @@ impl Uci {
         let mut token = Tokens::Nothing;
         let mut name = String::from(""); // Option name provided by the UCI command.
         let mut value = String::from(""); // Option value provided by the UCI command.
+        let mut raw_value = String::from(""); // The value as given, for file names.
         let mut eon = EngineOptionName::Nothing; // Engine Option Name to send to the engine.
 
         for p in parts {
@@ -392,7 +437,10 @@ impl Uci {
                 t if t == "value" => token = Tokens::Value,
                 _ => match token {
                     Tokens::Name => name = format!("{name} {p}"),
-                    Tokens::Value => value = p.to_lowercase(),
+                    Tokens::Value => {
+                        value = p.to_lowercase();
+                        raw_value = format!("{raw_value} {p}");
+                    }
                     Tokens::Nothing => (),
                 },
             }
@@ -405,6 +453,25 @@ impl Uci {
                 "hash" => eon = EngineOptionName::Hash(value),
                 "clear hash" => eon = EngineOptionName::ClearHash,
                 "sharp margin" => eon = EngineOptionName::SharpMargin(value),
+                "pawn hash" => eon = EngineOptionName::PawnHash(value),
+                "uci_chess960" => eon = EngineOptionName::Chess960(value),
+                "multipv" => eon = EngineOptionName::MultiPv(value),
+                "move overhead" => eon = EngineOptionName::MoveOverhead(value),
+                "contempt" => eon = EngineOptionName::Contempt(value),
+                "bookfile" => eon = EngineOptionName::BookFile(raw_value.trim().to_string()),
+                "usenodestime" => eon = EngineOptionName::UseNodesTime(value),
+                "sharponly" => eon = EngineOptionName::SharpOnly(value),
+                "sharpdepthcap" => eon = EngineOptionName::SharpDepthCap(value),
+                "repetitionpenalty" => eon = EngineOptionName::RepetitionPenalty(value),
+                "maxcheckextensions" => eon = EngineOptionName::MaxCheckExtensions(value),
+                "uci_analysemode" => eon = EngineOptionName::AnalyseMode(value),
+                "killerslots" => eon = EngineOptionName::KillerSlots(value),
+                "aspirationwindow" => eon = EngineOptionName::AspirationWindow(value),
+                "aspirationgrowth" => eon = EngineOptionName::AspirationGrowth(value),
+                "lmrmindepth" => eon = EngineOptionName::LmrMinDepth(value),
+                n if cfg!(feature = "tune") && SearchTunables::new().get(n).is_some() => {
+                    eon = EngineOptionName::Tune(n.to_string(), value)
+                }
                 _ => (),
             }
         }
@@ -428,6 +495,8 @@ impl Uci {
             let ui_element = match o.ui_element {
                 UiElement::Spin => String::from("type spin"),
                 UiElement::Button => String::from("type button"),
+                UiElement::Check => String::from("type check"),
+                UiElement::String => String::from("type string"),
             };
 
             let value_default = if let Some(v) = &o.default {
@@ -486,6 +555,13 @@ impl Uci {
             format!("cp {}", s.cp)
         };
 
+        // A score from a failed aspiration window is only a bound.
+        let score = match s.bound {
+            ScoreBound::Exact => score,
+            ScoreBound::Lower => format!("{score} lowerbound"),
+            ScoreBound::Upper => format!("{score} upperbound"),
+        };
+
         // Report depth and seldepth (if available).
         let depth = if s.seldepth > 0 {
             format!("depth {} seldepth {}", s.depth, s.seldepth)
@@ -503,8 +579,8 @@ impl Uci {
         let pv = s.pv_as_string();
 
         let info = format!(
-            "info score {} {} time {} nodes {} nps {}{}pv {}",
-            score, depth, s.time, s.nodes, s.nps, hash_full, pv,
+            "info {} multipv {} score {} time {} nodes {} nps {}{}pv {}",
+            depth, s.multipv, score, s.time, s.nodes, s.nps, hash_full, pv,
         );
 
         println!("{info}");
@@ -535,11 +611,19 @@ impl Uci {
         println!("info string {msg}");
     }
 
-    fn best_move(m: &Move) {
+    fn best_move(m: &Move, ponder: Option<Move>) {
+        println!("{}", Uci::best_move_line(m, ponder));
+    }
+
+    // The "bestmove" line, with the move to ponder on if there is one.
+    pub fn best_move_line(m: &Move, ponder: Option<Move>) -> String {
         if m.get_move() == 0 {
-            println!("bestmove 0000");
-        } else {
-            println!("bestmove {}", m.as_string());
+            return String::from("bestmove 0000");
+        }
+
+        match ponder.filter(|p| p.get_move() != 0) {
+            Some(p) => format!("bestmove {} ponder {}", m.as_string(), p.as_string()),
+            None => format!("bestmove {}", m.as_string()),
         }
     }
 }
@@ -551,6 +635,10 @@ impl Uci {
         print::position(&board.lock().expect(ErrFatal::LOCK), None);
     }
 
+    fn print_time_stats(stats: &TimeStats) {
+        println!("{}", print::time_stats_as_string(stats));
+    }
+
     fn print_history(board: &Arc<Mutex<Board>>) {
         let mtx_board = board.lock().expect(ErrFatal::LOCK);
         let length = mtx_board.history.len();
@@ -577,7 +665,12 @@ impl Uci {
         println!("help      :   This help information.");
         println!("board     :   Print the current board state.");
         println!("history   :   Print a list of past board states.");
+        println!("pgn       :   Print the game played so far as PGN.");
         println!("eval      :   Print evaluation for side to move.");
+        println!("timestats :   Print time management statistics.");
+        println!("savehash f:   Save the hash table to file f.");
+        println!("loadhash f:   Load the hash table from file f, saved with");
+        println!("              the same Hash size.");
         println!("exit      :   Quit/Exit the engine.");
         println!();
     }
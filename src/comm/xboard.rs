@@ -21,19 +21,344 @@ You should have received a copy of the GNU General Public License along
 with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
-use super::IComm;
-// use crate::{board::Board, movegen::MoveGenerator};
+// This file implements the Xboard (CECP) communication module.
 
-pub struct Xboard;
+use super::{CommControl, CommReport, CommType, IComm};
+use crate::{
+    board::Board,
+    defs::About,
+    engine::defs::{EngineOption, ErrFatal, Information},
+    misc::print,
+    movegen::defs::Move,
+    search::defs::{SearchSummary, TimeStats},
+};
+use crossbeam_channel::{self, Sender};
+use std::{
+    io::{self},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+};
 
+// Input will be turned into a report, which wil be sent to the engine. The
+// main engine thread will react accordingly.
+#[derive(PartialEq, Clone)]
+pub enum XboardReport {
+    // Xboard commands
+    Xboard,
+    ProtoVer(u8),
+    New,
+    Force,
+    Go,
+    UserMove(String),
+    MoveNow,
+    Time(u128),
+    Otim(u128),
+    Level(usize, u128, u128),
+    Result(String),
+    Quit,
+
+    // Empty or unknown command.
+    Unknown,
+}
+
+// This struct is used to instantiate the Comm Xboard module.
+pub struct Xboard {
+    control_handle: Option<JoinHandle<()>>,
+    report_handle: Option<JoinHandle<()>>,
+    control_tx: Option<Sender<CommControl>>,
+}
+
+// Public functions
 impl Xboard {
+    // Create a new Xboard module.
     pub fn new() -> Self {
-        Self {}
+        Self {
+            control_handle: None,
+            report_handle: None,
+            control_tx: None,
+        }
     }
 }
 
+impl Default for Xboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Any communication module must implement the trait IComm.
 impl IComm for Xboard {
-    fn start(&self) {
-        println!("XBoard communication.");
+    fn init(
+        &mut self,
+        report_tx: Sender<Information>,
+        board: Arc<Mutex<Board>>,
+        _options: Arc<Vec<EngineOption>>,
+    ) {
+        // Start threads
+        self.report_thread(report_tx);
+        self.control_thread(board);
+    }
+
+    // The creator of the Comm module can use this function to send
+    // messages or commands into the Control thread.
+    fn send(&self, msg: CommControl) {
+        if let Some(tx) = &self.control_tx {
+            tx.send(msg).expect(ErrFatal::CHANNEL);
+        }
+    }
+
+    // After the engine sends 'quit' to the control thread, it will call
+    // wait_for_shutdown() and then wait here until shutdown is completed.
+    fn wait_for_shutdown(&mut self) {
+        if let Some(h) = self.report_handle.take() {
+            h.join().expect(ErrFatal::THREAD);
+        }
+
+        if let Some(h) = self.control_handle.take() {
+            h.join().expect(ErrFatal::THREAD);
+        }
+    }
+
+    // This function just returns the name of the communication protocol.
+    fn get_protocol_name(&self) -> &'static str {
+        CommType::XBOARD
+    }
+}
+
+// Implement the report thread
+impl Xboard {
+    // The Report thread sends incoming data to the engine thread.
+    fn report_thread(&mut self, report_tx: Sender<Information>) {
+        // Create thread-local variables
+        let mut t_incoming_data = String::from("");
+        let t_report_tx = report_tx; // Report sender
+
+        // Actual thread creation.
+        let report_handle = thread::spawn(move || {
+            let mut quit = false;
+
+            // Keep running as long as 'quit' is not detected.
+            while !quit {
+                // Get data from stdin.
+                io::stdin()
+                    .read_line(&mut t_incoming_data)
+                    .expect(ErrFatal::READ_IO);
+
+                // Create a report from the incoming data.
+                let new_report = Xboard::create_report(&t_incoming_data);
+
+                // Check if the created report is valid, so it is something
+                // the engine will understand.
+                if new_report.is_valid() {
+                    // Send it to the engine thread.
+                    t_report_tx
+                        .send(Information::Comm(new_report.clone()))
+                        .expect(ErrFatal::HANDLE);
+
+                    // Terminate the reporting thread if "Quit" was detected.
+                    quit = new_report == CommReport::Xboard(XboardReport::Quit);
+                }
+
+                // Clear for next input
+                t_incoming_data = String::from("");
+            }
+        });
+
+        // Store the handle.
+        self.report_handle = Some(report_handle);
+    }
+}
+
+// Implement the control thread
+impl Xboard {
+    // The control thread receives commands from the engine thread.
+    fn control_thread(&mut self, board: Arc<Mutex<Board>>) {
+        // Create an incoming channel for the control thread.
+        let (control_tx, control_rx) = crossbeam_channel::unbounded::<CommControl>();
+
+        // Create the control thread.
+        let control_handle = thread::spawn(move || {
+            let mut quit = false;
+            let t_board = Arc::clone(&board);
+
+            // Keep running as long as Quit is not received.
+            while !quit {
+                let control = control_rx.recv().expect(ErrFatal::CHANNEL);
+
+                // Perform command as sent by the engine thread.
+                match control {
+                    CommControl::Identify => Xboard::features(),
+                    CommControl::Quit => quit = true,
+                    CommControl::SearchSummary(summary) => Xboard::search_summary(&summary),
+                    CommControl::InfoString(msg) => Xboard::comment(&msg),
+                    CommControl::SharpInfo(info) => Xboard::comment(&info.as_string()),
+                    CommControl::BestMove(bm, _) => Xboard::best_move(&bm),
+                    CommControl::IllegalMove(m) => Xboard::illegal_move(&m),
+
+                    // Custom prints for use in the console.
+                    CommControl::PrintBoard => Xboard::print_board(&t_board),
+                    CommControl::PrintTimeStats(stats) => Xboard::print_time_stats(&stats),
+
+                    // Comm Control commands that are not (yet) used.
+                    CommControl::Update
+                    | CommControl::Ready
+                    | CommControl::SearchCurrMove(_)
+                    | CommControl::SearchStats(_)
+                    | CommControl::PrintHistory
+                    | CommControl::PrintPgn(_)
+                    | CommControl::PrintHelp => (),
+                }
+            }
+        });
+
+        // Store handle and control sender.
+        self.control_handle = Some(control_handle);
+        self.control_tx = Some(control_tx);
+    }
+}
+
+// Private functions for this module.
+impl Xboard {
+    // This function turns the incoming data into XboardReports which the
+    // engine is able to understand and react to.
+    fn create_report(input: &str) -> CommReport {
+        // Trim CR/LF so only the usable characters remain.
+        let i = input.trim().to_string();
+        let parts: Vec<&str> = i.split_whitespace().collect();
+        let arg = |n: usize| parts.get(n).copied().unwrap_or("");
+
+        let report = match arg(0) {
+            "xboard" => XboardReport::Xboard,
+            "protover" => XboardReport::ProtoVer(arg(1).parse::<u8>().unwrap_or(1)),
+            "new" => XboardReport::New,
+            "force" => XboardReport::Force,
+            "go" => XboardReport::Go,
+            "usermove" if !arg(1).is_empty() => XboardReport::UserMove(arg(1).to_string()),
+            "?" => XboardReport::MoveNow,
+            "time" => XboardReport::Time(arg(1).parse::<u128>().unwrap_or(0)),
+            "otim" => XboardReport::Otim(arg(1).parse::<u128>().unwrap_or(0)),
+            "level" => Xboard::parse_level(&parts),
+            "result" => XboardReport::Result(parts[1..].join(" ")),
+            "quit" => XboardReport::Quit,
+
+            // Everything else is ignored.
+            _ => XboardReport::Unknown,
+        };
+
+        CommReport::Xboard(report)
+    }
+
+    // Parses "level MPS BASE INC". The base time is given in minutes, or
+    // as "minutes:seconds". The increment is given in seconds, and may
+    // have a fractional part. Both are converted to milliseconds.
+    fn parse_level(parts: &[&str]) -> XboardReport {
+        if parts.len() < 4 {
+            return XboardReport::Unknown;
+        }
+
+        let moves_per_session = parts[1].parse::<usize>().unwrap_or(0);
+        let mut base = parts[2].split(':').map(|x| x.parse::<u128>().unwrap_or(0));
+        let minutes = base.next().unwrap_or(0);
+        let seconds = base.next().unwrap_or(0);
+        let increment = parts[3].parse::<f64>().unwrap_or(0.0);
+
+        XboardReport::Level(
+            moves_per_session,
+            (minutes * 60 + seconds) * 1000,
+            (increment * 1000.0).round() as u128,
+        )
+    }
+}
+
+// Implements Xboard responses to send to the GUI.
+impl Xboard {
+    fn features() {
+        println!("feature done=0");
+        println!("feature myname=\"{} {}\"", About::ENGINE, About::VERSION);
+        println!("feature usermove=1 setboard=0 ping=0 playother=0 san=0");
+        println!("feature colors=0 analyze=0 sigint=0 sigterm=0 reuse=1");
+        println!("feature done=1");
+    }
+
+    // Thinking output: "ply score time nodes pv", with the time given in
+    // centiseconds.
+    fn search_summary(s: &SearchSummary) {
+        println!(
+            "{} {} {} {} {}",
+            s.depth,
+            s.cp,
+            s.time / 10,
+            s.nodes,
+            s.pv_as_string()
+        );
+    }
+
+    fn comment(msg: &str) {
+        println!("# {msg}");
+    }
+
+    fn best_move(m: &Move) {
+        if m.get_move() != 0 {
+            println!("move {}", m.as_string());
+        }
+    }
+
+    fn illegal_move(m: &str) {
+        println!("Illegal move: {m}");
+    }
+
+    fn print_board(board: &Arc<Mutex<Board>>) {
+        print::position(&board.lock().expect(ErrFatal::LOCK), None);
+    }
+
+    fn print_time_stats(stats: &TimeStats) {
+        println!("{}", print::time_stats_as_string(stats));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cecp_session() {
+        let session = [
+            "xboard",
+            "protover 2",
+            "accepted usermove",
+            "new",
+            "level 40 5 0",
+            "time 30000",
+            "otim 29500",
+            "usermove e2e4",
+            "force",
+            "usermove e7e5",
+            "go",
+            "level 0 2:30 0.5",
+            "result 1-0 {White mates}",
+            "quit",
+        ];
+
+        let expected = [
+            XboardReport::Xboard,
+            XboardReport::ProtoVer(2),
+            XboardReport::Unknown,
+            XboardReport::New,
+            XboardReport::Level(40, 300_000, 0),
+            XboardReport::Time(30000),
+            XboardReport::Otim(29500),
+            XboardReport::UserMove(String::from("e2e4")),
+            XboardReport::Force,
+            XboardReport::UserMove(String::from("e7e5")),
+            XboardReport::Go,
+            XboardReport::Level(0, 150_000, 500),
+            XboardReport::Result(String::from("1-0 {White mates}")),
+            XboardReport::Quit,
+        ];
+
+        for (line, report) in session.iter().zip(expected) {
+            let input = format!("{line}\n");
+            assert!(Xboard::create_report(&input) == CommReport::Xboard(report), "{line}");
+        }
     }
 }
@@ -66,7 +66,9 @@ impl Castling {
     pub const WQ: u8 = 2;
     pub const BK: u8 = 4;
     pub const BQ: u8 = 8;
-    pub const ALL: u8 = 15;
+
+    // All four rights, in the order their rook files are stored.
+    pub const RIGHTS: [u8; 4] = [Castling::WK, Castling::WQ, Castling::BK, Castling::BQ];
 }
 
 pub const EMPTY: u64 = 0;
@@ -77,7 +79,7 @@ pub const MAX_MOVE_RULE: u8 = 100; // 50/75 move rule
 
 // Define errors
 pub type EngineRunResult = Result<(), u8>;
-pub const ENGINE_RUN_ERRORS: [&str; 8] = [
+pub const ENGINE_RUN_ERRORS: [&str; 11] = [
     "FEN: Must have six parts",
     "FEN: Pieces and squares incorrect",
     "FEN: Color selection incorrect",
@@ -85,5 +87,12 @@ pub const ENGINE_RUN_ERRORS: [&str; 8] = [
     "FEN: En-passant square incorrect",
     "FEN: Half-move clock incorrect",
     "FEN: Full-move number incorrect",
-    "XBoard not yet implemented.",
+    "PGN: File could not be read",
+    "PGN: Move not legal in the game's position",
+    "Eval params: File could not be read",
+    "Eval params: Unknown weight or invalid value",
 ];
+pub const ERR_PGN_FILE: u8 = 7;
+pub const ERR_PGN_MOVE: u8 = 8;
+pub const ERR_EVAL_PARAMS_FILE: u8 = 9;
+pub const ERR_EVAL_PARAMS_VALUE: u8 = 10;
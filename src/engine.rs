@@ -31,15 +31,19 @@ mod utils;
 
 use crate::{
     board::Board,
-    comm::{uci::Uci, CommControl, CommType, IComm},
+    book::Book,
+    comm::{uci::Uci, xboard::Xboard, CommControl, CommType, IComm},
     defs::EngineRunResult,
     engine::defs::{
         EngineOption, EngineOptionDefaults, EngineOptionName, ErrFatal, Information, Settings,
-        UiElement,
+        PonderState, UiElement, XboardState,
     },
     misc::{cmdline::CmdLine, perft},
     movegen::{MoveGenerator, defs::Move},
-    search::{defs::SearchControl, SearchManager},
+    search::{
+        defs::{SearchControl, SearchTunables, TimeControl},
+        SearchManager,
+    },
 };
 use crossbeam_channel::Receiver;
 use std::sync::{Arc, Mutex, RwLock};
@@ -48,7 +52,10 @@ use transposition::{PerftData, SearchData, TT};
 #[cfg(feature = "extra")]
 use crate::{
     board::defs::Pieces,
-    extra::{testsuite, wizardry},
+    extra::{
+        epd::{self, EpdLimit},
+        testsuite, wizardry,
+    },
 };
 
 // This struct holds the chess engine and its functions, so they are not
@@ -65,9 +72,11 @@ pub struct Engine {
     mg: Arc<MoveGenerator>,                 // Move Generator.
     info_rx: Option<Receiver<Information>>, // Receiver for incoming information.
     search: SearchManager,                  // Search manager (active).
-    tmp_no_xboard: bool,                    // Temporary variable to disable xBoard
-    pondering: bool,                        // If ponder is active
-    delayed_bestmove: Option<Move>,         // 
+    xboard: XboardState,                    // Game state when playing through XBoard.
+    ponder: PonderState,                    // Ponder search and its held back move.
+    search_moves: Vec<Move>,                // Root moves for the next "go".
+    book: Option<Book>,                     // Opening book, if loaded.
+    time_control: Option<TimeControl>,      // Time control of the current game.
 }
 
 impl Engine {
@@ -78,14 +87,10 @@ impl Engine {
 
         // Create the command-line object.
         let cmdline = CmdLine::new();
-        let mut is_xboard = false;
 
         // Create the communication interface
         let comm: Box<dyn IComm> = match &cmdline.comm()[..] {
-            CommType::XBOARD => {
-                is_xboard = true;
-                Box::new(Uci::new())
-            }
+            CommType::XBOARD => Box::new(Xboard::new()),
             CommType::UCI => Box::new(Uci::new()),
             _ => panic!("{}", ErrFatal::CREATE_COMM),
         };
@@ -101,7 +106,7 @@ impl Engine {
         };
 
         // List of options that should be announced to the GUI.
-        let options = vec![
+        let mut options = vec![
             EngineOption::new(
                 EngineOptionName::HASH,
                 UiElement::Spin,
@@ -130,19 +135,154 @@ impl Engine {
                 Some("1".to_string()),
                 Some("64".to_string()),
             ),
+            EngineOption::new(
+                EngineOptionName::PAWN_HASH,
+                UiElement::Spin,
+                Some(EngineOptionDefaults::PAWN_HASH_DEFAULT.to_string()),
+                Some(EngineOptionDefaults::PAWN_HASH_MIN.to_string()),
+                Some(EngineOptionDefaults::PAWN_HASH_MAX.to_string()),
+            ),
+            EngineOption::new(
+                EngineOptionName::CHESS960,
+                UiElement::Check,
+                Some(EngineOptionDefaults::CHESS960_DEFAULT.to_string()),
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionName::MULTI_PV,
+                UiElement::Spin,
+                Some(EngineOptionDefaults::MULTI_PV_DEFAULT.to_string()),
+                Some(EngineOptionDefaults::MULTI_PV_MIN.to_string()),
+                Some(EngineOptionDefaults::MULTI_PV_MAX.to_string()),
+            ),
+            EngineOption::new(
+                EngineOptionName::MOVE_OVERHEAD,
+                UiElement::Spin,
+                Some(EngineOptionDefaults::MOVE_OVERHEAD_DEFAULT.to_string()),
+                Some(EngineOptionDefaults::MOVE_OVERHEAD_MIN.to_string()),
+                Some(EngineOptionDefaults::MOVE_OVERHEAD_MAX.to_string()),
+            ),
+            EngineOption::new(
+                EngineOptionName::CONTEMPT,
+                UiElement::Spin,
+                Some(EngineOptionDefaults::CONTEMPT_DEFAULT.to_string()),
+                Some(EngineOptionDefaults::CONTEMPT_MIN.to_string()),
+                Some(EngineOptionDefaults::CONTEMPT_MAX.to_string()),
+            ),
+            EngineOption::new(
+                EngineOptionName::BOOK_FILE,
+                UiElement::String,
+                Some(EngineOptionDefaults::BOOK_FILE_DEFAULT.to_string()),
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionName::USE_NODES_TIME,
+                UiElement::Check,
+                Some(EngineOptionDefaults::USE_NODES_TIME_DEFAULT.to_string()),
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionName::SHARP_ONLY,
+                UiElement::Check,
+                Some(EngineOptionDefaults::SHARP_ONLY_DEFAULT.to_string()),
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionName::SHARP_DEPTH_CAP,
+                UiElement::Spin,
+                Some(EngineOptionDefaults::SHARP_DEPTH_CAP_DEFAULT.to_string()),
+                Some(EngineOptionDefaults::SHARP_DEPTH_CAP_MIN.to_string()),
+                Some(EngineOptionDefaults::SHARP_DEPTH_CAP_MAX.to_string()),
+            ),
+            EngineOption::new(
+                EngineOptionName::REPETITION_PENALTY,
+                UiElement::Spin,
+                Some(EngineOptionDefaults::REPETITION_PENALTY_DEFAULT.to_string()),
+                Some(EngineOptionDefaults::REPETITION_PENALTY_MIN.to_string()),
+                Some(EngineOptionDefaults::REPETITION_PENALTY_MAX.to_string()),
+            ),
+            EngineOption::new(
+                EngineOptionName::MAX_CHECK_EXTENSIONS,
+                UiElement::Spin,
+                Some(EngineOptionDefaults::MAX_CHECK_EXTENSIONS_DEFAULT.to_string()),
+                Some(EngineOptionDefaults::MAX_CHECK_EXTENSIONS_MIN.to_string()),
+                Some(EngineOptionDefaults::MAX_CHECK_EXTENSIONS_MAX.to_string()),
+            ),
+            EngineOption::new(
+                EngineOptionName::ANALYSE_MODE,
+                UiElement::Check,
+                Some(EngineOptionDefaults::ANALYSE_MODE_DEFAULT.to_string()),
+                None,
+                None,
+            ),
+            EngineOption::new(
+                EngineOptionName::KILLER_SLOTS,
+                UiElement::Spin,
+                Some(EngineOptionDefaults::KILLER_SLOTS_DEFAULT.to_string()),
+                Some(EngineOptionDefaults::KILLER_SLOTS_MIN.to_string()),
+                Some(EngineOptionDefaults::KILLER_SLOTS_MAX.to_string()),
+            ),
+            EngineOption::new(
+                EngineOptionName::ASPIRATION_WINDOW,
+                UiElement::Spin,
+                Some(EngineOptionDefaults::ASPIRATION_WINDOW_DEFAULT.to_string()),
+                Some(EngineOptionDefaults::ASPIRATION_WINDOW_MIN.to_string()),
+                Some(EngineOptionDefaults::ASPIRATION_WINDOW_MAX.to_string()),
+            ),
+            EngineOption::new(
+                EngineOptionName::ASPIRATION_GROWTH,
+                UiElement::Spin,
+                Some(EngineOptionDefaults::ASPIRATION_GROWTH_DEFAULT.to_string()),
+                Some(EngineOptionDefaults::ASPIRATION_GROWTH_MIN.to_string()),
+                Some(EngineOptionDefaults::ASPIRATION_GROWTH_MAX.to_string()),
+            ),
+            EngineOption::new(
+                EngineOptionName::LMR_MIN_DEPTH,
+                UiElement::Spin,
+                Some(EngineOptionDefaults::LMR_MIN_DEPTH_DEFAULT.to_string()),
+                Some(EngineOptionDefaults::LMR_MIN_DEPTH_MIN.to_string()),
+                Some(EngineOptionDefaults::LMR_MIN_DEPTH_MAX.to_string()),
+            ),
         ];
 
+        // Search constants are only announced when built for tuning.
+        let tunables = SearchTunables::new();
+        if cfg!(feature = "tune") {
+            for (name, min, max) in SearchTunables::RANGES {
+                options.push(EngineOption::new(
+                    name,
+                    UiElement::Spin,
+                    tunables.get(name).map(|v| v.to_string()),
+                    Some(min.to_string()),
+                    Some(max.to_string()),
+                ));
+            }
+        }
+
         // Initialize correct TT.
         let tt_perft: Arc<Mutex<TT<PerftData>>>;
         let tt_search: Arc<RwLock<TT<SearchData>>>;
         if cmdline.perft() > 0 {
-            tt_perft = Arc::new(Mutex::new(TT::<PerftData>::new(tt_size)));
+            let perft_tt_size = if cmdline.has_perft_hash() { tt_size } else { 0 };
+            tt_perft = Arc::new(Mutex::new(TT::<PerftData>::new(perft_tt_size)));
             tt_search = Arc::new(RwLock::new(TT::<SearchData>::new(0)));
         } else {
             tt_perft = Arc::new(Mutex::new(TT::<PerftData>::new(0)));
             tt_search = Arc::new(RwLock::new(TT::<SearchData>::new(tt_size)));
         };
 
+        // Create the main board, with the pawn hash table its clones share.
+        let board = Board::new();
+        board
+            .pawn_table
+            .write()
+            .expect(ErrFatal::LOCK)
+            .resize(EngineOptionDefaults::PAWN_HASH_DEFAULT);
+
         // Create the engine itself.
         Self {
             quit: false,
@@ -151,35 +291,48 @@ impl Engine {
                 quiet,
                 tt_size,
                 sharp_margin: EngineOptionDefaults::SHARP_MARGIN_DEFAULT,
+                chess960: EngineOptionDefaults::CHESS960_DEFAULT,
+                multi_pv: EngineOptionDefaults::MULTI_PV_DEFAULT,
+                move_overhead: EngineOptionDefaults::MOVE_OVERHEAD_DEFAULT,
+                contempt: EngineOptionDefaults::CONTEMPT_DEFAULT,
+                use_nodes_time: EngineOptionDefaults::USE_NODES_TIME_DEFAULT,
+                sharp_only: EngineOptionDefaults::SHARP_ONLY_DEFAULT,
+                sharp_depth_cap: EngineOptionDefaults::SHARP_DEPTH_CAP_DEFAULT,
+                repetition_penalty: EngineOptionDefaults::REPETITION_PENALTY_DEFAULT,
+                max_check_extensions: EngineOptionDefaults::MAX_CHECK_EXTENSIONS_DEFAULT,
+                analyse_mode: EngineOptionDefaults::ANALYSE_MODE_DEFAULT,
+                killer_slots: EngineOptionDefaults::KILLER_SLOTS_DEFAULT,
+                aspiration_window: EngineOptionDefaults::ASPIRATION_WINDOW_DEFAULT,
+                aspiration_growth: EngineOptionDefaults::ASPIRATION_GROWTH_DEFAULT,
+                lmr_min_depth: EngineOptionDefaults::LMR_MIN_DEPTH_DEFAULT,
+                tunables,
             },
             options: Arc::new(options),
             cmdline,
             comm,
-            board: Arc::new(Mutex::new(Board::new())),
+            board: Arc::new(Mutex::new(board)),
             mg: Arc::new(MoveGenerator::new()),
             tt_perft,
             tt_search,
             info_rx: None,
             search: SearchManager::new(threads),
-            tmp_no_xboard: is_xboard,
-            pondering: false,
-            delayed_bestmove: None,
+            xboard: XboardState::new(),
+            ponder: PonderState::new(),
+            search_moves: Vec::new(),
+            book: None,
+            time_control: None,
         }
     }
 
     // Run the engine.
     pub fn run(&mut self) -> EngineRunResult {
-        // This is temporary. Quit the engine immediately if anyone tries
-        // to start it in XBoard mode, as this is not implemented yet.
-        if self.tmp_no_xboard {
-            return Err(7);
-        }
-
         self.print_ascii_logo();
         self.print_about(&self.settings);
         println!();
 
-        // Setup position and abort if this fails.
+        // Load the evaluation weights before the position is set up, so
+        // its cached scores use them. Abort if either fails.
+        self.setup_eval_params()?;
         self.setup_position()?;
 
         // Run a specific action if requested...
@@ -188,13 +341,25 @@ impl Engine {
         // Run perft if requested.
         if self.cmdline.perft() > 0 {
             action_requested = true;
-            perft::run(
-                self.board.clone(),
-                self.cmdline.perft(),
-                Arc::clone(&self.mg),
-                Arc::clone(&self.tt_perft),
-                self.settings.tt_size > 0,
-            );
+            let hashed = self.cmdline.has_perft_hash() && self.settings.tt_size > 0;
+            if self.cmdline.has_divide() {
+                perft::run_divide(
+                    self.board.clone(),
+                    self.cmdline.perft(),
+                    Arc::clone(&self.mg),
+                    Arc::clone(&self.tt_perft),
+                    hashed,
+                );
+            } else {
+                perft::run(
+                    self.board.clone(),
+                    self.cmdline.perft(),
+                    Arc::clone(&self.mg),
+                    Arc::clone(&self.tt_perft),
+                    hashed,
+                    self.settings.threads,
+                );
+            }
         }
 
         // === Only available with "extra" features enabled. ===
@@ -222,6 +387,19 @@ impl Engine {
             self.tt_search.write().expect(ErrFatal::LOCK).resize(0);
             testsuite::run(Arc::clone(&self.tt_perft), self.settings.tt_size > 0);
         }
+
+        #[cfg(feature = "extra")]
+        // Run a best move / avoid move suite from an EPD file if
+        // requested, searching each position to a fixed depth, or for a
+        // fixed time if one is given.
+        if let Some(path) = self.cmdline.epd_suite() {
+            action_requested = true;
+            let limit = match self.cmdline.epd_time() {
+                Some(ms) => EpdLimit::MoveTime(ms),
+                None => EpdLimit::Depth(self.cmdline.epd_depth()),
+            };
+            epd::run_file(&path, limit, Arc::clone(&self.mg), Arc::clone(&self.tt_search));
+        }
         // =====================================================
 
         // In the main loop, the engine manages its resources so it will be
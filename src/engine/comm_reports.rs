@@ -22,17 +22,22 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 use super::{
-    defs::{ErrFatal, ErrNormal},
+    defs::{EngineOptionDefaults, ErrFatal, ErrNormal},
     Engine,
 };
 use crate::{
-    comm::{uci::UciReport, CommControl, CommReport},
-    defs::FEN_START_POSITION,
+    book::Book,
+    comm::{uci::UciReport, xboard::XboardReport, CommControl, CommReport},
+    defs::{Sides, FEN_START_POSITION},
     engine::defs::EngineOptionName,
-    evaluation::evaluate_position,
-    search::{defs::{SearchControl, SearchMode, SearchParams, OVERHEAD}, SearchManager},
+    evaluation::evaluate_position_verbose,
+    misc::pgn,
+    search::{
+        defs::{GameTime, RepetitionPolicy, SearchControl, SearchMode, SearchParams},
+        Search, SearchManager,
+    },
 };
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 use crossbeam_channel;
 
 // This block implements handling of incoming information, which will be in
@@ -42,27 +47,42 @@ impl Engine {
         // Split out the comm reports according to their source.
         match comm_report {
             CommReport::Uci(u) => self.comm_reports_uci(u),
+            CommReport::Xboard(x) => self.comm_reports_xboard(x),
         }
     }
 
-    // Handles "Uci" Comm reports sent by the UCI-module.
-    fn comm_reports_uci(&mut self, u: &UciReport) {
-        // Setup default variables.
+    // Creates search parameters from the engine settings.
+    fn search_params(&mut self) -> SearchParams {
         let mut sp = SearchParams::new();
         sp.quiet = self.settings.quiet;
         sp.sharp_margin = self.settings.sharp_margin;
+        sp.multi_pv = self.settings.multi_pv;
+        sp.search_moves = std::mem::take(&mut self.search_moves);
+        sp.move_overhead = self.settings.move_overhead as u128;
+        sp.contempt = self.settings.contempt;
+        sp.use_nodes_time = self.settings.use_nodes_time;
+        sp.sharp_only = self.settings.sharp_only;
+        sp.sharp_depth_cap = self.settings.sharp_depth_cap;
+        sp.repetition = RepetitionPolicy::scaled(self.settings.repetition_penalty);
+        sp.max_check_extensions = self.settings.max_check_extensions;
+        sp.analyse_mode = self.settings.analyse_mode;
+        sp.killer_slots = self.settings.killer_slots;
+        sp.aspiration_window = self.settings.aspiration_window;
+        sp.aspiration_growth = self.settings.aspiration_growth;
+        sp.lmr_min_depth = self.settings.lmr_min_depth;
+        sp.tunables = self.settings.tunables;
+        sp
+    }
+
+    // Handles "Uci" Comm reports sent by the UCI-module.
+    fn comm_reports_uci(&mut self, u: &UciReport) {
+        // Setup default variables.
+        let mut sp = self.search_params();
 
         match u {
             UciReport::Uci => self.comm.send(CommControl::Identify),
 
-            UciReport::UciNewGame => {
-                self.board
-                    .lock()
-                    .expect(ErrFatal::LOCK)
-                    .fen_read(Some(FEN_START_POSITION))
-                    .expect(ErrFatal::NEW_GAME);
-                self.tt_search.write().expect(ErrFatal::LOCK).clear();
-            }
+            UciReport::UciNewGame => self.new_game(),
 
             UciReport::IsReady => self.comm.send(CommControl::Ready),
 
@@ -70,6 +90,7 @@ impl Engine {
                 match option {
                     EngineOptionName::Hash(value) => {
                         if let Ok(v) = value.parse::<usize>() {
+                            self.settings.tt_size = v;
                             self.tt_search.write().expect(ErrFatal::LOCK).resize(v);
                         } else {
                             let msg = String::from(ErrNormal::NOT_INT);
@@ -104,7 +125,6 @@ impl Engine {
                                     Arc::clone(&self.board),
                                     Arc::clone(&self.mg),
                                     Arc::clone(&self.tt_search),
-                                    self.settings.tt_size > 0,
                                 );
                             } else {
                                 let msg = format!("Thread count must be between 1 and 64, got {}", v);
@@ -116,6 +136,194 @@ impl Engine {
                         }
                     }
 
+                    EngineOptionName::PawnHash(value) => {
+                        if let Ok(v) = value.parse::<usize>() {
+                            let board = self.board.lock().expect(ErrFatal::LOCK);
+                            board.pawn_table.write().expect(ErrFatal::LOCK).resize(v);
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_INT);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
+                    EngineOptionName::Chess960(value) => {
+                        if let Ok(v) = value.parse::<bool>() {
+                            self.settings.chess960 = v;
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_BOOL);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
+                    EngineOptionName::MultiPv(value) => {
+                        if let Ok(v) = value.parse::<usize>() {
+                            self.settings.multi_pv = v.clamp(
+                                EngineOptionDefaults::MULTI_PV_MIN,
+                                EngineOptionDefaults::MULTI_PV_MAX,
+                            );
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_INT);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
+                    EngineOptionName::MoveOverhead(value) => {
+                        if let Ok(v) = value.parse::<usize>() {
+                            self.settings.move_overhead = v.clamp(
+                                EngineOptionDefaults::MOVE_OVERHEAD_MIN,
+                                EngineOptionDefaults::MOVE_OVERHEAD_MAX,
+                            );
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_INT);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
+                    EngineOptionName::Contempt(value) => {
+                        if let Ok(v) = value.parse::<i16>() {
+                            self.settings.contempt = v.clamp(
+                                EngineOptionDefaults::CONTEMPT_MIN,
+                                EngineOptionDefaults::CONTEMPT_MAX,
+                            );
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_INT);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
+                    EngineOptionName::BookFile(value) => {
+                        self.book = None;
+                        if !value.is_empty() && value != EngineOptionDefaults::BOOK_FILE_DEFAULT {
+                            match Book::load(Path::new(value)) {
+                                Ok(book) => self.book = Some(book),
+                                Err(e) => {
+                                    let msg = format!("{} ({e})", ErrNormal::BOOK_FAILED);
+                                    self.comm.send(CommControl::InfoString(msg));
+                                }
+                            }
+                        }
+                    }
+
+                    EngineOptionName::UseNodesTime(value) => {
+                        if let Ok(v) = value.parse::<bool>() {
+                            self.settings.use_nodes_time = v;
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_BOOL);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
+                    EngineOptionName::SharpOnly(value) => {
+                        if let Ok(v) = value.parse::<bool>() {
+                            self.settings.sharp_only = v;
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_BOOL);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
+                    EngineOptionName::SharpDepthCap(value) => {
+                        if let Ok(v) = value.parse::<i8>() {
+                            self.settings.sharp_depth_cap = v.clamp(
+                                EngineOptionDefaults::SHARP_DEPTH_CAP_MIN,
+                                EngineOptionDefaults::SHARP_DEPTH_CAP_MAX,
+                            );
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_INT);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
+                    EngineOptionName::RepetitionPenalty(value) => {
+                        if let Ok(v) = value.parse::<i16>() {
+                            self.settings.repetition_penalty = v.clamp(
+                                EngineOptionDefaults::REPETITION_PENALTY_MIN,
+                                EngineOptionDefaults::REPETITION_PENALTY_MAX,
+                            );
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_INT);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
+                    EngineOptionName::MaxCheckExtensions(value) => {
+                        if let Ok(v) = value.parse::<i8>() {
+                            self.settings.max_check_extensions = v.clamp(
+                                EngineOptionDefaults::MAX_CHECK_EXTENSIONS_MIN,
+                                EngineOptionDefaults::MAX_CHECK_EXTENSIONS_MAX,
+                            );
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_INT);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
+                    EngineOptionName::AnalyseMode(value) => {
+                        if let Ok(v) = value.parse::<bool>() {
+                            self.settings.analyse_mode = v;
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_BOOL);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
+                    EngineOptionName::KillerSlots(value) => {
+                        if let Ok(v) = value.parse::<usize>() {
+                            self.settings.killer_slots = v.clamp(
+                                EngineOptionDefaults::KILLER_SLOTS_MIN,
+                                EngineOptionDefaults::KILLER_SLOTS_MAX,
+                            );
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_INT);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
+                    EngineOptionName::AspirationWindow(value) => {
+                        if let Ok(v) = value.parse::<i16>() {
+                            self.settings.aspiration_window = v.clamp(
+                                EngineOptionDefaults::ASPIRATION_WINDOW_MIN,
+                                EngineOptionDefaults::ASPIRATION_WINDOW_MAX,
+                            );
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_INT);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
+                    EngineOptionName::AspirationGrowth(value) => {
+                        if let Ok(v) = value.parse::<i16>() {
+                            self.settings.aspiration_growth = v.clamp(
+                                EngineOptionDefaults::ASPIRATION_GROWTH_MIN,
+                                EngineOptionDefaults::ASPIRATION_GROWTH_MAX,
+                            );
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_INT);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
+                    EngineOptionName::LmrMinDepth(value) => {
+                        if let Ok(v) = value.parse::<i32>() {
+                            self.settings.lmr_min_depth = v.clamp(
+                                EngineOptionDefaults::LMR_MIN_DEPTH_MIN as i32,
+                                EngineOptionDefaults::LMR_MIN_DEPTH_MAX as i32,
+                            ) as i8;
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_INT);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
+                    EngineOptionName::Tune(name, value) => {
+                        if let Ok(v) = value.parse::<i16>() {
+                            self.settings.tunables.set(name, v);
+                        } else {
+                            let msg = String::from(ErrNormal::NOT_INT);
+                            self.comm.send(CommControl::InfoString(msg));
+                        }
+                    }
+
                     EngineOptionName::Nothing => (),
                 };
             }
@@ -142,71 +350,213 @@ impl Engine {
 
             UciReport::GoInfinite => {
                 sp.search_mode = SearchMode::Infinite;
-                self.search.start_search();
-                self.search.send(SearchControl::Start(sp));
+                self.go(sp);
             }
 
             UciReport::GoDepth(depth) => {
                 sp.depth = *depth;
                 sp.search_mode = SearchMode::Depth;
-                self.search.start_search();
-                self.search.send(SearchControl::Start(sp));
+                self.go(sp);
             }
 
             UciReport::GoMoveTime(msecs) => {
-                sp.move_time = *msecs - (OVERHEAD as u128);
+                sp.move_time = msecs.saturating_sub(sp.move_overhead);
                 sp.search_mode = SearchMode::MoveTime;
-                self.search.start_search();
-                self.search.send(SearchControl::Start(sp));
+                self.go(sp);
             }
 
             UciReport::GoNodes(nodes) => {
                 sp.nodes = *nodes;
                 sp.search_mode = SearchMode::Nodes;
-                self.search.start_search();
-                self.search.send(SearchControl::Start(sp));
+                self.go(sp);
             }
 
             UciReport::GoGameTime(gt) => {
                 sp.game_time = *gt;
                 sp.search_mode = SearchMode::GameTime;
-                self.search.start_search();
-                self.search.send(SearchControl::Start(sp));
+                self.go(sp);
+            }
+
+            UciReport::GoSearchMoves(moves, go) => {
+                self.search_moves = self.legal_search_moves(moves);
+                self.comm_reports_uci(go);
             }
 
             UciReport::Stop => {
-                self.search.stop_search();
-                self.search.send(SearchControl::Stop);
+                self.stop_search();
+                if let Some((m, ponder)) = self.ponder.release() {
+                    self.send_best_move(m, ponder);
+                }
             }
             UciReport::Quit => self.quit(),
 
             UciReport::GoPonder(gt) => {
                 sp.game_time = *gt;
                 sp.search_mode = SearchMode::Ponder;
-                self.pondering = true;
-                self.search.start_search();
-                self.search.send(SearchControl::Start(sp));
+                self.ponder.start();
+                self.go(sp);
             }
 
             UciReport::PonderHit => {
-                self.pondering = false;
-                if let Some(m) = self.delayed_bestmove.take() {
-                    self.comm.send(CommControl::BestMove(m));
-                    self.comm.send(CommControl::Update);
+                if let Some((m, ponder)) = self.ponder.release() {
+                    self.send_best_move(m, ponder);
                 }
             }
 
             // Custom commands
             UciReport::Board => self.comm.send(CommControl::PrintBoard),
             UciReport::History => self.comm.send(CommControl::PrintHistory),
+            UciReport::Pgn => {
+                let board_guard = self.board.lock().expect(ErrFatal::LOCK);
+                let pgn = pgn::game_to_pgn(&board_guard, "*", &self.mg);
+                self.comm.send(CommControl::PrintPgn(pgn));
+            }
             UciReport::Eval => {
                 let mut board_guard = self.board.lock().expect(ErrFatal::LOCK);
-                let e = evaluate_position(&mut *board_guard, &self.mg);
-                let msg = format!("Evaluation: {e} centipawns");
-                self.comm.send(CommControl::InfoString(msg));
+                let e = evaluate_position_verbose(&mut board_guard, &self.mg);
+                for line in e.as_string().lines() {
+                    self.comm.send(CommControl::InfoString(line.to_string()));
+                }
+            }
+            UciReport::TimeStats => self.search.send(SearchControl::ReportTimeStats),
+            UciReport::SaveHash(path) => {
+                let tt = self.tt_search.read().expect(ErrFatal::LOCK);
+                if let Err(e) = tt.save(Path::new(path)) {
+                    let msg = format!("{} ({e})", ErrNormal::HASH_SAVE_FAILED);
+                    self.comm.send(CommControl::InfoString(msg));
+                }
+            }
+            UciReport::LoadHash(path) => {
+                let mut tt = self.tt_search.write().expect(ErrFatal::LOCK);
+                if let Err(e) = tt.load(Path::new(path)) {
+                    let msg = format!("{} ({e})", ErrNormal::HASH_LOAD_FAILED);
+                    self.comm.send(CommControl::InfoString(msg));
+                }
             }
             UciReport::Help => self.comm.send(CommControl::PrintHelp),
             UciReport::Unknown => (),
         }
     }
+
+    // Handles "Xboard" Comm reports sent by the XBoard-module.
+    fn comm_reports_xboard(&mut self, x: &XboardReport) {
+        match x {
+            XboardReport::Xboard => (),
+            XboardReport::ProtoVer(_) => self.comm.send(CommControl::Identify),
+
+            XboardReport::New => {
+                self.stop_search();
+                self.new_game();
+                self.xboard.engine_side = Some(Sides::BLACK);
+            }
+
+            XboardReport::Force => {
+                self.stop_search();
+                self.xboard.engine_side = None;
+            }
+
+            XboardReport::Go => {
+                let us = self.board.lock().expect(ErrFatal::LOCK).us();
+                self.xboard.engine_side = Some(us);
+                self.xboard_think();
+            }
+
+            XboardReport::UserMove(m) => {
+                if self.execute_move(m.clone()) {
+                    let us = self.board.lock().expect(ErrFatal::LOCK).us();
+                    if self.xboard.engine_side == Some(us) {
+                        self.xboard_think();
+                    }
+                } else {
+                    self.comm.send(CommControl::IllegalMove(m.clone()));
+                }
+            }
+
+            XboardReport::MoveNow => self.stop_search(),
+            XboardReport::Time(centiseconds) => self.xboard.time = centiseconds * 10,
+            XboardReport::Otim(centiseconds) => self.xboard.otim = centiseconds * 10,
+
+            XboardReport::Level(moves_per_session, base, increment) => {
+                self.xboard.moves_per_session = *moves_per_session;
+                self.xboard.increment = *increment;
+                self.xboard.time = *base;
+                self.xboard.otim = *base;
+            }
+
+            XboardReport::Result(_) => {
+                self.stop_search();
+                self.xboard.engine_side = None;
+            }
+
+            XboardReport::Quit => self.quit(),
+            XboardReport::Unknown => (),
+        }
+    }
+
+    // Starts a search for the engine's side, on the clocks last sent by
+    // XBoard. With a number of moves per session, the moves still to go
+    // are counted from the move number.
+    fn xboard_think(&mut self) {
+        let mut sp = self.search_params();
+        let board = self.board.lock().expect(ErrFatal::LOCK);
+        let white = board.us() == Sides::WHITE;
+        let move_number = board.game_state.fullmove_number as usize;
+        std::mem::drop(board);
+
+        let xb = &self.xboard;
+        let (wtime, btime) = if white { (xb.time, xb.otim) } else { (xb.otim, xb.time) };
+        let moves_to_go = match xb.moves_per_session {
+            0 => None,
+            mps => Some(mps - (move_number.saturating_sub(1) % mps)),
+        };
+
+        sp.game_time = GameTime::new(wtime, btime, xb.increment, xb.increment, moves_to_go);
+        sp.search_mode = SearchMode::GameTime;
+        self.go(sp);
+    }
+
+    // Sets up the board for a new game and clears the TT. The time control
+    // of the new game is classified on its first search.
+    fn new_game(&mut self) {
+        self.time_control = None;
+        self.board
+            .lock()
+            .expect(ErrFatal::LOCK)
+            .fen_read(Some(FEN_START_POSITION))
+            .expect(ErrFatal::NEW_GAME);
+        self.tt_search.write().expect(ErrFatal::LOCK).clear();
+    }
+
+    fn stop_search(&mut self) {
+        self.search.stop_search();
+        self.search.send(SearchControl::Stop);
+    }
+
+    // Starts a search with the given parameters. If the opening book has
+    // a move for this position, it is played at once instead. This is not
+    // done for infinite searches and pondering, which must wait for the
+    // GUI, or when the GUI restricts the root moves. The first search on
+    // the clock classifies the time control of the game; later searches
+    // keep it, however little time is left.
+    fn go(&mut self, mut sp: SearchParams) {
+        if matches!(sp.search_mode, SearchMode::GameTime | SearchMode::Ponder) {
+            let us = self.board.lock().expect(ErrFatal::LOCK).us();
+            let game_time = sp.game_time;
+            let tc = self
+                .time_control
+                .get_or_insert_with(|| Search::classify_time_control(&game_time, us));
+            sp.time_control = Some(*tc);
+        }
+
+        let use_book = !matches!(sp.search_mode, SearchMode::Infinite | SearchMode::Ponder)
+            && sp.search_moves.is_empty();
+
+        if let Some(m) = self.book_move().filter(|_| use_book) {
+            self.send_best_move(m, None);
+        } else {
+            self.tt_search.write().expect(ErrFatal::LOCK).new_search();
+            self.search.start_search();
+            self.search.send(SearchControl::Start(Box::new(sp)));
+        }
+    }
 }
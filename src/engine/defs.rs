@@ -21,8 +21,13 @@ You should have received a copy of the GNU General Public License along
 with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
-pub use crate::engine::transposition::{HashFlag, PerftData, SearchData, TT, LocalTTCache};
-use crate::{comm::CommReport, search::defs::SearchReport};
+pub use crate::engine::transposition::{HashFlag, PerftData, SearchData, TT, TTPrefetch, LocalTTCache};
+use crate::{
+    comm::CommReport,
+    defs::{Side, Sides, MAX_PLY},
+    movegen::defs::Move,
+    search::defs::{SearchReport, SearchTunables, ASPIRATION_GROWTH, ASPIRATION_WINDOW, KILLER_SLOTS, LMR_MIN_DEPTH, MAX_CHECK_EXTENSIONS, MAX_KILLER_MOVES, OVERHEAD, SHARP_SEQUENCE_DEPTH_CAP},
+};
 
 // This struct holds messages that are reported on fatal engine errors.
 // These should never happen; if they do the engine is in an unknown state,
@@ -43,7 +48,11 @@ pub struct ErrNormal;
 impl ErrNormal {
     pub const NOT_LEGAL: &'static str = "This is not a legal move in this position.";
     pub const NOT_INT: &'static str = "The value given was not an integer.";
+    pub const NOT_BOOL: &'static str = "The value given was not true or false.";
     pub const FEN_FAILED: &'static str = "Setting up FEN failed. Board not changed.";
+    pub const BOOK_FAILED: &'static str = "Loading the opening book failed.";
+    pub const HASH_SAVE_FAILED: &'static str = "Saving the hash table failed.";
+    pub const HASH_LOAD_FAILED: &'static str = "Loading the hash table failed. Hash table not changed.";
 }
 
 // This struct holds the engine's settings.
@@ -52,6 +61,102 @@ pub struct Settings {
     pub quiet: bool,
     pub tt_size: usize,
     pub sharp_margin: i16,
+    pub chess960: bool,
+    pub multi_pv: usize,
+    pub move_overhead: usize,
+    pub contempt: i16,
+    pub use_nodes_time: bool,
+    pub sharp_only: bool,
+    pub sharp_depth_cap: i8,
+    pub repetition_penalty: i16,
+    pub max_check_extensions: i8,
+    pub analyse_mode: bool,
+    pub killer_slots: usize,
+    pub aspiration_window: i16,
+    pub aspiration_growth: i16,
+    pub lmr_min_depth: i8,
+    pub tunables: SearchTunables,
+}
+
+// This struct holds the state of a game played through XBoard. Unlike a
+// UCI GUI, XBoard sends the moves and clocks one at a time, and leaves it
+// to the engine to keep track of them.
+pub struct XboardState {
+    pub engine_side: Option<Side>, // Side played by the engine; None in force mode.
+    pub time: u128,                // Engine's clock in msecs.
+    pub otim: u128,                // Opponent's clock in msecs.
+    pub moves_per_session: usize,  // Moves per time control; 0 for the whole game.
+    pub increment: u128,           // Increment per move in msecs.
+}
+
+impl XboardState {
+    pub fn new() -> Self {
+        Self {
+            engine_side: Some(Sides::BLACK),
+            time: 0,
+            otim: 0,
+            moves_per_session: 0,
+            increment: 0,
+        }
+    }
+}
+
+impl Default for XboardState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The best move, and the expected reply to ponder on, if there is one.
+pub type BestMove = (Move, Option<Move>);
+
+// This struct holds the state of a ponder search. While pondering, the
+// engine must not send a best move, even if the search finishes before
+// the GUI says anything: the move is held back until a "ponderhit" or a
+// "stop" turns the ponder search into a search for a real move.
+pub struct PonderState {
+    pub active: bool,              // A ponder search is running.
+    pub delayed: Option<BestMove>, // Best move found while pondering.
+}
+
+impl PonderState {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            delayed: None,
+        }
+    }
+
+    // A new ponder search starts.
+    pub fn start(&mut self) {
+        self.active = true;
+        self.delayed = None;
+    }
+
+    // The search has finished with the given best move. Returns the move
+    // if it can be sent now; while pondering, it is held back.
+    pub fn finished(&mut self, m: BestMove) -> Option<BestMove> {
+        if self.active {
+            self.delayed = Some(m);
+            None
+        } else {
+            Some(m)
+        }
+    }
+
+    // The GUI sent "ponderhit" or "stop": pondering is over. Returns the
+    // best move if the search has already finished; otherwise it will be
+    // sent when it does.
+    pub fn release(&mut self) -> Option<BestMove> {
+        self.active = false;
+        self.delayed.take()
+    }
+}
+
+impl Default for PonderState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 // This enum provides informatin to the engine, with regard to incoming
@@ -65,6 +170,8 @@ pub enum Information {
 pub enum UiElement {
     Spin,
     Button,
+    Check,
+    String,
 }
 
 pub struct EngineOption {
@@ -99,6 +206,23 @@ pub enum EngineOptionName {
     ClearHash,
     SharpMargin(String),
     Threads(String),
+    PawnHash(String),
+    Chess960(String),
+    MultiPv(String),
+    MoveOverhead(String),
+    Contempt(String),
+    BookFile(String),
+    UseNodesTime(String),
+    SharpOnly(String),
+    SharpDepthCap(String),
+    RepetitionPenalty(String),
+    MaxCheckExtensions(String),
+    AnalyseMode(String),
+    KillerSlots(String),
+    AspirationWindow(String),
+    AspirationGrowth(String),
+    LmrMinDepth(String),
+    Tune(String, String),
     Nothing,
 }
 impl EngineOptionName {
@@ -106,6 +230,22 @@ impl EngineOptionName {
     pub const CLEAR_HASH: &'static str = "Clear Hash";
     pub const SHARP_MARGIN: &'static str = "Sharp Margin";
     pub const THREADS: &'static str = "Threads";
+    pub const PAWN_HASH: &'static str = "Pawn Hash";
+    pub const CHESS960: &'static str = "UCI_Chess960";
+    pub const MULTI_PV: &'static str = "MultiPV";
+    pub const MOVE_OVERHEAD: &'static str = "Move Overhead";
+    pub const CONTEMPT: &'static str = "Contempt";
+    pub const BOOK_FILE: &'static str = "BookFile";
+    pub const USE_NODES_TIME: &'static str = "UseNodesTime";
+    pub const SHARP_ONLY: &'static str = "SharpOnly";
+    pub const SHARP_DEPTH_CAP: &'static str = "SharpDepthCap";
+    pub const REPETITION_PENALTY: &'static str = "RepetitionPenalty";
+    pub const MAX_CHECK_EXTENSIONS: &'static str = "MaxCheckExtensions";
+    pub const ANALYSE_MODE: &'static str = "UCI_AnalyseMode";
+    pub const KILLER_SLOTS: &'static str = "KillerSlots";
+    pub const ASPIRATION_WINDOW: &'static str = "AspirationWindow";
+    pub const ASPIRATION_GROWTH: &'static str = "AspirationGrowth";
+    pub const LMR_MIN_DEPTH: &'static str = "LmrMinDepth";
 }
 
 pub struct EngineOptionDefaults;
@@ -117,4 +257,80 @@ impl EngineOptionDefaults {
     pub const SHARP_MARGIN_DEFAULT: i16 = 30;
     pub const SHARP_MARGIN_MIN: i16 = 0;
     pub const SHARP_MARGIN_MAX: i16 = 100;
+    pub const PAWN_HASH_DEFAULT: usize = 4;
+    pub const PAWN_HASH_MIN: usize = 0;
+    pub const PAWN_HASH_MAX: usize = 1024;
+    pub const CHESS960_DEFAULT: bool = false;
+    pub const MULTI_PV_DEFAULT: usize = 1;
+    pub const MULTI_PV_MIN: usize = 1;
+    pub const MULTI_PV_MAX: usize = 64;
+    pub const MOVE_OVERHEAD_DEFAULT: usize = OVERHEAD as usize;
+    pub const MOVE_OVERHEAD_MIN: usize = 0;
+    pub const MOVE_OVERHEAD_MAX: usize = 5000;
+    pub const CONTEMPT_DEFAULT: i16 = 0;
+    pub const CONTEMPT_MIN: i16 = -100;
+    pub const CONTEMPT_MAX: i16 = 100;
+    pub const BOOK_FILE_DEFAULT: &'static str = "<empty>";
+    pub const USE_NODES_TIME_DEFAULT: bool = false;
+    pub const SHARP_ONLY_DEFAULT: bool = false;
+    pub const SHARP_DEPTH_CAP_DEFAULT: i8 = SHARP_SEQUENCE_DEPTH_CAP;
+    pub const SHARP_DEPTH_CAP_MIN: i8 = 1;
+    pub const SHARP_DEPTH_CAP_MAX: i8 = 8;
+    pub const REPETITION_PENALTY_DEFAULT: i16 = 100;
+    pub const REPETITION_PENALTY_MIN: i16 = 0;
+    pub const REPETITION_PENALTY_MAX: i16 = 200;
+    pub const MAX_CHECK_EXTENSIONS_DEFAULT: i8 = MAX_CHECK_EXTENSIONS;
+    pub const MAX_CHECK_EXTENSIONS_MIN: i8 = 0;
+    pub const MAX_CHECK_EXTENSIONS_MAX: i8 = 32;
+    pub const ANALYSE_MODE_DEFAULT: bool = false;
+    pub const KILLER_SLOTS_DEFAULT: usize = KILLER_SLOTS;
+    pub const KILLER_SLOTS_MIN: usize = 1;
+    pub const KILLER_SLOTS_MAX: usize = MAX_KILLER_MOVES;
+    pub const ASPIRATION_WINDOW_DEFAULT: i16 = ASPIRATION_WINDOW;
+    pub const ASPIRATION_WINDOW_MIN: i16 = 10;
+    pub const ASPIRATION_WINDOW_MAX: i16 = 500;
+    pub const ASPIRATION_GROWTH_DEFAULT: i16 = ASPIRATION_GROWTH;
+    pub const ASPIRATION_GROWTH_MIN: i16 = 110;
+    pub const ASPIRATION_GROWTH_MAX: i16 = 800;
+    pub const LMR_MIN_DEPTH_DEFAULT: i8 = LMR_MIN_DEPTH;
+    pub const LMR_MIN_DEPTH_MIN: i8 = 1;
+    pub const LMR_MIN_DEPTH_MAX: i8 = MAX_PLY;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_finishes_before_ponderhit() {
+        let mut ponder = PonderState::new();
+        let m = Move::new(0x1234);
+
+        ponder.start();
+        assert!(ponder.finished((m, None)).is_none());
+        assert_eq!(ponder.release().map(|(x, _)| x.get_move()), Some(m.get_move()));
+
+        // Released once only.
+        assert!(ponder.release().is_none());
+    }
+
+    #[test]
+    fn test_ponderhit_before_search_finishes() {
+        let mut ponder = PonderState::new();
+        let m = Move::new(0x1234);
+
+        ponder.start();
+        assert!(ponder.release().is_none());
+        assert_eq!(ponder.finished((m, None)).map(|(x, _)| x.get_move()), Some(m.get_move()));
+    }
+
+    #[test]
+    fn test_new_ponder_search_forgets_old_move() {
+        let mut ponder = PonderState::new();
+
+        ponder.start();
+        ponder.finished((Move::new(0x1234), None));
+        ponder.start();
+        assert!(ponder.release().is_none());
+    }
 }
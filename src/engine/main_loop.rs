@@ -47,7 +47,6 @@ impl Engine {
             Arc::clone(&self.board),
             Arc::clone(&self.mg),
             Arc::clone(&self.tt_search),
-            self.settings.tt_size > 0,
         );
 
         // Update the Comm interface screen output (if any).
@@ -27,12 +27,9 @@ use crate::{comm::CommControl, search::defs::SearchReport};
 impl Engine {
     pub fn search_reports(&mut self, search_report: &SearchReport) {
         match search_report {
-            SearchReport::Finished(m) => {
-                if self.pondering {
-                    self.delayed_bestmove = Some(*m);
-                } else {
-                    self.comm.send(CommControl::BestMove(*m));
-                    self.comm.send(CommControl::Update);
+            SearchReport::Finished(m, ponder) => {
+                if let Some((m, ponder)) = self.ponder.finished((*m, *ponder)) {
+                    self.send_best_move(m, ponder);
                 }
             }
 
@@ -41,7 +38,9 @@ impl Engine {
             }
 
             SearchReport::SearchSummary(summary) => {
-                self.comm.send(CommControl::SearchSummary(summary.clone()));
+                let mut summary = summary.clone();
+                summary.pv = summary.pv.iter().map(|m| self.move_for_gui(*m)).collect();
+                self.comm.send(CommControl::SearchSummary(summary));
             }
 
             SearchReport::SearchStats(stats) => {
@@ -51,6 +50,21 @@ impl Engine {
             SearchReport::InfoString(msg) => {
                 self.comm.send(CommControl::InfoString(msg.clone()));
             }
+
+            SearchReport::SharpInfo(info) => {
+                let mut info = info.clone();
+                info.mv = self.move_for_gui(info.mv);
+                info.reply_sequence = info
+                    .reply_sequence
+                    .iter()
+                    .map(|m| self.move_for_gui(*m))
+                    .collect();
+                self.comm.send(CommControl::SharpInfo(info));
+            }
+
+            SearchReport::TimeStats(stats) => {
+                self.comm.send(CommControl::PrintTimeStats(stats.clone()));
+            }
         }
     }
 }
@@ -22,16 +22,31 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 use crate::{board::defs::ZobristKey, movegen::defs::ShortMove, search::defs::CHECKMATE_THRESHOLD};
+use std::{
+    fs,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
 
 const MEGABYTE: usize = 1024 * 1024;
 const ENTRIES_PER_BUCKET: usize = 4;
 const HIGH_FOUR_BYTES: u64 = 0xFF_FF_FF_FF_00_00_00_00;
 const LOW_FOUR_BYTES: u64 = 0x00_00_00_00_FF_FF_FF_FF;
 const SHIFT_TO_LOWER: u64 = 32;
+const HASH_FULL_SAMPLE: usize = 1000 / ENTRIES_PER_BUCKET;
 
 // Local TT cache size (entries per thread)
 const LOCAL_TT_CACHE_SIZE: usize = 1024;
 
+// A saved TT starts with a header: the marker, the file format version,
+// the table size in megabytes and buckets, the generation, and the number
+// of entries. Only used entries are saved, each with its bucket and slot.
+const TT_FILE_MARKER: &[u8; 4] = b"RSTT";
+const TT_FILE_VERSION: u32 = 1;
+const TT_HEADER_BYTES: usize = 4 + 4 + 8 + 8 + 1 + 8;
+const SEARCH_DATA_BYTES: usize = 1 + 1 + 2 + 2 + 4;
+const TT_RECORD_BYTES: usize = 8 + 1 + 4 + 1 + SEARCH_DATA_BYTES;
+
 /* ===== Data ========================================================= */
 
 pub trait IHashData {
@@ -85,6 +100,7 @@ pub struct SearchData {
     depth: i8,
     flag: HashFlag,
     value: i16,
+    static_eval: i16,
     best_move: ShortMove,
 }
 
@@ -94,6 +110,7 @@ impl IHashData for SearchData {
             depth: 0,
             flag: HashFlag::Nothing,
             value: 0,
+            static_eval: 0,
             best_move: ShortMove::new(0),
         }
     }
@@ -104,7 +121,14 @@ impl IHashData for SearchData {
 }
 
 impl SearchData {
-    pub fn create(depth: i8, ply: i8, flag: HashFlag, value: i16, best_move: ShortMove) -> Self {
+    pub fn create(
+        depth: i8,
+        ply: i8,
+        flag: HashFlag,
+        value: i16,
+        static_eval: i16,
+        best_move: ShortMove,
+    ) -> Self {
         // This is the value we're going to save into the TT.
         let mut v = value;
 
@@ -125,6 +149,7 @@ impl SearchData {
             depth,
             flag,
             value: v,
+            static_eval,
             best_move,
         }
     }
@@ -169,6 +194,73 @@ impl SearchData {
         }
         (value, self.best_move)
     }
+
+    // Returns the best move stored for the position, which may be empty.
+    pub fn best_move(&self) -> ShortMove {
+        self.best_move
+    }
+
+    // Returns the stored depth, bound type and value, without comparing
+    // against a search window. Mate scores are adjusted for the ply in the
+    // same way as get() does.
+    pub fn bound(&self, ply: i8) -> (i8, HashFlag, i16) {
+        let mut v = self.value;
+
+        if v > CHECKMATE_THRESHOLD {
+            v -= ply as i16;
+        }
+
+        if v < CHECKMATE_THRESHOLD {
+            v += ply as i16;
+        }
+
+        (self.depth, self.flag, v)
+    }
+
+    // Returns the static evaluation of the position, as it was when the
+    // entry was stored. This does not depend on depth or ply, so it can
+    // always be reused.
+    pub fn static_eval(&self) -> i16 {
+        self.static_eval
+    }
+}
+
+// Saving and loading. All numbers are little-endian.
+impl SearchData {
+    fn to_bytes(self) -> [u8; SEARCH_DATA_BYTES] {
+        let flag: u8 = match self.flag {
+            HashFlag::Nothing => 0,
+            HashFlag::Exact => 1,
+            HashFlag::Alpha => 2,
+            HashFlag::Beta => 3,
+        };
+
+        let mut bytes = [0; SEARCH_DATA_BYTES];
+        bytes[0] = self.depth as u8;
+        bytes[1] = flag;
+        bytes[2..4].copy_from_slice(&self.value.to_le_bytes());
+        bytes[4..6].copy_from_slice(&self.static_eval.to_le_bytes());
+        bytes[6..10].copy_from_slice(&self.best_move.get_move().to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let flag = match bytes[1] {
+            0 => HashFlag::Nothing,
+            1 => HashFlag::Exact,
+            2 => HashFlag::Alpha,
+            3 => HashFlag::Beta,
+            _ => return None,
+        };
+
+        Some(Self {
+            depth: bytes[0] as i8,
+            flag,
+            value: i16::from_le_bytes([bytes[2], bytes[3]]),
+            static_eval: i16::from_le_bytes([bytes[4], bytes[5]]),
+            best_move: ShortMove::new(u32::from_le_bytes([bytes[6], bytes[7], bytes[8], bytes[9]])),
+        })
+    }
 }
 
 /* ===== Local TT Cache =============================================== */
@@ -221,6 +313,7 @@ impl<D: IHashData + Copy + Clone> PartialEq for LocalTTCache<D> {
 #[derive(Copy, Clone)]
 struct Entry<D> {
     verification: u32,
+    generation: u8,
     data: D,
 }
 
@@ -228,9 +321,14 @@ impl<D: IHashData> Entry<D> {
     pub fn new() -> Self {
         Self {
             verification: 0,
+            generation: 0,
             data: D::new(),
         }
     }
+
+    pub fn is_used(&self) -> bool {
+        self.verification != 0
+    }
 }
 
 /* ===== Bucket ======================================================= */
@@ -247,26 +345,38 @@ impl<D: IHashData + Copy> Bucket<D> {
         }
     }
 
-    // Store a position in the bucket. Replace the position with the stored
-    // lowest depth, as positions with higher depth are more valuable.
-    pub fn store(&mut self, verification: u32, data: D, used_entries: &mut usize) {
-        let mut idx_lowest_depth = 0;
-
-        // Find the index of the entry with the lowest depth.
-        for entry in 1..ENTRIES_PER_BUCKET {
-            if self.bucket[entry].data.depth() < data.depth() {
-                idx_lowest_depth = entry
-            }
-        }
-
-        // If the verifiaction was 0, this entry in the bucket was never
-        // used before. Count the use of this entry.
-        if self.bucket[idx_lowest_depth].verification == 0 {
-            *used_entries += 1;
+    // Store a position in the bucket. If the position is already in the
+    // bucket, it is updated. Otherwise, entries left over from earlier
+    // searches are replaced first, and of those, the one with the lowest
+    // depth; positions with higher depth are more valuable.
+    pub fn store(&mut self, verification: u32, data: D, generation: u8) {
+        let index = self
+            .bucket
+            .iter()
+            .position(|e| e.verification == verification)
+            .unwrap_or_else(|| {
+                (0..ENTRIES_PER_BUCKET)
+                    .min_by_key(|&i| {
+                        let e = &self.bucket[i];
+                        (e.is_used() && e.generation == generation, e.data.depth())
+                    })
+                    .unwrap_or(0)
+            });
+
+        self.bucket[index] = Entry {
+            verification,
+            generation,
+            data,
         }
+    }
 
-        // Store.
-        self.bucket[idx_lowest_depth] = Entry { verification, data }
+    // Counts the entries in this bucket that were stored during the
+    // current search.
+    pub fn current_entries(&self, generation: u8) -> usize {
+        self.bucket
+            .iter()
+            .filter(|e| e.is_used() && e.generation == generation)
+            .count()
     }
 
     // Find a position in the bucket, where both the stored verification and
@@ -281,13 +391,58 @@ impl<D: IHashData + Copy> Bucket<D> {
     }
 }
 
+/* ===== TT prefetch ========================================== */
+
+// The address and layout of the TT's buckets. It asks the CPU to start
+// loading the bucket of a position into its cache, so it is there when the
+// position is probed a little later. This helps with large tables, where
+// most buckets are not cached. It doesn't access the TT, so it needs no
+// lock; on targets without a prefetch instruction, it does nothing.
+#[derive(Clone, Copy, Default)]
+pub struct TTPrefetch {
+    address: usize,
+    bucket_size: usize,
+    total_buckets: usize,
+}
+
+impl TTPrefetch {
+    #[inline(always)]
+    pub fn prefetch(&self, zobrist_key: ZobristKey) {
+        if self.total_buckets > 0 {
+            let index = bucket_index(zobrist_key, self.total_buckets);
+            let bucket = self.address.wrapping_add(index * self.bucket_size);
+
+            #[cfg(target_arch = "x86_64")]
+            // SAFETY: a prefetch is only a hint to the CPU; it doesn't access
+            // the memory, and can't fault, even if the TT has been resized
+            // since the address was taken.
+            unsafe {
+                use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+                _mm_prefetch(bucket as *const i8, _MM_HINT_T0);
+            }
+
+            #[cfg(not(target_arch = "x86_64"))]
+            let _ = bucket;
+        }
+    }
+}
+
+// Calculate the index (bucket) where the data of a position is stored.
+// Use only the upper half of the Zobrist key for this, so the lower half
+// can be used to calculate a verification.
+fn bucket_index(zobrist_key: ZobristKey, total_buckets: usize) -> usize {
+    let key = (zobrist_key & HIGH_FOUR_BYTES) >> SHIFT_TO_LOWER;
+
+    (key % total_buckets as u64) as usize
+}
+
 /* ===== TT =================================================== */
 
 // Transposition Table
 pub struct TT<D> {
     tt: Vec<Bucket<D>>,
     megabytes: usize,
-    used_entries: usize,
+    generation: u8,
     total_buckets: usize,
     total_entries: usize,
 }
@@ -303,7 +458,7 @@ impl<D: IHashData + Copy + Clone> TT<D> {
         Self {
             tt: vec![Bucket::<D>::new(); total_buckets],
             megabytes,
-            used_entries: 0,
+            generation: 0,
             total_buckets,
             total_entries,
         }
@@ -318,7 +473,7 @@ impl<D: IHashData + Copy + Clone> TT<D> {
 
         self.tt = vec![Bucket::<D>::new(); total_buckets];
         self.megabytes = megabytes;
-        self.used_entries = 0;
+        self.generation = 0;
         self.total_buckets = total_buckets;
         self.total_entries = total_entries;
     }
@@ -326,17 +481,17 @@ impl<D: IHashData + Copy + Clone> TT<D> {
     // Insert a position at the calculated index, by storing it in the
     // index's bucket.
     pub fn insert(&mut self, zobrist_key: ZobristKey, data: D) {
-        if self.megabytes > 0 {
+        if self.is_enabled() {
             let index = self.calculate_index(zobrist_key);
             let verification = self.calculate_verification(zobrist_key);
-            self.tt[index].store(verification, data, &mut self.used_entries);
+            self.tt[index].store(verification, data, self.generation);
         }
     }
 
     // Probe the TT by both verification and depth. Both have to
     // match for the position to be the correct one we're looking for.
     pub fn probe(&self, zobrist_key: ZobristKey) -> Option<&D> {
-        if self.megabytes > 0 {
+        if self.is_enabled() {
             let index = self.calculate_index(zobrist_key);
             let verification = self.calculate_verification(zobrist_key);
 
@@ -346,32 +501,143 @@ impl<D: IHashData + Copy + Clone> TT<D> {
         }
     }
 
+    // A TT of size 0 is disabled. It holds no buckets, and storing or
+    // probing positions does nothing.
+    pub fn is_enabled(&self) -> bool {
+        self.total_buckets > 0
+    }
+
+    // Returns where the buckets are, so a search thread can prefetch them
+    // without locking the TT for every move. Get a new one after resizing.
+    pub fn prefetcher(&self) -> TTPrefetch {
+        TTPrefetch {
+            address: self.tt.as_ptr() as usize,
+            bucket_size: std::mem::size_of::<Bucket<D>>(),
+            total_buckets: self.total_buckets,
+        }
+    }
+
     // Clear TT by replacing it with a new one.
     pub fn clear(&mut self) {
         self.resize(self.megabytes);
     }
 
+    // Starts a new search. Entries stored from now on belong to the new
+    // generation, and entries from earlier searches become the first ones
+    // to be replaced. They can still be probed until that happens.
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
     // Provides TT usage in permille (1 per 1000, as oppposed to percent,
-    // which is 1 per 100.)
-    pub fn hash_full(&self) -> u16 {
-        if self.megabytes > 0 {
-            ((self.used_entries as f64 / self.total_entries as f64) * 1000f64).floor() as u16
+    // which is 1 per 100.) Only entries from the current search are
+    // counted, taken from a sample at the start of the table.
+    pub fn hashfull_permille(&self) -> u16 {
+        if self.is_enabled() {
+            let sample = self.total_buckets.min(HASH_FULL_SAMPLE);
+            let used: usize = self.tt[..sample]
+                .iter()
+                .map(|b| b.current_entries(self.generation))
+                .sum();
+            let entries = sample * ENTRIES_PER_BUCKET;
+
+            ((used as f64 / entries as f64) * 1000f64).floor() as u16
         } else {
             0
         }
     }
 }
 
+// Saving and loading the search TT, so an analysis can be continued
+// later with what was already found.
+impl TT<SearchData> {
+    // Writes the used entries of the TT to a file.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let used = || {
+            self.tt.iter().enumerate().flat_map(|(b, bucket)| {
+                bucket.bucket.iter().enumerate().filter(|(_, e)| e.is_used()).map(move |(i, e)| (b, i, e))
+            })
+        };
+
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        file.write_all(TT_FILE_MARKER)?;
+        file.write_all(&TT_FILE_VERSION.to_le_bytes())?;
+        file.write_all(&(self.megabytes as u64).to_le_bytes())?;
+        file.write_all(&(self.total_buckets as u64).to_le_bytes())?;
+        file.write_all(&[self.generation])?;
+        file.write_all(&(used().count() as u64).to_le_bytes())?;
+
+        for (b, i, e) in used() {
+            file.write_all(&(b as u64).to_le_bytes())?;
+            file.write_all(&[i as u8])?;
+            file.write_all(&e.verification.to_le_bytes())?;
+            file.write_all(&[e.generation])?;
+            file.write_all(&e.data.to_bytes())?;
+        }
+
+        file.flush()
+    }
+
+    // Replaces the contents of the TT with the entries in a file saved by
+    // save(). The file must have been saved from a TT of the same size.
+    // If it can't be loaded, the TT is not changed.
+    pub fn load(&mut self, path: &Path) -> io::Result<()> {
+        let bytes = fs::read(path)?;
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap_or_default());
+
+        if bytes.len() < TT_HEADER_BYTES || &bytes[0..4] != TT_FILE_MARKER {
+            return Err(invalid(String::from("not a saved hash table")));
+        }
+
+        let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        if version != TT_FILE_VERSION {
+            return Err(invalid(format!("file format version {version} is not supported")));
+        }
+
+        let (megabytes, buckets) = (u64_at(8) as usize, u64_at(16) as usize);
+        if megabytes != self.megabytes || buckets != self.total_buckets {
+            let msg = format!("saved with {megabytes} MB, but the hash table is {} MB", self.megabytes);
+            return Err(invalid(msg));
+        }
+
+        let generation = bytes[24];
+        let count = u64_at(25) as usize;
+        let records = &bytes[TT_HEADER_BYTES..];
+        if records.len() != count.saturating_mul(TT_RECORD_BYTES) {
+            return Err(invalid(String::from("the file is damaged")));
+        }
+
+        let mut tt = vec![Bucket::<SearchData>::new(); self.total_buckets];
+        for r in records.chunks_exact(TT_RECORD_BYTES) {
+            let b = u64::from_le_bytes(r[0..8].try_into().unwrap_or_default()) as usize;
+            let i = r[8] as usize;
+            let data = SearchData::from_bytes(&r[14..]);
+            match data {
+                Some(data) if b < self.total_buckets && i < ENTRIES_PER_BUCKET => {
+                    tt[b].bucket[i] = Entry {
+                        verification: u32::from_le_bytes([r[9], r[10], r[11], r[12]]),
+                        generation: r[13],
+                        data,
+                    };
+                }
+                _ => return Err(invalid(String::from("the file is damaged"))),
+            }
+        }
+
+        self.tt = tt;
+        self.generation = generation;
+        Ok(())
+    }
+}
+
 // Private functions
 impl<D: IHashData + Copy + Clone> TT<D> {
     // Calculate the index (bucket) where the data is going to be stored.
     // Use only the upper half of the Zobrist key for this, so the lower
     // half can be used to calculate a verification.
     fn calculate_index(&self, zobrist_key: ZobristKey) -> usize {
-        let key = (zobrist_key & HIGH_FOUR_BYTES) >> SHIFT_TO_LOWER;
-        let total = self.total_buckets as u64;
-
-        (key % total) as usize
+        bucket_index(zobrist_key, self.total_buckets)
     }
 
     // Many positions will end up at the same index, and thus in the same
@@ -392,3 +658,148 @@ impl<D: IHashData + Copy + Clone> TT<D> {
         (total_buckets, total_entries)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Creates a key that lands in the given bucket, with the given
+    // verification.
+    fn key(tt: &TT<SearchData>, bucket: usize, verification: u32) -> ZobristKey {
+        assert!(bucket < tt.total_buckets);
+        ((bucket as u64) << SHIFT_TO_LOWER) | verification as u64
+    }
+
+    fn data(depth: i8) -> SearchData {
+        SearchData::create(depth, 0, HashFlag::Exact, 0, 0, ShortMove::new(0))
+    }
+
+    #[test]
+    fn test_hashfull_counts_current_search() {
+        let mut tt: TT<SearchData> = TT::new(1);
+        let keys: Vec<ZobristKey> = (0..HASH_FULL_SAMPLE)
+            .flat_map(|b| (1..=2).map(move |v| (b, v)))
+            .map(|(b, v)| key(&tt, b, v))
+            .collect();
+
+        keys.iter().for_each(|&k| tt.insert(k, data(5)));
+        assert_eq!(tt.hashfull_permille(), 500);
+
+        // After a new search starts, the old entries no longer count as
+        // used, but they can still be probed and reused.
+        tt.new_search();
+        assert_eq!(tt.hashfull_permille(), 0);
+        assert!(keys.iter().all(|&k| tt.probe(k).is_some()));
+
+        keys.iter().for_each(|&k| tt.insert(k, data(6)));
+        assert_eq!(tt.hashfull_permille(), 500);
+        assert!(keys.iter().all(|&k| tt.probe(k).unwrap().depth() == 6));
+    }
+
+    #[test]
+    fn test_hashfull_empty_and_populated() {
+        let mut tt: TT<SearchData> = TT::new(1);
+        assert_eq!(tt.hashfull_permille(), 0);
+
+        // Store random positions, three times as many as the table holds.
+        let mut zobrist_key: ZobristKey = 0x9E37_79B9_7F4A_7C15;
+        for _ in 0..tt.total_entries * 3 {
+            zobrist_key ^= zobrist_key << 13;
+            zobrist_key ^= zobrist_key >> 7;
+            zobrist_key ^= zobrist_key << 17;
+            tt.insert(zobrist_key, data(1));
+        }
+        assert!(tt.hashfull_permille() > 900);
+
+        // A disabled table is never full.
+        let disabled: TT<SearchData> = TT::new(0);
+        assert_eq!(disabled.hashfull_permille(), 0);
+    }
+
+    #[test]
+    fn test_stale_entries_replaced_first() {
+        let mut tt: TT<SearchData> = TT::new(1);
+        let found = |tt: &TT<SearchData>, v: u32| tt.probe(key(tt, 0, v)).is_some();
+
+        // One search stores two shallow entries in a bucket, the next one
+        // stores two deep entries.
+        (1..=2).for_each(|v| tt.insert(key(&tt, 0, v), data(1)));
+        tt.new_search();
+        (3..=4).for_each(|v| tt.insert(key(&tt, 0, v), data(10)));
+
+        // New shallow entries replace the stale ones, not the deep ones.
+        (5..=6).for_each(|v| tt.insert(key(&tt, 0, v), data(1)));
+        assert!(!found(&tt, 1) && !found(&tt, 2));
+        assert!((3..=6).all(|v| found(&tt, v)));
+
+        // With only current entries left, the shallowest one is replaced.
+        tt.insert(key(&tt, 0, 7), data(1));
+        assert!(found(&tt, 3) && found(&tt, 4) && found(&tt, 7));
+        assert!(!(found(&tt, 5) && found(&tt, 6)));
+    }
+
+    #[test]
+    fn test_deep_entry_survives_shallow_collisions() {
+        let mut tt: TT<SearchData> = TT::new(1);
+        let deep = key(&tt, 5, 1);
+        tt.insert(deep, data(12));
+        (2..=4).for_each(|v| tt.insert(key(&tt, 5, v), data(2)));
+
+        // Many shallow positions colliding in the same bucket replace the
+        // shallow entries and each other, but never the deep one.
+        for v in 5..=40 {
+            tt.insert(key(&tt, 5, v), data(1));
+            assert_eq!(tt.probe(deep).map(|d| d.depth()), Some(12));
+            assert!(tt.probe(key(&tt, 5, v)).is_some());
+        }
+    }
+
+    #[test]
+    fn test_probe_returns_static_eval() {
+        let mut tt: TT<SearchData> = TT::new(1);
+        let k = key(&tt, 3, 1234);
+        let stored = SearchData::create(4, 2, HashFlag::Beta, 50, -37, ShortMove::new(0));
+        tt.insert(k, stored);
+
+        assert_eq!(tt.probe(k).unwrap().static_eval(), -37);
+
+        // The TT is sized by the entry size, which grew with the static
+        // evaluation; a megabyte must still hold all entries.
+        let entry_size = std::mem::size_of::<Entry<SearchData>>();
+        assert!(tt.total_entries * entry_size <= MEGABYTE);
+        assert!((tt.total_buckets + 1) * entry_size * ENTRIES_PER_BUCKET > MEGABYTE);
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let path = std::env::temp_dir().join("rustic_test_tt_round_trip.hash");
+        let mut tt: TT<SearchData> = TT::new(1);
+        let k = key(&tt, 7, 4321);
+        let best_move = ShortMove::new(0x1234);
+        tt.insert(k, SearchData::create(9, 0, HashFlag::Exact, 75, -12, best_move));
+        tt.save(&path).unwrap();
+
+        let mut loaded: TT<SearchData> = TT::new(1);
+        loaded.load(&path).unwrap();
+        let entry = loaded.probe(k).expect("Entry lost");
+        assert_eq!(entry.get(9, 0, -100, 100).0, Some(75));
+        assert_eq!(entry.best_move().get_move(), best_move.get_move());
+        assert_eq!(entry.static_eval(), -12);
+
+        // A table of another size rejects the file and keeps its entries.
+        let mut other: TT<SearchData> = TT::new(2);
+        let other_key = key(&other, 1, 99);
+        other.insert(other_key, data(3));
+        let error = other.load(&path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(other.probe(other_key).is_some());
+        assert!(other.probe(k).is_none());
+
+        // So does a file that is not a saved table.
+        fs::write(&path, b"not a hash table").unwrap();
+        assert!(loaded.load(&path).is_err());
+        assert!(loaded.probe(k).is_some());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -21,11 +21,16 @@ You should have received a copy of the GNU General Public License along
 with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
-use super::{defs::ErrFatal, Engine};
+use super::{
+    defs::{ErrFatal, ErrNormal},
+    Engine,
+};
 use crate::{
+    comm::{CommControl, CommType},
     board::Board,
-    defs::{EngineRunResult, FEN_KIWIPETE_POSITION},
-    misc::parse,
+    defs::{EngineRunResult, ERR_PGN_FILE, ERR_PGN_MOVE, FEN_KIWIPETE_POSITION},
+    evaluation::params::EvalParams,
+    misc::{parse, pgn},
     misc::parse::PotentialMove,
     movegen::{
         defs::{Move, MoveList, MoveType},
@@ -33,7 +38,7 @@ use crate::{
     },
 };
 use if_chain::if_chain;
-use std::sync::Mutex;
+use std::{fs, sync::Mutex};
 
 impl Engine {
     // This function sets up a position using a given FEN-string.
@@ -44,6 +49,12 @@ impl Engine {
         let kp = self.cmdline.has_kiwipete();
         let fen = if kp { FEN_KIWIPETE_POSITION } else { f };
 
+        // A PGN file replaces the FEN-string: its game is played out from
+        // its own start position. KiwiPete still takes precedence.
+        if let (Some(path), false) = (self.cmdline.pgn(), kp) {
+            return self.setup_pgn(&path);
+        }
+
         // Lock the board, setup the FEN-string, and drop the lock.
         self.board
             .lock()
@@ -53,6 +64,36 @@ impl Engine {
         Ok(())
     }
 
+    // Replaces the compiled evaluation weights with the ones in the file
+    // given on the command line, if any. The board's clones share them.
+    pub fn setup_eval_params(&mut self) -> EngineRunResult {
+        if let Some(path) = self.cmdline.eval_params() {
+            let params = EvalParams::from_file(&path)?;
+            self.board.lock().expect(ErrFatal::LOCK).set_eval_params(params);
+        }
+
+        Ok(())
+    }
+
+    // Sets up the position at the end of the first game in the PGN file.
+    fn setup_pgn(&mut self, path: &str) -> EngineRunResult {
+        let contents = fs::read_to_string(path).map_err(|_| ERR_PGN_FILE)?;
+        let (fen, moves) = pgn::load_first_position_and_moves(&contents);
+
+        self.board
+            .lock()
+            .expect(ErrFatal::LOCK)
+            .fen_read(Some(&fen))?;
+
+        for m in moves {
+            if !self.execute_move(m) {
+                return Err(ERR_PGN_MOVE);
+            }
+        }
+
+        Ok(())
+    }
+
     // This function executes a move on the internal board, if it legal to
     // do so in the given position.
     pub fn execute_move(&mut self, m: String) -> bool {
@@ -64,10 +105,80 @@ impl Engine {
 
         if let Ok(ips) = is_pseudo_legal {
             is_legal = self.board.lock().expect(ErrFatal::LOCK).make(ips, &self.mg);
+        } else {
+            // Not a coordinate move; try to read it as SAN, so moves can
+            // be pasted from PGN.
+            let mut board = self.board.lock().expect(ErrFatal::LOCK);
+            if let Some(san_move) = board.san_to_move(&m, &self.mg) {
+                is_legal = board.make(san_move, &self.mg);
+            }
         }
         is_legal
     }
 
+    // Converts the moves given with "go searchmoves" into legal moves in
+    // the current position. Moves that are not legal are reported to the
+    // GUI and left out.
+    pub fn legal_search_moves(&self, moves: &[String]) -> Vec<Move> {
+        let mut legal_moves = Vec::new();
+
+        for m in moves.iter() {
+            let empty = (0usize, 0usize, 0usize);
+            let potential_move = parse::algebraic_move_to_number(&m[..]).unwrap_or(empty);
+            let mut board = self.board.lock().expect(ErrFatal::LOCK).clone();
+            let legal = match self.pseudo_legal(potential_move, &self.board, &self.mg) {
+                Ok(pl) if board.make(pl, &self.mg) => Some(pl),
+                _ => None,
+            };
+
+            match legal {
+                Some(lm) => legal_moves.push(lm),
+                None => {
+                    let msg = format!("searchmoves: {} ({m})", ErrNormal::NOT_LEGAL);
+                    self.comm.send(CommControl::InfoString(msg));
+                }
+            }
+        }
+
+        legal_moves
+    }
+
+    // With UCI_Chess960 enabled, the GUI expects castling to be sent as
+    // the king taking its own rook. This converts a move for output.
+    pub fn move_for_gui(&self, m: Move) -> Move {
+        if self.settings.chess960 && m.castling() {
+            let board = self.board.lock().expect(ErrFatal::LOCK);
+            m.with_to(board.castling_rook_move(m.to()).0)
+        } else {
+            m
+        }
+    }
+
+    // Sends the best move, and the move to ponder on, to the GUI. XBoard
+    // leaves it to the engine to keep track of the game, so there the move
+    // is also played on the board, unless the game moved on while the
+    // engine was thinking.
+    pub fn send_best_move(&mut self, m: Move, ponder: Option<Move>) {
+        if self.comm.get_protocol_name() == CommType::XBOARD {
+            let mut board = self.board.lock().expect(ErrFatal::LOCK);
+            if self.xboard.engine_side != Some(board.us()) || m.get_move() == 0 {
+                return;
+            }
+            board.make(m, &self.mg);
+        }
+
+        let ponder = ponder.map(|p| self.move_for_gui(p));
+        self.comm.send(CommControl::BestMove(self.move_for_gui(m), ponder));
+        self.comm.send(CommControl::Update);
+    }
+
+    // Returns a move from the opening book for the current position, if a
+    // book is loaded and it knows the position.
+    pub fn book_move(&self) -> Option<Move> {
+        let board = self.board.lock().expect(ErrFatal::LOCK);
+        self.book.as_ref()?.weighted_move(&board, &self.mg)
+    }
+
     // After the engine receives an incoming move, it checks if this move
     // is actually in the list of pseudo-legal moves for this position.
     pub fn pseudo_legal(
@@ -87,9 +198,14 @@ impl Engine {
         // determine final legality when executing the move.
         for i in 0..ml.len() {
             let current = ml.get_move(i);
+            // Castling can also be given as the king taking its own rook,
+            // as Chess960 GUIs do.
+            let is_king_takes_rook =
+                current.castling() && m.1 == mtx_board.castling_rook_move(current.to()).0;
+
             if_chain! {
                 if m.0 == current.from();
-                if m.1 == current.to();
+                if m.1 == current.to() || is_king_takes_rook;
                 if m.2 == current.promoted();
                 then {
                     result = Ok(current);
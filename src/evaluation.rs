@@ -23,28 +23,85 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 pub mod defs;
 pub mod kingsafety;
+pub mod kpk;
+pub mod material;
 pub mod mobility;
+pub mod params;
 pub mod pawn;
 pub mod psqt;
+pub mod space;
+pub mod threats;
 
 use crate::{board::Board, defs::Sides, movegen::MoveGenerator};
+use defs::{EvalBreakdown, TEMPO_BONUS};
 use psqt::KING_EDGE;
 
 pub fn evaluate_position(board: &mut Board, move_gen: &MoveGenerator) -> i16 {
+    evaluate_position_verbose(board, move_gen).total()
+}
+
+// Evaluates the position, keeping the terms separate. This is what
+// evaluate_position() sums up; the "eval" command prints it.
+pub fn evaluate_position_verbose(board: &mut Board, move_gen: &MoveGenerator) -> EvalBreakdown {
     const KING_ONLY: i16 = 300; // PSQT-points
-    let side = board.game_state.active_color as usize;
+    let mut eval = EvalBreakdown {
+        side: board.game_state.active_color as usize,
+        scale: material::SCALE_NORMAL,
+        ..Default::default()
+    };
+
+    // Known endgames are scored exactly. The other terms would only blur
+    // the result, and a draw should be a draw for either side to move.
+    if let Some(score) = kpk::evaluate_kpk(board) {
+        eval.endgame = score;
+        return eval;
+    }
+
     let w_psqt = board.game_state.psqt[Sides::WHITE];
     let b_psqt = board.game_state.psqt[Sides::BLACK];
-    let mut value = w_psqt - b_psqt;
+    let w_psqt_eg = board.game_state.psqt_eg[Sides::WHITE];
+    let b_psqt_eg = board.game_state.psqt_eg[Sides::BLACK];
+
+    // Taper between the middlegame and endgame PSQT's according to the
+    // material left on the board: phase 24 is pure middlegame, 0 is pure
+    // endgame. The PSQT's include the piece values; these are split off
+    // into the material term.
+    let phase = board.calculate_game_phase() as i32;
+    let mg = (w_psqt - b_psqt) as i32;
+    let eg = (w_psqt_eg - b_psqt_eg) as i32;
+    let tapered = ((mg * phase + eg * (24 - phase)) / 24) as i16;
+    let piece_values = material::evaluate_piece_values(board);
+    eval.psqt = tapered - piece_values;
+
+    // Add bishop pair bonus and material imbalance
+    eval.material = piece_values + material::evaluate_bishop_pair(board) + material::evaluate_imbalance(board);
 
     // Add cached pawn structure evaluation
-    value += board.get_cached_pawn_structure_score();
+    eval.pawns = board.get_cached_pawn_structure_score();
+
+    // Add passed pawn terms depending on the kings (not cached, as the
+    // kings are not part of the pawn hash)
+    eval.pawns += pawn::evaluate_passed_pawns_with_kings(board);
+    eval.pawns += pawn::evaluate_rook_behind_passers(board);
 
     // Add cached mobility evaluation
-    value += board.get_cached_mobility_score(move_gen);
+    eval.mobility = board.get_cached_mobility_score(move_gen);
+
+    // Add rook on the seventh rank bonus
+    eval.mobility += mobility::evaluate_rook_seventh(board, Sides::WHITE)
+        - mobility::evaluate_rook_seventh(board, Sides::BLACK);
+
+    // Add trapped bishop penalties
+    eval.mobility += mobility::evaluate_trapped_bishops(board, move_gen);
 
     // Add king safety evaluation (not cached for now, as it's complex)
-    value += kingsafety::evaluate_king_safety(board, move_gen);
+    eval.king_safety = kingsafety::evaluate_king_safety(board, move_gen);
+
+    // Add threats and hanging pieces
+    eval.threats = threats::evaluate_threats(board, move_gen);
+
+    // Add space
+    eval.space = space::evaluate_space(board, Sides::WHITE) - space::evaluate_space(board, Sides::BLACK);
 
     // If one of the sides is down to a bare king, apply the KING_EDGE PSQT
     // to drive that king to the edge and mate it.
@@ -56,18 +113,194 @@ pub fn evaluate_position(board: &mut Board, move_gen: &MoveGenerator) -> i16 {
         if w_king_square < 64 && b_king_square < 64 {
             let w_king_edge = KING_EDGE[w_king_square];
             let b_king_edge = KING_EDGE[b_king_square];
-            value += w_king_edge - b_king_edge;
+            eval.king_edge = w_king_edge - b_king_edge;
+        }
+    }
+
+    // Scale drawish endgames towards zero.
+    eval.scale = material::draw_scale_factor(board);
+
+    // The terms are from white's point of view: a positive value means
+    // "white is better", a negative value means "black is better".
+    // Alpha/Beta requires the value returned from the viewpoint of the
+    // side that is being evaluated, so total() flips the sum if it is
+    // black to move.
+
+    // The side to move gets the tempo bonus. It is applied after the flip,
+    // so it always counts for board.us() and changes sign with a null move.
+    eval.tempo = TEMPO_BONUS;
+
+    eval
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        board::defs::Pieces,
+        evaluation::{defs::PIECE_VALUES, params::EvalParams, pawn::DOUBLED_PAWN_PENALTY},
+    };
+
+    #[test]
+    fn test_evaluation_is_symmetric() {
+        let mg = MoveGenerator::new();
+        let fens = [
+            // Start position
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            // Castled middlegame
+            "r1bq1rk1/pp2bppp/2n1pn2/2pp4/3P4/2PBPN2/PP1N1PPP/R1BQ1RK1 w - - 0 8",
+            // En-passant capture available
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3",
+        ];
+
+        for fen in fens {
+            let mut board = Board::new();
+            board.fen_read(Some(fen)).unwrap();
+            let mut mirrored = board.mirror();
+
+            // The evaluation is from the side to move's point of view, and
+            // the mirror also swaps the side to move. In white's point of
+            // view, the mirrored score is the negated original.
+            let score = evaluate_position(&mut board, &mg);
+            let mirrored_score = evaluate_position(&mut mirrored, &mg);
+            assert_eq!(score, mirrored_score, "asymmetric evaluation of {fen}");
+        }
+    }
+
+    #[test]
+    fn test_tempo_bonus_follows_side_to_move() {
+        let mg = MoveGenerator::new();
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R";
+
+        let mut white = Board::new();
+        white.fen_read(Some(&format!("{fen} w KQkq - 4 4"))).unwrap();
+        let mut black = Board::new();
+        black.fen_read(Some(&format!("{fen} b KQkq - 4 4"))).unwrap();
+
+        // Convert both to white's point of view: only the tempo differs.
+        let from_white = evaluate_position(&mut white, &mg);
+        let from_black = -evaluate_position(&mut black, &mg);
+
+        assert_eq!(from_white - from_black, 2 * TEMPO_BONUS);
+    }
+
+    #[test]
+    fn test_opposite_bishops_score_reduced() {
+        let mg = MoveGenerator::new();
+
+        // Same material (white a pawn up), bishops on opposite or same colors.
+        let mut opposite = Board::new();
+        opposite.fen_read(Some("2b1k3/5p2/8/8/8/8/4PP2/2B1K3 w - - 0 1")).unwrap();
+        let mut same = Board::new();
+        same.fen_read(Some("4kb2/5p2/8/8/8/8/4PP2/2B1K3 w - - 0 1")).unwrap();
+
+        let opposite_score = evaluate_position(&mut opposite, &mg) - TEMPO_BONUS;
+        let same_score = evaluate_position(&mut same, &mg) - TEMPO_BONUS;
+
+        assert!(same_score > 0);
+        assert!(opposite_score < same_score / 2);
+    }
+
+    #[test]
+    fn test_active_king_preferred_in_pawn_endgame() {
+        let mg = MoveGenerator::new();
+
+        // Same pawns, white king in the center or behind its pawns.
+        let mut central = Board::new();
+        central.fen_read(Some("8/5ppp/4k3/8/4K3/8/5PPP/8 w - - 0 1")).unwrap();
+        let mut cornered = Board::new();
+        cornered.fen_read(Some("8/5ppp/4k3/8/8/8/5PPP/7K w - - 0 1")).unwrap();
+
+        let central_eval = evaluate_position_verbose(&mut central, &mg);
+        let cornered_eval = evaluate_position_verbose(&mut cornered, &mg);
+
+        // Without pieces, the pawn shelter is worth nothing any more.
+        assert_eq!(cornered_eval.king_safety, 0);
+        assert!(central_eval.total() > cornered_eval.total());
+    }
+
+    #[test]
+    fn test_kpk_scored_from_bitbase() {
+        let mg = MoveGenerator::new();
+        let eval = |fen: &str| {
+            let mut board = Board::new();
+            board.fen_read(Some(fen)).unwrap();
+            evaluate_position(&mut board, &mg)
+        };
+
+        // Drawn with white to move: exactly a draw, without the tempo.
+        assert_eq!(eval("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1"), 0);
+
+        // Won with black to move: decisive against black.
+        assert!(eval("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1") <= -kpk::KPK_WIN);
+    }
+
+    #[test]
+    fn test_breakdown_sums_to_evaluation() {
+        let mg = MoveGenerator::new();
+        let fens = [
+            "r1bq1rk1/pp2bppp/2n1pn2/2pp4/3P4/2PBPN2/PP1N1PPP/R1BQ1RK1 w - - 0 8",
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 b - - 0 1",
+            "2b1k3/5p2/8/8/8/8/4PP2/2B1K3 w - - 0 1",
+            "8/8/8/4k3/8/8/8/KQ6 b - - 0 1",
+            "4k3/8/4K3/8/4P3/8/8/8 b - - 0 1",
+        ];
+
+        for fen in fens {
+            let mut board = Board::new();
+            board.fen_read(Some(fen)).unwrap();
+            let e = evaluate_position_verbose(&mut board, &mg);
+
+            let sum = e.material
+                + e.psqt
+                + e.pawns
+                + e.mobility
+                + e.king_safety
+                + e.threats
+                + e.space
+                + e.king_edge
+                + e.endgame;
+            let scaled = (sum as i32 * e.scale as i32 / 64) as i16;
+            let from_side_to_move = if e.side == Sides::BLACK { -scaled } else { scaled };
+
+            assert_eq!(from_side_to_move + e.tempo, evaluate_position(&mut board, &mg), "{fen}");
         }
     }
 
-    // This function calculates the evaluation from white's point of view:
-    // a positive value means "white is better", a negative value means
-    // "black is better". Alpha/Beta requires the value returned from the
-    // viewpoint of the side that is being evaluated. Therefore if it is
-    // black to move, the value must first be flipped to black's viewpoint
-    // before it can be returned.
+    #[test]
+    fn test_breakdown_material_term() {
+        let mg = MoveGenerator::new();
+        let mut board = Board::new();
+
+        // White is a knight up; neither side has the bishop pair.
+        board.fen_read(Some("4k3/pppb4/8/8/8/8/PPPBN3/4K3 w - - 0 1")).unwrap();
+        let e = evaluate_position_verbose(&mut board, &mg);
+        assert_eq!(e.material, PIECE_VALUES[Pieces::KNIGHT] + material::evaluate_imbalance(&board));
+    }
+
+    #[test]
+    fn test_eval_params_file_changes_evaluation() {
+        let mg = MoveGenerator::new();
+        let fen = "r3k3/ppp5/8/8/8/4P3/PPP1P3/R3K3 w - - 0 1";
+        let mut board = Board::new();
+        board.fen_read(Some(fen)).unwrap();
+        let before = evaluate_position_verbose(&mut board, &mg);
+        let before_score = evaluate_position(&mut board, &mg);
 
-    value = if side == Sides::BLACK { -value } else { value };
+        let path = std::env::temp_dir().join("rustic_test_eval_params.toml");
+        let contents = format!("DOUBLED_PAWN_PENALTY = {}\n", 2 * DOUBLED_PAWN_PENALTY);
+        std::fs::write(&path, contents).unwrap();
+        let params = EvalParams::from_file(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        board.set_eval_params(params.unwrap());
+        let after = evaluate_position_verbose(&mut board, &mg);
 
-    value
+        // White's pair of doubled pawns now costs twice as much; nothing
+        // else changes.
+        let extra = 2 * DOUBLED_PAWN_PENALTY;
+        assert_eq!(before.scale, material::SCALE_NORMAL);
+        assert_eq!(after.pawns, before.pawns + extra);
+        assert_eq!(after.white_sum(), before.white_sum() + extra);
+        assert_eq!(evaluate_position(&mut board, &mg), before_score + extra);
+    }
 }
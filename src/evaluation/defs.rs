@@ -20,3 +20,118 @@ for more details.
 You should have received a copy of the GNU General Public License along
 with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
+
+use crate::{
+    board::defs::Pieces,
+    defs::{NrOf, Piece, Side, Sides},
+};
+
+// Bonus for the side to move. Having the move is worth something, and
+// adding it keeps evaluations of odd and even search depths closer.
+pub const TEMPO_BONUS: i16 = 12;
+
+// Material values of the pieces in centipawns, indexed K, Q, R, B, N, P.
+// This is the one place to tune them; the PSQT's carry their own. The king
+// can't be traded, so it has no material value; see exchange_value().
+pub const PIECE_VALUES: [i16; NrOf::PIECE_TYPES] = [0, 900, 500, 330, 320, 100];
+
+// Value of the king in exchanges: more than all other pieces combined, so
+// the king never comes out ahead when it captures or is captured.
+pub const KING_EXCHANGE_VALUE: i16 = 10_000;
+
+// The value of a piece when trading it: the material value, the king's
+// exchange value, or nothing for an empty square (Pieces::NONE).
+pub fn exchange_value(piece: Piece) -> i16 {
+    match piece {
+        Pieces::KING => KING_EXCHANGE_VALUE,
+        Pieces::NONE => 0,
+        _ => PIECE_VALUES[piece],
+    }
+}
+
+// The separate terms of an evaluation, from white's point of view. The
+// sum of the terms is scaled towards a draw, turned to the side to move's
+// point of view, and then the tempo bonus is added.
+#[derive(Clone, Copy, Default)]
+pub struct EvalBreakdown {
+    pub material: i16,    // Piece values and the bishop pair.
+    pub psqt: i16,        // Tapered PSQT's, without the piece values.
+    pub pawns: i16,       // Pawn structure and passed pawns.
+    pub mobility: i16,    // Mobility, rooks on the seventh, trapped bishops.
+    pub king_safety: i16, // King safety.
+    pub threats: i16,     // Threats and hanging pieces.
+    pub space: i16,       // Space behind the pawns.
+    pub king_edge: i16,   // Driving a bare king to the edge.
+    pub endgame: i16,     // Known endgames (KPK), instead of the other terms.
+    pub scale: i16,       // Draw scale factor, out of 64.
+    pub side: Side,       // Side to move.
+    pub tempo: i16,       // Tempo bonus for the side to move.
+}
+
+impl EvalBreakdown {
+    // Sum of the terms from white's point of view, before scaling.
+    pub fn white_sum(&self) -> i16 {
+        self.material
+            + self.psqt
+            + self.pawns
+            + self.mobility
+            + self.king_safety
+            + self.threats
+            + self.space
+            + self.king_edge
+            + self.endgame
+    }
+
+    // The evaluation from the side to move's point of view.
+    pub fn total(&self) -> i16 {
+        let scaled = (self.white_sum() as i32 * self.scale as i32 / 64) as i16;
+        let value = if self.side == Sides::BLACK { -scaled } else { scaled };
+        value + self.tempo
+    }
+
+    pub fn as_string(&self) -> String {
+        let terms = [
+            ("material", self.material),
+            ("psqt", self.psqt),
+            ("pawns", self.pawns),
+            ("mobility", self.mobility),
+            ("king safety", self.king_safety),
+            ("threats", self.threats),
+            ("space", self.space),
+            ("king edge", self.king_edge),
+            ("endgame", self.endgame),
+        ];
+        let mut s = String::from("term (white's view)   score\n");
+        for (name, score) in terms {
+            s.push_str(&format!("{name:<20} {score:>6}\n"));
+        }
+        s.push_str(&format!("{:<20} {:>6}\n", "sum", self.white_sum()));
+        s.push_str(&format!("{:<20} {:>3}/64\n", "draw scale", self.scale));
+        s.push_str(&format!("{:<20} {:>6}\n", "tempo (side to move)", self.tempo));
+        s.push_str(&format!("{:<20} {:>6}", "total (side to move)", self.total()));
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_piece_value_ordering() {
+        let value = |piece| PIECE_VALUES[piece];
+        assert!(value(Pieces::QUEEN) > value(Pieces::ROOK));
+        assert!(value(Pieces::ROOK) > value(Pieces::BISHOP));
+        assert!((value(Pieces::BISHOP) - value(Pieces::KNIGHT)).abs() < value(Pieces::PAWN) / 2);
+        assert!(value(Pieces::KNIGHT) > value(Pieces::PAWN));
+        assert!(value(Pieces::PAWN) > 0);
+
+        // The king is handled separately: worth more than all the other
+        // pieces of a side together, and an empty square is worth nothing.
+        let army = 8 * value(Pieces::PAWN)
+            + 2 * (value(Pieces::KNIGHT) + value(Pieces::BISHOP) + value(Pieces::ROOK))
+            + value(Pieces::QUEEN);
+        assert!(exchange_value(Pieces::KING) > army);
+        assert_eq!(exchange_value(Pieces::NONE), 0);
+    }
+}
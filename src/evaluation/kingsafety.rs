@@ -31,39 +31,36 @@ use crate::{
     movegen::MoveGenerator,
 };
 
-// King safety evaluation constants - rebalanced for better performance
-const MISSING_PAWN_PENALTY: [i16; 4] = [0, 15, 25, 35]; // Penalty for 0, 1, 2, 3 missing pawns
-const OPEN_FILE_PENALTY: i16 = 20;
-const HALF_OPEN_FILE_PENALTY: i16 = 10;
-const PAWN_STORM_PENALTY: i16 = 8;
-const WEAK_SQUARES_PENALTY: i16 = 12;
+// King safety evaluation constants - rebalanced for better performance.
+// Except for the tropism weights, these are the defaults of the EvalParams
+// weights with the same names.
+pub const MISSING_PAWN_PENALTY: [i16; 4] = [0, 15, 25, 35]; // Penalty for 0, 1, 2, 3 missing pawns
+pub const OPEN_FILE_PENALTY: i16 = 20;
+pub const HALF_OPEN_FILE_PENALTY: i16 = 10;
+pub const PAWN_STORM_PENALTY: i16 = 8;
+pub const WEAK_SQUARES_PENALTY: i16 = 12;
 
 // Attack evaluation constants
-const ATTACK_UNIT_WEIGHTS: [i16; 6] = [0, 0, 30, 50, 70, 85]; // For 0-5+ pieces attacking
-const SAFE_CHECK_BONUS: i16 = 40;
-const UNSAFE_CHECK_BONUS: i16 = 20;
+pub const ATTACK_UNIT_WEIGHTS: [i16; 6] = [0, 0, 30, 50, 70, 85]; // For 0-5+ pieces attacking
+pub const SAFE_CHECK_BONUS: i16 = 40;
+pub const UNSAFE_CHECK_BONUS: i16 = 20;
+
+// King tropism weights per piece type (K, Q, R, B, N, P), per square closer
+// to the enemy king. Pawns and the king itself are not counted.
+const TROPISM_WEIGHTS: [i16; 6] = [0, 5, 3, 2, 3, 0];
 
 // Piece attack values
-const KNIGHT_ATTACK_VALUE: i16 = 15;
-const BISHOP_ATTACK_VALUE: i16 = 15;
-const ROOK_ATTACK_VALUE: i16 = 25;
-const QUEEN_ATTACK_VALUE: i16 = 40;
-
-// Castling zones for pawn shield evaluation
-const KINGSIDE_CASTLE_MASK: [Bitboard; 2] = [
-    0x00000000000000E0, // White kingside (f1, g1, h1)
-    0xE000000000000000, // Black kingside (f8, g8, h8)
-];
-
-const QUEENSIDE_CASTLE_MASK: [Bitboard; 2] = [
-    0x000000000000001C, // White queenside (c1, d1, e1)
-    0x1C00000000000000, // Black queenside (c8, d8, e8)
-];
+pub const KNIGHT_ATTACK_VALUE: i16 = 15;
+pub const BISHOP_ATTACK_VALUE: i16 = 15;
+pub const ROOK_ATTACK_VALUE: i16 = 25;
+pub const QUEEN_ATTACK_VALUE: i16 = 40;
 
 // King zone masks for attack evaluation (squares around king)
 const KING_ZONE_MASKS: [Bitboard; 64] = init_king_zone_masks();
 
-// Note: Pawn shield evaluation is done by checking specific squares rather than using masks
+// Pawn shield masks per side and king square: the two ranks in front of
+// the king, on the king's file and the files next to it.
+const PAWN_SHIELD_MASKS: [[Bitboard; 64]; 2] = init_pawn_shield_masks();
 
 pub fn evaluate_king_safety(board: &Board, move_gen: &MoveGenerator) -> i16 {
     let white_safety = calculate_king_safety(board, move_gen, Sides::WHITE);
@@ -78,7 +75,7 @@ pub fn evaluate_king_safety(board: &Board, move_gen: &MoveGenerator) -> i16 {
 
 fn calculate_game_phase_factor(board: &Board) -> i16 {
     // Calculate a simple game phase factor based on piece count
-    // 100 = full middle game, 50 = endgame
+    // 100 = full middle game, 50 = endgame, 0 = pawn endgame
     let mut piece_count = 0;
     
     // Count major and minor pieces (exclude pawns and kings)
@@ -92,7 +89,13 @@ fn calculate_game_phase_factor(board: &Board) -> i16 {
     // Scale from 50 (endgame) to 100 (middle game)
     // With 30 pieces at start, we get 100%; with 6 pieces, we get 50%
     let factor = 50 + (piece_count as i16 * 50) / 30;
-    factor.min(100).max(50)
+
+    // The shelter and attack terms are tuned for the middlegame, so they
+    // never count for more than the middlegame share of the tapered eval.
+    // Towards a pawn endgame they fade out, and the king is valued for
+    // being active instead, by the endgame king PSQT (psqt::KING_EG).
+    let middlegame_share = board.calculate_game_phase() * 100 / 24;
+    factor.min(100).max(50).min(middlegame_share)
 }
 
 fn calculate_king_safety(board: &Board, move_gen: &MoveGenerator, side: Side) -> i16 {
@@ -105,11 +108,8 @@ fn calculate_king_safety(board: &Board, move_gen: &MoveGenerator, side: Side) ->
     
     let mut safety_score = 0;
     
-    // Determine castling status
-    let castling_status = determine_castling_status(board, king_square, side);
-    
-    // Evaluate pawn shield based on castling position
-    safety_score += evaluate_pawn_shield(board, king_square, side, castling_status);
+    // Evaluate pawn shield in front of the king
+    safety_score += evaluate_pawn_shield(board, king_square, side);
     
     // Evaluate open files near king
     safety_score += evaluate_open_files(board, king_square, side);
@@ -122,104 +122,57 @@ fn calculate_king_safety(board: &Board, move_gen: &MoveGenerator, side: Side) ->
     
     // Evaluate weak squares around king
     safety_score += evaluate_weak_squares(board, king_square, side);
+
+    // Enemy pieces close to our king
+    safety_score -= evaluate_king_tropism(board, side ^ 1);
     
     safety_score
 }
 
-#[derive(Debug, Clone, Copy)]
-enum CastlingStatus {
-    Kingside,
-    Queenside,
-    Center,
-}
-
-fn determine_castling_status(_board: &Board, king_square: Square, side: Side) -> CastlingStatus {
-    // Additional safety check
-    if king_square >= 64 {
-        return CastlingStatus::Center;
+// Reward pieces of the given side for being close to the enemy king. This
+// complements the king zone attack evaluation, but uses a smooth distance
+// instead of zone membership, so pieces approaching the king already count.
+pub fn evaluate_king_tropism(board: &Board, side: Side) -> i16 {
+    let enemy_king = board.king_square(side ^ 1);
+    if enemy_king >= 64 {
+        return 0;
     }
-    
-    let king_bb = 1u64 << king_square;
-    
-    if (king_bb & KINGSIDE_CASTLE_MASK[side]) != 0 {
-        CastlingStatus::Kingside
-    } else if (king_bb & QUEENSIDE_CASTLE_MASK[side]) != 0 {
-        CastlingStatus::Queenside
-    } else {
-        CastlingStatus::Center
+
+    let mut score = 0;
+    for piece in [Pieces::QUEEN, Pieces::ROOK, Pieces::BISHOP, Pieces::KNIGHT] {
+        let mut pieces = board.get_pieces(piece, side);
+        while pieces > 0 {
+            let square = bits::next(&mut pieces);
+            let distance = Board::square_distance(square, enemy_king) as i16;
+            score += (7 - distance) * TROPISM_WEIGHTS[piece];
+        }
     }
+
+    score
 }
 
-fn evaluate_pawn_shield(board: &Board, king_square: Square, side: Side, castling_status: CastlingStatus) -> i16 {
+// Counts the files in front of the king without a pawn of its own in the
+// shield. Files that have no shield squares (the king is on the edge of
+// the board, or on the last rank) are not counted.
+fn evaluate_pawn_shield(board: &Board, king_square: Square, side: Side) -> i16 {
     // Additional safety check
     if king_square >= 64 {
         return 0;
     }
-    
-    let friendly_pawns = board.get_pieces(Pieces::PAWN, side);
+
+    let shield = PAWN_SHIELD_MASKS[side][king_square];
+    let shield_pawns = board.get_pieces(Pieces::PAWN, side) & shield;
     let king_file = king_square % 8;
     let mut missing_pawns = 0;
-    
-    match castling_status {
-        CastlingStatus::Kingside => {
-            // Count key pawn positions for kingside castling
-            if side == Sides::WHITE {
-                // Check only g2, h2 for white (most important pawns)
-                let g2 = (friendly_pawns & (1u64 << 14)) != 0; // g2
-                let h2 = (friendly_pawns & (1u64 << 15)) != 0; // h2
-                
-                if !g2 { missing_pawns += 1; }
-                if !h2 { missing_pawns += 1; }
-            } else {
-                // Check only g7, h7 for black (most important pawns)
-                let g7 = (friendly_pawns & (1u64 << 54)) != 0; // g7
-                let h7 = (friendly_pawns & (1u64 << 55)) != 0; // h7
-                
-                if !g7 { missing_pawns += 1; }
-                if !h7 { missing_pawns += 1; }
-            }
-        },
-        CastlingStatus::Queenside => {
-            // Count key pawn positions for queenside castling
-            if side == Sides::WHITE {
-                // Check only b2, c2 for white (most important pawns)
-                let b2 = (friendly_pawns & (1u64 << 9)) != 0;  // b2
-                let c2 = (friendly_pawns & (1u64 << 10)) != 0; // c2
-                
-                if !b2 { missing_pawns += 1; }
-                if !c2 { missing_pawns += 1; }
-            } else {
-                // Check only b7, c7 for black (most important pawns)
-                let b7 = (friendly_pawns & (1u64 << 49)) != 0; // b7
-                let c7 = (friendly_pawns & (1u64 << 50)) != 0; // c7
-                
-                if !b7 { missing_pawns += 1; }
-                if !c7 { missing_pawns += 1; }
-            }
-        },
-        CastlingStatus::Center => {
-            // King in center - only check king's file and one adjacent file for basic protection
-            let pawn_rank = if side == Sides::WHITE { 1 } else { 6 };
-            
-            // Check king's file
-            let king_file_pawn = pawn_rank * 8 + king_file;
-            if (friendly_pawns & (1u64 << king_file_pawn)) == 0 {
-                missing_pawns += 1;
-            }
-            
-            // Check one adjacent file (prefer e-file for central king)
-            let adjacent_file = if king_file >= 4 { king_file - 1 } else { king_file + 1 };
-            if adjacent_file < 8 {
-                let adjacent_pawn = pawn_rank * 8 + adjacent_file;
-                if (friendly_pawns & (1u64 << adjacent_pawn)) == 0 {
-                    missing_pawns += 1;
-                }
-            }
+
+    for file in &BB_FILES[king_file.saturating_sub(1)..=(king_file + 1).min(7)] {
+        if shield & file != 0 && shield_pawns & file == 0 {
+            missing_pawns += 1;
         }
     }
-    
+
     missing_pawns = missing_pawns.min(3);
-    -MISSING_PAWN_PENALTY[missing_pawns]
+    -board.eval_params.missing_pawn_penalty[missing_pawns]
 }
 
 fn evaluate_open_files(board: &Board, king_square: Square, side: Side) -> i16 {
@@ -247,10 +200,10 @@ fn evaluate_open_files(board: &Board, king_square: Square, side: Side) -> i16 {
         
         if !friendly_on_file && !enemy_on_file {
             // Completely open file
-            penalty += OPEN_FILE_PENALTY;
+            penalty += board.eval_params.open_file_penalty;
         } else if !friendly_on_file && enemy_on_file {
             // Half-open file (dangerous for king)
-            penalty += HALF_OPEN_FILE_PENALTY;
+            penalty += board.eval_params.half_open_file_penalty;
         }
     }
     
@@ -304,7 +257,7 @@ fn evaluate_pawn_storm(board: &Board, king_square: Square, side: Side) -> i16 {
             // Calculate storm penalty based on proximity to king
             let distance = (king_rank as i32 - most_advanced_rank as i32).abs();
             if distance <= 2 {
-                storm_penalty += PAWN_STORM_PENALTY * (3 - distance as i16);
+                storm_penalty += board.eval_params.pawn_storm_penalty * (3 - distance as i16);
             }
         }
     }
@@ -333,7 +286,7 @@ fn evaluate_king_attacks(board: &Board, move_gen: &MoveGenerator, king_square: S
         let knight_attacks = move_gen.get_non_slider_attacks(Pieces::KNIGHT, knight_square);
         
         if (knight_attacks & king_zone) != 0 {
-            attack_value += KNIGHT_ATTACK_VALUE;
+            attack_value += board.eval_params.knight_attack_value;
             attacker_count += 1;
         }
         
@@ -355,7 +308,7 @@ fn evaluate_king_attacks(board: &Board, move_gen: &MoveGenerator, king_square: S
         let bishop_attacks = move_gen.get_slider_attacks(Pieces::BISHOP, bishop_square, occupancy);
         
         if (bishop_attacks & king_zone) != 0 {
-            attack_value += BISHOP_ATTACK_VALUE;
+            attack_value += board.eval_params.bishop_attack_value;
             attacker_count += 1;
         }
         
@@ -377,7 +330,7 @@ fn evaluate_king_attacks(board: &Board, move_gen: &MoveGenerator, king_square: S
         let rook_attacks = move_gen.get_slider_attacks(Pieces::ROOK, rook_square, occupancy);
         
         if (rook_attacks & king_zone) != 0 {
-            attack_value += ROOK_ATTACK_VALUE;
+            attack_value += board.eval_params.rook_attack_value;
             attacker_count += 1;
         }
         
@@ -399,7 +352,7 @@ fn evaluate_king_attacks(board: &Board, move_gen: &MoveGenerator, king_square: S
         let queen_attacks = move_gen.get_slider_attacks(Pieces::QUEEN, queen_square, occupancy);
         
         if (queen_attacks & king_zone) != 0 {
-            attack_value += QUEEN_ATTACK_VALUE;
+            attack_value += board.eval_params.queen_attack_value;
             attacker_count += 1;
         }
         
@@ -416,10 +369,11 @@ fn evaluate_king_attacks(board: &Board, move_gen: &MoveGenerator, king_square: S
     
     // Apply attack weight based on number of attackers
     let weight_index = attacker_count.min(5);
-    let weighted_attack = (attack_value * ATTACK_UNIT_WEIGHTS[weight_index]) / 100;
+    let weighted_attack = (attack_value * board.eval_params.attack_unit_weights[weight_index]) / 100;
     
     // Add check bonuses
-    let check_bonus = safe_checks * SAFE_CHECK_BONUS + unsafe_checks * UNSAFE_CHECK_BONUS;
+    let check_bonus = safe_checks * board.eval_params.safe_check_bonus
+        + unsafe_checks * board.eval_params.unsafe_check_bonus;
     
     -(weighted_attack + check_bonus)
 }
@@ -458,7 +412,7 @@ fn evaluate_weak_squares(board: &Board, king_square: Square, side: Side) -> i16
     
     // Cap the penalty to avoid excessive punishment
     let capped_weak_squares = weak_squares.min(4);
-    -capped_weak_squares * WEAK_SQUARES_PENALTY
+    -capped_weak_squares * board.eval_params.weak_squares_penalty
 }
 
 fn is_safe_check(board: &Board, attacker_square: Square, attacker_side: Side) -> bool {
@@ -504,7 +458,8 @@ fn can_be_defended_by_pawn(square: Square, friendly_pawns: Bitboard, side: Side)
         ];
         
         for defending_file in defending_files.iter().flatten() {
-            let pawn_rank = (rank + 1).min(6);
+            // Mirrors the white case: no pawn can defend the 7th and 8th rank.
+            let pawn_rank = rank + 1;
             if pawn_rank <= 6 {
                 let pawn_square = pawn_rank * 8 + defending_file;
                 if (friendly_pawns & (1u64 << pawn_square)) != 0 {
@@ -550,4 +505,83 @@ const fn init_king_zone_masks() -> [Bitboard; 64] {
     }
     
     masks
-} 
\ No newline at end of file
+}
+
+// Initialize pawn shield masks (two ranks of three files ahead of the king)
+const fn init_pawn_shield_masks() -> [[Bitboard; 64]; 2] {
+    let mut masks = [[0; 64]; 2];
+    let mut square = 0;
+
+    while square < 64 {
+        let file = (square % 8) as i32;
+        let rank = (square / 8) as i32;
+
+        let mut side = 0;
+        while side < 2 {
+            let forward = if side == Sides::WHITE { 1 } else { -1 };
+            let mut mask = 0;
+
+            let mut ahead = 1;
+            while ahead <= 2 {
+                let new_rank = rank + forward * ahead;
+                let mut df = -1i32;
+                while df <= 1 {
+                    let new_file = file + df;
+                    if new_file >= 0 && new_file < 8 && new_rank >= 0 && new_rank < 8 {
+                        mask |= 1u64 << (new_rank * 8 + new_file);
+                    }
+                    df += 1;
+                }
+                ahead += 1;
+            }
+
+            masks[side][square] = mask;
+            side += 1;
+        }
+        square += 1;
+    }
+
+    masks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tropism(fen: &str, side: Side) -> i16 {
+        let mut board = Board::new();
+        board.fen_read(Some(fen)).unwrap();
+        evaluate_king_tropism(&board, side)
+    }
+
+    #[test]
+    fn test_queen_near_king_scores_higher() {
+        let near = tropism("6k1/8/5Q2/8/8/8/8/K7 w - - 0 1", Sides::WHITE);
+        let far = tropism("6k1/8/8/8/8/8/8/K6Q w - - 0 1", Sides::WHITE);
+
+        assert!(near > far);
+        assert_eq!(near, 5 * TROPISM_WEIGHTS[Pieces::QUEEN]);
+    }
+
+    fn shield(fen: &str, side: Side) -> i16 {
+        let mut board = Board::new();
+        board.fen_read(Some(fen)).unwrap();
+        evaluate_pawn_shield(&board, board.king_square(side), side)
+    }
+
+    #[test]
+    fn test_pawn_shield_for_king_on_any_square() {
+        // A king on e1 without d, e and f pawns misses its whole shield.
+        // Only checking e2 and d2 counted two missing pawns.
+        let exposed = shield("4k3/pppppppp/8/8/8/8/PPP3PP/4K3 w - - 0 1", Sides::WHITE);
+        assert_eq!(exposed, -MISSING_PAWN_PENALTY[3]);
+        assert!(exposed < -MISSING_PAWN_PENALTY[2]);
+
+        // Pawns one or two ranks ahead both shield the king, for either side.
+        assert_eq!(shield("4k3/8/8/8/8/5P2/PPPPP1PP/4K3 w - - 0 1", Sides::WHITE), 0);
+        assert_eq!(shield("4k3/3p4/4pp2/8/8/8/8/4K3 b - - 0 1", Sides::BLACK), 0);
+
+        // A king on the edge only has two files to shield.
+        assert_eq!(shield("4k3/8/8/8/8/8/6P1/7K w - - 0 1", Sides::WHITE), -MISSING_PAWN_PENALTY[1]);
+    }
+}
@@ -0,0 +1,296 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// King and pawn versus king. Whether such an ending is won or drawn
+// depends on the exact king positions, which the normal evaluation can't
+// see. A bitbase with one bit per position (won or not) is built once by
+// retrograde analysis, and then looked up.
+//
+// Positions are stored with the pawn as white, on the files a-d; others
+// are flipped and mirrored to match. A position is indexed by the side
+// to move, both king squares, and the pawn square.
+
+use crate::{
+    board::{defs::Pieces, Board},
+    defs::{Side, Sides, Square},
+    misc::bits,
+};
+use std::sync::OnceLock;
+
+// Score for a won KPK ending, from the pawn's side. The pawn's rank is
+// added, so the engine keeps pushing it.
+pub const KPK_WIN: i16 = 900;
+pub const KPK_RANK_BONUS: i16 = 10;
+
+// Pawn squares: files a-d on ranks 2-7.
+const PAWN_SQUARES: usize = 24;
+const POSITIONS: usize = 2 * 64 * 64 * PAWN_SQUARES;
+
+// Results during the retrograde analysis. These are bit flags, so the
+// results of all moves from a position can be or-ed together.
+const INVALID: u8 = 0;
+const UNKNOWN: u8 = 1;
+const DRAW: u8 = 2;
+const WIN: u8 = 4;
+
+// One bit per position: set if the pawn's side wins. Built on first use.
+static KPK_BITBASE: OnceLock<Vec<u64>> = OnceLock::new();
+
+// Returns the score of a KPK position from white's point of view, or None
+// if the material is not exactly king and pawn versus king.
+pub fn evaluate_kpk(board: &Board) -> Option<i16> {
+    if board.occupancy().count_ones() != 3 {
+        return None;
+    }
+
+    let (strong, mut pawns) = if board.get_pieces(Pieces::PAWN, Sides::WHITE) > 0 {
+        (Sides::WHITE, board.get_pieces(Pieces::PAWN, Sides::WHITE))
+    } else {
+        (Sides::BLACK, board.get_pieces(Pieces::PAWN, Sides::BLACK))
+    };
+    if pawns == 0 {
+        return None;
+    }
+
+    // Normalize: the pawn is white, on the files a-d.
+    let flip = if strong == Sides::WHITE { 0 } else { 56 };
+    let mut pawn = bits::next(&mut pawns) ^ flip;
+    let mut strong_king = board.king_square(strong) ^ flip;
+    let mut weak_king = board.king_square(strong ^ 1) ^ flip;
+    if pawn % 8 > 3 {
+        pawn ^= 7;
+        strong_king ^= 7;
+        weak_king ^= 7;
+    }
+    let stm = if board.us() == strong { Sides::WHITE } else { Sides::BLACK };
+
+    let score = if probe(stm, strong_king, weak_king, pawn) {
+        KPK_WIN + (pawn / 8) as i16 * KPK_RANK_BONUS
+    } else {
+        0
+    };
+
+    Some(if strong == Sides::WHITE { score } else { -score })
+}
+
+// Returns true if the position (normalized as above) is won for white.
+pub fn probe(stm: Side, white_king: Square, black_king: Square, pawn: Square) -> bool {
+    let bitbase = KPK_BITBASE.get_or_init(build);
+    let i = index(stm, white_king, black_king, pawn);
+    bitbase[i / 64] & (1 << (i % 64)) != 0
+}
+
+fn index(stm: Side, white_king: Square, black_king: Square, pawn: Square) -> usize {
+    let pawn_index = (pawn / 8 - 1) * 4 + pawn % 8;
+    stm + 2 * black_king + 128 * white_king + 8192 * pawn_index
+}
+
+fn distance(a: Square, b: Square) -> usize {
+    let files = (a % 8).abs_diff(b % 8);
+    let ranks = (a / 8).abs_diff(b / 8);
+    files.max(ranks)
+}
+
+// The squares a king on the given square can go to.
+fn king_moves(square: Square) -> impl Iterator<Item = Square> {
+    let (file, rank) = ((square % 8) as i8, (square / 8) as i8);
+    [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)]
+        .into_iter()
+        .map(move |(df, dr)| (file + df, rank + dr))
+        .filter(|&(f, r)| (0..8).contains(&f) && (0..8).contains(&r))
+        .map(|(f, r)| (r * 8 + f) as Square)
+}
+
+// A white pawn attacks the squares diagonally in front of it.
+fn pawn_attacks(pawn: Square, square: Square) -> bool {
+    square / 8 == pawn / 8 + 1 && (square % 8).abs_diff(pawn % 8) == 1
+}
+
+// Runs the retrograde analysis: positions that are won or drawn right
+// away are classified first, and then positions are classified from the
+// results of their moves until nothing changes. What is left can't be
+// won, and is a draw.
+fn build() -> Vec<u64> {
+    let mut results = vec![INVALID; POSITIONS];
+    let positions = || {
+        (0..PAWN_SQUARES).flat_map(|p| {
+            let pawn = (p / 4 + 1) * 8 + p % 4;
+            (0..64).flat_map(move |wk| {
+                (0..64).flat_map(move |bk| [Sides::WHITE, Sides::BLACK].map(|stm| (stm, wk, bk, pawn)))
+            })
+        })
+    };
+
+    for (stm, wk, bk, pawn) in positions() {
+        results[index(stm, wk, bk, pawn)] = initial_result(stm, wk, bk, pawn);
+    }
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (stm, wk, bk, pawn) in positions() {
+            let i = index(stm, wk, bk, pawn);
+            if results[i] == UNKNOWN {
+                results[i] = classify(&results, stm, wk, bk, pawn);
+                changed |= results[i] != UNKNOWN;
+            }
+        }
+    }
+
+    let mut bitbase = vec![0u64; POSITIONS / 64];
+    for (i, &result) in results.iter().enumerate() {
+        if result == WIN {
+            bitbase[i / 64] |= 1 << (i % 64);
+        }
+    }
+    bitbase
+}
+
+fn initial_result(stm: Side, wk: Square, bk: Square, pawn: Square) -> u8 {
+    let promotion = pawn + 8;
+
+    // Pieces on the same square, touching kings, or black in check with
+    // white to move.
+    if wk == bk
+        || wk == pawn
+        || bk == pawn
+        || distance(wk, bk) <= 1
+        || (stm == Sides::WHITE && pawn_attacks(pawn, bk))
+    {
+        return INVALID;
+    }
+
+    // White promotes, and the queen can't be taken right away.
+    if stm == Sides::WHITE
+        && pawn / 8 == 6
+        && wk != promotion
+        && bk != promotion
+        && (distance(bk, promotion) > 1 || distance(wk, promotion) == 1)
+    {
+        return WIN;
+    }
+
+    if stm == Sides::BLACK {
+        // Black is stalemated, or takes the undefended pawn.
+        let is_safe = |s: Square| distance(wk, s) > 1 && !pawn_attacks(pawn, s);
+        if !king_moves(bk).any(is_safe) {
+            return DRAW;
+        }
+        if distance(bk, pawn) == 1 && distance(wk, pawn) > 1 {
+            return DRAW;
+        }
+    }
+
+    UNKNOWN
+}
+
+fn classify(results: &[u8], stm: Side, wk: Square, bk: Square, pawn: Square) -> u8 {
+    let mut r = INVALID;
+
+    if stm == Sides::WHITE {
+        for s in king_moves(wk).filter(|&s| distance(s, bk) > 1 && s != pawn) {
+            r |= results[index(Sides::BLACK, s, bk, pawn)];
+        }
+
+        // Pushing to the seventh rank; promotions are classified already.
+        let push = pawn + 8;
+        if pawn / 8 < 6 && push != wk && push != bk {
+            r |= results[index(Sides::BLACK, wk, bk, push)];
+            if pawn / 8 == 1 && push + 8 != wk && push + 8 != bk {
+                r |= results[index(Sides::BLACK, wk, bk, push + 8)];
+            }
+        }
+
+        if r & WIN != 0 {
+            WIN
+        } else if r & UNKNOWN != 0 {
+            UNKNOWN
+        } else {
+            DRAW
+        }
+    } else {
+        for s in king_moves(bk).filter(|&s| distance(s, wk) > 1 && !pawn_attacks(pawn, s) && s != pawn) {
+            r |= results[index(Sides::WHITE, wk, s, pawn)];
+        }
+
+        if r & DRAW != 0 {
+            DRAW
+        } else if r & UNKNOWN != 0 {
+            UNKNOWN
+        } else {
+            WIN
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kpk_score(fen: &str) -> Option<i16> {
+        let mut board = Board::new();
+        board.fen_read(Some(fen)).unwrap();
+        evaluate_kpk(&board)
+    }
+
+    #[test]
+    fn test_kpk_pawn_promotes() {
+        // The white king holds a key square in front of its pawn, so the
+        // pawn promotes with either side to move.
+        let win = KPK_WIN + 3 * KPK_RANK_BONUS;
+        assert_eq!(kpk_score("4k3/8/4K3/8/4P3/8/8/8 w - - 0 1"), Some(win));
+        assert_eq!(kpk_score("4k3/8/4K3/8/4P3/8/8/8 b - - 0 1"), Some(win));
+
+        // The black king is outside the square of the pawn.
+        assert!(kpk_score("8/8/8/1P3k2/8/8/8/7K w - - 0 1").unwrap() > 0);
+
+        // The same for a black pawn.
+        assert_eq!(kpk_score("8/8/8/3p4/8/3k4/8/3K4 w - - 0 1"), Some(-win));
+    }
+
+    #[test]
+    fn test_kpk_defending_king_takes_opposition() {
+        // With white to move, black keeps the opposition and the white king
+        // never reaches a key square. With black to move, black must give
+        // way.
+        assert_eq!(kpk_score("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1"), Some(0));
+        assert!(kpk_score("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1").unwrap() > 0);
+
+        // Black steps into the square of the pawn.
+        assert_eq!(kpk_score("8/8/8/1P3k2/8/8/8/7K b - - 0 1"), Some(0));
+
+        // Black to move is stalemated; with white to move, Kd6 and Kd7
+        // escorts the pawn in.
+        assert_eq!(kpk_score("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"), Some(0));
+        assert!(kpk_score("4k3/4P3/4K3/8/8/8/8/8 w - - 0 1").unwrap() > 0);
+
+        // A rook pawn is a draw when the defending king reaches the corner.
+        assert_eq!(kpk_score("k7/8/1K6/P7/8/8/8/8 w - - 0 1"), Some(0));
+    }
+
+    #[test]
+    fn test_kpk_ignores_other_material() {
+        assert_eq!(kpk_score("4k3/8/4K3/8/4P3/8/4P3/8 w - - 0 1"), None);
+        assert_eq!(kpk_score("4k3/8/4K3/8/4N3/8/8/8 w - - 0 1"), None);
+    }
+}
@@ -0,0 +1,205 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// Material based evaluation terms.
+
+use super::defs::PIECE_VALUES;
+use crate::{
+    board::{
+        defs::{Pieces, BB_DARK_SQUARES},
+        Board,
+    },
+    defs::{Side, Sides},
+};
+
+// Bonus for having two or more bishops in the middlegame. It grows to
+// twice this value in the endgame, where the pair is at its strongest on
+// an open board.
+const BISHOP_PAIR_BONUS: i16 = 30;
+
+// Material imbalance. Knights gain value when there are many pawns on
+// the board to block the position, and rooks need open files, so they
+// lose value. The adjustments are per piece, for each own pawn more (or
+// fewer) than IMBALANCE_PAWNS. Two rooks do partly the same job, so the
+// second one is worth a bit less.
+const IMBALANCE_PAWNS: i16 = 5;
+const KNIGHT_PAWN_ADJUSTMENT: i16 = 6;
+const ROOK_PAWN_ADJUSTMENT: i16 = -12;
+const ROOK_PAIR_PENALTY: i16 = -16;
+
+// Draw scaling, out of 64. Opposite colored bishop endings with few pawns
+// are very drawish, even a pawn or two up.
+pub const SCALE_NORMAL: i16 = 64;
+const SCALE_OPPOSITE_BISHOPS: i16 = 24;
+const OPPOSITE_BISHOPS_MAX_PAWNS: u32 = 8;
+
+// Returns the material balance by piece values from white's point of view.
+pub fn evaluate_piece_values(board: &Board) -> i16 {
+    [Pieces::QUEEN, Pieces::ROOK, Pieces::BISHOP, Pieces::KNIGHT, Pieces::PAWN]
+        .iter()
+        .map(|&piece| {
+            let white = board.get_pieces(piece, Sides::WHITE).count_ones() as i16;
+            let black = board.get_pieces(piece, Sides::BLACK).count_ones() as i16;
+            (white - black) * PIECE_VALUES[piece]
+        })
+        .sum()
+}
+
+// Returns the bishop pair bonus from white's point of view.
+pub fn evaluate_bishop_pair(board: &Board) -> i16 {
+    let phase = board.calculate_game_phase();
+    let bonus = BISHOP_PAIR_BONUS + BISHOP_PAIR_BONUS * (24 - phase) / 24;
+
+    let white = if has_bishop_pair(board, Sides::WHITE) { bonus } else { 0 };
+    let black = if has_bishop_pair(board, Sides::BLACK) { bonus } else { 0 };
+
+    white - black
+}
+
+// Returns the material imbalance adjustments from white's point of view.
+pub fn evaluate_imbalance(board: &Board) -> i16 {
+    side_imbalance(board, Sides::WHITE) - side_imbalance(board, Sides::BLACK)
+}
+
+// Returns the factor (out of 64) by which the evaluation should be scaled
+// towards a draw. Currently only opposite colored bishop endgames with
+// few pawns are scaled down.
+pub fn draw_scale_factor(board: &Board) -> i16 {
+    let w_bishops = board.get_pieces(Pieces::BISHOP, Sides::WHITE);
+    let b_bishops = board.get_pieces(Pieces::BISHOP, Sides::BLACK);
+    let other_pieces = [Pieces::QUEEN, Pieces::ROOK, Pieces::KNIGHT]
+        .iter()
+        .fold(0, |all, &piece| {
+            all | board.get_pieces(piece, Sides::WHITE) | board.get_pieces(piece, Sides::BLACK)
+        });
+    let pawns = board.get_pieces(Pieces::PAWN, Sides::WHITE) | board.get_pieces(Pieces::PAWN, Sides::BLACK);
+
+    let one_bishop_each = w_bishops.count_ones() == 1 && b_bishops.count_ones() == 1;
+    let opposite_colors = (w_bishops & BB_DARK_SQUARES == 0) != (b_bishops & BB_DARK_SQUARES == 0);
+
+    if one_bishop_each
+        && opposite_colors
+        && other_pieces == 0
+        && pawns.count_ones() <= OPPOSITE_BISHOPS_MAX_PAWNS
+    {
+        SCALE_OPPOSITE_BISHOPS
+    } else {
+        SCALE_NORMAL
+    }
+}
+
+fn side_imbalance(board: &Board, side: Side) -> i16 {
+    let count = |piece| board.get_pieces(piece, side).count_ones() as i16;
+    let pawns = count(Pieces::PAWN) - IMBALANCE_PAWNS;
+    let knights = count(Pieces::KNIGHT);
+    let rooks = count(Pieces::ROOK);
+
+    let mut score = knights * pawns * KNIGHT_PAWN_ADJUSTMENT + rooks * pawns * ROOK_PAWN_ADJUSTMENT;
+    if rooks >= 2 {
+        score += ROOK_PAIR_PENALTY;
+    }
+
+    score
+}
+
+fn has_bishop_pair(board: &Board, side: Side) -> bool {
+    board.get_pieces(Pieces::BISHOP, side).count_ones() >= 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bishop_pair_score(fen: &str) -> i16 {
+        let mut board = Board::new();
+        board.fen_read(Some(fen)).unwrap();
+        evaluate_bishop_pair(&board)
+    }
+
+    #[test]
+    fn test_bishop_pair_goes_to_side_with_two_bishops() {
+        // White has two bishops, black has bishop and knight.
+        let white_pair = bishop_pair_score("2b1kn2/8/8/8/8/8/8/2B1KB2 w - - 0 1");
+        assert!(white_pair > 0);
+
+        // Colors reversed.
+        let black_pair = bishop_pair_score("2b1kb2/8/8/8/8/8/8/2B1KN2 w - - 0 1");
+        assert_eq!(black_pair, -white_pair);
+    }
+
+    #[test]
+    fn test_bishop_pair_cancels_out() {
+        assert_eq!(bishop_pair_score("2b1kb2/8/8/8/8/8/8/2B1KB2 w - - 0 1"), 0);
+    }
+
+    #[test]
+    fn test_bishop_pair_worth_more_in_endgame() {
+        let endgame = bishop_pair_score("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1");
+        let middlegame = bishop_pair_score("rnbqk1nr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert!(endgame > middlegame);
+        assert!(middlegame > 0);
+    }
+
+    fn material_score(fen: &str) -> i16 {
+        let mut board = Board::new();
+        board.fen_read(Some(fen)).unwrap();
+        evaluate_piece_values(&board) + evaluate_bishop_pair(&board) + evaluate_imbalance(&board)
+    }
+
+    #[test]
+    fn test_knights_favored_by_pawns_bishops_by_open_board() {
+        // Two knights against two bishops: the knights do better with a
+        // full pawn structure than on a sparse board.
+        let closed = material_score("2b1kb2/pppppppp/8/8/8/8/PPPPPPPP/1N2K1N1 w - - 0 1");
+        let open = material_score("2b1kb2/p6p/8/8/8/8/P6P/1N2K1N1 w - - 0 1");
+
+        assert!(open < 0);
+        assert_eq!(closed - open, 2 * (8 - 2) * KNIGHT_PAWN_ADJUSTMENT);
+    }
+
+    #[test]
+    fn test_rook_pair_and_rook_pawn_adjustments() {
+        // The same rooks with a full and with an empty pawn structure.
+        let rooks_full = material_score("4k3/8/8/8/8/8/PPPPPPPP/R3K2R w - - 0 1")
+            - material_score("4k3/8/8/8/8/8/PPPPPPPP/4K3 w - - 0 1");
+        let rooks_empty = material_score("4k3/8/8/8/8/8/8/R3K2R w - - 0 1");
+        assert_eq!(rooks_full, 2 * PIECE_VALUES[Pieces::ROOK] + 2 * 3 * ROOK_PAWN_ADJUSTMENT + ROOK_PAIR_PENALTY);
+        assert_eq!(rooks_empty, 2 * PIECE_VALUES[Pieces::ROOK] - 2 * 5 * ROOK_PAWN_ADJUSTMENT + ROOK_PAIR_PENALTY);
+    }
+
+    #[test]
+    fn test_opposite_bishops_scaled_towards_draw() {
+        // White is a pawn up, bishops on c1 (dark) and c8 (light).
+        let mut board = Board::new();
+        board.fen_read(Some("2b1k3/5p2/8/8/8/8/4PP2/2B1K3 w - - 0 1")).unwrap();
+        assert_eq!(draw_scale_factor(&board), SCALE_OPPOSITE_BISHOPS);
+    }
+
+    #[test]
+    fn test_same_colored_bishops_not_scaled() {
+        // Bishops on c1 and f8 are both on dark squares.
+        let mut board = Board::new();
+        board.fen_read(Some("4kb2/5p2/8/8/8/8/4PP2/2B1K3 w - - 0 1")).unwrap();
+        assert_eq!(draw_scale_factor(&board), SCALE_NORMAL);
+    }
+}
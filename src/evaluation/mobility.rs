@@ -23,7 +23,7 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::{
     board::{
-        defs::{Pieces, BB_FILES, BB_SQUARES},
+        defs::{Pieces, Ranks, BB_FILES, BB_RANKS, BB_SQUARES},
         Board,
     },
     defs::{Bitboard, Side, Sides, Square},
@@ -32,21 +32,22 @@ use crate::{
 };
 
 // Mobility bonuses per piece type (indexed by mobility count)
-// Separate middle game and endgame values for better evaluation
-const KNIGHT_MOBILITY_MG: [i16; 9] = [-30, -20, -10, 0, 10, 20, 25, 30, 32];
-const KNIGHT_MOBILITY_EG: [i16; 9] = [-20, -15, -5, 5, 15, 20, 25, 28, 30];
+// Separate middle game and endgame values for better evaluation. These are
+// the defaults of the EvalParams weights with the same names.
+pub const KNIGHT_MOBILITY_MG: [i16; 9] = [-30, -20, -10, 0, 10, 20, 25, 30, 32];
+pub const KNIGHT_MOBILITY_EG: [i16; 9] = [-20, -15, -5, 5, 15, 20, 25, 28, 30];
 
-const BISHOP_MOBILITY_MG: [i16; 14] = [-30, -20, -10, 0, 10, 20, 25, 30, 32, 35, 37, 40, 42, 45];
-const BISHOP_MOBILITY_EG: [i16; 14] = [-20, -15, -5, 5, 15, 20, 25, 28, 30, 32, 35, 37, 40, 42];
+pub const BISHOP_MOBILITY_MG: [i16; 14] = [-30, -20, -10, 0, 10, 20, 25, 30, 32, 35, 37, 40, 42, 45];
+pub const BISHOP_MOBILITY_EG: [i16; 14] = [-20, -15, -5, 5, 15, 20, 25, 28, 30, 32, 35, 37, 40, 42];
 
-const ROOK_MOBILITY_MG: [i16; 15] = [-30, -20, -10, 0, 5, 10, 15, 20, 25, 30, 32, 35, 37, 40, 42];
-const ROOK_MOBILITY_EG: [i16; 15] = [-20, -15, -5, 5, 10, 15, 20, 25, 30, 35, 40, 45, 50, 55, 60];
+pub const ROOK_MOBILITY_MG: [i16; 15] = [-30, -20, -10, 0, 5, 10, 15, 20, 25, 30, 32, 35, 37, 40, 42];
+pub const ROOK_MOBILITY_EG: [i16; 15] = [-20, -15, -5, 5, 10, 15, 20, 25, 30, 35, 40, 45, 50, 55, 60];
 
-const QUEEN_MOBILITY_MG: [i16; 28] = [
+pub const QUEEN_MOBILITY_MG: [i16; 28] = [
     -30, -20, -10, 0, 5, 10, 15, 20, 25, 30, 32, 35, 37, 40, 42, 45, 47, 50, 52, 55, 57, 60, 62,
     65, 67, 70, 72, 75,
 ];
-const QUEEN_MOBILITY_EG: [i16; 28] = [
+pub const QUEEN_MOBILITY_EG: [i16; 28] = [
     -20, -15, -5, 5, 10, 15, 20, 25, 30, 35, 40, 45, 50, 55, 60, 65, 70, 75, 80, 85, 90, 95, 100,
     105, 110, 115, 120, 125,
 ];
@@ -59,6 +60,23 @@ const ROOK_HALF_OPEN_FILE_BONUS_EG: i16 = 25;
 const BISHOP_LONG_DIAGONAL_BONUS_MG: i16 = 15;
 const BISHOP_LONG_DIAGONAL_BONUS_EG: i16 = 10;
 
+// Rook on the seventh rank (the opponent's second rank)
+const ROOK_SEVENTH_BONUS: i16 = 20;
+const ROOK_SEVENTH_DOUBLED_BONUS: i16 = 15;
+const ROOK_SEVENTH_KING_BONUS: i16 = 15;
+
+// A bishop that grabbed a rook pawn can be shut in by the pawn next to it:
+// after Bxa7 b6 or ...Bxh2 g3 it will be lost for a pawn or two. These
+// are the (bishop, blocking pawn) squares for a white bishop; the squares
+// are mirrored for black.
+const TRAPPED_BISHOP_SQUARES: [(Square, Square); 4] = [
+    (48, 41), // a7, b6
+    (55, 46), // h7, g6
+    (40, 33), // a6, b5
+    (47, 38), // h6, g5
+];
+const TRAPPED_BISHOP_PENALTY: i16 = -150;
+
 // Game phase calculation
 fn calculate_game_phase(board: &Board) -> i16 {
     let mut phase = 0;
@@ -95,6 +113,7 @@ fn calculate_side_mobility(board: &Board, move_gen: &MoveGenerator, side: Side,
     let occupancy = board.occupancy();
     let own_pieces = board.bb_side[side];
     let opponent_pieces = board.bb_side[side ^ 1];
+    let params = &board.eval_params;
     
     // Calculate opponent attacks for better mobility assessment
     let opponent_attacks = calculate_opponent_attacks(board, move_gen, side ^ 1);
@@ -107,8 +126,8 @@ fn calculate_side_mobility(board: &Board, move_gen: &MoveGenerator, side: Side,
         let safe_moves = attacks & !own_pieces & !opponent_attacks;
         let mobility_count = safe_moves.count_ones() as usize;
         
-        mobility_score_mg += get_knight_mobility_bonus_mg(mobility_count);
-        mobility_score_eg += get_knight_mobility_bonus_eg(mobility_count);
+        mobility_score_mg += mobility_bonus(&params.knight_mobility_mg, mobility_count);
+        mobility_score_eg += mobility_bonus(&params.knight_mobility_eg, mobility_count);
     }
     
     // Bishop mobility
@@ -119,8 +138,8 @@ fn calculate_side_mobility(board: &Board, move_gen: &MoveGenerator, side: Side,
         let safe_moves = attacks & !own_pieces & !opponent_attacks;
         let mobility_count = safe_moves.count_ones() as usize;
         
-        mobility_score_mg += get_bishop_mobility_bonus_mg(mobility_count);
-        mobility_score_eg += get_bishop_mobility_bonus_eg(mobility_count);
+        mobility_score_mg += mobility_bonus(&params.bishop_mobility_mg, mobility_count);
+        mobility_score_eg += mobility_bonus(&params.bishop_mobility_eg, mobility_count);
         
         // Long diagonal bonus
         if is_bishop_on_long_diagonal(square, attacks) {
@@ -137,8 +156,8 @@ fn calculate_side_mobility(board: &Board, move_gen: &MoveGenerator, side: Side,
         let safe_moves = attacks & !own_pieces & !opponent_attacks;
         let mobility_count = safe_moves.count_ones() as usize;
         
-        mobility_score_mg += get_rook_mobility_bonus_mg(mobility_count);
-        mobility_score_eg += get_rook_mobility_bonus_eg(mobility_count);
+        mobility_score_mg += mobility_bonus(&params.rook_mobility_mg, mobility_count);
+        mobility_score_eg += mobility_bonus(&params.rook_mobility_eg, mobility_count);
         
         // Open/half-open file bonus
         let (mg_bonus, eg_bonus) = evaluate_rook_file_bonus_phased(board, square, side);
@@ -154,8 +173,8 @@ fn calculate_side_mobility(board: &Board, move_gen: &MoveGenerator, side: Side,
         let safe_moves = attacks & !own_pieces & !opponent_attacks;
         let mobility_count = safe_moves.count_ones() as usize;
         
-        mobility_score_mg += get_queen_mobility_bonus_mg(mobility_count);
-        mobility_score_eg += get_queen_mobility_bonus_eg(mobility_count);
+        mobility_score_mg += mobility_bonus(&params.queen_mobility_mg, mobility_count);
+        mobility_score_eg += mobility_bonus(&params.queen_mobility_eg, mobility_count);
     }
     
     // Interpolate between middle game and endgame scores
@@ -215,69 +234,10 @@ fn calculate_opponent_attacks(board: &Board, move_gen: &MoveGenerator, side: Sid
     attacks
 }
 
-// Updated mobility bonus functions with game phase support
-fn get_knight_mobility_bonus_mg(mobility_count: usize) -> i16 {
-    if mobility_count < KNIGHT_MOBILITY_MG.len() {
-        KNIGHT_MOBILITY_MG[mobility_count]
-    } else {
-        KNIGHT_MOBILITY_MG[KNIGHT_MOBILITY_MG.len() - 1]
-    }
-}
-
-fn get_knight_mobility_bonus_eg(mobility_count: usize) -> i16 {
-    if mobility_count < KNIGHT_MOBILITY_EG.len() {
-        KNIGHT_MOBILITY_EG[mobility_count]
-    } else {
-        KNIGHT_MOBILITY_EG[KNIGHT_MOBILITY_EG.len() - 1]
-    }
-}
-
-fn get_bishop_mobility_bonus_mg(mobility_count: usize) -> i16 {
-    if mobility_count < BISHOP_MOBILITY_MG.len() {
-        BISHOP_MOBILITY_MG[mobility_count]
-    } else {
-        BISHOP_MOBILITY_MG[BISHOP_MOBILITY_MG.len() - 1]
-    }
-}
-
-fn get_bishop_mobility_bonus_eg(mobility_count: usize) -> i16 {
-    if mobility_count < BISHOP_MOBILITY_EG.len() {
-        BISHOP_MOBILITY_EG[mobility_count]
-    } else {
-        BISHOP_MOBILITY_EG[BISHOP_MOBILITY_EG.len() - 1]
-    }
-}
-
-fn get_rook_mobility_bonus_mg(mobility_count: usize) -> i16 {
-    if mobility_count < ROOK_MOBILITY_MG.len() {
-        ROOK_MOBILITY_MG[mobility_count]
-    } else {
-        ROOK_MOBILITY_MG[ROOK_MOBILITY_MG.len() - 1]
-    }
-}
-
-fn get_rook_mobility_bonus_eg(mobility_count: usize) -> i16 {
-    if mobility_count < ROOK_MOBILITY_EG.len() {
-        ROOK_MOBILITY_EG[mobility_count]
-    } else {
-        ROOK_MOBILITY_EG[ROOK_MOBILITY_EG.len() - 1]
-    }
-}
-
-fn get_queen_mobility_bonus_mg(mobility_count: usize) -> i16 {
-    if mobility_count < QUEEN_MOBILITY_MG.len() {
-        QUEEN_MOBILITY_MG[mobility_count]
-    } else {
-        QUEEN_MOBILITY_MG[QUEEN_MOBILITY_MG.len() - 1]
-    }
-}
-
-fn get_queen_mobility_bonus_eg(mobility_count: usize) -> i16 {
-    if mobility_count < QUEEN_MOBILITY_EG.len() {
-        QUEEN_MOBILITY_EG[mobility_count]
-    } else {
-        QUEEN_MOBILITY_EG[QUEEN_MOBILITY_EG.len() - 1]
-    }
+// The bonus for a piece with this many safe squares. Pieces with more
+// squares than the table covers get its last entry.
+fn mobility_bonus(table: &[i16], mobility_count: usize) -> i16 {
+    table[mobility_count.min(table.len() - 1)]
 }
 
 fn evaluate_rook_file_bonus_phased(board: &Board, rook_square: Square, side: Side) -> (i16, i16) {
@@ -313,4 +273,146 @@ fn is_bishop_on_long_diagonal(square: Square, attacks: Bitboard) -> bool {
     } else {
         false
     }
-} 
\ No newline at end of file
+}
+
+// Bonus for rooks on the seventh rank, as seen from the given side. A rook
+// there attacks pawns that haven't moved yet and cuts off a king on the
+// back rank. It is worth more when another rook backs it up on the same
+// file, or when the enemy king is actually confined to the back rank.
+pub fn evaluate_rook_seventh(board: &Board, side: Side) -> i16 {
+    let (seventh, back_rank) = if side == Sides::WHITE {
+        (BB_RANKS[Ranks::R7], BB_RANKS[Ranks::R8])
+    } else {
+        (BB_RANKS[Ranks::R2], BB_RANKS[Ranks::R1])
+    };
+
+    let rooks = board.get_pieces(Pieces::ROOK, side);
+    let mut rooks_on_seventh = rooks & seventh;
+    let enemy_king_on_back_rank = board.get_pieces(Pieces::KING, side ^ 1) & back_rank > 0;
+    let mut bonus = 0;
+
+    while rooks_on_seventh > 0 {
+        let square = bits::next(&mut rooks_on_seventh);
+        let file = Board::square_on_file_rank(square).0 as usize;
+
+        bonus += ROOK_SEVENTH_BONUS;
+
+        if (rooks & BB_FILES[file]).count_ones() > 1 {
+            bonus += ROOK_SEVENTH_DOUBLED_BONUS;
+        }
+
+        if enemy_king_on_back_rank {
+            bonus += ROOK_SEVENTH_KING_BONUS;
+        }
+    }
+
+    bonus
+}
+
+// Penalty for trapped bishops, from white's point of view. A bishop is
+// trapped if it stands on one of the trap squares with the blocking pawn
+// next to it, that pawn is defended by another pawn, and the bishop can't
+// get out to a safe square beyond the opponent's two back ranks.
+pub fn evaluate_trapped_bishops(board: &Board, move_gen: &MoveGenerator) -> i16 {
+    side_trapped_bishops(board, move_gen, Sides::WHITE) - side_trapped_bishops(board, move_gen, Sides::BLACK)
+}
+
+fn side_trapped_bishops(board: &Board, move_gen: &MoveGenerator, side: Side) -> i16 {
+    let bishops = board.get_pieces(Pieces::BISHOP, side);
+    let enemy_pawns = board.get_pieces(Pieces::PAWN, side ^ 1);
+    let (enemy_pawn_attacks, enemy_camp, mirror) = if side == Sides::WHITE {
+        (bits::black_pawn_attacks(enemy_pawns), BB_RANKS[Ranks::R7] | BB_RANKS[Ranks::R8], 0)
+    } else {
+        (bits::white_pawn_attacks(enemy_pawns), BB_RANKS[Ranks::R1] | BB_RANKS[Ranks::R2], 56)
+    };
+    let own_pieces = board.bb_side[side];
+    let mut penalty = 0;
+
+    for (bishop, blocker) in TRAPPED_BISHOP_SQUARES {
+        let (bishop, blocker) = (bishop ^ mirror, blocker ^ mirror);
+        let blocked = bishops & BB_SQUARES[bishop] > 0
+            && enemy_pawns & BB_SQUARES[blocker] > 0
+            && enemy_pawn_attacks & BB_SQUARES[blocker] > 0;
+
+        if blocked {
+            let attacks = move_gen.get_slider_attacks(Pieces::BISHOP, bishop, board.occupancy());
+            let escapes = attacks & !own_pieces & !enemy_pawn_attacks & !enemy_camp & !BB_SQUARES[blocker];
+            if escapes == 0 {
+                penalty += TRAPPED_BISHOP_PENALTY;
+            }
+        }
+    }
+
+    penalty
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        let mut board = Board::new();
+        board.fen_read(Some(fen)).unwrap();
+        board
+    }
+
+    #[test]
+    fn test_bishop_trapped_after_bxh2_g3() {
+        let mg = MoveGenerator::new();
+
+        // After ...Bxh2 g3 the bishop can only go to g1, and the g3 pawn
+        // is defended by f2.
+        let trapped = board("rnbqk1nr/pppp1ppp/8/4p3/4P3/5NP1/PPPP1P1b/RNBQKB1R w KQkq - 0 4");
+        assert_eq!(evaluate_trapped_bishops(&trapped, &mg), -TRAPPED_BISHOP_PENALTY);
+
+        // Without the f2 pawn, Bxg3 gets the bishop out.
+        let free = board("rnbqk1nr/pppp1ppp/8/4p3/4P3/5NP1/PPPP3b/RNBQKB1R w KQkq - 0 4");
+        assert_eq!(evaluate_trapped_bishops(&free, &mg), 0);
+    }
+
+    #[test]
+    fn test_bishop_trapped_after_bxa7_b6() {
+        let mg = MoveGenerator::new();
+
+        let trapped = board("r3k3/B1p5/1p6/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(evaluate_trapped_bishops(&trapped, &mg), TRAPPED_BISHOP_PENALTY);
+
+        // With the c-pawn on c5, b6 is not defended, and Bxb6 frees the
+        // bishop.
+        let free = board("r3k3/B7/1p6/2p5/8/8/8/4K3 w - - 0 1");
+        assert_eq!(evaluate_trapped_bishops(&free, &mg), 0);
+    }
+
+    #[test]
+    fn test_rook_seventh_against_back_rank_king() {
+        let board = board("6k1/R7/8/8/8/8/8/6K1 w - - 0 1");
+
+        assert_eq!(
+            evaluate_rook_seventh(&board, Sides::WHITE),
+            ROOK_SEVENTH_BONUS + ROOK_SEVENTH_KING_BONUS
+        );
+        assert_eq!(evaluate_rook_seventh(&board, Sides::BLACK), 0);
+    }
+
+    #[test]
+    fn test_rook_seventh_doubled_on_file() {
+        // The king has left the back rank; the rook on a7 is backed up by a1.
+        let board = board("8/R7/6k1/8/8/8/8/R5K1 w - - 0 1");
+
+        assert_eq!(
+            evaluate_rook_seventh(&board, Sides::WHITE),
+            ROOK_SEVENTH_BONUS + ROOK_SEVENTH_DOUBLED_BONUS
+        );
+    }
+
+    #[test]
+    fn test_rook_seventh_for_black() {
+        let board = board("6k1/8/8/8/8/8/r7/6K1 b - - 0 1");
+
+        assert_eq!(
+            evaluate_rook_seventh(&board, Sides::BLACK),
+            ROOK_SEVENTH_BONUS + ROOK_SEVENTH_KING_BONUS
+        );
+        assert_eq!(evaluate_rook_seventh(&board, Sides::WHITE), 0);
+    }
+}
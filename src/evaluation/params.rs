@@ -0,0 +1,265 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// Evaluation weights that can be loaded from a file at startup, instead
+// of using the compiled constants. The file uses a TOML subset: one
+// "NAME = value" per line, where the name is that of the constant and
+// the value is an integer or an array of integers ("[1, 2, 3]", which
+// may span several lines). Comments start with '#'. Weights that are
+// not in the file keep their compiled value.
+
+use super::{kingsafety, mobility, pawn};
+use crate::defs::{ERR_EVAL_PARAMS_FILE, ERR_EVAL_PARAMS_VALUE};
+use std::fs;
+
+const COMMENT: char = '#';
+const ASSIGN: char = '=';
+const ARRAY_START: char = '[';
+const ARRAY_END: char = ']';
+const SEPARATOR: char = ',';
+
+#[derive(Clone)]
+pub struct EvalParams {
+    // Pawn structure
+    pub doubled_pawn_penalty: i16,
+    pub isolated_pawn_penalty: i16,
+    pub backward_pawn_penalty: i16,
+    pub doubled_isolated_pawn_penalty: i16,
+    pub passed_pawn_bonus: [i16; 8],
+    pub connected_passer_bonus: [i16; 8],
+    pub candidate_passer_bonus: [i16; 8],
+    pub connected_pawn_bonus: i16,
+    pub pawn_chain_bonus: i16,
+    pub central_pawn_bonus: i16,
+    pub rook_file_pawn_penalty: i16,
+
+    // Mobility, indexed by the number of safe squares
+    pub knight_mobility_mg: [i16; 9],
+    pub knight_mobility_eg: [i16; 9],
+    pub bishop_mobility_mg: [i16; 14],
+    pub bishop_mobility_eg: [i16; 14],
+    pub rook_mobility_mg: [i16; 15],
+    pub rook_mobility_eg: [i16; 15],
+    pub queen_mobility_mg: [i16; 28],
+    pub queen_mobility_eg: [i16; 28],
+
+    // King safety
+    pub missing_pawn_penalty: [i16; 4],
+    pub open_file_penalty: i16,
+    pub half_open_file_penalty: i16,
+    pub pawn_storm_penalty: i16,
+    pub weak_squares_penalty: i16,
+    pub attack_unit_weights: [i16; 6],
+    pub safe_check_bonus: i16,
+    pub unsafe_check_bonus: i16,
+    pub knight_attack_value: i16,
+    pub bishop_attack_value: i16,
+    pub rook_attack_value: i16,
+    pub queen_attack_value: i16,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self {
+            doubled_pawn_penalty: pawn::DOUBLED_PAWN_PENALTY,
+            isolated_pawn_penalty: pawn::ISOLATED_PAWN_PENALTY,
+            backward_pawn_penalty: pawn::BACKWARD_PAWN_PENALTY,
+            doubled_isolated_pawn_penalty: pawn::DOUBLED_ISOLATED_PAWN_PENALTY,
+            passed_pawn_bonus: pawn::PASSED_PAWN_BONUS,
+            connected_passer_bonus: pawn::CONNECTED_PASSER_BONUS,
+            candidate_passer_bonus: pawn::CANDIDATE_PASSER_BONUS,
+            connected_pawn_bonus: pawn::CONNECTED_PAWN_BONUS,
+            pawn_chain_bonus: pawn::PAWN_CHAIN_BONUS,
+            central_pawn_bonus: pawn::CENTRAL_PAWN_BONUS,
+            rook_file_pawn_penalty: pawn::ROOK_FILE_PAWN_PENALTY,
+
+            knight_mobility_mg: mobility::KNIGHT_MOBILITY_MG,
+            knight_mobility_eg: mobility::KNIGHT_MOBILITY_EG,
+            bishop_mobility_mg: mobility::BISHOP_MOBILITY_MG,
+            bishop_mobility_eg: mobility::BISHOP_MOBILITY_EG,
+            rook_mobility_mg: mobility::ROOK_MOBILITY_MG,
+            rook_mobility_eg: mobility::ROOK_MOBILITY_EG,
+            queen_mobility_mg: mobility::QUEEN_MOBILITY_MG,
+            queen_mobility_eg: mobility::QUEEN_MOBILITY_EG,
+
+            missing_pawn_penalty: kingsafety::MISSING_PAWN_PENALTY,
+            open_file_penalty: kingsafety::OPEN_FILE_PENALTY,
+            half_open_file_penalty: kingsafety::HALF_OPEN_FILE_PENALTY,
+            pawn_storm_penalty: kingsafety::PAWN_STORM_PENALTY,
+            weak_squares_penalty: kingsafety::WEAK_SQUARES_PENALTY,
+            attack_unit_weights: kingsafety::ATTACK_UNIT_WEIGHTS,
+            safe_check_bonus: kingsafety::SAFE_CHECK_BONUS,
+            unsafe_check_bonus: kingsafety::UNSAFE_CHECK_BONUS,
+            knight_attack_value: kingsafety::KNIGHT_ATTACK_VALUE,
+            bishop_attack_value: kingsafety::BISHOP_ATTACK_VALUE,
+            rook_attack_value: kingsafety::ROOK_ATTACK_VALUE,
+            queen_attack_value: kingsafety::QUEEN_ATTACK_VALUE,
+        }
+    }
+}
+
+impl EvalParams {
+    // Loads the weights from the file at the given path.
+    pub fn from_file(path: &str) -> Result<Self, u8> {
+        let contents = fs::read_to_string(path).map_err(|_| ERR_EVAL_PARAMS_FILE)?;
+        Self::parse(&contents)
+    }
+
+    // Starts from the compiled weights, and overrides the ones given in
+    // the text. Unknown names, values that are not integers, and arrays
+    // of the wrong length are errors.
+    pub fn parse(text: &str) -> Result<Self, u8> {
+        let mut params = Self::default();
+        let mut statement = String::new();
+
+        for line in text.lines() {
+            let line = line.split(COMMENT).next().unwrap_or("").trim();
+            statement.push_str(line);
+            statement.push(' ');
+
+            // An array can be continued on the next line.
+            if statement.contains(ARRAY_START) && !statement.contains(ARRAY_END) {
+                continue;
+            }
+
+            if !statement.trim().is_empty() {
+                params.set(&statement)?;
+            }
+            statement.clear();
+        }
+
+        if statement.trim().is_empty() {
+            Ok(params)
+        } else {
+            Err(ERR_EVAL_PARAMS_VALUE)
+        }
+    }
+
+    // Sets the weight in a "NAME = value" statement.
+    fn set(&mut self, statement: &str) -> Result<(), u8> {
+        let (name, value) = statement.split_once(ASSIGN).ok_or(ERR_EVAL_PARAMS_VALUE)?;
+        let name = name.trim();
+        let value = value.trim();
+
+        let values = match value.strip_prefix(ARRAY_START) {
+            Some(array) => array
+                .strip_suffix(ARRAY_END)
+                .ok_or(ERR_EVAL_PARAMS_VALUE)?
+                .split(SEPARATOR)
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::parse::<i16>)
+                .collect::<Result<Vec<i16>, _>>(),
+            None => value.parse::<i16>().map(|v| vec![v]),
+        }
+        .map_err(|_| ERR_EVAL_PARAMS_VALUE)?;
+
+        let (_, weight) = self
+            .weights_mut()
+            .into_iter()
+            .find(|(n, _)| *n == name)
+            .ok_or(ERR_EVAL_PARAMS_VALUE)?;
+
+        // A single value can only set a single weight, and an array must
+        // set every entry of the table.
+        if weight.len() != values.len() || (weight.len() == 1 && value.starts_with(ARRAY_START)) {
+            return Err(ERR_EVAL_PARAMS_VALUE);
+        }
+        weight.copy_from_slice(&values);
+
+        Ok(())
+    }
+
+    // The weights by the name of their compiled constant. Single values
+    // are slices of length 1.
+    fn weights_mut(&mut self) -> Vec<(&'static str, &mut [i16])> {
+        use std::slice::from_mut;
+
+        vec![
+            ("DOUBLED_PAWN_PENALTY", from_mut(&mut self.doubled_pawn_penalty)),
+            ("ISOLATED_PAWN_PENALTY", from_mut(&mut self.isolated_pawn_penalty)),
+            ("BACKWARD_PAWN_PENALTY", from_mut(&mut self.backward_pawn_penalty)),
+            ("DOUBLED_ISOLATED_PAWN_PENALTY", from_mut(&mut self.doubled_isolated_pawn_penalty)),
+            ("PASSED_PAWN_BONUS", &mut self.passed_pawn_bonus),
+            ("CONNECTED_PASSER_BONUS", &mut self.connected_passer_bonus),
+            ("CANDIDATE_PASSER_BONUS", &mut self.candidate_passer_bonus),
+            ("CONNECTED_PAWN_BONUS", from_mut(&mut self.connected_pawn_bonus)),
+            ("PAWN_CHAIN_BONUS", from_mut(&mut self.pawn_chain_bonus)),
+            ("CENTRAL_PAWN_BONUS", from_mut(&mut self.central_pawn_bonus)),
+            ("ROOK_FILE_PAWN_PENALTY", from_mut(&mut self.rook_file_pawn_penalty)),
+            ("KNIGHT_MOBILITY_MG", &mut self.knight_mobility_mg),
+            ("KNIGHT_MOBILITY_EG", &mut self.knight_mobility_eg),
+            ("BISHOP_MOBILITY_MG", &mut self.bishop_mobility_mg),
+            ("BISHOP_MOBILITY_EG", &mut self.bishop_mobility_eg),
+            ("ROOK_MOBILITY_MG", &mut self.rook_mobility_mg),
+            ("ROOK_MOBILITY_EG", &mut self.rook_mobility_eg),
+            ("QUEEN_MOBILITY_MG", &mut self.queen_mobility_mg),
+            ("QUEEN_MOBILITY_EG", &mut self.queen_mobility_eg),
+            ("MISSING_PAWN_PENALTY", &mut self.missing_pawn_penalty),
+            ("OPEN_FILE_PENALTY", from_mut(&mut self.open_file_penalty)),
+            ("HALF_OPEN_FILE_PENALTY", from_mut(&mut self.half_open_file_penalty)),
+            ("PAWN_STORM_PENALTY", from_mut(&mut self.pawn_storm_penalty)),
+            ("WEAK_SQUARES_PENALTY", from_mut(&mut self.weak_squares_penalty)),
+            ("ATTACK_UNIT_WEIGHTS", &mut self.attack_unit_weights),
+            ("SAFE_CHECK_BONUS", from_mut(&mut self.safe_check_bonus)),
+            ("UNSAFE_CHECK_BONUS", from_mut(&mut self.unsafe_check_bonus)),
+            ("KNIGHT_ATTACK_VALUE", from_mut(&mut self.knight_attack_value)),
+            ("BISHOP_ATTACK_VALUE", from_mut(&mut self.bishop_attack_value)),
+            ("ROOK_ATTACK_VALUE", from_mut(&mut self.rook_attack_value)),
+            ("QUEEN_ATTACK_VALUE", from_mut(&mut self.queen_attack_value)),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_overrides_named_weights() {
+        let text = "# Tuned weights\n\
+            DOUBLED_PAWN_PENALTY = -60\n\
+            SAFE_CHECK_BONUS = 45 # was 40\n\
+            MISSING_PAWN_PENALTY = [0, 10,\n  20, 30]\n";
+
+        let params = EvalParams::parse(text).unwrap();
+        assert_eq!(params.doubled_pawn_penalty, -60);
+        assert_eq!(params.safe_check_bonus, 45);
+        assert_eq!(params.missing_pawn_penalty, [0, 10, 20, 30]);
+        assert_eq!(params.isolated_pawn_penalty, pawn::ISOLATED_PAWN_PENALTY);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_weights() {
+        for text in [
+            "NO_SUCH_WEIGHT = 1",
+            "DOUBLED_PAWN_PENALTY = lots",
+            "DOUBLED_PAWN_PENALTY = [1]",
+            "MISSING_PAWN_PENALTY = [0, 10, 20]",
+            "MISSING_PAWN_PENALTY = [0, 10, 20, 30",
+            "DOUBLED_PAWN_PENALTY -60",
+        ] {
+            assert!(EvalParams::parse(text).is_err(), "{text}");
+        }
+    }
+}
@@ -22,19 +22,50 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 use crate::{
-    board::{defs::Pieces, Board},
+    board::{
+        defs::{Pieces, BB_FILES},
+        Board,
+    },
     defs::{Bitboard, Sides},
+    evaluation::params::EvalParams,
     misc::bits,
 };
 
-// Pawn structure evaluation scores - optimized single values
+// Pawn structure evaluation scores - optimized single values. These are
+// the defaults of the EvalParams weights with the same names.
 pub const DOUBLED_PAWN_PENALTY: i16 = -30;
 pub const ISOLATED_PAWN_PENALTY: i16 = -35;
 pub const BACKWARD_PAWN_PENALTY: i16 = -22;
 
+// Extra penalty for each pawn that is both doubled and isolated, on top
+// of the doubled and isolated penalties: no pawn can ever defend it.
+pub const DOUBLED_ISOLATED_PAWN_PENALTY: i16 = -15;
+
 // Passed pawn bonuses by rank - stronger than before but single values
 pub const PASSED_PAWN_BONUS: [i16; 8] = [0, 20, 30, 55, 95, 160, 240, 0];
 
+// Extra bonus by rank for each passed pawn with another passed pawn next
+// to it (on an adjacent file, at most one rank apart). Such pawns can
+// protect each other, and are very hard to stop.
+pub const CONNECTED_PASSER_BONUS: [i16; 8] = [0, 5, 10, 20, 35, 60, 90, 0];
+
+// Candidate passed pawns by rank: pawns that can become passed by
+// trading off the enemy pawns that stop them. A candidate on the 7th
+// rank can't exist, as nothing can stop it on the adjacent files.
+pub const CANDIDATE_PASSER_BONUS: [i16; 8] = [0, 5, 10, 15, 25, 40, 0, 0];
+
+// Passed pawn terms that depend on the king positions, scaled by how far
+// the pawn has advanced (PASSED_PAWN_BONUS rank index). These are not part
+// of the cached pawn structure score, because kings move all the time.
+pub const PASSED_PAWN_ENEMY_KING_DISTANCE: i16 = 2; // Per square to promotion
+pub const PASSED_PAWN_OWN_KING_PROXIMITY: i16 = 1; // Per square closer to pawn
+pub const BLOCKADED_PASSED_PAWN_PENALTY: i16 = -20;
+
+// Rooks behind passed pawns (Tarrasch rule). Our rook behind the pawn
+// supports its advance; an enemy rook behind it attacks it from behind.
+pub const ROOK_BEHIND_PASSER_BONUS: i16 = 25;
+pub const ENEMY_ROOK_BEHIND_PASSER_PENALTY: i16 = -15;
+
 pub const CONNECTED_PAWN_BONUS: i16 = 10;
 pub const PAWN_CHAIN_BONUS: i16 = 6;
 
@@ -42,19 +73,83 @@ pub const PAWN_CHAIN_BONUS: i16 = 6;
 pub const CENTRAL_PAWN_BONUS: i16 = 6; // For pawns on d/e files
 pub const ROOK_FILE_PAWN_PENALTY: i16 = -10; // For pawns on a/h files
 
+#[derive(Copy, Clone)]
+struct PawnHashEntry {
+    key: u64,
+    score: i16,
+}
+
+/// Pawn hash table shared by all search threads. It stores the full pawn
+/// structure evaluation by pawn hash, so nodes with the same pawns don't
+/// have to evaluate them again. The table is indexed by key % size, and
+/// a newer entry always replaces the older one.
+pub struct PawnHashTable {
+    entries: Vec<PawnHashEntry>,
+}
+
+impl PawnHashTable {
+    const EMPTY: PawnHashEntry = PawnHashEntry { key: 0, score: 0 };
+
+    /// Create a table of the requested size. A size of 0 disables it.
+    pub fn new(megabytes: usize) -> Self {
+        let count = megabytes * 1024 * 1024 / std::mem::size_of::<PawnHashEntry>();
+        Self {
+            entries: vec![Self::EMPTY; count],
+        }
+    }
+
+    /// Replace the table with a new, empty one of the requested size.
+    pub fn resize(&mut self, megabytes: usize) {
+        *self = Self::new(megabytes);
+    }
+
+    /// Remove all entries from the table.
+    pub fn clear(&mut self) {
+        self.entries.fill(Self::EMPTY);
+    }
+
+    /// Find the pawn structure score stored for this pawn hash. (An empty
+    /// entry matches key 0, which means no pawns and thus a score of 0.)
+    pub fn probe(&self, key: u64) -> Option<i16> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        let entry = self.entries[self.index(key)];
+        if entry.key == key {
+            Some(entry.score)
+        } else {
+            None
+        }
+    }
+
+    /// Store the pawn structure score for this pawn hash.
+    pub fn store(&mut self, key: u64, score: i16) {
+        if !self.entries.is_empty() {
+            let index = self.index(key);
+            self.entries[index] = PawnHashEntry { key, score };
+        }
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key % self.entries.len() as u64) as usize
+    }
+}
+
 /// Comprehensive pawn structure evaluation - optimized for performance
 pub fn evaluate_pawn_structure(board: &Board) -> i16 {
     let white_pawns = board.get_pieces(Pieces::PAWN, Sides::WHITE);
     let black_pawns = board.get_pieces(Pieces::PAWN, Sides::BLACK);
     
-    let white_score = evaluate_side_pawns(white_pawns, black_pawns, true);
-    let black_score = evaluate_side_pawns(black_pawns, white_pawns, false);
+    let params = &board.eval_params;
+    let white_score = evaluate_side_pawns(white_pawns, black_pawns, true, params);
+    let black_score = evaluate_side_pawns(black_pawns, white_pawns, false, params);
     
     white_score - black_score
 }
 
 /// Evaluate pawn structure for one side - performance optimized
-fn evaluate_side_pawns(own_pawns: Bitboard, enemy_pawns: Bitboard, is_white: bool) -> i16 {
+fn evaluate_side_pawns(own_pawns: Bitboard, enemy_pawns: Bitboard, is_white: bool, params: &EvalParams) -> i16 {
     let mut score = 0i16;
     
     // Doubled pawns analysis using the sophisticated bitboard approach
@@ -65,25 +160,33 @@ fn evaluate_side_pawns(own_pawns: Bitboard, enemy_pawns: Bitboard, is_white: boo
     };
     
     let doubled_count = (rear_doubles.count_ones() + front_doubles.count_ones()) as i16;
-    score += doubled_count * DOUBLED_PAWN_PENALTY;
+    score += doubled_count * params.doubled_pawn_penalty;
     
     // Isolated pawns
     let isolated = bits::isolated_pawns(own_pawns);
-    score += isolated.count_ones() as i16 * ISOLATED_PAWN_PENALTY;
+    score += isolated.count_ones() as i16 * params.isolated_pawn_penalty;
+
+    // Doubled and isolated pawns
+    let doubled_isolated = (rear_doubles | front_doubles) & isolated;
+    score += doubled_isolated.count_ones() as i16 * params.doubled_isolated_pawn_penalty;
     
     // Backward pawns  
     let backward = bits::backward_pawns(own_pawns, enemy_pawns, is_white);
-    score += backward.count_ones() as i16 * BACKWARD_PAWN_PENALTY;
+    score += backward.count_ones() as i16 * params.backward_pawn_penalty;
     
     // Passed pawns
     let passed = get_passed_pawns(own_pawns, enemy_pawns, is_white);
-    score += evaluate_passed_pawns(passed, is_white);
+    score += evaluate_passed_pawns(passed, is_white, params);
+
+    // Candidate passed pawns
+    let candidates = candidate_passers(own_pawns, enemy_pawns, is_white);
+    score += evaluate_candidate_passers(candidates, is_white, params);
     
     // Connected and chained pawns
-    score += evaluate_pawn_connections(own_pawns, is_white);
+    score += evaluate_pawn_connections(own_pawns, is_white, params);
     
     // File-specific evaluations
-    score += evaluate_pawn_files(own_pawns);
+    score += evaluate_pawn_files(own_pawns, params);
     
     score
 }
@@ -98,9 +201,14 @@ fn get_passed_pawns(own_pawns: Bitboard, enemy_pawns: Bitboard, is_white: bool)
 }
 
 /// Evaluate passed pawns with rank-based bonuses
-fn evaluate_passed_pawns(passed_pawns: Bitboard, is_white: bool) -> i16 {
+fn evaluate_passed_pawns(passed_pawns: Bitboard, is_white: bool, params: &EvalParams) -> i16 {
     let mut score = 0i16;
     let mut pawns_copy = passed_pawns;
+
+    // Passed pawns with another passer on an adjacent file, on the same
+    // rank or one rank above or below it.
+    let beside = ((passed_pawns & !BB_FILES[0]) >> 1) | ((passed_pawns & !BB_FILES[7]) << 1);
+    let connected = passed_pawns & (beside | (beside << 8) | (beside >> 8));
     
     while pawns_copy != 0 {
         let square = bits::next(&mut pawns_copy);
@@ -108,14 +216,168 @@ fn evaluate_passed_pawns(passed_pawns: Bitboard, is_white: bool) -> i16 {
         
         // Adjust rank for white/black perspective
         let pawn_rank = if is_white { rank } else { 7 - rank };
-        score += PASSED_PAWN_BONUS[pawn_rank];
+        score += params.passed_pawn_bonus[pawn_rank];
+
+        if connected & (1u64 << square) != 0 {
+            score += params.connected_passer_bonus[pawn_rank];
+        }
     }
     
     score
 }
 
+/// Candidate passed pawns: pawns on a file without enemy pawns in front,
+/// that are not passed yet, but can become passed by exchanging. This is
+/// the case if the pawn and its helpers (own pawns on the adjacent files,
+/// level with it or behind it) outnumber its stoppers (enemy pawns on the
+/// adjacent files, in front of it).
+pub fn candidate_passers(own_pawns: Bitboard, enemy_pawns: Bitboard, is_white: bool) -> Bitboard {
+    let front_spans = |pawns| if is_white { bits::white_front_spans(pawns) } else { bits::black_front_spans(pawns) };
+    let rear_spans = |pawns| if is_white { bits::white_rear_spans(pawns) } else { bits::black_rear_spans(pawns) };
+    let passed = get_passed_pawns(own_pawns, enemy_pawns, is_white);
+    let mut candidates = 0u64;
+    let mut pawns_copy = own_pawns & !passed;
+
+    while pawns_copy != 0 {
+        let square = bits::next(&mut pawns_copy);
+        let pawn = 1u64 << square;
+
+        if front_spans(pawn) & enemy_pawns != 0 {
+            continue;
+        }
+
+        let beside = ((pawn & !BB_FILES[0]) >> 1) | ((pawn & !BB_FILES[7]) << 1);
+        let stoppers = enemy_pawns & front_spans(beside);
+        let helpers = own_pawns & (beside | rear_spans(beside));
+
+        if helpers.count_ones() + 1 > stoppers.count_ones() {
+            candidates |= pawn;
+        }
+    }
+
+    candidates
+}
+
+/// Evaluate candidate passed pawns with rank-based bonuses
+fn evaluate_candidate_passers(candidates: Bitboard, is_white: bool, params: &EvalParams) -> i16 {
+    let mut score = 0i16;
+    let mut pawns_copy = candidates;
+
+    while pawns_copy != 0 {
+        let square = bits::next(&mut pawns_copy);
+        let rank = square / 8;
+        let pawn_rank = if is_white { rank } else { 7 - rank };
+        score += params.candidate_passer_bonus[pawn_rank];
+    }
+
+    score
+}
+
+/// Evaluate passed pawns with respect to both kings and blockading pieces.
+/// The score is from white's point of view.
+pub fn evaluate_passed_pawns_with_kings(board: &Board) -> i16 {
+    let white_pawns = board.get_pieces(Pieces::PAWN, Sides::WHITE);
+    let black_pawns = board.get_pieces(Pieces::PAWN, Sides::BLACK);
+
+    let white_passed = get_passed_pawns(white_pawns, black_pawns, true);
+    let black_passed = get_passed_pawns(black_pawns, white_pawns, false);
+
+    evaluate_side_passers_with_kings(board, white_passed, true)
+        - evaluate_side_passers_with_kings(board, black_passed, false)
+}
+
+/// King distance and blockade terms for the passed pawns of one side
+fn evaluate_side_passers_with_kings(board: &Board, passed_pawns: Bitboard, is_white: bool) -> i16 {
+    let (us, them) = if is_white {
+        (Sides::WHITE, Sides::BLACK)
+    } else {
+        (Sides::BLACK, Sides::WHITE)
+    };
+    let own_king = board.king_square(us);
+    let enemy_king = board.king_square(them);
+    let occupancy = board.occupancy();
+    let mut score = 0i16;
+    let mut pawns_copy = passed_pawns;
+
+    while pawns_copy != 0 {
+        let square = bits::next(&mut pawns_copy);
+        let file = square % 8;
+        let rank = square / 8;
+        let pawn_rank = if is_white { rank } else { 7 - rank } as i16;
+        let (promotion_square, advance_square) = if is_white {
+            (56 + file, square + 8)
+        } else {
+            (file, square - 8)
+        };
+
+        // The further the enemy king is from the promotion square, and the
+        // closer our own king is to the pawn, the better.
+        let enemy_distance = Board::square_distance(enemy_king, promotion_square) as i16;
+        let own_proximity = 7 - Board::square_distance(own_king, square) as i16;
+        score += pawn_rank
+            * (enemy_distance * PASSED_PAWN_ENEMY_KING_DISTANCE
+                + own_proximity * PASSED_PAWN_OWN_KING_PROXIMITY);
+
+        // A pawn that can't advance is worth a lot less.
+        if occupancy & (1u64 << advance_square) != 0 {
+            score += BLOCKADED_PASSED_PAWN_PENALTY;
+        }
+    }
+
+    score
+}
+
+/// Evaluate rooks on the same file as, and behind, passed pawns.
+/// The score is from white's point of view.
+pub fn evaluate_rook_behind_passers(board: &Board) -> i16 {
+    let white_pawns = board.get_pieces(Pieces::PAWN, Sides::WHITE);
+    let black_pawns = board.get_pieces(Pieces::PAWN, Sides::BLACK);
+
+    let white_passed = bits::white_passed_pawns(white_pawns, black_pawns);
+    let black_passed = bits::black_passed_pawns(black_pawns, white_pawns);
+
+    evaluate_side_rook_behind_passers(board, white_passed, true)
+        - evaluate_side_rook_behind_passers(board, black_passed, false)
+}
+
+/// Rook behind passed pawn terms for the passed pawns of one side
+fn evaluate_side_rook_behind_passers(board: &Board, passed_pawns: Bitboard, is_white: bool) -> i16 {
+    let (us, them) = if is_white {
+        (Sides::WHITE, Sides::BLACK)
+    } else {
+        (Sides::BLACK, Sides::WHITE)
+    };
+    let own_rooks = board.get_pieces(Pieces::ROOK, us);
+    let enemy_rooks = board.get_pieces(Pieces::ROOK, them);
+    let mut score = 0i16;
+    let mut pawns_copy = passed_pawns;
+
+    while pawns_copy != 0 {
+        let square = bits::next(&mut pawns_copy);
+        let file = square % 8;
+
+        // Squares on the pawn's file, behind it from our point of view.
+        let below = (1u64 << square) - 1;
+        let behind = if is_white {
+            BB_FILES[file] & below
+        } else {
+            BB_FILES[file] & !below & !(1u64 << square)
+        };
+
+        if own_rooks & behind != 0 {
+            score += ROOK_BEHIND_PASSER_BONUS;
+        }
+
+        if enemy_rooks & behind != 0 {
+            score += ENEMY_ROOK_BEHIND_PASSER_PENALTY;
+        }
+    }
+
+    score
+}
+
 /// Evaluate pawn connections and chains
-fn evaluate_pawn_connections(pawns: Bitboard, is_white: bool) -> i16 {
+fn evaluate_pawn_connections(pawns: Bitboard, is_white: bool, params: &EvalParams) -> i16 {
     let mut score = 0i16;
     let pawn_attacks = if is_white {
         bits::white_pawn_attacks(pawns)
@@ -125,17 +387,17 @@ fn evaluate_pawn_connections(pawns: Bitboard, is_white: bool) -> i16 {
     
     // Connected pawns: pawns that defend each other
     let connected = pawns & pawn_attacks;
-    score += connected.count_ones() as i16 * CONNECTED_PAWN_BONUS;
+    score += connected.count_ones() as i16 * params.connected_pawn_bonus;
     
     // Pawn chains: evaluate longer chains more favorably
-    let chain_count = count_pawn_chains(pawns, is_white);
-    score += chain_count * PAWN_CHAIN_BONUS;
+    let chain_count = count_pawn_chains(pawns);
+    score += chain_count * params.pawn_chain_bonus;
     
     score
 }
 
 /// Count pawn chains (connected groups of pawns)
-fn count_pawn_chains(pawns: Bitboard, is_white: bool) -> i16 {
+fn count_pawn_chains(pawns: Bitboard) -> i16 {
     let mut chains = 0i16;
     let mut processed = 0u64;
     let mut pawns_copy = pawns;
@@ -149,7 +411,7 @@ fn count_pawn_chains(pawns: Bitboard, is_white: bool) -> i16 {
         }
         
         // Find connected pawns starting from this pawn
-        let chain_pawns = find_connected_pawns(pawn_bb, pawns, is_white);
+        let chain_pawns = find_connected_pawns(pawn_bb, pawns);
         
         if chain_pawns.count_ones() >= 2 {
             chains += (chain_pawns.count_ones() - 1) as i16; // Chain bonus scales with length
@@ -161,19 +423,17 @@ fn count_pawn_chains(pawns: Bitboard, is_white: bool) -> i16 {
     chains
 }
 
-/// Find all pawns connected to the starting pawn
-fn find_connected_pawns(start: Bitboard, all_pawns: Bitboard, is_white: bool) -> Bitboard {
+/// Find all pawns connected to the starting pawn. Connections run both to
+/// the pawns a pawn defends and to the pawns defending it, so the whole
+/// chain is found from any of its pawns, for either color.
+fn find_connected_pawns(start: Bitboard, all_pawns: Bitboard) -> Bitboard {
     let mut connected = start;
     let mut to_check = start;
     
     loop {
-        let pawn_attacks = if is_white {
-            bits::white_pawn_attacks(to_check)
-        } else {
-            bits::black_pawn_attacks(to_check)
-        };
+        let neighbours = bits::white_pawn_attacks(to_check) | bits::black_pawn_attacks(to_check);
         
-        let new_connections = (pawn_attacks & all_pawns) & !connected;
+        let new_connections = (neighbours & all_pawns) & !connected;
         
         if new_connections == 0 {
             break; // No new connections found
@@ -187,18 +447,18 @@ fn find_connected_pawns(start: Bitboard, all_pawns: Bitboard, is_white: bool) ->
 }
 
 /// Evaluate pawns based on their files
-fn evaluate_pawn_files(pawns: Bitboard) -> i16 {
+fn evaluate_pawn_files(pawns: Bitboard, params: &EvalParams) -> i16 {
     let mut score = 0i16;
     
     // Central files (d, e) are valuable
     let d_file_pawns = pawns & crate::board::defs::BB_FILES[3]; // d-file
     let e_file_pawns = pawns & crate::board::defs::BB_FILES[4]; // e-file
-    score += (d_file_pawns.count_ones() + e_file_pawns.count_ones()) as i16 * CENTRAL_PAWN_BONUS;
+    score += (d_file_pawns.count_ones() + e_file_pawns.count_ones()) as i16 * params.central_pawn_bonus;
     
     // Rook files (a, h) are less valuable
     let a_file_pawns = pawns & crate::board::defs::BB_FILES[0]; // a-file
     let h_file_pawns = pawns & crate::board::defs::BB_FILES[7]; // h-file
-    score += (a_file_pawns.count_ones() + h_file_pawns.count_ones()) as i16 * ROOK_FILE_PAWN_PENALTY;
+    score += (a_file_pawns.count_ones() + h_file_pawns.count_ones()) as i16 * params.rook_file_pawn_penalty;
     
     score
 }
@@ -398,4 +658,189 @@ mod tests {
             }
         }
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_passed_pawns_with_doubled_pawns() {
+        // Both of a pair of doubled pawns with nothing in front are passed.
+        let doubled = BB_SQUARES[27] | BB_SQUARES[35]; // d4, d5
+        let far_away = BB_SQUARES[48]; // a7
+        assert_eq!(get_passed_pawns(doubled, far_away, true), doubled);
+        assert_eq!(get_passed_pawns_old(doubled, far_away, true), doubled);
+
+        // Doubled enemy pawns in front, on the same or an adjacent file.
+        let white_pawns = BB_SQUARES[11]; // d2
+        for black_pawns in [
+            BB_SQUARES[43] | BB_SQUARES[51], // d6, d7
+            BB_SQUARES[26] | BB_SQUARES[18], // c4, c3
+            BB_SQUARES[28] | BB_SQUARES[20], // e4, e3
+        ] {
+            assert_eq!(get_passed_pawns_old(white_pawns, black_pawns, true), 0);
+            assert_eq!(get_passed_pawns(white_pawns, black_pawns, true), 0);
+        }
+
+        // The same for black, with the board mirrored.
+        let black_pawns = BB_SQUARES[51]; // d7
+        for white_pawns in [
+            BB_SQUARES[19] | BB_SQUARES[11], // d3, d2
+            BB_SQUARES[34] | BB_SQUARES[42], // c5, c6
+            BB_SQUARES[36] | BB_SQUARES[44], // e5, e6
+        ] {
+            assert_eq!(get_passed_pawns_old(black_pawns, white_pawns, false), 0);
+            assert_eq!(get_passed_pawns(black_pawns, white_pawns, false), 0);
+        }
+    }
+
+    #[test]
+    fn test_doubled_isolated_pawns_penalized_beyond_sum() {
+        // White c2 and c3, with the a- and e-pawns further away.
+        let white_pawns = BB_SQUARES[8] | BB_SQUARES[10] | BB_SQUARES[18] | BB_SQUARES[12];
+        let black_pawns = BB_SQUARES[48] | BB_SQUARES[50] | BB_SQUARES[52];
+
+        let params = EvalParams::default();
+        let naive = EvalParams {
+            doubled_isolated_pawn_penalty: 0,
+            ..EvalParams::default()
+        };
+        let combined = evaluate_side_pawns(white_pawns, black_pawns, true, &params);
+        let sum = evaluate_side_pawns(white_pawns, black_pawns, true, &naive);
+
+        assert_eq!(combined - sum, 2 * DOUBLED_ISOLATED_PAWN_PENALTY);
+        assert!(combined < sum);
+
+        // A doubled pawn with a neighbour gets no extra penalty.
+        let supported = white_pawns | BB_SQUARES[9];
+        assert_eq!(
+            evaluate_side_pawns(supported, black_pawns, true, &params),
+            evaluate_side_pawns(supported, black_pawns, true, &naive)
+        );
+    }
+
+    #[test]
+    fn test_connected_passers_on_sixth_rank() {
+        let params = EvalParams::default();
+
+        // d6 and e6 against b6 and g6.
+        let connected = BB_SQUARES[43] | BB_SQUARES[44];
+        let isolated = BB_SQUARES[41] | BB_SQUARES[46];
+        let connected_score = evaluate_passed_pawns(connected, true, &params);
+        let isolated_score = evaluate_passed_pawns(isolated, true, &params);
+        assert_eq!(connected_score - isolated_score, 2 * CONNECTED_PASSER_BONUS[5]);
+
+        // One rank apart still counts; two ranks apart doesn't.
+        let d6_e5 = BB_SQUARES[43] | BB_SQUARES[36];
+        let d6_e4 = BB_SQUARES[43] | BB_SQUARES[28];
+        assert_eq!(
+            evaluate_passed_pawns(d6_e5, true, &params),
+            PASSED_PAWN_BONUS[5] + PASSED_PAWN_BONUS[4] + CONNECTED_PASSER_BONUS[5] + CONNECTED_PASSER_BONUS[4]
+        );
+        assert_eq!(evaluate_passed_pawns(d6_e4, true, &params), PASSED_PAWN_BONUS[5] + PASSED_PAWN_BONUS[3]);
+
+        // The a- and h-files don't wrap around.
+        let a6_h5 = BB_SQUARES[40] | BB_SQUARES[39];
+        assert_eq!(evaluate_passed_pawns(a6_h5, true, &params), PASSED_PAWN_BONUS[5] + PASSED_PAWN_BONUS[4]);
+    }
+
+    #[test]
+    fn test_candidate_passer_in_queenside_majority() {
+        // White a2, b2, c2 against black a7, b7: the c-pawn is the
+        // candidate, supported by b2 against the single stopper on b7.
+        let white_pawns = BB_SQUARES[8] | BB_SQUARES[9] | BB_SQUARES[10];
+        let black_pawns = BB_SQUARES[48] | BB_SQUARES[49];
+
+        assert_eq!(candidate_passers(white_pawns, black_pawns, true), BB_SQUARES[10]);
+        assert_eq!(candidate_passers(black_pawns, white_pawns, false), 0);
+
+        // Mirrored for black: a7, b7, c7 against a2, b2.
+        let black_majority = BB_SQUARES[48] | BB_SQUARES[49] | BB_SQUARES[50];
+        let white_minority = BB_SQUARES[8] | BB_SQUARES[9];
+        assert_eq!(candidate_passers(black_majority, white_minority, false), BB_SQUARES[50]);
+
+        // Without the b2 helper, c2 can't get past b7 and d7.
+        let lone = BB_SQUARES[10];
+        let stoppers = BB_SQUARES[49] | BB_SQUARES[51];
+        assert_eq!(candidate_passers(lone, stoppers, true), 0);
+
+        // Passed pawns are not candidates.
+        assert_eq!(candidate_passers(BB_SQUARES[10], 0, true), 0);
+    }
+
+    fn passed_pawns_with_kings(fen: &str) -> i16 {
+        let mut board = Board::new();
+        board.fen_read(Some(fen)).unwrap();
+        evaluate_passed_pawns_with_kings(&board)
+    }
+
+    #[test]
+    fn test_unstoppable_passer_beats_blockaded_passer() {
+        // The black king is far outside the square of the pawn.
+        let unstoppable = passed_pawns_with_kings("8/8/8/3P4/8/8/k7/3K4 w - - 0 1");
+        // The black king sits right in front of the pawn.
+        let blockaded = passed_pawns_with_kings("8/8/3k4/3P4/8/8/8/3K4 w - - 0 1");
+
+        assert!(unstoppable > 0);
+        assert!(unstoppable > blockaded);
+    }
+
+    #[test]
+    fn test_passed_pawns_with_kings_is_symmetric() {
+        let white = passed_pawns_with_kings("8/8/3k4/3P4/8/8/8/3K4 w - - 0 1");
+        let black = passed_pawns_with_kings("3k4/8/8/8/3p4/3K4/8/8 w - - 0 1");
+
+        assert_eq!(white, -black);
+    }
+
+    fn rook_behind_passers(fen: &str) -> i16 {
+        let mut board = Board::new();
+        board.fen_read(Some(fen)).unwrap();
+        evaluate_rook_behind_passers(&board)
+    }
+
+    #[test]
+    fn test_rook_behind_passed_a_pawn() {
+        assert_eq!(
+            rook_behind_passers("4k3/8/8/8/P7/8/8/R3K3 w - - 0 1"),
+            ROOK_BEHIND_PASSER_BONUS
+        );
+
+        // Same rook in front of the pawn doesn't count.
+        assert_eq!(rook_behind_passers("R3k3/8/8/8/P7/8/8/4K3 w - - 0 1"), 0);
+    }
+
+    #[test]
+    fn test_enemy_rook_behind_passed_pawn() {
+        assert_eq!(
+            rook_behind_passers("4k3/8/8/8/P7/8/8/r3K3 w - - 0 1"),
+            ENEMY_ROOK_BEHIND_PASSER_PENALTY
+        );
+
+        // Mirrored for black: the black rook on a8 is behind the a5 pawn.
+        assert_eq!(
+            rook_behind_passers("r3k3/8/8/p7/8/8/8/4K3 w - - 0 1"),
+            -ROOK_BEHIND_PASSER_BONUS
+        );
+    }
+
+    #[test]
+    fn test_pawn_hash_shared_by_identical_pawn_structures() {
+        let mut table = PawnHashTable::new(1);
+
+        let mut board = Board::new();
+        board.fen_read(Some("r3k2r/ppp2ppp/8/3p4/3P4/8/PPP2PPP/R3K2R w - - 0 1")).unwrap();
+        let key = board.compute_pawn_hash();
+        let score = evaluate_pawn_structure(&board);
+        table.store(key, score);
+
+        // Same pawns, different pieces.
+        let mut other = Board::new();
+        other.fen_read(Some("4k3/ppp2ppp/2n5/3p4/3P4/5N2/PPP2PPP/4K3 w - - 0 1")).unwrap();
+        let other_key = other.compute_pawn_hash();
+
+        assert_eq!(other_key, key);
+        assert_eq!(table.probe(other_key), Some(score));
+
+        // Different pawns miss.
+        let mut different = Board::new();
+        different.fen_read(Some("4k3/ppp2ppp/8/8/3P4/8/PPP2PPP/4K3 w - - 0 1")).unwrap();
+        assert_eq!(table.probe(different.compute_pawn_hash()), None);
+    }
+}
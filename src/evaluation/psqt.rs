@@ -108,6 +108,85 @@ const PAWN_MG: Psqt = [
 pub const PSQT_MG: [Psqt; NrOf::PIECE_TYPES] =
     [KING_MG, QUEEN_MG, ROOK_MG, BISHOP_MG, KNIGHT_MG, PAWN_MG];
 
+// Endgame tables. With few pieces left, the king becomes an active piece
+// and belongs in the center, and advanced pawns are worth a lot more
+// because they are close to promotion.
+
+#[rustfmt::skip]
+const KING_EG: Psqt = [
+    -50, -30, -30, -30, -30, -30, -30, -50,
+    -30, -10,   0,   0,   0,   0, -10, -30,
+    -30,   0,  20,  25,  25,  20,   0, -30,
+    -30,   0,  25,  35,  35,  25,   0, -30,
+    -30,   0,  25,  35,  35,  25,   0, -30,
+    -30,   0,  20,  25,  25,  20,   0, -30,
+    -30, -10,   0,   0,   0,   0, -10, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+#[rustfmt::skip]
+const QUEEN_EG: Psqt = [
+    880, 890, 895, 900, 900, 895, 890, 880,
+    890, 900, 905, 905, 905, 905, 900, 890,
+    895, 905, 910, 915, 915, 910, 905, 895,
+    900, 905, 915, 920, 920, 915, 905, 900,
+    900, 905, 915, 920, 920, 915, 905, 900,
+    895, 905, 910, 915, 915, 910, 905, 895,
+    890, 900, 905, 905, 905, 905, 900, 890,
+    880, 890, 895, 900, 900, 895, 890, 880
+];
+
+#[rustfmt::skip]
+const ROOK_EG: Psqt = [
+   510, 510, 510, 510, 510, 510, 510, 510,
+   520, 520, 520, 520, 520, 520, 520, 520,
+   505, 505, 505, 505, 505, 505, 505, 505,
+   500, 500, 500, 500, 500, 500, 500, 500,
+   500, 500, 500, 500, 500, 500, 500, 500,
+   500, 500, 500, 500, 500, 500, 500, 500,
+   500, 500, 500, 500, 500, 500, 500, 500,
+   500, 500, 500, 500, 500, 500, 500, 500
+];
+
+#[rustfmt::skip]
+const BISHOP_EG: Psqt = [
+    305, 310, 310, 310, 310, 310, 310, 305,
+    310, 320, 320, 320, 320, 320, 320, 310,
+    310, 320, 325, 330, 330, 325, 320, 310,
+    310, 320, 330, 335, 335, 330, 320, 310,
+    310, 320, 330, 335, 335, 330, 320, 310,
+    310, 320, 325, 330, 330, 325, 320, 310,
+    310, 320, 320, 320, 320, 320, 320, 310,
+    305, 310, 310, 310, 310, 310, 310, 305
+];
+
+#[rustfmt::skip]
+const KNIGHT_EG: Psqt = [
+    280, 290, 295, 295, 295, 295, 290, 280,
+    290, 300, 305, 305, 305, 305, 300, 290,
+    295, 305, 315, 320, 320, 315, 305, 295,
+    295, 305, 320, 325, 325, 320, 305, 295,
+    295, 305, 320, 325, 325, 320, 305, 295,
+    295, 305, 315, 320, 320, 315, 305, 295,
+    290, 300, 305, 305, 305, 305, 300, 290,
+    280, 290, 295, 295, 295, 295, 290, 280
+];
+
+#[rustfmt::skip]
+const PAWN_EG: Psqt = [
+    100, 100, 100, 100, 100, 100, 100, 100,
+    200, 200, 200, 200, 200, 200, 200, 200,
+    160, 160, 160, 160, 160, 160, 160, 160,
+    135, 135, 135, 135, 135, 135, 135, 135,
+    120, 120, 120, 120, 120, 120, 120, 120,
+    110, 110, 110, 110, 110, 110, 110, 110,
+    105, 105, 105, 105, 105, 105, 105, 105,
+    100, 100, 100, 100, 100, 100, 100, 100
+];
+
+pub const PSQT_EG: [Psqt; NrOf::PIECE_TYPES] =
+    [KING_EG, QUEEN_EG, ROOK_EG, BISHOP_EG, KNIGHT_EG, PAWN_EG];
+
 // When one side has a bare king, this PSQT is used to drive that king to
 // the edge of the board and mate it there.
 #[rustfmt::skip]
@@ -186,8 +265,17 @@ pub const FLIP: [usize; 64] = [
      0,  1,  2,  3,  4,  5,  6,  7,
 ];
 
-// Apply PSQT's to position
+// Apply middlegame PSQT's to position
 pub fn apply(board: &Board) -> (i16, i16) {
+    apply_tables(board, &PSQT_MG)
+}
+
+// Apply endgame PSQT's to position
+pub fn apply_eg(board: &Board) -> (i16, i16) {
+    apply_tables(board, &PSQT_EG)
+}
+
+fn apply_tables(board: &Board, tables: &[Psqt; NrOf::PIECE_TYPES]) -> (i16, i16) {
     let mut w_psqt: i16 = 0;
     let mut b_psqt: i16 = 0;
     let bb_white = board.bb_pieces[Sides::WHITE]; // Array of white piece bitboards
@@ -201,15 +289,48 @@ pub fn apply(board: &Board) -> (i16, i16) {
         // Iterate over pieces of the current piece_type for white.
         while white_pieces > 0 {
             let square = bits::next(&mut white_pieces);
-            w_psqt += PSQT_MG[piece_type][FLIP[square]] as i16;
+            w_psqt += tables[piece_type][FLIP[square]];
         }
 
         // Iterate over pieces of the current piece_type for black.
         while black_pieces > 0 {
             let square = bits::next(&mut black_pieces);
-            b_psqt += PSQT_MG[piece_type][square] as i16;
+            b_psqt += tables[piece_type][square];
         }
     }
 
     (w_psqt, b_psqt)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn psqt_values(fen: &str) -> ((i16, i16), (i16, i16)) {
+        let mut board = Board::new();
+        board.fen_read(Some(fen)).unwrap();
+        (apply(&board), apply_eg(&board))
+    }
+
+    #[test]
+    fn test_endgame_table_rewards_central_king() {
+        // Same material, white king on e4 or on its home square.
+        let (central_mg, central_eg) = psqt_values("4k3/8/8/8/4K3/8/PPP5/8 w - - 0 1");
+        let (home_mg, home_eg) = psqt_values("4k3/8/8/8/8/8/PPP5/4K3 w - - 0 1");
+
+        let eg_gain = central_eg.0 - home_eg.0;
+        let mg_gain = central_mg.0 - home_mg.0;
+
+        assert!(eg_gain > 0, "endgame table should prefer a central king");
+        assert!(eg_gain > mg_gain, "centralization should be worth more in the endgame");
+    }
+
+    #[test]
+    fn test_tables_agree_on_black_and_white() {
+        // Mirrored position: both tables must give both sides the same score.
+        let (mg, eg) = psqt_values("4k3/pp3ppp/2n5/8/8/2N5/PP3PPP/4K3 w - - 0 1");
+
+        assert_eq!(mg.0, mg.1);
+        assert_eq!(eg.0, eg.1);
+    }
+}
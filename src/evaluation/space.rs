@@ -0,0 +1,102 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// Space: the room a side has behind its pawns to maneuver its pieces.
+// This counts the safe squares in the center on our side of the board and
+// the first rank of the opponent's half, with the squares behind our own
+// pawns counting double. Space matters more when there are many pieces
+// that need room, so the count is scaled by the number of minor pieces.
+
+use crate::{
+    board::{
+        defs::{Files, Pieces, Ranks, BB_FILES, BB_RANKS},
+        Board,
+    },
+    defs::{Bitboard, Side, Sides},
+    misc::bits,
+};
+
+// Number of minor pieces on the board at which one space square is worth
+// one centipawn.
+const SPACE_MINORS_DIVISOR: i16 = 4;
+
+pub fn evaluate_space(board: &Board, side: Side) -> i16 {
+    let own_pawns = board.get_pieces(Pieces::PAWN, side);
+    let enemy_pawns = board.get_pieces(Pieces::PAWN, side ^ 1);
+    let center_files = BB_FILES[Files::C] | BB_FILES[Files::D] | BB_FILES[Files::E] | BB_FILES[Files::F];
+
+    let (zone, enemy_pawn_attacks, behind_pawns) = if side == Sides::WHITE {
+        (
+            center_files & (BB_RANKS[Ranks::R2] | BB_RANKS[Ranks::R3] | BB_RANKS[Ranks::R4] | BB_RANKS[Ranks::R5]),
+            bits::black_pawn_attacks(enemy_pawns),
+            bits::white_rear_spans(own_pawns),
+        )
+    } else {
+        (
+            center_files & (BB_RANKS[Ranks::R7] | BB_RANKS[Ranks::R6] | BB_RANKS[Ranks::R5] | BB_RANKS[Ranks::R4]),
+            bits::white_pawn_attacks(enemy_pawns),
+            bits::black_rear_spans(own_pawns),
+        )
+    };
+
+    let safe: Bitboard = zone & !own_pawns & !enemy_pawn_attacks;
+    let squares = (safe.count_ones() + (safe & behind_pawns).count_ones()) as i16;
+    let minors = (board.get_pieces(Pieces::KNIGHT, Sides::WHITE)
+        | board.get_pieces(Pieces::KNIGHT, Sides::BLACK)
+        | board.get_pieces(Pieces::BISHOP, Sides::WHITE)
+        | board.get_pieces(Pieces::BISHOP, Sides::BLACK))
+    .count_ones() as i16;
+
+    squares * minors / SPACE_MINORS_DIVISOR
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn space(fen: &str, side: Side) -> i16 {
+        let mut board = Board::new();
+        board.fen_read(Some(fen)).unwrap();
+        evaluate_space(&board, side)
+    }
+
+    #[test]
+    fn test_pawn_advance_gains_space() {
+        let start = space("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", Sides::WHITE);
+        let advanced = space("rnbqkbnr/pppppppp/8/8/2PPP3/8/PP3PPP/RNBQKBNR w KQkq - 0 1", Sides::WHITE);
+        assert!(advanced > start);
+    }
+
+    #[test]
+    fn test_cramped_side_has_less_space() {
+        // Black's pawns are stuck on the 6th and 7th ranks behind white's
+        // chain on d5 and e4.
+        let fen = "r1bqkb1r/pp1n1ppp/2pp1n2/3P4/2P1P3/2N2N2/PP3PPP/R1BQKB1R w KQkq - 0 1";
+        assert!(space(fen, Sides::WHITE) > space(fen, Sides::BLACK));
+    }
+
+    #[test]
+    fn test_no_space_without_minor_pieces() {
+        assert_eq!(space("r3k2r/pppppppp/8/8/2PPP3/8/PP3PPP/R3K2R w - - 0 1", Sides::WHITE), 0);
+    }
+}
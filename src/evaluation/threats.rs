@@ -0,0 +1,196 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// Threats: pieces attacked by less valuable enemy pieces, pieces that
+// are attacked but not defended at all, and pieces that are defended but
+// not well enough to hold them.
+
+use super::defs::exchange_value;
+use crate::{
+    board::{defs::Pieces, Board},
+    defs::{Bitboard, NrOf, Piece, Side, Sides},
+    misc::bits,
+    movegen::MoveGenerator,
+};
+
+// An attacker must be worth at least this much less than the piece it
+// attacks to threaten it. Bishops and knights are considered equal, so
+// they don't "threaten" each other.
+const THREAT_VALUE_MARGIN: i16 = 50;
+
+// Bonus for attacking an enemy piece with a less valuable piece, indexed
+// by the attacked piece. The king is never scored; that's a check.
+const THREAT_BONUS: [i16; NrOf::PIECE_TYPES] = [0, 50, 40, 25, 25, 0];
+
+// Penalty for a piece that is attacked and not defended, indexed by the
+// piece that is hanging.
+const HANGING_PIECE_PENALTY: [i16; NrOf::PIECE_TYPES] = [0, -60, -40, -25, -25, -10];
+
+// Penalty for a piece that is defended, but not well enough: it's
+// attacked by a less valuable piece, and by more pieces than defend it.
+// Indexed by the piece.
+const UNDERDEFENDED_PIECE_PENALTY: [i16; NrOf::PIECE_TYPES] = [0, -40, -30, -20, -20, 0];
+
+pub fn evaluate_threats(board: &Board, move_gen: &MoveGenerator) -> i16 {
+    let white_attacks = attacks_by_piece(board, move_gen, Sides::WHITE);
+    let black_attacks = attacks_by_piece(board, move_gen, Sides::BLACK);
+
+    let white = side_threats(board, move_gen, Sides::WHITE, &white_attacks, &black_attacks);
+    let black = side_threats(board, move_gen, Sides::BLACK, &black_attacks, &white_attacks);
+
+    white - black
+}
+
+// Threats made by the given side, minus its own hanging pieces.
+fn side_threats(
+    board: &Board,
+    move_gen: &MoveGenerator,
+    side: Side,
+    own_attacks: &[Bitboard; NrOf::PIECE_TYPES],
+    enemy_attacks: &[Bitboard; NrOf::PIECE_TYPES],
+) -> i16 {
+    let own_all = own_attacks.iter().fold(0, |all, a| all | a);
+    let enemy_all = enemy_attacks.iter().fold(0, |all, a| all | a);
+    let mut score = 0;
+
+    for piece in [Pieces::QUEEN, Pieces::ROOK, Pieces::BISHOP, Pieces::KNIGHT, Pieces::PAWN] {
+        // Enemy pieces attacked by one of our less valuable pieces.
+        let lesser_attacks = (0..NrOf::PIECE_TYPES)
+            .filter(|&attacker| is_less_valuable(attacker, piece))
+            .fold(0, |all, attacker| all | own_attacks[attacker]);
+        let threatened = board.get_pieces(piece, side ^ 1) & lesser_attacks;
+        score += threatened.count_ones() as i16 * THREAT_BONUS[piece];
+
+        // Our pieces attacked by the opponent, but not defended.
+        let hanging = board.get_pieces(piece, side) & enemy_all & !own_all;
+        score += hanging.count_ones() as i16 * HANGING_PIECE_PENALTY[piece];
+
+        // Our pieces that are defended, but not sufficiently.
+        let enemy_lesser_attacks = (0..NrOf::PIECE_TYPES)
+            .filter(|&attacker| is_less_valuable(attacker, piece))
+            .fold(0, |all, attacker| all | enemy_attacks[attacker]);
+        let mut defended = board.get_pieces(piece, side) & enemy_all & own_all;
+        while defended > 0 {
+            let square = bits::next(&mut defended);
+            let attackers = board.attackers_to(square, side ^ 1, move_gen).count_ones();
+            let defenders = board.attackers_to(square, side, move_gen).count_ones();
+
+            if enemy_lesser_attacks & (1u64 << square) != 0 && attackers > defenders {
+                score += UNDERDEFENDED_PIECE_PENALTY[piece];
+            }
+        }
+    }
+
+    score
+}
+
+// Whether the attacker is clearly less valuable than the attacked piece.
+// The king attacks nothing this way; it's never less valuable.
+fn is_less_valuable(attacker: Piece, piece: Piece) -> bool {
+    exchange_value(attacker) + THREAT_VALUE_MARGIN < exchange_value(piece)
+}
+
+// Squares attacked by each of the piece types of the given side.
+fn attacks_by_piece(board: &Board, move_gen: &MoveGenerator, side: Side) -> [Bitboard; NrOf::PIECE_TYPES] {
+    let occupancy = board.occupancy();
+    let mut attacks = [0; NrOf::PIECE_TYPES];
+
+    attacks[Pieces::PAWN] = if side == Sides::WHITE {
+        bits::white_pawn_attacks(board.get_pieces(Pieces::PAWN, side))
+    } else {
+        bits::black_pawn_attacks(board.get_pieces(Pieces::PAWN, side))
+    };
+
+    for piece in [Pieces::KING, Pieces::QUEEN, Pieces::ROOK, Pieces::BISHOP, Pieces::KNIGHT] {
+        let mut pieces = board.get_pieces(piece, side);
+        while pieces > 0 {
+            let square = bits::next(&mut pieces);
+            attacks[piece] |= match piece {
+                Pieces::KING | Pieces::KNIGHT => move_gen.get_non_slider_attacks(piece, square),
+                _ => move_gen.get_slider_attacks(piece, square, occupancy),
+            };
+        }
+    }
+
+    attacks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn threats(fen: &str) -> i16 {
+        let mut board = Board::new();
+        board.fen_read(Some(fen)).unwrap();
+        evaluate_threats(&board, &MoveGenerator::new())
+    }
+
+    #[test]
+    fn test_knight_forking_two_rooks() {
+        // The rooks defend each other, so only the fork counts.
+        let score = threats("6k1/2r1r3/8/3N4/8/8/8/6K1 w - - 0 1");
+        assert_eq!(score, 2 * THREAT_BONUS[Pieces::ROOK]);
+    }
+
+    #[test]
+    fn test_hanging_bishop() {
+        // The white bishop on d4 is attacked by the rook and not defended.
+        let score = threats("8/7k/8/8/3B3r/8/8/K7 w - - 0 1");
+        assert_eq!(score, HANGING_PIECE_PENALTY[Pieces::BISHOP]);
+    }
+
+    #[test]
+    fn test_defended_bishop_attacked_by_queen() {
+        // The bishop on d4 is defended by the pawn, and the queen can't
+        // take it without losing herself.
+        assert_eq!(threats("8/7k/8/8/3B3q/2P5/8/K7 w - - 0 1"), 0);
+    }
+
+    #[test]
+    fn test_underdefended_bishop() {
+        // The bishop on d4 is attacked by a pawn and a rook, and only
+        // defended by a pawn.
+        let score = threats("3r3k/8/5p2/4p3/3B4/2P5/8/K7 w - - 0 1");
+        assert_eq!(
+            score,
+            UNDERDEFENDED_PIECE_PENALTY[Pieces::BISHOP] - THREAT_BONUS[Pieces::BISHOP]
+        );
+
+        // Without the rook, the pawn defending the bishop is enough.
+        let score = threats("7k/8/5p2/4p3/3B4/2P5/8/K7 w - - 0 1");
+        assert_eq!(score, -THREAT_BONUS[Pieces::BISHOP]);
+
+        // Outnumbered, but only by more valuable pieces.
+        assert_eq!(threats("8/3r3k/8/8/3B3r/2K5/8/8 w - - 0 1"), 0);
+    }
+
+    #[test]
+    fn test_hanging_queen() {
+        // The white queen is attacked by the knight and not defended.
+        let score = threats("8/7k/8/5n2/3Q4/8/8/K7 w - - 0 1");
+        assert_eq!(
+            score,
+            HANGING_PIECE_PENALTY[Pieces::QUEEN] - THREAT_BONUS[Pieces::QUEEN]
+        );
+    }
+}
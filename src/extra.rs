@@ -21,6 +21,7 @@ You should have received a copy of the GNU General Public License along
 with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
+pub mod epd;
 pub mod epds;
 pub mod testsuite;
 pub mod wizardry;
@@ -0,0 +1,325 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// Runs tactical test suites in EPD format. Each position is searched to
+// a fixed depth or for a fixed time, and the move found is checked
+// against the "bm" (best move) and "am" (avoid move) opcodes.
+
+use crate::{
+    board::Board,
+    engine::defs::{Information, SearchData, TT},
+    movegen::{defs::Move, MoveGenerator},
+    search::{
+        defs::{SearchControl, SearchInfo, SearchMode, SearchParams, SearchRefs, ThreadLocalData},
+        Search,
+    },
+};
+use crossbeam_channel::unbounded;
+use std::{
+    fs,
+    sync::{Arc, RwLock},
+    time::Instant,
+};
+
+const SEMI_COLON: char = ';';
+const QUOTE: char = '"';
+
+// The first four fields of an EPD line are the FEN without the move
+// counters. Those can be given by the "hmvc" and "fmvn" opcodes.
+const EPD_FEN_FIELDS: usize = 4;
+const OP_BEST_MOVE: &str = "bm";
+const OP_AVOID_MOVE: &str = "am";
+const OP_ID: &str = "id";
+const OP_HALFMOVE_CLOCK: &str = "hmvc";
+const OP_FULLMOVE_NUMBER: &str = "fmvn";
+
+// How long each position is searched.
+#[derive(Copy, Clone)]
+pub enum EpdLimit {
+    Depth(i8),
+    MoveTime(u128),
+}
+
+// One position of the suite, with its expected moves as written in the
+// EPD line. These are usually in SAN, but coordinate notation is also
+// accepted when matching.
+pub struct EpdPosition {
+    pub fen: String,
+    pub id: String,
+    pub best_moves: Vec<String>,
+    pub avoid_moves: Vec<String>,
+}
+
+// The outcome for one position.
+pub struct EpdOutcome {
+    pub id: String,
+    pub found: String,
+    pub passed: bool,
+}
+
+// The outcome of the whole suite.
+pub struct EpdResult {
+    pub outcomes: Vec<EpdOutcome>,
+    pub errors: usize,
+}
+
+impl EpdResult {
+    pub fn passed(&self) -> usize {
+        self.outcomes.iter().filter(|o| o.passed).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.outcomes.len() - self.passed()
+    }
+}
+
+impl EpdPosition {
+    // Parses one EPD line. Returns None for lines that are not a valid
+    // EPD record, or have neither a "bm" nor an "am" opcode to test.
+    pub fn parse(line: &str) -> Option<EpdPosition> {
+        let line = line.trim();
+        let mut fields = line.splitn(EPD_FEN_FIELDS + 1, char::is_whitespace);
+        let fen_fields: Vec<&str> = fields.by_ref().take(EPD_FEN_FIELDS).collect();
+        if fen_fields.len() < EPD_FEN_FIELDS {
+            return None;
+        }
+
+        let mut position = EpdPosition {
+            fen: String::new(),
+            id: String::new(),
+            best_moves: Vec::new(),
+            avoid_moves: Vec::new(),
+        };
+        let mut halfmove_clock = "0";
+        let mut fullmove_number = "1";
+
+        for operation in split_operations(fields.next().unwrap_or("")) {
+            let (opcode, operands) = operation.split_once(' ').unwrap_or((operation, ""));
+            let operands = operands.trim();
+            match opcode {
+                OP_BEST_MOVE => position.best_moves = operands.split_whitespace().map(String::from).collect(),
+                OP_AVOID_MOVE => position.avoid_moves = operands.split_whitespace().map(String::from).collect(),
+                OP_ID => position.id = operands.trim_matches(QUOTE).to_string(),
+                OP_HALFMOVE_CLOCK => halfmove_clock = operands,
+                OP_FULLMOVE_NUMBER => fullmove_number = operands,
+                _ => (),
+            }
+        }
+
+        if position.best_moves.is_empty() && position.avoid_moves.is_empty() {
+            return None;
+        }
+
+        position.fen = format!("{} {halfmove_clock} {fullmove_number}", fen_fields.join(" "));
+        Some(position)
+    }
+
+    // A move passes if it is one of the best moves, and none of the moves
+    // to avoid. Expected moves are matched as SAN first, and otherwise as
+    // coordinate notation.
+    pub fn accepts(&self, board: &Board, m: Move, mg: &MoveGenerator) -> bool {
+        let matches = |expected: &String| match board.san_to_move(expected, mg) {
+            Some(x) => x.get_move() == m.get_move(),
+            None => *expected == m.as_string(),
+        };
+
+        let is_best = self.best_moves.is_empty() || self.best_moves.iter().any(matches);
+        let is_avoided = self.avoid_moves.iter().any(matches);
+
+        is_best && !is_avoided
+    }
+}
+
+// Runs the suite in the given EPD file and prints a summary.
+pub fn run_file(path: &str, limit: EpdLimit, mg: Arc<MoveGenerator>, tt: Arc<RwLock<TT<SearchData>>>) {
+    match fs::read_to_string(path) {
+        Ok(contents) => {
+            let lines: Vec<&str> = contents.lines().collect();
+            let result = run(&lines, limit, mg, tt);
+
+            println!(
+                "\nEPD suite {path}: {} passed, {} failed, {} skipped",
+                result.passed(),
+                result.failed(),
+                result.errors,
+            );
+            for o in result.outcomes.iter().filter(|o| !o.passed) {
+                println!("Failed: {} (found {})", o.id, o.found);
+            }
+        }
+        Err(e) => println!("Could not read EPD file {path}: {e}"),
+    }
+}
+
+// Searches each position of the suite and checks the move found. Empty
+// lines and comments starting with '#' are skipped; other lines that
+// can't be parsed or set up are counted as errors.
+pub fn run(
+    lines: &[&str],
+    limit: EpdLimit,
+    mg: Arc<MoveGenerator>,
+    tt: Arc<RwLock<TT<SearchData>>>,
+) -> EpdResult {
+    let mut result = EpdResult {
+        outcomes: Vec::new(),
+        errors: 0,
+    };
+
+    for (nr, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let mut board = Board::new();
+        let position = match EpdPosition::parse(line) {
+            Some(p) if board.fen_read(Some(&p.fen)).is_ok() => p,
+            _ => {
+                println!("Test {}: skipped, invalid EPD line", nr + 1);
+                result.errors += 1;
+                continue;
+            }
+        };
+
+        let now = Instant::now();
+        let best_move = search(&mut board, limit, &mg, &tt);
+        let passed = position.accepts(&board, best_move, &mg);
+        let found = board.move_to_san(best_move, &mg);
+        let id = if position.id.is_empty() {
+            format!("{}", nr + 1)
+        } else {
+            position.id
+        };
+
+        println!(
+            "Test {id}: found {found} - Result: {} ({} ms)",
+            if passed { "OK" } else { "Fail" },
+            now.elapsed().as_millis()
+        );
+
+        result.outcomes.push(EpdOutcome { id, found, passed });
+    }
+
+    result
+}
+
+// Searches the position from a clean TT, and returns the best move.
+fn search(
+    board: &mut Board,
+    limit: EpdLimit,
+    mg: &Arc<MoveGenerator>,
+    tt: &Arc<RwLock<TT<SearchData>>>,
+) -> Move {
+    let (_control_tx, control_rx) = unbounded::<SearchControl>();
+    let (report_tx, _report_rx) = unbounded::<Information>();
+    let mut search_params = SearchParams::new();
+    let mut search_info = SearchInfo::new();
+    let mut thread_local_data = ThreadLocalData::new(0);
+
+    search_params.quiet = true;
+    match limit {
+        EpdLimit::Depth(depth) => {
+            search_params.search_mode = SearchMode::Depth;
+            search_params.depth = depth;
+        }
+        EpdLimit::MoveTime(ms) => {
+            search_params.search_mode = SearchMode::MoveTime;
+            search_params.move_time = ms;
+        }
+    }
+
+    if let Ok(mut tt) = tt.write() {
+        tt.clear();
+    }
+
+    let mut refs = SearchRefs {
+        board,
+        mg,
+        tt,
+        tt_enabled: true,
+        search_params: &mut search_params,
+        search_info: &mut search_info,
+        control_rx: &control_rx,
+        report_tx: &report_tx,
+        thread_local_data: &mut thread_local_data,
+    };
+
+    let (best_move, _) = Search::iterative_deepening(&mut refs);
+    best_move
+}
+
+// Splits the operations part of an EPD line on semicolons, except for
+// semicolons within quoted strings.
+fn split_operations(operations: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+
+    for (i, c) in operations.char_indices() {
+        match c {
+            QUOTE => in_quotes = !in_quotes,
+            SEMI_COLON if !in_quotes => {
+                result.push(operations[start..i].trim());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    result.push(operations[start..].trim());
+
+    result.into_iter().filter(|op| !op.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUITE: [&str; 3] = [
+        "r1bqkbnr/pppp1ppp/2n5/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - bm Qxf7#; id \"scholar's mate\";",
+        "4k3/8/8/3q4/8/8/8/3RK3 w - - bm Rxd5; id \"free queen\"; hmvc 12;",
+        "3qk3/8/8/3p4/8/8/8/3RK3 w - - am Rxd5; id \"poisoned pawn\";",
+    ];
+
+    #[test]
+    fn test_parse_epd_line() {
+        let position = EpdPosition::parse(SUITE[1]).unwrap();
+        assert_eq!(position.fen, "4k3/8/8/3q4/8/8/8/3RK3 w - - 12 1");
+        assert_eq!(position.id, "free queen");
+        assert_eq!(position.best_moves, vec!["Rxd5"]);
+        assert!(position.avoid_moves.is_empty());
+
+        let position = EpdPosition::parse(SUITE[2]).unwrap();
+        assert_eq!(position.avoid_moves, vec!["Rxd5"]);
+        assert!(EpdPosition::parse("4k3/8/8/8/8/8/8/4K3 w - - id \"nothing\";").is_none());
+    }
+
+    #[test]
+    fn test_suite_finds_best_moves() {
+        let mg = Arc::new(MoveGenerator::new());
+        let tt = Arc::new(RwLock::new(TT::new(16)));
+        let result = run(&SUITE, EpdLimit::Depth(4), mg, tt);
+
+        assert_eq!(result.errors, 0);
+        assert_eq!(result.passed(), SUITE.len());
+        assert_eq!(result.outcomes[0].found, "Qxf7#");
+    }
+}
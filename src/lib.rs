@@ -2,6 +2,7 @@
 // This allows the crate to be used as both a binary and library
 
 pub mod board;
+pub mod book;
 pub mod comm;
 pub mod defs;
 pub mod engine;
@@ -22,6 +22,7 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 mod board;
+mod book;
 mod comm;
 mod defs;
 mod engine;
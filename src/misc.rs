@@ -25,4 +25,5 @@ pub mod bits;
 pub mod cmdline;
 pub mod parse;
 pub mod perft;
+pub mod pgn;
 pub mod print;
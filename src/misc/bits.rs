@@ -45,22 +45,25 @@ pub fn file_fill(bitboard: Bitboard) -> Bitboard {
     result
 }
 
-/// Get all squares "north" (higher ranks) of the given bitboard, excluding the original squares
+/// Get all squares "north" (higher ranks) of any of the given squares. A
+/// square north of another given square is included, so pawns on the same
+/// file can be found with pawns & north_fill(pawns).
 pub fn north_fill(bitboard: Bitboard) -> Bitboard {
-    let mut fill = bitboard;
+    let mut fill = bitboard << 8;
     fill |= fill << 8;
     fill |= fill << 16;
     fill |= fill << 32;
-    fill & !bitboard
+    fill
 }
 
-/// Get all squares "south" (lower ranks) of the given bitboard, excluding the original squares  
+/// Get all squares "south" (lower ranks) of any of the given squares. A
+/// square south of another given square is included.
 pub fn south_fill(bitboard: Bitboard) -> Bitboard {
-    let mut fill = bitboard;
+    let mut fill = bitboard >> 8;
     fill |= fill >> 8;
     fill |= fill >> 16;
     fill |= fill >> 32;
-    fill & !bitboard
+    fill
 }
 
 /// White front spans: all squares in front of white pawns (towards 8th rank)
@@ -326,3 +329,46 @@ pub fn black_passed_pawns(black_pawns: Bitboard, white_pawns: Bitboard) -> Bitbo
     
     passed
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::defs::BB_SQUARES;
+
+    const D2: Bitboard = BB_SQUARES[11];
+    const D4: Bitboard = BB_SQUARES[27];
+
+    #[test]
+    fn test_fills_leave_out_the_starting_square() {
+        let d_file = BB_FILES[3];
+        let above_d4 = d_file & !(BB_RANKS[0] | BB_RANKS[1] | BB_RANKS[2] | BB_RANKS[3]);
+        let below_d4 = d_file & (BB_RANKS[0] | BB_RANKS[1] | BB_RANKS[2]);
+
+        assert_eq!(north_fill(D4), above_d4);
+        assert_eq!(south_fill(D4), below_d4);
+        assert_eq!(north_fill(BB_SQUARES[59]), 0);
+        assert_eq!(south_fill(BB_SQUARES[3]), 0);
+    }
+
+    #[test]
+    fn test_fills_include_stacked_squares() {
+        // d4 lies north of d2, and d2 south of d4.
+        let pawns = D2 | D4;
+
+        assert_eq!(north_fill(pawns) & pawns, D4);
+        assert_eq!(south_fill(pawns) & pawns, D2);
+        assert_eq!(north_fill(pawns), north_fill(D2));
+        assert_eq!(south_fill(pawns), south_fill(D4));
+    }
+
+    #[test]
+    fn test_doubled_pawns_found() {
+        assert_eq!(white_doubled_pawns(D2 | D4), (D2, D4));
+        assert_eq!(black_doubled_pawns(D2 | D4), (D4, D2));
+
+        // Pawns on different files are not doubled.
+        let spread = D2 | BB_SQUARES[28];
+        assert_eq!(white_doubled_pawns(spread), (0, 0));
+        assert_eq!(black_doubled_pawns(spread), (0, 0));
+    }
+}
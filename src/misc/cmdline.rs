@@ -36,12 +36,30 @@ impl CmdLineArgs {
     const FEN_SHORT: char = 'f';
     const FEN_HELP: &'static str = "Set up the given position";
 
+    // PGN
+    const PGN_LONG: &'static str = "pgn";
+    const PGN_SHORT: char = 'g';
+    const PGN_HELP: &'static str = "Set up the final position of the game in the given PGN file (ignore --fen)";
+
+    // Evaluation weights
+    const EVAL_PARAMS_LONG: &'static str = "eval-params";
+    const EVAL_PARAMS_HELP: &'static str = "Load evaluation weights from the given file";
+
     // Perft
     const PERFT_LONG: &'static str = "perft";
     const PERFT_SHORT: char = 'p';
     const PERFT_HELP: &'static str = "Run perft to the given depth";
     const PERFT_DEFAULT: i8 = 0;
 
+    // Divide (perft per root move)
+    const DIVIDE_LONG: &'static str = "divide";
+    const DIVIDE_SHORT: char = 'd';
+    const DIVIDE_HELP: &'static str = "Split perft results by root move";
+
+    // Hashed perft (leaf node counts stored in the TT)
+    const PERFT_HASH_LONG: &'static str = "perft-hash";
+    const PERFT_HASH_HELP: &'static str = "Store perft results in a TT of --hash MB";
+
     // Interface
     const COMM_LONG: &'static str = "comm";
     const COMM_SHORT: char = 'c';
@@ -79,6 +97,19 @@ impl CmdLineArgs {
     const EPD_TEST_LONG: &'static str = "epdtest";
     const EPD_TEST_SHORT: char = 'e';
     const EPD_TEST_HELP: &'static str = "Run EPD Test Suite";
+
+    // Best move / avoid move suite
+    const EPD_SUITE_LONG: &'static str = "epdsuite";
+    const EPD_SUITE_SHORT: char = 's';
+    const EPD_SUITE_HELP: &'static str = "Run the bm/am test suite in the given EPD file";
+
+    const EPD_DEPTH_LONG: &'static str = "epddepth";
+    const EPD_DEPTH_HELP: &'static str = "Search depth per EPD suite position";
+    #[cfg(feature = "extra")]
+    const EPD_DEPTH_DEFAULT: i8 = 8;
+
+    const EPD_TIME_LONG: &'static str = "epdtime";
+    const EPD_TIME_HELP: &'static str = "Search time in ms per EPD suite position (overrides depth)";
 }
 
 pub struct CmdLine {
@@ -106,6 +137,18 @@ impl CmdLine {
             .clone()
     }
 
+    pub fn pgn(&self) -> Option<String> {
+        self.arguments
+            .get_one::<String>(CmdLineArgs::PGN_LONG)
+            .cloned()
+    }
+
+    pub fn eval_params(&self) -> Option<String> {
+        self.arguments
+            .get_one::<String>(CmdLineArgs::EVAL_PARAMS_LONG)
+            .cloned()
+    }
+
     pub fn perft(&self) -> i8 {
         *self
             .arguments
@@ -135,6 +178,14 @@ impl CmdLine {
         self.arguments.get_flag(CmdLineArgs::QUIET_LONG)
     }
 
+    pub fn has_divide(&self) -> bool {
+        self.arguments.get_flag(CmdLineArgs::DIVIDE_LONG)
+    }
+
+    pub fn has_perft_hash(&self) -> bool {
+        self.arguments.get_flag(CmdLineArgs::PERFT_HASH_LONG)
+    }
+
     #[cfg(feature = "extra")]
     pub fn has_wizardry(&self) -> bool {
         self.arguments.get_flag(CmdLineArgs::WIZARDRY_LONG)
@@ -145,6 +196,28 @@ impl CmdLine {
         self.arguments.get_flag(CmdLineArgs::EPD_TEST_LONG)
     }
 
+    #[cfg(feature = "extra")]
+    pub fn epd_suite(&self) -> Option<String> {
+        self.arguments
+            .get_one::<String>(CmdLineArgs::EPD_SUITE_LONG)
+            .cloned()
+    }
+
+    #[cfg(feature = "extra")]
+    pub fn epd_depth(&self) -> i8 {
+        *self
+            .arguments
+            .get_one::<i8>(CmdLineArgs::EPD_DEPTH_LONG)
+            .unwrap_or(&CmdLineArgs::EPD_DEPTH_DEFAULT)
+    }
+
+    #[cfg(feature = "extra")]
+    pub fn epd_time(&self) -> Option<u128> {
+        self.arguments
+            .get_one::<u128>(CmdLineArgs::EPD_TIME_LONG)
+            .copied()
+    }
+
     fn get() -> ArgMatches {
         let mut cmd_line = clap::Command::new(About::ENGINE)
             .version(About::VERSION)
@@ -169,6 +242,21 @@ impl CmdLine {
                     .default_value(FEN_START_POSITION)
                     .value_parser(value_parser!(String)),
             )
+            .arg(
+                Arg::new(CmdLineArgs::PGN_LONG)
+                    .short(CmdLineArgs::PGN_SHORT)
+                    .long(CmdLineArgs::PGN_LONG)
+                    .help(CmdLineArgs::PGN_HELP)
+                    .num_args(1)
+                    .value_parser(value_parser!(String)),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::EVAL_PARAMS_LONG)
+                    .long(CmdLineArgs::EVAL_PARAMS_LONG)
+                    .help(CmdLineArgs::EVAL_PARAMS_HELP)
+                    .num_args(1)
+                    .value_parser(value_parser!(String)),
+            )
             .arg(
                 Arg::new(CmdLineArgs::PERFT_LONG)
                     .short(CmdLineArgs::PERFT_SHORT)
@@ -177,6 +265,19 @@ impl CmdLine {
                     .value_parser(value_parser!(i8))
                     .num_args(1),
             )
+            .arg(
+                Arg::new(CmdLineArgs::DIVIDE_LONG)
+                    .short(CmdLineArgs::DIVIDE_SHORT)
+                    .long(CmdLineArgs::DIVIDE_LONG)
+                    .help(CmdLineArgs::DIVIDE_HELP)
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new(CmdLineArgs::PERFT_HASH_LONG)
+                    .long(CmdLineArgs::PERFT_HASH_LONG)
+                    .help(CmdLineArgs::PERFT_HASH_HELP)
+                    .action(ArgAction::SetTrue),
+            )
             .arg(
                 Arg::new(CmdLineArgs::THREADS_LONG)
                     .short(CmdLineArgs::THREADS_SHORT)
@@ -223,6 +324,28 @@ impl CmdLine {
                         .long(CmdLineArgs::EPD_TEST_LONG)
                         .help(CmdLineArgs::EPD_TEST_HELP)
                         .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new(CmdLineArgs::EPD_SUITE_LONG)
+                        .short(CmdLineArgs::EPD_SUITE_SHORT)
+                        .long(CmdLineArgs::EPD_SUITE_LONG)
+                        .help(CmdLineArgs::EPD_SUITE_HELP)
+                        .value_parser(value_parser!(String))
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new(CmdLineArgs::EPD_DEPTH_LONG)
+                        .long(CmdLineArgs::EPD_DEPTH_LONG)
+                        .help(CmdLineArgs::EPD_DEPTH_HELP)
+                        .value_parser(value_parser!(i8))
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new(CmdLineArgs::EPD_TIME_LONG)
+                        .long(CmdLineArgs::EPD_TIME_LONG)
+                        .help(CmdLineArgs::EPD_TIME_HELP)
+                        .value_parser(value_parser!(u128))
+                        .num_args(1),
                 );
         }
 
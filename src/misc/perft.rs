@@ -26,7 +26,7 @@ use crate::{
     engine::defs::{ErrFatal, PerftData, TT},
     misc::print,
     movegen::{
-        defs::{MoveList, MoveType},
+        defs::{Move, MoveList, MoveType},
         MoveGenerator,
     },
 };
@@ -44,6 +44,7 @@ pub fn run(
     mg: Arc<MoveGenerator>,
     tt: Arc<Mutex<TT<PerftData>>>,
     tt_enabled: bool,
+    threads: usize,
 ) {
     let mut total_time: u128 = 0;
     let mut total_nodes: u64 = 0;
@@ -55,13 +56,13 @@ pub fn run(
     let mtx_board = board.lock().expect(ErrFatal::LOCK);
 
     // Clone the locked board for local use.
-            let mut local_board = mtx_board.clone_for_search();
+            let local_board = mtx_board.clone_for_search();
 
     // The function now has its own local board. Drop the guard. It is not
     // necessary to keep the lock until perft runs out.
     std::mem::drop(mtx_board);
 
-    println!("Benchmarking perft 1-{depth}:");
+    println!("Benchmarking perft 1-{depth} ({threads} threads):");
 
     print::position(&local_board, None);
 
@@ -71,7 +72,7 @@ pub fn run(
         let now = Instant::now();
         let mut leaf_nodes = 0;
 
-        leaf_nodes += perft(&mut local_board, d, &mg, &tt, tt_enabled);
+        leaf_nodes += perft_parallel(&local_board, d, &mg, &tt, tt_enabled, threads);
 
         // Measure time and speed
         let elapsed = now.elapsed().as_millis();
@@ -86,7 +87,7 @@ pub fn run(
         if tt_enabled {
             hash_full = format!(
                 ", hash full: {}%",
-                tt.lock().expect(ErrFatal::LOCK).hash_full() as f64 / 10f64
+                tt.lock().expect(ErrFatal::LOCK).hashfull_permille() as f64 / 10f64
             );
         }
 
@@ -102,6 +103,131 @@ pub fn run(
     println!("Execution speed: {final_lnps} leaves/second");
 }
 
+// This function runs perft for each root move separately, and prints the
+// number of leaf nodes per move and in total. Comparing this output with
+// that of another engine quickly narrows down a move generator bug.
+pub fn run_divide(
+    board: Arc<Mutex<Board>>,
+    depth: i8,
+    mg: Arc<MoveGenerator>,
+    tt: Arc<Mutex<TT<PerftData>>>,
+    tt_enabled: bool,
+) {
+    let mut local_board = board.lock().expect(ErrFatal::LOCK).clone_for_search();
+
+    println!("Divide perft {depth}:");
+
+    print::position(&local_board, None);
+
+    let now = Instant::now();
+    let moves = divide(&mut local_board, depth, &mg, &tt, tt_enabled);
+    let elapsed = now.elapsed().as_millis();
+
+    for (m, leaf_nodes) in moves.iter() {
+        println!("{}: {leaf_nodes}", m.as_string());
+    }
+
+    let total: u64 = moves.iter().map(|(_, leaf_nodes)| leaf_nodes).sum();
+    println!();
+    println!("Moves: {}", moves.len());
+    println!("Nodes: {total} ({elapsed} ms)");
+}
+
+// Runs perft for each legal root move, and returns the moves with the
+// number of leaf nodes each of them generates.
+pub fn divide(
+    board: &mut Board,
+    depth: i8,
+    mg: &MoveGenerator,
+    tt: &Mutex<TT<PerftData>>,
+    tt_enabled: bool,
+) -> Vec<(Move, u64)> {
+    let mut result: Vec<(Move, u64)> = Vec::new();
+    let mut move_list: MoveList = MoveList::new();
+
+    if depth < 1 {
+        return result;
+    }
+
+    mg.generate_moves(board, &mut move_list, MoveType::All);
+
+    for i in 0..move_list.len() {
+        let m = move_list.get_move(i);
+
+        if board.make(m, mg) {
+            result.push((m, perft(board, depth - 1, mg, tt, tt_enabled)));
+            board.unmake();
+        }
+    }
+
+    result
+}
+
+// Runs perft with the leaf node counts of visited positions stored in the
+// TT, so transpositions within the tree are counted only once. This is the
+// perft the engine runs from the command line with --perft-hash.
+pub fn perft_hashed(
+    board: &mut Board,
+    depth: i8,
+    mg: &MoveGenerator,
+    tt: &Mutex<TT<PerftData>>,
+) -> u64 {
+    perft(board, depth, mg, tt, true)
+}
+
+// Runs perft with the legal root moves divided over the given number of
+// threads. Each thread runs perft on its own clone of the board, and the
+// leaf node counts are added up, so the result is the same as that of a
+// single-threaded perft. The threads share the TT.
+pub fn perft_parallel(
+    board: &Board,
+    depth: i8,
+    mg: &MoveGenerator,
+    tt: &Mutex<TT<PerftData>>,
+    tt_enabled: bool,
+    threads: usize,
+) -> u64 {
+    if threads <= 1 || depth < 2 {
+        let mut board = board.clone_for_search();
+        return if tt_enabled {
+            perft_hashed(&mut board, depth, mg, tt)
+        } else {
+            perft(&mut board, depth, mg, tt, false)
+        };
+    }
+
+    let mut move_list: MoveList = MoveList::new();
+    mg.generate_moves(board, &mut move_list, MoveType::All);
+    let moves: Vec<Move> = (0..move_list.len()).map(|i| move_list.get_move(i)).collect();
+
+    std::thread::scope(|s| {
+        let handles: Vec<_> = (0..threads)
+            .map(|t| {
+                let moves = &moves;
+                s.spawn(move || {
+                    let mut thread_board = board.clone_for_search();
+                    let mut leaf_nodes: u64 = 0;
+
+                    // Every thread takes every n-th root move.
+                    for m in moves.iter().skip(t).step_by(threads) {
+                        if thread_board.make(*m, mg) {
+                            leaf_nodes += perft(&mut thread_board, depth - 1, mg, tt, tt_enabled);
+                            thread_board.unmake();
+                        }
+                    }
+
+                    leaf_nodes
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|h| h.join().expect(ErrFatal::THREAD))
+            .sum()
+    })
+}
+
 // This is the actual Perft function. It is public, because it is used by
 // the "testsuite" module.
 pub fn perft(
@@ -166,3 +292,108 @@ pub fn perft(
     // Return the number of leaf nodes for the given position and depth.
     leaf_nodes
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defs::FEN_START_POSITION;
+
+    fn perft_fen(fen: &str, depth: i8) -> u64 {
+        let mut board = Board::new();
+        let mg = MoveGenerator::new();
+        let tt = Mutex::new(TT::<PerftData>::new(0));
+
+        board.fen_read(Some(fen)).unwrap();
+        perft(&mut board, depth, &mg, &tt, false)
+    }
+
+    #[test]
+    fn test_divide_start_position() {
+        let mut board = Board::new();
+        let mg = MoveGenerator::new();
+        let tt = Mutex::new(TT::<PerftData>::new(0));
+        board.fen_read(Some(FEN_START_POSITION)).unwrap();
+
+        // Whatever white plays, black has 20 replies.
+        let moves = divide(&mut board, 2, &mg, &tt, false);
+        assert_eq!(moves.len(), 20);
+        assert!(moves.iter().all(|(_, leaf_nodes)| *leaf_nodes == 20));
+
+        let moves = divide(&mut board, 3, &mg, &tt, false);
+        let subtotal = |mv: &str| moves.iter().find(|(m, _)| m.as_string() == mv).unwrap().1;
+        assert_eq!(subtotal("e2e4"), 600);
+        assert_eq!(subtotal("d2d4"), 560);
+        assert_eq!(subtotal("b2b4"), 421);
+        assert_eq!(subtotal("g1f3"), 440);
+        assert_eq!(moves.iter().map(|(_, leaf_nodes)| leaf_nodes).sum::<u64>(), 8902);
+    }
+
+    fn perft_hashed_fen(fen: &str, depth: i8) -> u64 {
+        let mut board = Board::new();
+        let mg = MoveGenerator::new();
+        let tt = Mutex::new(TT::<PerftData>::new(16));
+
+        board.fen_read(Some(fen)).unwrap();
+        perft_hashed(&mut board, depth, &mg, &tt)
+    }
+
+    #[test]
+    fn test_hashed_perft_agrees() {
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(perft_hashed_fen(kiwipete, 4), perft_fen(kiwipete, 4));
+    }
+
+    #[test]
+    fn test_hashed_perft_agrees_depth_5() {
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut board = Board::new();
+        let mg = MoveGenerator::new();
+        let tt = Mutex::new(TT::<PerftData>::new(0));
+        board.fen_read(Some(kiwipete)).unwrap();
+
+        // Plain perft is the slow one at this depth, so spread it over all
+        // cores.
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let plain = perft_parallel(&board, 5, &mg, &tt, false, threads);
+
+        assert_eq!(plain, 193_690_690);
+        assert_eq!(perft_hashed_fen(kiwipete, 5), plain);
+    }
+
+    #[test]
+    fn test_parallel_perft_agrees() {
+        let mut board = Board::new();
+        let mg = MoveGenerator::new();
+        let tt = Mutex::new(TT::<PerftData>::new(0));
+        board.fen_read(Some(FEN_START_POSITION)).unwrap();
+
+        let single = perft(&mut board, 4, &mg, &tt, false);
+        let parallel = perft_parallel(&board, 4, &mg, &tt, false, 4);
+        assert_eq!(single, 197_281);
+        assert_eq!(parallel, single);
+    }
+
+    #[test]
+    fn test_perft_normal_castling() {
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+        assert_eq!(perft_fen(FEN_START_POSITION, 4), 197_281);
+        assert_eq!(perft_fen(kiwipete, 3), 97_862);
+    }
+
+    #[test]
+    fn test_perft_chess960_castling() {
+        // King on g1 between rooks on f1 and h1: castling queenside makes
+        // the king and rook pass through each other.
+        let fen = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9";
+        assert_eq!(perft_fen(fen, 1), 21);
+        assert_eq!(perft_fen(fen, 2), 528);
+        assert_eq!(perft_fen(fen, 3), 12_189);
+        assert_eq!(perft_fen(fen, 4), 326_672);
+
+        let fen = "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9";
+        assert_eq!(perft_fen(fen, 1), 21);
+        assert_eq!(perft_fen(fen, 2), 807);
+        assert_eq!(perft_fen(fen, 3), 18_002);
+    }
+}
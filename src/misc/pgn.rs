@@ -0,0 +1,260 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// Exports the game played on a board as PGN.
+
+use crate::{
+    board::Board,
+    defs::{Sides, FEN_START_POSITION},
+    movegen::{defs::Move, MoveGenerator},
+};
+
+// Tokens ending the movetext of a game.
+const PGN_RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+// PGN export format: movetext lines are at most 79 characters.
+const PGN_LINE_LENGTH: usize = 79;
+const PGN_UNKNOWN: &str = "?";
+const PGN_UNKNOWN_DATE: &str = "????.??.??";
+
+// Writes the moves in the board's history as PGN, with the seven-tag
+// roster and the given result ("1-0", "0-1", "1/2-1/2" or "*"). The
+// history only holds the moves, so the board is taken back to the start
+// of the game, and the moves are replayed to write them in SAN. If the
+// game didn't start from the normal start position, the FEN is added.
+pub fn game_to_pgn(board: &Board, result: &str, mg: &MoveGenerator) -> String {
+    let moves: Vec<Move> = (0..board.history.len())
+        .map(|i| board.history.get_ref(i).next_move)
+        .collect();
+
+    let mut game = board.clone_for_engine();
+    while !game.history.is_empty() {
+        game.unmake();
+    }
+
+    let mut pgn = String::new();
+    for (tag, value) in [
+        ("Event", PGN_UNKNOWN),
+        ("Site", PGN_UNKNOWN),
+        ("Date", PGN_UNKNOWN_DATE),
+        ("Round", PGN_UNKNOWN),
+        ("White", PGN_UNKNOWN),
+        ("Black", PGN_UNKNOWN),
+        ("Result", result),
+    ] {
+        pgn.push_str(&format!("[{tag} \"{value}\"]\n"));
+    }
+
+    let fen = game.to_fen();
+    if fen != FEN_START_POSITION {
+        pgn.push_str("[SetUp \"1\"]\n");
+        pgn.push_str(&format!("[FEN \"{fen}\"]\n"));
+    }
+    pgn.push('\n');
+
+    // Collect the movetext tokens: move numbers, moves, and the result.
+    let mut tokens: Vec<String> = Vec::new();
+    for (i, &m) in moves.iter().enumerate() {
+        let number = game.game_state.fullmove_number;
+        if game.us() == Sides::WHITE {
+            tokens.push(format!("{number}."));
+        } else if i == 0 {
+            tokens.push(format!("{number}..."));
+        }
+
+        tokens.push(game.move_to_san(m, mg));
+        game.make(m, mg);
+    }
+    tokens.push(result.to_string());
+
+    // Wrap the tokens into lines.
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > PGN_LINE_LENGTH {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    pgn.push_str(&line);
+    pgn.push('\n');
+
+    pgn
+}
+
+// Reads the first game from the PGN, and returns its start position as
+// a FEN-string and its moves as written (usually SAN). The start position
+// comes from the FEN tag if there is one. Comments, variations, move
+// numbers and numeric annotation glyphs are skipped.
+pub fn load_first_position_and_moves(pgn: &str) -> (String, Vec<String>) {
+    let mut fen = FEN_START_POSITION.to_string();
+    let mut movetext = String::new();
+
+    for line in pgn.lines().map(str::trim) {
+        if line.starts_with('[') {
+            // A tag after the movetext belongs to the next game.
+            if !movetext.trim().is_empty() {
+                break;
+            }
+            if let Some(tag) = line.strip_prefix("[FEN ") {
+                fen = tag.trim_end_matches(']').trim().trim_matches('"').to_string();
+            }
+        } else if !line.starts_with('%') {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+
+    let mut moves = Vec::new();
+    for token in strip_comments_and_variations(&movetext).split_whitespace() {
+        if PGN_RESULTS.contains(&token) {
+            break;
+        }
+
+        // Move numbers can be written against the move: "1.e4", "3...Nf6".
+        let token = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+        if !token.is_empty() && !token.starts_with('$') {
+            moves.push(token.to_string());
+        }
+    }
+
+    (fen, moves)
+}
+
+// Removes {comments}, ; comments up to the end of the line, and
+// (variations), which can be nested.
+fn strip_comments_and_variations(movetext: &str) -> String {
+    let mut result = String::new();
+    let mut variation_depth = 0;
+    let mut in_comment = false;
+    let mut in_line_comment = false;
+
+    for c in movetext.chars() {
+        match c {
+            _ if in_line_comment => in_line_comment = c != '\n',
+            '}' if in_comment => in_comment = false,
+            _ if in_comment => (),
+            '{' => in_comment = true,
+            ';' => in_line_comment = true,
+            '(' => variation_depth += 1,
+            ')' => variation_depth -= 1,
+            _ if variation_depth > 0 => (),
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(board: &mut Board, mg: &MoveGenerator, moves: &[&str]) {
+        for san in moves {
+            let m = board.san_to_move(san, mg).expect("SAN should parse");
+            assert!(board.make(m, mg));
+        }
+    }
+
+    // Reads the moves back from the movetext, skipping move numbers and
+    // the result.
+    fn movetext(pgn: &str) -> Vec<&str> {
+        pgn.lines()
+            .filter(|l| !l.starts_with('[') && !l.is_empty())
+            .flat_map(|l| l.split_whitespace())
+            .filter(|t| !t.ends_with('.') && !PGN_RESULTS.contains(t))
+            .collect()
+    }
+
+    #[test]
+    fn test_pgn_from_start_position() {
+        let mg = MoveGenerator::new();
+        let mut board = Board::new();
+        board.fen_read(None).unwrap();
+        let moves = ["e4", "e5", "Nf3", "Nc6", "Bc4", "Bc5", "O-O", "Nf6", "d3", "O-O"];
+        play(&mut board, &mg, &moves);
+
+        let pgn = game_to_pgn(&board, "*", &mg);
+        assert!(pgn.starts_with("[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n"));
+        assert!(pgn.contains("[Result \"*\"]\n\n"));
+        assert!(!pgn.contains("[FEN"));
+        assert!(pgn.ends_with("1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. O-O Nf6 5. d3 O-O *\n"));
+        assert_eq!(movetext(&pgn), moves);
+    }
+
+    #[test]
+    fn test_pgn_round_trips_promotion_castling_and_checks() {
+        let mg = MoveGenerator::new();
+        let fen = "4k3/1P6/8/8/8/8/8/R3K3 w Q - 0 1";
+        let mut board = Board::new();
+        board.fen_read(Some(fen)).unwrap();
+        play(&mut board, &mg, &["b8=Q+", "Kd7", "O-O-O+", "Ke6", "Qe8+"]);
+
+        let pgn = game_to_pgn(&board, "1-0", &mg);
+        assert!(pgn.contains(&format!("[SetUp \"1\"]\n[FEN \"{fen}\"]\n")));
+        assert!(pgn.ends_with("1. b8=Q+ Kd7 2. O-O-O+ Ke6 3. Qe8+ 1-0\n"));
+
+        // Replaying the movetext from the FEN gives the same position.
+        let mut replay = Board::new();
+        replay.fen_read(Some(fen)).unwrap();
+        play(&mut replay, &mg, &movetext(&pgn));
+        assert_eq!(replay.game_state.zobrist_key, board.game_state.zobrist_key);
+    }
+
+    #[test]
+    fn test_load_first_game_from_pgn() {
+        let pgn = "[Event \"Test\"]\n[White \"?\"]\n[Black \"?\"]\n[Result \"*\"]\n\n\
+            1. e4 {best by test} e5 2. Nf3 (2. f4 exf4 (2... d5) 3. Nf3) Nc6\n\
+            3. Bb5 $1 a6 ; the Morphy defence\n4.Ba4 Nf6 5. O-O *\n\n\
+            [Event \"Next\"]\n\n1. d4 d5 *\n";
+
+        let (fen, moves) = load_first_position_and_moves(pgn);
+        assert_eq!(fen, FEN_START_POSITION);
+        assert_eq!(moves, ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O"]);
+
+        let mg = MoveGenerator::new();
+        let mut board = Board::new();
+        board.fen_read(Some(&fen)).unwrap();
+        let moves: Vec<&str> = moves.iter().map(String::as_str).collect();
+        play(&mut board, &mg, &moves);
+        assert_eq!(
+            board.to_fen(),
+            "r1bqkb1r/1ppp1ppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 3 5"
+        );
+    }
+
+    #[test]
+    fn test_load_pgn_with_fen_tag() {
+        let fen = "4k3/1P6/8/8/8/8/8/R3K3 w Q - 0 1";
+        let pgn = format!("[SetUp \"1\"]\n[FEN \"{fen}\"]\n\n1. b8=Q+ Kd7 2. O-O-O+ 1-0\n");
+
+        let (loaded_fen, moves) = load_first_position_and_moves(&pgn);
+        assert_eq!(loaded_fen, fen);
+        assert_eq!(moves, ["b8=Q+", "Kd7", "O-O-O+"]);
+    }
+}
@@ -28,6 +28,7 @@ use crate::{
     },
     defs::{Bitboard, Castling, NrOf, Sides},
     movegen::defs::{Move, MoveList},
+    search::defs::{GamePhase, TimeStats},
 };
 
 type AsciiBoard = [char; NrOf::SQUARES];
@@ -146,6 +147,7 @@ fn metadata(board: &Board) {
     let hmc = board.game_state.halfmove_clock;
     let fmn = board.game_state.fullmove_number;
 
+    println!("{:<20}{}", "FEN:", board.to_fen());
     println!("{:<20}{:x}", "Zobrist key:", board.game_state.zobrist_key);
     println!("{:<20}{}", "Active Color:", active_color);
     println!("{:<20}{}", "Castling:", castling);
@@ -172,6 +174,35 @@ pub fn castling_as_string(permissions: u8) -> String {
     castling_as_string
 }
 
+// Converts the time management statistics to a printable table. Phases
+// without any moves yet are shown as "-".
+pub fn time_stats_as_string(stats: &TimeStats) -> String {
+    let phases = [
+        (GamePhase::Opening, "Opening:"),
+        (GamePhase::EarlyMiddlegame, "Early middlegame:"),
+        (GamePhase::LateMiddlegame, "Late middlegame:"),
+        (GamePhase::Endgame, "Endgame:"),
+    ];
+    let success_rate = format!("{:.1}%", stats.success_rate() * 100.0);
+    let average = format!("{} ms", stats.average_time_per_move);
+
+    let mut s = String::new();
+    s += &format!("{:<20}{}\n", "Moves:", stats.total_moves);
+    s += &format!("{:<20}{}\n", "Success rate:", success_rate);
+    s += &format!("{:<20}{}\n", "Time losses:", stats.time_losses);
+    s += &format!("{:<20}{}\n", "Average per move:", average);
+
+    for (phase, name) in phases {
+        let time = match stats.time_usage_by_phase.get(&phase) {
+            Some(t) => format!("{t} ms"),
+            None => String::from("-"),
+        };
+        s += &format!("{name:<20}{time}\n");
+    }
+
+    s
+}
+
 // ===== Printing used for development purposes only =====
 
 // This prints a bitboard (64-bit number) to the screen in an 8x8 grid.
@@ -208,3 +239,26 @@ pub fn move_data(m: Move, nr: u8) {
         m.get_sort_score(),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_stats_lists_all_phases() {
+        let mut stats = TimeStats::new();
+        stats.update(1000, true, GamePhase::Opening);
+        stats.update(3000, true, GamePhase::LateMiddlegame);
+        stats.update(6000, false, GamePhase::LateMiddlegame);
+
+        let s = time_stats_as_string(&stats);
+
+        assert!(s.contains("Moves:              3"));
+        assert!(s.contains("Success rate:       66.7%"));
+        assert!(s.contains("Average per move:   3333 ms"));
+        assert!(s.contains("Opening:            500 ms"));
+        assert!(s.contains("Early middlegame:   -"));
+        assert!(s.contains("Late middlegame:    3750 ms"));
+        assert!(s.contains("Endgame:            -"));
+    }
+}
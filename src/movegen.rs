@@ -29,7 +29,7 @@ mod movelist;
 
 use crate::{
     board::{
-        defs::{Pieces, Squares, BB_RANKS, BB_SQUARES},
+        defs::{Files, Pieces, BB_RANKS, BB_SQUARES},
         Board,
     },
     defs::{Bitboard, Castling, NrOf, Piece, Side, Sides, Square, EMPTY},
@@ -182,6 +182,7 @@ impl MoveGenerator {
         let bb_opponent_pieces = board.bb_side[board.opponent()];
         let bb_empty = !board.occupancy();
         let bb_fourth = BB_RANKS[Board::fourth_rank(us)];
+        let bb_promotion = BB_RANKS[Board::promotion_rank(us)];
         let direction = if us == Sides::WHITE { UP } else { DOWN };
         let rotation_count = (NrOf::SQUARES as i8 + direction) as u32;
         let mut bb_pawns = board.get_pieces(Pieces::PAWN, us);
@@ -192,13 +193,16 @@ impl MoveGenerator {
             let to = (from as i8 + direction) as usize;
             let mut bb_moves = 0;
 
-            // Generate pawn pushes
-            if mt == MoveType::All || mt == MoveType::Quiet {
-                let bb_push = BB_SQUARES[to];
-                let bb_one_step = bb_push & bb_empty;
-                let bb_two_step = bb_one_step.rotate_left(rotation_count) & bb_empty & bb_fourth;
-                bb_moves |= bb_one_step | bb_two_step;
-            }
+            // Generate pawn pushes. Pushes to the promotion rank are
+            // generated with the captures, as quiescence search needs them.
+            let bb_push = BB_SQUARES[to];
+            let bb_one_step = bb_push & bb_empty;
+            let bb_two_step = bb_one_step.rotate_left(rotation_count) & bb_empty & bb_fourth;
+            bb_moves |= match mt {
+                MoveType::All => bb_one_step | bb_two_step,
+                MoveType::Quiet => (bb_one_step & !bb_promotion) | bb_two_step,
+                MoveType::Capture => bb_one_step & bb_promotion,
+            };
 
             // Generate pawn captures
             if mt == MoveType::All || mt == MoveType::Capture {
@@ -219,73 +223,67 @@ impl MoveGenerator {
         // Create shorthand variables.
         let us = board.us();
         let opponent = board.opponent();
-        let castle_perms_white = (board.game_state.castling & (Castling::WK | Castling::WQ)) > 0;
-        let castle_perms_black = (board.game_state.castling & (Castling::BK | Castling::BQ)) > 0;
+        let our_rights = if us == Sides::WHITE {
+            Castling::WK | Castling::WQ
+        } else {
+            Castling::BK | Castling::BQ
+        };
+
+        if board.game_state.castling & our_rights == 0 {
+            return;
+        }
+
         let bb_occupancy = board.occupancy();
-        let mut bb_king = board.get_pieces(Pieces::KING, us);
-        let from = bits::next(&mut bb_king);
-
-        // Generate castling moves for white.
-        if us == Sides::WHITE && castle_perms_white {
-            // Kingside
-            if board.game_state.castling & Castling::WK > 0 {
-                let bb_kingside_blockers = BB_SQUARES[Squares::F1] | BB_SQUARES[Squares::G1];
-                let is_kingside_blocked = (bb_occupancy & bb_kingside_blockers) > 0;
-
-                if !is_kingside_blocked
-                    && !self.square_attacked(board, opponent, Squares::E1)
-                    && !self.square_attacked(board, opponent, Squares::F1)
-                {
-                    let to = BB_SQUARES[from] << 2;
-                    self.add_move(board, Pieces::KING, from, to, list);
-                }
+        let bb_rooks = board.get_pieces(Pieces::ROOK, us);
+        let king_from = board.king_square(us);
+        let back_rank = king_from - (king_from % 8);
+
+        // The king and rook always end up on the same squares as in normal
+        // chess. In Chess960 they can start anywhere on the back rank, so
+        // the squares to check are taken from the king's and rook's actual
+        // start squares. This also covers normal chess.
+        for right in Castling::RIGHTS {
+            if board.game_state.castling & right & our_rights == 0 {
+                continue;
             }
 
-            if board.game_state.castling & Castling::WQ > 0 {
-                // Queenside
-                let bb_queenside_blockers =
-                    BB_SQUARES[Squares::B1] | BB_SQUARES[Squares::C1] | BB_SQUARES[Squares::D1];
-                let is_queenside_blocked = (bb_occupancy & bb_queenside_blockers) > 0;
-
-                if !is_queenside_blocked
-                    && !self.square_attacked(board, opponent, Squares::E1)
-                    && !self.square_attacked(board, opponent, Squares::D1)
-                {
-                    let to = BB_SQUARES[from] >> 2;
-                    self.add_move(board, Pieces::KING, from, to, list);
-                }
+            let is_kingside = right & (Castling::WK | Castling::BK) > 0;
+            let (king_to, rook_to) = if is_kingside {
+                (back_rank + Files::G, back_rank + Files::F)
+            } else {
+                (back_rank + Files::C, back_rank + Files::D)
+            };
+            let rook_from = board.castling_rook_square(right);
+
+            // The castling rook must actually be there.
+            if bb_rooks & BB_SQUARES[rook_from] == 0 {
+                continue;
             }
-        }
 
-        // Generate castling moves for black.
-        if us == Sides::BLACK && castle_perms_black {
-            // Kingside
-            if board.game_state.castling & Castling::BK > 0 {
-                let bb_kingside_blockers = BB_SQUARES[Squares::F8] | BB_SQUARES[Squares::G8];
-                let is_kingside_blocked = (bb_occupancy & bb_kingside_blockers) > 0;
-
-                if !is_kingside_blocked
-                    && !self.square_attacked(board, opponent, Squares::E8)
-                    && !self.square_attacked(board, opponent, Squares::F8)
-                {
-                    let to = BB_SQUARES[from] << 2;
-                    self.add_move(board, Pieces::KING, from, to, list);
-                }
+            // All squares the king and rook travel over must be empty,
+            // except for the castling king and rook themselves.
+            let bb_king_path = squares_from_to(king_from, king_to);
+            let bb_rook_path = squares_from_to(rook_from, rook_to);
+            let bb_blockers = bb_occupancy & !BB_SQUARES[king_from] & !BB_SQUARES[rook_from];
+            let is_blocked = (bb_king_path | bb_rook_path) & bb_blockers > 0;
+
+            // The king may not be in check, or pass through check.
+            let mut bb_king_squares = bb_king_path;
+            let mut is_attacked = false;
+            while bb_king_squares > 0 && !is_attacked {
+                let square = bits::next(&mut bb_king_squares);
+                is_attacked = self.square_attacked(board, opponent, square);
             }
 
-            // Queenside
-            if board.game_state.castling & Castling::BQ > 0 {
-                let bb_queenside_blockers =
-                    BB_SQUARES[Squares::B8] | BB_SQUARES[Squares::C8] | BB_SQUARES[Squares::D8];
-                let is_queenside_blocked = (bb_occupancy & bb_queenside_blockers) > 0;
-
-                if !is_queenside_blocked
-                    && !self.square_attacked(board, opponent, Squares::E8)
-                    && !self.square_attacked(board, opponent, Squares::D8)
-                {
-                    let to = BB_SQUARES[from] >> 2;
-                    self.add_move(board, Pieces::KING, from, to, list);
-                }
+            if !is_blocked && !is_attacked {
+                let move_data = Pieces::KING
+                    | king_from << Shift::FROM_SQ
+                    | king_to << Shift::TO_SQ
+                    | Pieces::NONE << Shift::CAPTURE
+                    | Pieces::NONE << Shift::PROMOTION
+                    | 1 << Shift::CASTLING;
+
+                list.push(Move::new(move_data));
             }
         }
     }
@@ -316,7 +314,6 @@ impl MoveGenerator {
             };
             let promotion = is_pawn && Board::square_on_rank(to_square, promotion_rank);
             let double_step = is_pawn && ((to_square as i8 - from as i8).abs() == 16);
-            let castling = (piece == Pieces::KING) && ((to_square as i8 - from as i8).abs() == 2);
 
             // Gather all data for this move into one 64-bit integer.
             let mut move_data = (piece)
@@ -324,8 +321,7 @@ impl MoveGenerator {
                 | to_square << Shift::TO_SQ
                 | capture << Shift::CAPTURE
                 | (en_passant as usize) << Shift::EN_PASSANT
-                | (double_step as usize) << Shift::DOUBLE_STEP
-                | (castling as usize) << Shift::CASTLING;
+                | (double_step as usize) << Shift::DOUBLE_STEP;
 
             // Push the move to the piece list...
             if !promotion {
@@ -372,3 +368,56 @@ impl MoveGenerator {
             || (bb_pawns & attackers[Pieces::PAWN] > 0)
     }
 }
+
+// Returns a bitboard with all the squares from one square up to and
+// including another square on the same rank.
+fn squares_from_to(a: Square, b: Square) -> Bitboard {
+    let (low, high) = (a.min(b), a.max(b));
+    ((1u64 << (high - low + 1)) - 1) << low
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_captures_are_exactly_captures_and_promotions() {
+        // Captures with promotion on a8, promotions on b8, en passant on
+        // d6, and a rook capture along the a-file.
+        let mg = MoveGenerator::new();
+        let mut board = Board::new();
+        board.fen_read(Some("r3k3/1P6/8/3pP3/8/2n5/8/R3K3 w Q d6 0 1")).unwrap();
+
+        let mut all = MoveList::new();
+        let mut captures = MoveList::new();
+        let mut quiet = MoveList::new();
+        mg.generate_moves(&board, &mut all, MoveType::All);
+        mg.generate_moves(&board, &mut captures, MoveType::Capture);
+        mg.generate_moves(&board, &mut quiet, MoveType::Quiet);
+
+        let strings = |ml: &MoveList| -> Vec<String> {
+            let mut moves: Vec<String> = (0..ml.len()).map(|i| ml.get_move(i).as_string()).collect();
+            moves.sort();
+            moves
+        };
+        let is_tactical =
+            |m: Move| m.captured() != Pieces::NONE || m.promoted() != Pieces::NONE || m.en_passant();
+
+        let mut expected: Vec<String> = (0..all.len())
+            .map(|i| all.get_move(i))
+            .filter(|&m| is_tactical(m))
+            .map(|m| m.as_string())
+            .collect();
+        expected.sort();
+
+        assert_eq!(strings(&captures), expected);
+        assert_eq!(captures.len(), 10);
+        assert!(expected.contains(&String::from("e5d6")));
+        assert!(expected.contains(&String::from("b7b8q")));
+        assert!(expected.contains(&String::from("b7a8n")));
+
+        // The quiet moves are the remaining ones.
+        assert_eq!(quiet.len() + captures.len(), all.len());
+        assert!((0..quiet.len()).all(|i| !is_tactical(quiet.get_move(i))));
+    }
+}
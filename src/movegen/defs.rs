@@ -89,6 +89,8 @@ impl Shift {
     pub const SORTSCORE: usize = 24;
 }
 
+// Quiet moves and captures together make up all moves. Promotions are
+// counted as captures, even when they don't capture anything.
 #[derive(Copy, Clone, PartialEq)]
 pub enum MoveType {
     Quiet,
@@ -151,6 +153,13 @@ impl Move {
         self.data = (self.data & !mask) | v;
     }
 
+    // Returns a copy of the move with a different to-square. This is used
+    // to send castling to Chess960 GUIs as the king taking its own rook.
+    pub fn with_to(self, to: Square) -> Self {
+        let mask: usize = 0x3F << Shift::TO_SQ;
+        Self::new((self.data & !mask) | (to << Shift::TO_SQ))
+    }
+
     pub fn as_string(&self) -> String {
         format!(
             "{}{}{}",
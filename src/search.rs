@@ -27,7 +27,10 @@ mod alpha_beta;
 pub mod defs;
 mod iter_deep;
 mod qsearch;
+mod see;
 mod sorting;
+#[cfg(test)]
+mod test_utils;
 mod time;
 mod utils;
 
@@ -35,12 +38,12 @@ use crate::{
     board::Board,
     engine::defs::{ErrFatal, Information},
     engine::defs::{SearchData, TT},
-    movegen::MoveGenerator,
+    movegen::{defs::Move, MoveGenerator},
 };
-use crossbeam_channel::Sender;
+use crossbeam_channel::{Receiver, Sender};
 use defs::{
     SearchControl, SearchInfo, SearchParams, SearchRefs, SearchReport, SearchSummary,
-    SearchTerminate, ThreadId, ThreadLocalData, TimeStats,
+    SearchTerminate, ThreadId, ThreadLocalData, ThreadResult, TimeStats, VOTE_SCORE_OFFSET,
 };
 use std::{
     sync::{Arc, Mutex, RwLock, atomic::{AtomicBool, Ordering}},
@@ -48,8 +51,18 @@ use std::{
     time::Instant,
 };
 
-// Thread-safe termination flag
-static SEARCH_TERMINATED: AtomicBool = AtomicBool::new(false);
+// Lazy SMP: everything the threads of one search manager share. All
+// threads search the same root and share the TT. The termination flag
+// stops all of them at once, and the helper threads send their results
+// to the main thread, which picks the best one and reports it.
+#[derive(Clone)]
+struct SharedSearch {
+    terminated: Arc<AtomicBool>,
+    result_tx: Sender<ThreadResult>,
+    result_rx: Receiver<ThreadResult>,
+    helpers: usize,
+    time_stats: Arc<Mutex<TimeStats>>,
+}
 
 pub struct Search {
     handle: Option<JoinHandle<()>>,
@@ -66,14 +79,13 @@ impl Search {
         }
     }
 
-    pub fn init(
+    fn init(
         &mut self,
         report_tx: Sender<Information>,
         board: Arc<Mutex<Board>>,
         mg: Arc<MoveGenerator>,
         tt: Arc<RwLock<TT<SearchData>>>,
-        tt_enabled: bool,
-        time_stats: Arc<Mutex<TimeStats>>,
+        shared: SharedSearch,
     ) {
         // Set up a channel for incoming commands
         let (control_tx, control_rx) = crossbeam_channel::unbounded::<SearchControl>();
@@ -88,11 +100,12 @@ impl Search {
             let arc_board = Arc::clone(&board);
             let arc_mg = Arc::clone(&mg);
             let arc_tt = Arc::clone(&tt);
-            let arc_time_stats = Arc::clone(&time_stats);
+            let arc_time_stats = Arc::clone(&shared.time_stats);
             let mut search_params = SearchParams::new();
 
             // Create thread-local data structures
             let mut thread_local_data = ThreadLocalData::new(thread_id);
+            thread_local_data.terminated = Arc::clone(&shared.terminated);
             let mut quit = false;
             let mut halt = true;
 
@@ -104,17 +117,24 @@ impl Search {
                 // And react accordingly
                 match cmd {
                     SearchControl::Start(sp) => {
-                        search_params = sp;
+                        search_params = *sp;
+                        // Helper threads don't report to the GUI.
+                        search_params.quiet |= !thread_local_data.is_main_thread();
                         halt = false; // This will start the search
-                        SEARCH_TERMINATED.store(false, Ordering::Relaxed);
                     }
                     SearchControl::Stop => {
                         halt = true;
-                        SEARCH_TERMINATED.store(true, Ordering::Relaxed);
                     }
                     SearchControl::Quit => {
                         quit = true;
-                        SEARCH_TERMINATED.store(true, Ordering::Relaxed);
+                    }
+                    SearchControl::ReportTimeStats => {
+                        // All threads share the statistics; one report is enough.
+                        if thread_id == 0 {
+                            let stats = arc_time_stats.lock().expect(ErrFatal::LOCK).clone();
+                            let report = Information::Search(SearchReport::TimeStats(stats));
+                            t_report_tx.send(report).expect(ErrFatal::CHANNEL);
+                        }
                     }
                     SearchControl::Nothing => (),
                 }
@@ -139,7 +159,7 @@ impl Search {
                         board: &mut board,
                         mg: &arc_mg,
                         tt: &arc_tt,
-                        tt_enabled,
+                        tt_enabled: true,
                         search_params: &mut search_params,
                         search_info: &mut search_info,
                         control_rx: &control_rx,
@@ -149,27 +169,44 @@ impl Search {
 
                     // Start the search using Iterative Deepening
                     let (best_move, terminate) = Search::iterative_deepening(&mut search_refs);
-
-                    // Update the persistent time statistics
-                    let mut time_stats_guard = arc_time_stats.lock().expect(ErrFatal::LOCK);
-                    *time_stats_guard = search_info.time_stats.clone();
-                    std::mem::drop(time_stats_guard);
-
-                    // Inform the engine that the search has finished
-                    let information = Information::Search(SearchReport::Finished(best_move));
-                    t_report_tx.send(information).expect(ErrFatal::CHANNEL);
-
-                    // If the search was finished due to a Stop or Quit
-                    // command then either halt or quit the search
-                    match terminate {
-                        SearchTerminate::Stop => {
-                            halt = true;
-                        }
-                        SearchTerminate::Quit => {
-                            halt = true;
-                            quit = true;
+                    let tt_enabled = search_refs.tt_enabled;
+                    let result = (
+                        best_move,
+                        thread_local_data.completed_depth,
+                        thread_local_data.best_score,
+                    );
+
+                    if thread_local_data.is_main_thread() {
+                        // Stop the helpers and let all threads vote.
+                        shared.terminated.store(true, Ordering::Relaxed);
+                        let mut results = vec![result];
+                        for _ in 0..shared.helpers {
+                            results.push(shared.result_rx.recv().expect(ErrFatal::CHANNEL));
                         }
-                        SearchTerminate::Nothing => (),
+                        let best_move = SearchManager::vote_best_move(&results);
+                        let ponder_move = if tt_enabled {
+                            Search::ponder_move(&mut board, &arc_mg, &arc_tt, best_move)
+                        } else {
+                            None
+                        };
+
+                        // Update the persistent time statistics
+                        let mut time_stats_guard = arc_time_stats.lock().expect(ErrFatal::LOCK);
+                        *time_stats_guard = search_info.time_stats.clone();
+                        std::mem::drop(time_stats_guard);
+
+                        // Inform the engine that the search has finished
+                        let information = Information::Search(SearchReport::Finished(best_move, ponder_move));
+                        t_report_tx.send(information).expect(ErrFatal::CHANNEL);
+                    } else {
+                        shared.result_tx.send(result).expect(ErrFatal::CHANNEL);
+                    }
+
+                    // The search is done; wait for the next Start command.
+                    // If it was finished due to a Quit command, quit.
+                    halt = true;
+                    if terminate == SearchTerminate::Quit {
+                        quit = true;
                     }
                 }
             }
@@ -199,6 +236,7 @@ impl Search {
 pub struct SearchManager {
     workers: Vec<Search>,
     thread_count: usize,
+    terminated: Arc<AtomicBool>,
     search_start_time: Option<Instant>,
     time_stats: TimeStats,
 }
@@ -213,6 +251,7 @@ impl SearchManager {
         Self { 
             workers,
             thread_count: threads,
+            terminated: Arc::new(AtomicBool::new(false)),
             search_start_time: None,
             time_stats: TimeStats::new(),
         }
@@ -224,17 +263,22 @@ impl SearchManager {
         board: Arc<Mutex<Board>>,
         mg: Arc<MoveGenerator>,
         tt: Arc<RwLock<TT<SearchData>>>,
-        tt_enabled: bool,
     ) {
-        let time_stats = Arc::new(Mutex::new(self.time_stats.clone()));
+        let (result_tx, result_rx) = crossbeam_channel::unbounded::<ThreadResult>();
+        let shared = SharedSearch {
+            terminated: Arc::clone(&self.terminated),
+            result_tx,
+            result_rx,
+            helpers: self.thread_count.saturating_sub(1),
+            time_stats: Arc::new(Mutex::new(self.time_stats.clone())),
+        };
         for w in self.workers.iter_mut() {
             w.init(
                 report_tx.clone(),
                 Arc::clone(&board),
                 Arc::clone(&mg),
                 Arc::clone(&tt),
-                tt_enabled,
-                Arc::clone(&time_stats),
+                shared.clone(),
             );
         }
     }
@@ -254,15 +298,15 @@ impl SearchManager {
 
     pub fn start_search(&mut self) {
         self.search_start_time = Some(Instant::now());
-        SEARCH_TERMINATED.store(false, Ordering::Relaxed);
+        self.terminated.store(false, Ordering::Relaxed);
     }
 
     pub fn stop_search(&self) {
-        SEARCH_TERMINATED.store(true, Ordering::Relaxed);
+        self.terminated.store(true, Ordering::Relaxed);
     }
 
     pub fn is_terminated(&self) -> bool {
-        SEARCH_TERMINATED.load(Ordering::Relaxed)
+        self.terminated.load(Ordering::Relaxed)
     }
 
     pub fn thread_count(&self) -> usize {
@@ -276,6 +320,33 @@ impl SearchManager {
     pub fn update_time_stats(&mut self, new_stats: TimeStats) {
         self.time_stats = new_stats;
     }
+
+    // Picks the best move from the results of all search threads. Each
+    // thread votes for its best move, weighted by its completed depth and
+    // by how much its score exceeds the lowest score. A deep consensus
+    // outvotes a single thread that liked a move at a shallow depth. On
+    // a tie, the earliest result (the main thread) wins.
+    pub fn vote_best_move(results: &[ThreadResult]) -> Move {
+        let valid = || results.iter().filter(|r| r.0.get_move() != 0);
+        let min_score = valid().map(|r| r.2 as i64).min().unwrap_or(0);
+        let mut votes: Vec<(Move, i64)> = Vec::new();
+
+        for &(mv, depth, score) in valid() {
+            let weight = (score as i64 - min_score + VOTE_SCORE_OFFSET) * depth.max(1) as i64;
+            match votes.iter_mut().find(|v| v.0.get_move() == mv.get_move()) {
+                Some(vote) => vote.1 += weight,
+                None => votes.push((mv, weight)),
+            }
+        }
+
+        votes
+            .iter()
+            .fold(None, |best: Option<(Move, i64)>, &v| match best {
+                Some(b) if b.1 >= v.1 => Some(b),
+                _ => Some(v),
+            })
+            .map_or(Move::new(0), |(mv, _)| mv)
+    }
 }
 
 #[cfg(test)]
@@ -283,9 +354,17 @@ mod tests {
     use super::*;
     use crate::{
         board::Board,
+        comm::uci::Uci,
+        defs::FEN_START_POSITION,
         engine::defs::{SearchData, TT},
-        movegen::MoveGenerator,
-        search::defs::{SearchControl, SearchInfo, SearchParams, SearchRefs, ThreadLocalData},
+        movegen::{
+            defs::{MoveList, MoveType},
+            MoveGenerator,
+        },
+        search::{
+            defs::{SearchControl, SearchMode, SearchParams, ThreadLocalData, CHECK_TERMINATION},
+            test_utils::TestSearch,
+        },
     };
     use crossbeam_channel::unbounded;
     use std::sync::{Arc, RwLock};
@@ -347,12 +426,11 @@ mod tests {
             Arc::clone(&board),
             Arc::clone(&mg),
             Arc::clone(&tt),
-            true,
         );
 
         // Test that we can send commands to all threads
         let search_params = SearchParams::new();
-        manager.send(SearchControl::Start(search_params));
+        manager.send(SearchControl::Start(Box::new(search_params)));
         
         // Test that we can stop all threads
         manager.send(SearchControl::Stop);
@@ -370,7 +448,7 @@ mod tests {
         
         // Add some test data to the batch
         let test_key = 0x1234567890ABCDEF;
-        let test_data = SearchData::create(5, 0, crate::engine::defs::HashFlag::Exact, 100, crate::movegen::defs::ShortMove::new(0));
+        let test_data = SearchData::create(5, 0, crate::engine::defs::HashFlag::Exact, 100, 0, crate::movegen::defs::ShortMove::new(0));
         
         tld.tt_batch.add(test_key, test_data);
         assert_eq!(tld.tt_batch.len(), 1);
@@ -389,29 +467,314 @@ mod tests {
 
     #[test]
     fn test_search_refs_with_thread_local_data() {
+        let mut test = TestSearch::new(FEN_START_POSITION);
+        let refs = test.refs();
+
+        // Test that the refs structure is properly constructed
+        assert_eq!(refs.thread_local_data.thread_id, 0);
+        assert_eq!(refs.tt_enabled, true);
+    }
+
+    #[test]
+    fn test_search_without_tt() {
+        // With "Hash 0", the search works without the TT, even if it was
+        // told to use one.
+        let mut test = TestSearch::to_depth("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1", 6).with_tt(0);
+        let mut refs = test.refs();
+
+        let (best_move, _) = Search::iterative_deepening(&mut refs);
+        assert_eq!(best_move.as_string(), "d1d5");
+        assert_eq!(refs.thread_local_data.completed_depth, 6);
+        assert!(!refs.tt_enabled);
+        assert_eq!(refs.thread_local_data.tt_batch.len(), 0);
+        assert!(!test.tt.read().unwrap().is_enabled());
+        assert_eq!(test.tt.read().unwrap().hashfull_permille(), 0);
+    }
+
+    #[test]
+    fn test_mate_distance_pruning_mate_in_two() {
+        // 1. Rb7 Kg8 2. Ra8#
+        let fen = "7k/8/8/8/8/8/R7/1R4K1 w - - 0 1";
+        let mut nodes = [0usize; 2];
+
+        for (i, mate_distance_pruning) in [false, true].into_iter().enumerate() {
+            let mut test = TestSearch::to_depth(fen, 7);
+            test.search_params.mate_distance_pruning = mate_distance_pruning;
+
+            Search::iterative_deepening(&mut test.refs());
+            nodes[i] = test.search_info.nodes;
+
+            let mate = test.summaries().last().map(|s| s.mate);
+            assert_eq!(mate, Some(2));
+        }
+
+        assert!(nodes[1] < nodes[0], "mate distance pruning should search fewer nodes");
+    }
+
+    #[test]
+    fn test_multi_pv_reports_distinct_lines() {
+        let mut test = TestSearch::to_depth(FEN_START_POSITION, 4);
+        test.search_params.multi_pv = 3;
+
+        let (best_move, _) = Search::iterative_deepening(&mut test.refs());
+
+        let lines: Vec<SearchSummary> =
+            test.summaries().into_iter().filter(|s| s.depth == 4).collect();
+
+        assert_eq!(lines.iter().map(|s| s.multipv).collect::<Vec<usize>>(), vec![1, 2, 3]);
+        assert!(lines[0].pv[0] == best_move);
+        assert!(lines[0].cp >= lines[1].cp && lines[1].cp >= lines[2].cp);
+
+        let mut first_moves: Vec<String> = lines.iter().map(|s| s.pv[0].as_string()).collect();
+        first_moves.sort();
+        first_moves.dedup();
+        assert_eq!(first_moves.len(), 3);
+        assert_eq!(lines[0].pv_as_string().split(' ').count(), lines[0].pv.len());
+    }
+
+    #[test]
+    fn test_search_moves_restricts_root() {
+        let mut test = TestSearch::to_depth(FEN_START_POSITION, 4);
+
+        // Restrict the search to 1. a3, which is not the best move.
+        let mut move_list = MoveList::new();
+        test.mg.generate_moves(&test.board, &mut move_list, MoveType::All);
+        let a3 = (0..move_list.len())
+            .map(|i| move_list.get_move(i))
+            .find(|m| m.as_string() == "a2a3")
+            .unwrap();
+        test.search_params.search_moves = vec![a3];
+
+        let mut refs = test.refs();
+        let (best_move, _) = Search::iterative_deepening(&mut refs);
+
+        assert!(best_move.get_move() == a3.get_move());
+        assert_eq!(refs.search_info.root_analysis.len(), 1);
+        assert!(refs.search_info.root_analysis[0].mv.get_move() == a3.get_move());
+    }
+    // Plays the knights out and back, so that 1. Nf3 now repeats the
+    // position after the first move, and returns the best move found.
+    // The search runs to an odd depth, so Black has the last reply and
+    // White's best new move scores less than a pawn: between the draw
+    // scores under contempt 100 and -100. At even depths it scores about
+    // a pawn and a half, above both.
+    fn best_move_after_knight_shuffle(contempt: i16) -> String {
+        let mut test = TestSearch::to_depth(FEN_START_POSITION, 5);
+        for m in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+            let mut move_list = MoveList::new();
+            test.mg.generate_moves(&test.board, &mut move_list, MoveType::All);
+            let mv = (0..move_list.len())
+                .map(|i| move_list.get_move(i))
+                .find(|x| x.as_string() == m)
+                .unwrap();
+            assert!(test.board.make(mv, &test.mg));
+        }
+        test.search_params.contempt = contempt;
+
+        let (best_move, _) = Search::iterative_deepening(&mut test.refs());
+        best_move.as_string()
+    }
+
+    #[test]
+    fn test_contempt_avoids_repetition() {
+        assert_ne!(best_move_after_knight_shuffle(100), "g1f3");
+        assert_eq!(best_move_after_knight_shuffle(-100), "g1f3");
+    }
+
+    // Sets up a search of the position on a one minute clock.
+    fn game_time_search(fen: &str) -> TestSearch {
+        let mut test = TestSearch::new(fen);
+        test.search_params.search_mode = SearchMode::GameTime;
+        test.search_params.game_time.wtime = 60_000;
+        test.search_params.game_time.btime = 60_000;
+        test.search_params.quiet = true;
+        test
+    }
+
+    #[test]
+    fn test_nodes_time_stops_at_node_budget() {
+        let mut test = game_time_search(FEN_START_POSITION);
+        test.search_params.use_nodes_time = true;
+        test.search_info.time_stats.average_nps = 50_000;
+        // When analysing, a stable best move doesn't stop the search
+        // early, so it runs into the budget in any position.
+        test.search_params.analyse_mode = true;
+
+        let mut refs = test.refs();
+        Search::iterative_deepening(&mut refs);
+
+        // The budget follows from the allocated time at the recorded speed.
+        // Termination is only checked every CHECK_TERMINATION nodes, so the
+        // search may overshoot by about that much before it unwinds.
+        let budget = refs.search_info.node_budget.expect("No node budget");
+        let expected = (refs.search_info.allocated_time * 50_000 / 1000) as usize;
+        let nodes = refs.search_info.nodes;
+        assert_eq!(budget, expected);
+        assert!(nodes >= budget, "{nodes} nodes, budget {budget}");
+        assert!(nodes <= budget + 2 * (CHECK_TERMINATION + 1), "{nodes} nodes, budget {budget}");
+    }
+
+    #[test]
+    fn test_single_legal_move_plays_instantly() {
+        // Black just took on f1 with check; Rxf1 is the only legal reply.
+        let mut test = game_time_search("k7/8/8/8/8/8/6PP/3R1r1K w - - 0 1");
+        let mut refs = test.refs();
+
+        let (best_move, _) = Search::iterative_deepening(&mut refs);
+
+        assert_eq!(best_move.as_string(), "d1f1");
+        assert_eq!(refs.search_info.depth, 1);
+        // One shallow search of the only move. Going on to depth 2 takes
+        // more than a hundred nodes.
+        assert!(refs.search_info.nodes < 100);
+        assert_eq!(refs.search_info.root_analysis.len(), 1);
+        assert_eq!(test.summaries().len(), 1);
+    }
+
+    // Searches the position on a one minute clock with node-based time, so
+    // the result doesn't depend on the speed of the machine. Returns the
+    // nodes searched and the node budget.
+    fn nodes_time_search(fen: &str) -> (usize, usize) {
+        let mut test = game_time_search(fen);
+        test.search_params.use_nodes_time = true;
+        test.search_info.time_stats.average_nps = 50_000;
+
+        Search::iterative_deepening(&mut test.refs());
+        let budget = test.search_info.node_budget.expect("No node budget");
+
+        (test.search_info.nodes, budget)
+    }
+
+    #[test]
+    fn test_stable_best_move_terminates_early() {
+        // Black's queen hangs to the c3 pawn.
+        let obvious = "rnb1kbnr/pppp1ppp/8/4p3/3q4/2P5/PP1PPPPP/RNBQKBNR w KQkq - 0 3";
+        let sharp = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+        let (obvious_nodes, obvious_budget) = nodes_time_search(obvious);
+        let (sharp_nodes, sharp_budget) = nodes_time_search(sharp);
+
+        assert!(obvious_nodes < obvious_budget);
+        assert!(obvious_nodes * sharp_budget < sharp_nodes * obvious_budget);
+    }
+
+    #[test]
+    fn test_vote_outvotes_shallow_blunder() {
+        let mut board = Board::new();
+        board.fen_read(None).unwrap();
+        let mg = MoveGenerator::new();
+        let mut move_list = MoveList::new();
+        mg.generate_moves(&board, &mut move_list, MoveType::All);
+        let find = |s: &str| {
+            (0..move_list.len())
+                .map(|i| move_list.get_move(i))
+                .find(|m| m.as_string() == s)
+                .unwrap()
+        };
+        let (e4, g4) = (find("e2e4"), find("g2g4"));
+
+        // Three threads agree on e4 at depth 12; one thread still likes
+        // g4 at depth 6, with a better looking score.
+        let results = [(e4, 12, 35), (g4, 6, 60), (e4, 12, 32), (e4, 11, 30)];
+        assert_eq!(SearchManager::vote_best_move(&results).as_string(), "e2e4");
+
+        // Null moves don't vote; without any results there is no move.
+        let results = [(Move::new(0), 20, 500), (g4, 6, 60)];
+        assert_eq!(SearchManager::vote_best_move(&results).as_string(), "g2g4");
+        assert_eq!(SearchManager::vote_best_move(&[]).get_move(), 0);
+    }
+
+    // Runs a search with the given number of threads through the search
+    // manager, as the engine does. Returns the reported best move and ponder
+    // move.
+    fn smp_search(fen: &str, threads: usize, depth: i8) -> (Move, Option<Move>) {
+        let mut manager = SearchManager::new(threads);
+        let (info_tx, info_rx) = unbounded::<Information>();
         let mut board = Board::new();
+        board.fen_read(Some(fen)).unwrap();
+        let board = Arc::new(Mutex::new(board));
         let mg = Arc::new(MoveGenerator::new());
-        let tt: Arc<RwLock<TT<SearchData>>> = Arc::new(RwLock::new(TT::new(32)));
-        let (_control_tx, control_rx) = unbounded::<SearchControl>();
-        let (report_tx, _report_rx) = unbounded::<Information>();
+        let tt = Arc::new(RwLock::new(TT::<SearchData>::new(32)));
+
+        manager.init(info_tx, board, mg, tt);
+
         let mut search_params = SearchParams::new();
-        let mut search_info = SearchInfo::new();
-        let mut thread_local_data = ThreadLocalData::new(0);
-
-        let refs = SearchRefs {
-            board: &mut board,
-            mg: &mg,
-            tt: &tt,
-            tt_enabled: true,
-            search_params: &mut search_params,
-            search_info: &mut search_info,
-            control_rx: &control_rx,
-            report_tx: &report_tx,
-            thread_local_data: &mut thread_local_data,
+        search_params.search_mode = crate::search::defs::SearchMode::Depth;
+        search_params.depth = depth;
+        manager.start_search();
+        manager.send(SearchControl::Start(Box::new(search_params)));
+
+        let (best_move, ponder_move) = loop {
+            if let Information::Search(SearchReport::Finished(m, ponder)) = info_rx.recv().unwrap() {
+                break (m, ponder);
+            }
         };
 
-        // Test that the refs structure is properly constructed
-        assert_eq!(refs.thread_local_data.thread_id, 0);
-        assert_eq!(refs.tt_enabled, true);
+        manager.send(SearchControl::Quit);
+        manager.wait_for_shutdown();
+
+        (best_move, ponder_move)
+    }
+
+    #[test]
+    fn test_bestmove_includes_ponder_move() {
+        // After Nc7+ the king has to move, so the PV is longer than one move.
+        let fen = "r3k3/pp6/8/1N6/8/8/PP6/4K3 w - - 0 1";
+        let (best_move, ponder_move) = smp_search(fen, 1, 5);
+
+        let ponder_move = ponder_move.expect("a ponder move");
+        let line = Uci::best_move_line(&best_move, Some(ponder_move));
+        assert_eq!(line, format!("bestmove b5c7 ponder {}", ponder_move.as_string()));
+
+        // The ponder move is a legal reply to the best move.
+        let mg = MoveGenerator::new();
+        let mut board = Board::new();
+        board.fen_read(Some(fen)).unwrap();
+        assert!(board.make(best_move, &mg));
+        assert!(board.make(ponder_move, &mg));
+
+        // Without a reply, only the best move is sent.
+        assert_eq!(Uci::best_move_line(&best_move, None), "bestmove b5c7");
+    }
+
+    #[test]
+    fn test_lazy_smp_finds_same_move() {
+        // The knight forks king and rook on c7.
+        let fen = "r3k3/pp6/8/1N6/8/8/PP6/4K3 w - - 0 1";
+
+        let (single_move, _) = smp_search(fen, 1, 7);
+        let (smp_move, _) = smp_search(fen, 4, 7);
+
+        assert_eq!(single_move.as_string(), "b5c7");
+        assert_eq!(smp_move.as_string(), single_move.as_string());
+    }
+
+    // Searches the position to the given depth as the thread with the
+    // given ID, on the given TT, and returns the number of nodes.
+    fn thread_search(fen: &str, thread_id: ThreadId, depth: i8, tt: &Arc<RwLock<TT<SearchData>>>) -> usize {
+        let mut test = TestSearch::to_depth(fen, depth);
+        test.tt = Arc::clone(tt);
+        test.thread_local_data = ThreadLocalData::new(thread_id);
+
+        let mut refs = test.refs();
+        Search::iterative_deepening(&mut refs);
+        refs.search_info.nodes
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_helper_thread_entries_save_main_thread_nodes() {
+        let fen = "r1b2rk1/2q1bppp/p2ppn2/1p6/3BPP2/2N2B2/PPPQ2PP/R4RK1 w - - 0 13";
+
+        // The main thread on its own.
+        let alone = thread_search(fen, 0, 6, &Arc::new(RwLock::new(TT::new(32))));
+
+        // A helper thread runs first, and publishes its entries to the
+        // shared TT, which the main thread then searches with. Running
+        // the threads one after the other keeps the node counts exact.
+        let shared_tt = Arc::new(RwLock::new(TT::new(32)));
+        thread_search(fen, 1, 6, &shared_tt);
+        let helped = thread_search(fen, 0, 6, &shared_tt);
+
+        assert!(helped < alone, "{helped} nodes with the helper's entries, {alone} without");
+    }
+}
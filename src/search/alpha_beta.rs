@@ -24,20 +24,22 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 //! Alpha-beta search implementation with modern chess engine optimisations.
 //! 
 //! This module implements the core search algorithm using alpha-beta pruning
-//! enhanced with Late Move Reduction (LMR), null move pruning, multicut pruning,
-//! transposition tables, and repetition detection.
+//! enhanced with Late Move Reduction (LMR), (reverse) futility pruning, razoring,
+//! mate distance pruning, null move pruning, multicut pruning, singular
+//! extensions, transposition tables, and repetition detection.
 
 use super::{
     defs::{
-        RootMoveAnalysis, SearchTerminate, CHECKMATE, CHECK_TERMINATION,
-        INF, SEND_STATS, STALEMATE, NULL_MOVE_REDUCTION,
+        RootMoveAnalysis, SearchTerminate, SharpSequence, CHECKMATE, CHECKMATE_THRESHOLD, CHECK_TERMINATION,
+        INF, SEND_STATS, STALEMATE, RFP_MAX_DEPTH, LMP_MAX_DEPTH, late_move_count,
         MULTICUT_DEPTH, MULTICUT_REDUCTION, MULTICUT_CUTOFFS, MULTICUT_MOVES,
-        LMR_REDUCTION, LMR_MOVE_THRESHOLD, LMR_LATE_THRESHOLD, LMR_LATE_REDUCTION, LMR_MIN_DEPTH,
+        SINGULAR_MIN_DEPTH, MAX_LINE_EXTENSIONS, RECAPTURE_EXTENSION,
     },
     Search, SearchRefs,
 };
 use crate::{
-    defs::MAX_PLY,
+    board::defs::Pieces,
+    defs::{MAX_MOVE_RULE, MAX_PLY},
     engine::defs::{ErrFatal, HashFlag, SearchData},
     evaluation,
     movegen::defs::{Move, MoveList, MoveType, ShortMove},
@@ -48,8 +50,15 @@ impl Search {
     /// 
     /// Implements minimax with alpha-beta pruning, enhanced with:
     /// - Late Move Reduction (LMR) for efficient deep search
+    /// - Mate distance pruning to stop searching for longer mates
+    /// - Reverse futility pruning (static null move) near the horizon
+    /// - Futility pruning of hopeless quiet moves at depth 1 and 2
+    /// - Razoring into quiescence search at depth 1
+    /// - Late move pruning of quiet moves at low depth
     /// - Null move pruning to eliminate hopeless branches
     /// - Multicut pruning for tactical positions
+    /// - Singular extension of a TT move that is clearly better than the rest
+    /// - Internal iterative deepening at PV nodes without a TT move
     /// - Transposition table caching with thread-local optimisation
     /// - Repetition detection with positional penalty system
     /// 
@@ -65,13 +74,24 @@ impl Search {
     pub fn alpha_beta(
         mut depth: i8,
         mut alpha: i16,
-        beta: i16,
+        mut beta: i16,
         pv: &mut Vec<Move>,
         refs: &mut SearchRefs,
     ) -> i16 {
         let quiet = refs.search_params.quiet;
         let is_root = refs.search_info.ply == 0;
 
+        // A move is only excluded here during a singular extension
+        // verification search. Such a search must not be cut short by, or
+        // stored in, the TT entry of the full node.
+        let excluded_move = refs.search_info.excluded_moves[refs.search_info.ply as usize];
+        let is_singular_search = excluded_move.get_move() != 0;
+
+        // When searching a further MultiPV line, the root moves heading the
+        // better lines are skipped. The result is not the real best move, so
+        // it must not replace the best move, root analysis or TT entry.
+        let is_extra_pv_line = is_root && !refs.search_info.root_excluded.is_empty();
+
         // Update thread-local node count for search statistics
         refs.thread_local_data.increment_nodes();
 
@@ -89,49 +109,77 @@ impl Search {
             return evaluation::evaluate_position(refs.board, refs.mg);
         }
 
-        // Check extension: search deeper when in check for tactical accuracy
+        // Mate distance pruning: no line from here can be better than being
+        // mated right now or mating on the next move, so narrow the window.
+        // If it closes, a shorter mate has already been found elsewhere.
+        if !is_root && refs.search_params.mate_distance_pruning {
+            let ply = refs.search_info.ply as i16;
+            alpha = alpha.max(-CHECKMATE + ply);
+            beta = beta.min(CHECKMATE - ply - 1);
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
+        // Check extension: search deeper when in check for tactical accuracy,
+        // until the line has used up its check extensions.
         let is_check = refs.mg.square_attacked(
             refs.board,
             refs.board.opponent(),
             refs.board.king_square(refs.board.us()),
         );
 
-        if is_check {
+        let ply = refs.search_info.ply as usize;
+        let line_checks = if is_root { 0 } else { refs.search_info.check_extensions[ply - 1] };
+        let extend_check = is_check && line_checks < refs.search_params.max_check_extensions;
+        if extend_check {
             depth += 1;
         }
+        refs.search_info.check_extensions[ply] = line_checks + extend_check as i8;
+
+        // Fifty-move rule: after 100 plies without a capture or pawn move,
+        // the game is drawn, unless the move reaching the limit mated.
+        if !is_root && refs.board.game_state.halfmove_clock >= MAX_MOVE_RULE {
+            if is_check && !Search::has_legal_move(refs) {
+                return -CHECKMATE + refs.search_info.ply as i16;
+            }
+            return Search::draw_score(refs);
+        }
+
+        // Neither side can checkmate: this is a dead draw. There is no need
+        // to generate moves, or to drop into quiescence search.
+        if !is_root && refs.board.is_insufficient_material() {
+            return Search::draw_score(refs);
+        }
 
         // Switch to quiescence search when depth exhausted
         if depth <= 0 {
-            return Search::quiescence(alpha, beta, pv, refs);
+            return Search::quiescence(alpha, beta, 0, pv, refs);
         }
 
         refs.search_info.nodes += 1;
+        refs.search_info.seldepth = refs.search_info.seldepth.max(refs.search_info.ply);
 
         // Repetition detection with graduated penalty system
         // Penalise repetitions more heavily when winning to avoid draws
         if !is_root {
             let repetition_count = Search::is_repetition(refs.board);
             if repetition_count > 0 {
+                let policy = refs.search_params.repetition;
+                if policy.is_disabled() || refs.search_params.analyse_mode {
+                    return Search::draw_score(refs);
+                }
+
                 // Evaluate current position to determine advantage level
                 let current_eval = evaluation::evaluate_position(refs.board, refs.mg);
-                
-                // Apply graduated penalty based on our advantage
-                let penalty = match current_eval {
-                    eval if eval >= 300 => -150,   // Significant advantage: large penalty
-                    eval if eval >= 150 => -75,    // Good advantage: medium penalty  
-                    eval if eval >= 50 => -25,     // Small advantage: small penalty
-                    eval if eval <= -150 => 0,     // Losing: no penalty (allow repetition)
-                    _ => -10,                       // Roughly equal: tiny penalty
-                };
-                
-                // Scale penalty for multiple repetitions
-                let final_penalty = penalty * (repetition_count as i16 + 1);
-                return final_penalty;
+                let penalty = policy.penalty(current_eval, repetition_count);
+                return penalty + Search::draw_score(refs);
             }
         }
 
         let mut tt_value: Option<i16> = None;
         let mut tt_move: ShortMove = ShortMove::new(0);
+        let mut tt_data: Option<SearchData> = None;
 
         // Transposition table lookup with thread-local caching optimisation
         // Check local cache first to reduce contention on global TT
@@ -140,6 +188,7 @@ impl Search {
                 let tt_result = data.get(depth, refs.search_info.ply, alpha, beta);
                 tt_value = tt_result.0;
                 tt_move = tt_result.1;
+                tt_data = Some(*data);
             } else {
                 // Fall back to global TT if not cached locally
                 if let Some(data) = refs
@@ -151,6 +200,7 @@ impl Search {
                     let tt_result = data.get(depth, refs.search_info.ply, alpha, beta);
                     tt_value = tt_result.0;
                     tt_move = tt_result.1;
+                    tt_data = Some(*data);
                     
                     // Cache result locally for future access
                     refs.thread_local_data.local_tt_cache.insert(
@@ -163,23 +213,85 @@ impl Search {
 
         // Return cached evaluation if available and not at root
         if let Some(v) = tt_value {
-            if !is_root {
+            if !is_root && !is_singular_search {
                 return v;
             }
         }
 
+        // Static evaluation of this node, shared by the static pruning
+        // techniques below. Meaningless when in check, so it is not computed.
+        // A TT hit already has it, so the position isn't evaluated again.
+        let static_eval = if is_check {
+            -INF
+        } else if let Some(data) = tt_data {
+            data.static_eval()
+        } else {
+            evaluation::evaluate_position(refs.board, refs.mg)
+        };
+
+        // Improving: the static evaluation is better than it was the last
+        // time this side was to move. Pruning is stricter when it isn't.
+        let improving = refs
+            .search_info
+            .update_improving(refs.search_info.ply, static_eval);
+        let tunables = refs.search_params.tunables;
+        let futility_margin = tunables.futility_margin * depth.clamp(0, 2) as i16
+            - if improving { 0 } else { tunables.futility_not_improving };
+
+        // Reverse futility pruning (static null move): if the static
+        // evaluation beats beta by a depth-dependent margin, a real search
+        // is unlikely to fall below beta. Never used near mate scores.
+        if !is_root
+            && !is_check
+            && tt_value.is_none()
+            && depth <= RFP_MAX_DEPTH
+            && beta.abs() < CHECKMATE_THRESHOLD
+            && static_eval - tunables.rfp_margin * depth as i16 >= beta
+        {
+            return static_eval;
+        }
+
+        // Razoring: at depth 1, a static evaluation far below alpha is
+        // verified by quiescence search. If even the captures can't reach
+        // alpha, the node fails low and is stored as an upper bound.
+        if !is_root
+            && !is_check
+            && !is_singular_search
+            && depth == 1
+            && alpha.abs() < CHECKMATE_THRESHOLD
+            && static_eval + tunables.razor_margin < alpha
+        {
+            let mut tmp_pv: Vec<Move> = Vec::new();
+            let score = Search::quiescence(alpha, beta, 0, &mut tmp_pv, refs);
+            if score <= alpha {
+                Search::store_tt_entry(
+                    depth,
+                    HashFlag::Alpha,
+                    score,
+                    static_eval,
+                    ShortMove::new(0),
+                    refs,
+                );
+                return score;
+            }
+        }
+
         // Null move pruning: assume opponent's best move isn't good enough
-        // Skip in check, at root, or with insufficient material
+        // Skip in check, at root, with insufficient material, or when beta
+        // is a mate score: a null move can't prove a mate.
         if !is_root
-            && depth > NULL_MOVE_REDUCTION
+            && !is_singular_search
+            && depth > tunables.null_move_reduction
             && !is_check
+            && beta.abs() < CHECKMATE_THRESHOLD
             && !Search::is_insufficient_material(refs)
         {
             refs.board.make_null_move();
             refs.search_info.ply += 1;
             let mut tmp_pv: Vec<Move> = Vec::new();
+            let reduction = tunables.null_move_reduction(depth, static_eval, beta);
             let score = -Search::alpha_beta(
-                depth - 1 - NULL_MOVE_REDUCTION,
+                depth - 1 - reduction,
                 -beta,
                 -beta + 1,
                 &mut tmp_pv,
@@ -202,7 +314,7 @@ impl Search {
 
         // Multicut pruning: if several moves beat beta at reduced depth,
         // assume position is too good and cut early
-        if !is_root && depth >= MULTICUT_DEPTH && !is_check {
+        if !is_root && !is_singular_search && depth >= MULTICUT_DEPTH && !is_check {
             let max_moves = std::cmp::min(MULTICUT_MOVES as usize, move_list.len() as usize);
             let mut cutoffs = 0;
             for j in 0..max_moves {
@@ -252,50 +364,160 @@ impl Search {
             Search::pick_move(&mut move_list, i as u8);
             let current_move = move_list.get_move(i as u8);
 
-            if !refs.board.make(current_move, refs.mg) {
+            if is_singular_search && current_move.to_short_move().get_move() == excluded_move.get_move() {
                 continue;
             }
 
-            refs.search_info.ply += 1;
-            legal_moves_found += 1;
+            // With "go searchmoves", only the given root moves are searched.
+            if is_root
+                && !refs.search_params.search_moves.is_empty()
+                && !refs
+                    .search_params
+                    .search_moves
+                    .iter()
+                    .any(|m| m.get_move() == current_move.get_move())
+            {
+                continue;
+            }
 
-            let mut tmp_pv: Vec<Move> = Vec::new();
-            let mut score: i16;
+            if is_extra_pv_line
+                && refs
+                    .search_info
+                    .root_excluded
+                    .iter()
+                    .any(|m| m.get_move() == current_move.to_short_move().get_move())
+            {
+                continue;
+            }
 
-            // Late Move Reduction (LMR) analysis
-            // Identify quiet moves that are candidates for reduction
-            let is_quiet_move = current_move.captured() == 0 
-                && current_move.promoted() == 0 
-                && !current_move.castling() 
+            // Identify quiet moves that are candidates for pruning and reduction
+            let is_quiet_move = current_move.captured() == Pieces::NONE
+                && current_move.promoted() == Pieces::NONE
+                && !current_move.castling()
                 && !current_move.en_passant();
-            
-            // Avoid reducing check-giving moves (potentially tactical)
-            let gives_check = if is_quiet_move {
-                refs.board.make(current_move, refs.mg);
-                let opponent_king_sq = refs.board.king_square(refs.board.opponent());
-                let is_check_giving = refs.mg.square_attacked(
-                    refs.board,
-                    refs.board.us(),
-                    opponent_king_sq,
-                );
-                refs.board.unmake();
-                is_check_giving
-            } else {
-                false
-            };
-            
+
             // Protect historically good moves (killer moves)
             let is_killer_move = {
                 let ply = refs.search_info.ply as usize;
                 if ply < refs.search_info.killer_moves.len() {
                     let short_move = current_move.to_short_move();
-                    refs.search_info.killer_moves[ply].iter()
+                    let slots = refs.search_params.killer_slots;
+                    refs.search_info.killer_moves[ply][..slots].iter()
                         .any(|&killer| killer.get_move() == short_move.get_move())
                 } else {
                     false
                 }
             };
-            
+
+            // Singular extension: if the TT move beat a lower bound at
+            // sufficient depth, and no other move comes close to that bound
+            // in a reduced search, the TT move is the only good one and is
+            // extended by one ply.
+            let mut extension = 0;
+            if !is_root
+                && !is_singular_search
+                && depth >= SINGULAR_MIN_DEPTH
+                && refs.search_info.line_extensions < MAX_LINE_EXTENSIONS
+                && current_move.to_short_move().get_move() == tt_move.get_move()
+            {
+                if let Some(data) = tt_data {
+                    let (tt_depth, tt_flag, tt_score) = data.bound(refs.search_info.ply);
+                    if tt_depth >= depth - 3
+                        && matches!(tt_flag, HashFlag::Exact | HashFlag::Beta)
+                        && tt_score.abs() < CHECKMATE_THRESHOLD
+                        && Search::is_singular(tt_move, tt_score, depth, refs)
+                    {
+                        extension = 1;
+                        refs.search_info.singular_extensions += 1;
+                    }
+                }
+            }
+
+            // Recapture extension: taking back on the square where the
+            // opponent just captured is searched one ply deeper, so an
+            // exchange isn't cut off halfway at the horizon.
+            if extension == 0
+                && refs.search_params.recapture_extension
+                && current_move.captured() != Pieces::NONE
+                && refs.search_info.line_extensions < MAX_LINE_EXTENSIONS
+                && Search::previous_move(refs.board)
+                    .is_some_and(|prev| prev.captured() != Pieces::NONE && prev.to() == current_move.to())
+            {
+                extension = RECAPTURE_EXTENSION;
+                refs.search_info.recapture_extensions += 1;
+            }
+            let new_depth = depth - 1 + extension;
+
+            // A root move repeating the position for the third time ends
+            // the game in a draw, whatever the search below it would say.
+            let is_threefold = is_root && refs.board.would_be_threefold(current_move, refs.mg);
+
+            if !refs.board.make(current_move, refs.mg) {
+                continue;
+            }
+
+            // The child position will be probed first thing in the recursive
+            // call. Start loading its TT bucket now, while the move is
+            // checked for pruning and reductions.
+            if refs.tt_enabled && refs.search_params.tt_prefetch {
+                refs.thread_local_data.tt_prefetch.prefetch(refs.board.game_state.zobrist_key);
+            }
+
+            legal_moves_found += 1;
+
+            // Tell the GUI which root move is being searched.
+            if is_root && !quiet {
+                Search::send_move_to_gui(refs, current_move, legal_moves_found as u8);
+            }
+
+            // Avoid pruning or reducing check-giving moves (potentially tactical).
+            // The move has been made, so the opponent is now the side to move.
+            let gives_check = is_quiet_move
+                && refs.mg.square_attacked(
+                    refs.board,
+                    refs.board.opponent(),
+                    refs.board.king_square(refs.board.us()),
+                );
+
+            // Futility pruning: near the horizon, a quiet move cannot lift a
+            // hopeless static evaluation above alpha. The first legal move is
+            // always searched so checkmate and stalemate are still detected.
+            if refs.search_params.futility_pruning
+                && !is_root
+                && !is_check
+                && depth <= 2
+                && is_quiet_move
+                && !gives_check
+                && legal_moves_found > 1
+                && alpha.abs() < CHECKMATE_THRESHOLD
+                && static_eval + futility_margin <= alpha
+            {
+                refs.board.unmake();
+                continue;
+            }
+
+            // Late move pruning: at low depth, once enough moves have been
+            // searched, the remaining quiet moves are skipped outright rather
+            // than searched at reduced depth. Not used while mates are possible.
+            if !is_root
+                && !is_check
+                && depth <= LMP_MAX_DEPTH
+                && is_quiet_move
+                && !gives_check
+                && !is_killer_move
+                && best_eval_score > -CHECKMATE_THRESHOLD
+                && legal_moves_found as usize > late_move_count(depth, improving)
+            {
+                refs.board.unmake();
+                continue;
+            }
+
+            refs.search_info.ply += 1;
+            refs.search_info.line_extensions += extension;
+
+            let mut tmp_pv: Vec<Move> = Vec::new();
+            let mut score: i16;
+
             // Protect moves with high history heuristic scores
             let has_high_history = {
                 let piece = current_move.piece();
@@ -306,56 +528,55 @@ impl Search {
             
             // Apply LMR conditions: deep enough, not in check, quiet move,
             // not tactically important, and sufficient moves searched
-            let lmr_applies = depth >= LMR_MIN_DEPTH 
+            let lmr_applies = depth >= refs.search_params.lmr_min_depth 
                 && !is_check 
                 && is_quiet_move 
                 && !gives_check
                 && !is_killer_move
                 && !has_high_history
-                && legal_moves_found >= LMR_MOVE_THRESHOLD as i32;
+                && legal_moves_found >= tunables.lmr_move_threshold as i32;
 
             // Search current move with appropriate algorithm
-            if legal_moves_found > 1 {
+            if is_threefold {
+                score = -Search::draw_score(refs);
+            } else if legal_moves_found > 1 {
                 if lmr_applies {
-                    // Late Move Reduction: search at reduced depth first
-                    let reduction = if legal_moves_found > LMR_LATE_THRESHOLD as i32 {
-                        LMR_LATE_REDUCTION
-                    } else {
-                        LMR_REDUCTION
-                    };
-                    
-                    // Conservative reduction in tactical positions
-                    let safe_reduction = if depth <= 6 { 
-                        std::cmp::min(reduction, 1) // Limit reduction when shallow
-                    } else { 
-                        reduction 
-                    };
-                    
+                    // Late Move Reduction: search at reduced depth first,
+                    // reducing more as depth and move number grow
+                    // An improving position is reduced one ply less.
+                    let reduction = (tunables.lmr_reduction(depth, legal_moves_found as usize)
+                        - improving as i8)
+                        .max(0);
+                    if reduction > 0 {
+                        refs.search_info.lmr_reductions += 1;
+                    }
+
                     // First: reduced-depth search with zero-width window
-                    let reduced_depth = std::cmp::max(1, depth - 1 - safe_reduction);
+                    let reduced_depth = std::cmp::max(1, new_depth - reduction);
                     score = -Search::alpha_beta(reduced_depth, -alpha - 1, -alpha, &mut tmp_pv, refs);
                     
                     // Re-search at full depth if LMR suggests move is promising
                     if score > alpha {
-                        score = -Search::alpha_beta(depth - 1, -alpha - 1, -alpha, &mut tmp_pv, refs);
+                        score = -Search::alpha_beta(new_depth, -alpha - 1, -alpha, &mut tmp_pv, refs);
                         if score > alpha && score < beta {
-                            score = -Search::alpha_beta(depth - 1, -beta, -alpha, &mut tmp_pv, refs);
+                            score = -Search::alpha_beta(new_depth, -beta, -alpha, &mut tmp_pv, refs);
                         }
                     }
                 } else {
                     // Standard Principal Variation Search (PVS)
-                    score = -Search::alpha_beta(depth - 1, -alpha - 1, -alpha, &mut tmp_pv, refs);
+                    score = -Search::alpha_beta(new_depth, -alpha - 1, -alpha, &mut tmp_pv, refs);
                     if score > alpha && score < beta {
-                        score = -Search::alpha_beta(depth - 1, -beta, -alpha, &mut tmp_pv, refs);
+                        score = -Search::alpha_beta(new_depth, -beta, -alpha, &mut tmp_pv, refs);
                     }
                 }
             } else {
                 // First move: search with full window
-                score = -Search::alpha_beta(depth - 1, -beta, -alpha, &mut tmp_pv, refs);
+                score = -Search::alpha_beta(new_depth, -beta, -alpha, &mut tmp_pv, refs);
             }
 
             refs.board.unmake();
             refs.search_info.ply -= 1;
+            refs.search_info.line_extensions -= extension;
 
             if refs.search_info.terminate != SearchTerminate::Nothing {
                 break;
@@ -373,74 +594,78 @@ impl Search {
                     pv.push(current_move);
                     pv.extend(tmp_pv);
 
-                    if is_root {
+                    if is_root && !is_extra_pv_line {
                         refs.thread_local_data.update_best_move(current_move);
                     }
 
                     // Beta cutoff: position too good for opponent
                     if score >= beta {
                         hash_flag = HashFlag::Beta;
+
+                        // Remember quiet moves that cause cutoffs for move ordering
+                        if is_quiet_move {
+                            Search::update_quiet_cutoff(current_move, depth, refs);
+                        }
                         break;
                     }
                 }
             }
 
             // Collect sharp sequence analysis for root moves
-            if is_root {
-                let mut good_replies = 0;
-                let mut reply: Option<Move> = None;
-                let mut reply_sequence: Vec<Move> = Vec::new();
-
-                if score > alpha - refs.search_params.sharp_margin {
-                    (good_replies, reply, reply_sequence) = Search::collect_sharp_sequence(
-                        depth - 1,
-                        -beta,
-                        -alpha + refs.search_params.sharp_margin,
+            if is_root && !is_extra_pv_line {
+                let mut sharp = SharpSequence::default();
+
+                // The replies are the opponent's, so the move is made again.
+                if score > alpha - refs.search_params.sharp_margin
+                    && refs.board.make(current_move, refs.mg)
+                {
+                    refs.search_info.ply += 1;
+                    sharp = Search::collect_sharp_sequence(
+                        (depth - 1).min(refs.search_params.sharp_depth_cap),
+                        alpha.saturating_sub(refs.search_params.sharp_margin),
+                        beta,
                         refs,
                     );
+                    refs.board.unmake();
+                    refs.search_info.ply -= 1;
                 }
 
                 root_analysis.push(RootMoveAnalysis {
                     mv: current_move,
                     eval: score,
-                    good_replies,
-                    reply,
-                    reply_sequence,
+                    good_replies: sharp.good_replies,
+                    reply: sharp.reply,
+                    reply_gap: sharp.reply_gap,
+                    our_forced: sharp.our_forced,
+                    reply_sequence: sharp.sequence,
                 });
             }
         }
 
+        // In a verification search, the excluded move may have been the only
+        // legal one. That makes it singular, so fail low.
+        if is_singular_search {
+            return if legal_moves_found == 0 { alpha } else { best_eval_score };
+        }
+
         // Handle terminal positions (checkmate/stalemate)
         if legal_moves_found == 0 {
             if is_check {
                 return -CHECKMATE + refs.search_info.ply as i16;
-            } else {
+            } else if is_root {
                 return STALEMATE;
+            } else {
+                return Search::draw_score(refs);
             }
         }
 
-        // Store position in transposition table using thread-local batching
-        if refs.tt_enabled {
-            let tt_data = SearchData::create(
-                depth,
-                refs.search_info.ply,
-                hash_flag,
-                best_eval_score,
-                best_move,
-            );
-
-            // Batch TT updates to reduce lock contention
-            refs.thread_local_data.tt_batch.add(
-                refs.board.game_state.zobrist_key,
-                tt_data,
-            );
-
-            // Flush batch if full to maintain memory usage
-            if refs.thread_local_data.tt_batch.is_full() {
-                Search::flush_tt_batch(refs);
-            }
+        if is_extra_pv_line {
+            return best_eval_score;
         }
 
+        // Store position in transposition table using thread-local batching
+        Search::store_tt_entry(depth, hash_flag, best_eval_score, static_eval, best_move, refs);
+
         if is_root {
             refs.search_info.root_analysis = root_analysis;
         }
@@ -448,6 +673,66 @@ impl Search {
         best_eval_score
     }
 
+    /// Verify whether the TT move is singular.
+    /// 
+    /// Searches all other moves at reduced depth with a null window just
+    /// below the TT value. If they all fail low, the TT move is the only
+    /// move that holds the position.
+    fn is_singular(tt_move: ShortMove, tt_score: i16, depth: i8, refs: &mut SearchRefs) -> bool {
+        let ply = refs.search_info.ply as usize;
+        let singular_beta = tt_score - refs.search_params.tunables.singular_margin * depth as i16;
+        let mut tmp_pv: Vec<Move> = Vec::new();
+
+        refs.search_info.excluded_moves[ply] = tt_move;
+        let score = Search::alpha_beta(
+            (depth - 1) / 2,
+            singular_beta - 1,
+            singular_beta,
+            &mut tmp_pv,
+            refs,
+        );
+        refs.search_info.excluded_moves[ply] = ShortMove::new(0);
+
+        score < singular_beta
+    }
+
+    /// Store a search result for the current position in the transposition table.
+    /// 
+    /// Entries are added to the thread-local batch, which is flushed to the
+    /// global TT once full to reduce lock contention.
+    fn store_tt_entry(
+        depth: i8,
+        hash_flag: HashFlag,
+        value: i16,
+        static_eval: i16,
+        best_move: ShortMove,
+        refs: &mut SearchRefs,
+    ) {
+        if !refs.tt_enabled {
+            return;
+        }
+
+        let tt_data = SearchData::create(
+            depth,
+            refs.search_info.ply,
+            hash_flag,
+            value,
+            static_eval,
+            best_move,
+        );
+
+        // Batch TT updates to reduce lock contention
+        refs.thread_local_data.tt_batch.add(
+            refs.board.game_state.zobrist_key,
+            tt_data,
+        );
+
+        // Flush batch if full to maintain memory usage
+        if refs.thread_local_data.tt_batch.is_full() {
+            Search::flush_tt_batch(refs);
+        }
+    }
+
     /// Flush thread-local transposition table batch to global TT.
     /// 
     /// Reduces lock contention by batching multiple TT updates together
@@ -467,6 +752,10 @@ impl Search {
     /// 
     /// Identifies forced sequences where opponent has limited good responses,
     /// used for detecting tactical shots and calculating concrete variations.
+    /// While the opponent's reply is forced, our follow-up is checked in the
+    /// same way, so the result shows whether both sides' moves are forced.
+    /// Called with the opponent to move; the window and all scores are from
+    /// the point of view of the side to move at the root.
     /// 
     /// # Arguments
     /// * `depth` - Remaining search depth for sequence collection
@@ -475,88 +764,78 @@ impl Search {
     /// * `refs` - Search context and game state
     /// 
     /// # Returns
-    /// Tuple of (good_replies_count, best_reply_move, forced_sequence)
+    /// The number of good replies, the reply, the gap to the second best
+    /// reply (INF if there is only one legal reply), whether our follow-ups
+    /// are forced and the forced sequence
     fn collect_sharp_sequence(
         depth: i8,
         alpha: i16,
         beta: i16,
         refs: &mut SearchRefs,
-    ) -> (usize, Option<Move>, Vec<Move>) {
-        let mut move_list = MoveList::new();
-        refs.mg.generate_moves(refs.board, &mut move_list, MoveType::All);
-
-        let mut evals: Vec<(Move, i16)> = Vec::new();
-        let mut best_eval = INF;
-        let mut best_move: Option<Move> = None;
-
-        // Evaluate all opponent responses
-        for i in 0..move_list.len() {
-            if Search::time_up(refs) {
-                break;
-            }
-
-            let mv = move_list.get_move(i);
-            if refs.board.make(mv, refs.mg) {
-                refs.search_info.ply += 1;
-                let mut node_pv: Vec<Move> = Vec::new();
-                let score = -Search::alpha_beta(depth - 1, -beta, -alpha, &mut node_pv, refs);
-                if Search::time_up(refs) {
-                    refs.board.unmake();
-                    refs.search_info.ply -= 1;
-                    return (0, None, Vec::new());
-                }
-                refs.board.unmake();
-                refs.search_info.ply -= 1;
-
-                if score < best_eval {
-                    best_eval = score;
-                    best_move = Some(mv);
-                }
-                evals.push((mv, score));
-            }
-        }
-
-        // Count moves within sharp margin (good responses for opponent)
-        let good: Vec<Move> = evals
-            .iter()
-            .filter(|(_, e)| *e <= best_eval + refs.search_params.sharp_margin)
-            .map(|(m, _)| *m)
-            .collect();
-
+    ) -> SharpSequence {
+        // The opponent minimises; scores of their replies need no negation.
+        let Some(replies) = Search::search_each_move(depth, alpha, beta, false, refs) else {
+            return SharpSequence::default();
+        };
+        let (good, best_move, reply_gap) = Search::good_moves(&replies, false, refs);
         let reply = if good.len() == 1 { Some(good[0]) } else { best_move };
 
+        let mut result = SharpSequence {
+            good_replies: good.len(),
+            reply,
+            reply_gap,
+            our_forced: false,
+            sequence: Vec::new(),
+        };
+
         // If not exactly one good reply, or too shallow, return basic info
-        if good.len() != 1 || depth <= 1 || reply.is_none() {
-            return (good.len(), reply, Vec::new());
+        if good.len() != 1 || depth <= 1 {
+            return result;
         }
 
         // Build forced sequence when opponent has only one good reply
         let forced = good[0];
-        let mut sequence: Vec<Move> = vec![forced];
+        result.sequence.push(forced);
 
         if refs.board.make(forced, refs.mg) {
             refs.search_info.ply += 1;
-            let mut pv: Vec<Move> = Vec::new();
-            Search::alpha_beta(depth - 1, alpha, beta, &mut pv, refs);
-            if Search::time_up(refs) {
+
+            // Our follow-ups are scored the same way as the replies.
+            let Some(follow_ups) = Search::search_each_move(depth - 1, alpha, beta, true, refs) else {
                 refs.board.unmake();
                 refs.search_info.ply -= 1;
-                return (0, None, sequence);
-            }
+                return SharpSequence { sequence: result.sequence, ..Default::default() };
+            };
+            let (ours, my_best, _) = Search::good_moves(&follow_ups, true, refs);
+            result.our_forced = ours.len() == 1;
 
-            // Extend sequence recursively if deep enough
+            // Extend sequence recursively if deep enough. Our follow-up is
+            // the one the search of the forced reply expected; if a TT
+            // cutoff left no PV, it is our best scored move.
+            let expected = replies.iter().find(|r| r.0 == forced).and_then(|r| r.2);
             if depth > 2 {
-                if let Some(my_move) = pv.get(0).cloned() {
+                if let Some(my_move) = expected.or(my_best) {
                     if refs.board.make(my_move, refs.mg) {
                         refs.search_info.ply += 1;
-                        let (_, _, mut next_seq) =
-                            Search::collect_sharp_sequence(depth - 2, alpha, beta, refs);
+                        let mut next = Search::collect_sharp_sequence(depth - 2, alpha, beta, refs);
                         if Search::time_up(refs) {
                             refs.board.unmake();
-                            refs.search_info.ply -= 1;
-                            return (0, Some(forced), sequence);
+                            refs.board.unmake();
+                            refs.search_info.ply -= 2;
+                            return SharpSequence {
+                                reply: Some(forced),
+                                sequence: result.sequence,
+                                ..Default::default()
+                            };
                         }
-                        sequence.append(&mut next_seq);
+
+                        // A sequence of two or more moves includes our next
+                        // follow-up, which must be forced as well.
+                        if next.sequence.len() > 1 {
+                            result.our_forced &= next.our_forced;
+                        }
+                        result.sequence.push(my_move);
+                        result.sequence.append(&mut next.sequence);
                         refs.board.unmake();
                         refs.search_info.ply -= 1;
                     }
@@ -567,7 +846,93 @@ impl Search {
             refs.search_info.ply -= 1;
         }
 
-        (good.len(), reply, sequence)
+        result
+    }
+
+    // Searches each legal move in the current position with the given depth
+    // and window, and returns the moves with their scores from the point of
+    // view of the side to move at the root, and the first move of the PV
+    // behind them. Returns None if time runs out.
+    fn search_each_move(
+        depth: i8,
+        alpha: i16,
+        beta: i16,
+        root_side_to_move: bool,
+        refs: &mut SearchRefs,
+    ) -> Option<Vec<(Move, i16, Option<Move>)>> {
+        let mut move_list = MoveList::new();
+        refs.mg.generate_moves(refs.board, &mut move_list, MoveType::All);
+
+        let mut evals: Vec<(Move, i16, Option<Move>)> = Vec::new();
+        for i in 0..move_list.len() {
+            if Search::time_up(refs) {
+                return None;
+            }
+
+            let mv = move_list.get_move(i);
+            if refs.board.make(mv, refs.mg) {
+                refs.search_info.ply += 1;
+                let mut node_pv: Vec<Move> = Vec::new();
+                let score = if root_side_to_move {
+                    -Search::alpha_beta(depth - 1, -beta, -alpha, &mut node_pv, refs)
+                } else {
+                    Search::alpha_beta(depth - 1, alpha, beta, &mut node_pv, refs)
+                };
+                refs.board.unmake();
+                refs.search_info.ply -= 1;
+                if Search::time_up(refs) {
+                    return None;
+                }
+                evals.push((mv, score, node_pv.first().cloned()));
+            }
+        }
+
+        Some(evals)
+    }
+
+    // Returns the moves scoring within the sharp margin of the best one, the
+    // best move and how much worse the second best move is (INF if there is
+    // only one). We maximise the scores, the opponent minimises them.
+    fn good_moves(
+        evals: &[(Move, i16, Option<Move>)],
+        maximise: bool,
+        refs: &SearchRefs,
+    ) -> (Vec<Move>, Option<Move>, i16) {
+        // Turn the scores around for the opponent, so higher is better.
+        let value = |e: i16| if maximise { e } else { -e };
+        let mut best_eval = -INF;
+        let mut best_move: Option<Move> = None;
+        for (mv, e, _) in evals {
+            if value(*e) > best_eval {
+                best_eval = value(*e);
+                best_move = Some(*mv);
+            }
+        }
+
+        // The window starts the margin below our best line, so our moves
+        // scoring exactly that much less may have failed low and don't
+        // count as good.
+        let margin = refs.search_params.sharp_margin;
+        let is_good = |e: i16| {
+            if maximise {
+                e > best_eval - margin
+            } else {
+                value(e) >= best_eval - margin
+            }
+        };
+        let good: Vec<Move> = evals
+            .iter()
+            .filter(|(m, e, _)| Some(*m) == best_move || is_good(*e))
+            .map(|(m, _, _)| *m)
+            .collect();
+        let gap = evals
+            .iter()
+            .filter(|(m, _, _)| Some(*m) != best_move)
+            .map(|(_, e, _)| best_eval.saturating_sub(value(*e)))
+            .min()
+            .unwrap_or(INF);
+
+        (good, best_move, gap)
     }
 }
 
@@ -575,55 +940,25 @@ impl Search {
 mod tests {
     use super::*;
     use crate::{
-        board::Board,
-        engine::defs::{Information, SearchData, TT},
-        movegen::{MoveGenerator, defs::{MoveList, MoveType}},
-        search::defs::{SearchControl, SearchInfo, SearchParams, SearchRefs, ThreadLocalData},
+        defs::FEN_START_POSITION,
+        engine::defs::Information,
+        evaluation::defs::PIECE_VALUES,
+        movegen::defs::{MoveList, MoveType},
+        search::{
+            defs::{
+                RepetitionPolicy, SearchInfo, SearchParams, SearchReport, SearchTunables, DRAW, MIN_TIME_CURR_MOVE,
+                LMR_BASE, LMR_MAX_MOVES, LMR_MIN_DEPTH, LMR_MOVE_THRESHOLD, NULL_MOVE_REDUCTION,
+            },
+            test_utils::TestSearch,
+        },
     };
-    use crossbeam_channel::unbounded;
-    use std::sync::{Arc, RwLock};
-
-    fn create_test_search_refs() -> (
-        Board, 
-        Arc<MoveGenerator>, 
-        Arc<RwLock<TT<SearchData>>>,
-        SearchParams,
-        SearchInfo,
-        ThreadLocalData,
-        crossbeam_channel::Receiver<SearchControl>,
-        crossbeam_channel::Sender<Information>
-    ) {
-        let mut board = Board::new();
-        board.fen_read(Some("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")).unwrap();
-        let mg = Arc::new(MoveGenerator::new());
-        let tt = Arc::new(RwLock::new(TT::new(32)));
-        let search_params = SearchParams::new();
-        let search_info = SearchInfo::new();
-        let thread_local_data = ThreadLocalData::new(0);
-        let (_control_tx, control_rx) = unbounded::<SearchControl>();
-        let (report_tx, _report_rx) = unbounded::<Information>();
-        
-        (board, mg, tt, search_params, search_info, thread_local_data, control_rx, report_tx)
-    }
 
     #[test]
     fn test_lmr_conditions_depth_too_low() {
-        let (mut board, mg, tt, mut search_params, mut search_info, mut thread_local_data, control_rx, report_tx) = create_test_search_refs();
-        
         // Set up position with quiet moves available
-        board.fen_read(Some("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2")).unwrap();
+        let mut test = TestSearch::new("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
         
-        let mut refs = SearchRefs {
-            board: &mut board,
-            mg: &mg,
-            tt: &tt,
-            tt_enabled: true,
-            search_params: &mut search_params,
-            search_info: &mut search_info,
-            control_rx: &control_rx,
-            report_tx: &report_tx,
-            thread_local_data: &mut thread_local_data,
-        };
+        let mut refs = test.refs();
 
         let mut pv = Vec::new();
         // With depth 3, LMR should not be applied (requires depth >= 4)
@@ -635,22 +970,10 @@ mod tests {
 
     #[test]
     fn test_lmr_conditions_in_check() {
-        let (mut board, mg, tt, mut search_params, mut search_info, mut thread_local_data, control_rx, report_tx) = create_test_search_refs();
-        
         // Set up position where king is in check
-        board.fen_read(Some("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3")).unwrap();
+        let mut test = TestSearch::new("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
         
-        let mut refs = SearchRefs {
-            board: &mut board,
-            mg: &mg,
-            tt: &tt,
-            tt_enabled: true,
-            search_params: &mut search_params,
-            search_info: &mut search_info,
-            control_rx: &control_rx,
-            report_tx: &report_tx,
-            thread_local_data: &mut thread_local_data,
-        };
+        let mut refs = test.refs();
 
         let mut pv = Vec::new();
         // When in check, LMR should not be applied
@@ -662,25 +985,11 @@ mod tests {
 
     #[test]
     fn test_quiet_move_identification() {
-        let (mut board, mg, tt, mut search_params, mut search_info, mut thread_local_data, control_rx, report_tx) = create_test_search_refs();
-        
         // Set up position with mix of quiet and non-quiet moves
-        board.fen_read(Some("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2")).unwrap();
-        
-        let mut refs = SearchRefs {
-            board: &mut board,
-            mg: &mg,
-            tt: &tt,
-            tt_enabled: true,
-            search_params: &mut search_params,
-            search_info: &mut search_info,
-            control_rx: &control_rx,
-            report_tx: &report_tx,
-            thread_local_data: &mut thread_local_data,
-        };
+        let test = TestSearch::new("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
 
         let mut move_list = MoveList::new();
-        mg.generate_moves(&board, &mut move_list, MoveType::All);
+        test.mg.generate_moves(&test.board, &mut move_list, MoveType::All);
         
         // Test that we can identify quiet vs non-quiet moves
         let mut quiet_moves = 0;
@@ -688,8 +997,8 @@ mod tests {
         
         for i in 0..move_list.len() {
             let mv = move_list.get_move(i);
-            let is_quiet = mv.captured() == 0 
-                && mv.promoted() == 0 
+            let is_quiet = mv.captured() == Pieces::NONE
+                && mv.promoted() == Pieces::NONE
                 && !mv.castling() 
                 && !mv.en_passant();
             
@@ -711,69 +1020,60 @@ mod tests {
     }
 
     #[test]
-    fn test_lmr_reduction_amounts() {
-        let (mut board, mg, tt, mut search_params, mut search_info, mut thread_local_data, control_rx, report_tx) = create_test_search_refs();
-        
-        // Test that reduction amounts are calculated correctly
-        board.fen_read(Some("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2")).unwrap();
-        
-        let mut refs = SearchRefs {
-            board: &mut board,
-            mg: &mg,
-            tt: &tt,
-            tt_enabled: true,
-            search_params: &mut search_params,
-            search_info: &mut search_info,
-            control_rx: &control_rx,
-            report_tx: &report_tx,
-            thread_local_data: &mut thread_local_data,
-        };
+    fn test_null_move_reduction_grows_and_clamps() {
+        let tunables = SearchTunables::new();
+
+        // The base reduction applies at low depth and without a margin,
+        // also when the static evaluation is below beta.
+        assert_eq!(tunables.null_move_reduction(4, 0, 0), NULL_MOVE_REDUCTION);
+        assert_eq!(tunables.null_move_reduction(4, -500, 0), NULL_MOVE_REDUCTION);
+
+        // Grows with depth...
+        assert_eq!(tunables.null_move_reduction(6, 0, 0), NULL_MOVE_REDUCTION + 1);
+        assert_eq!(tunables.null_move_reduction(12, 0, 0), NULL_MOVE_REDUCTION + 2);
+
+        // ...and with the margin over beta, by at most 3.
+        assert_eq!(tunables.null_move_reduction(4, 250, 50), NULL_MOVE_REDUCTION + 1);
+        assert_eq!(tunables.null_move_reduction(4, 650, 0), NULL_MOVE_REDUCTION + 3);
+        assert_eq!(tunables.null_move_reduction(4, INF, -INF), NULL_MOVE_REDUCTION + 3);
+    }
 
-        // Test that constants are set correctly
-        assert_eq!(LMR_REDUCTION, 1);
-        assert_eq!(LMR_LATE_REDUCTION, 1);
+    #[test]
+    fn test_lmr_reduction_amounts() {
+        let tunables = SearchTunables::new();
         assert_eq!(LMR_MOVE_THRESHOLD, 4);
-        assert_eq!(LMR_LATE_THRESHOLD, 8);
         assert_eq!(LMR_MIN_DEPTH, 4);
-        
-        // Test reduction logic
-        let early_move_count = 5; // Between LMR_MOVE_THRESHOLD and LMR_LATE_THRESHOLD
-        let late_move_count = 10; // Greater than LMR_LATE_THRESHOLD
-        
-        let early_reduction = if early_move_count > LMR_LATE_THRESHOLD as i32 {
-            LMR_LATE_REDUCTION
-        } else {
-            LMR_REDUCTION
-        };
-        
-        let late_reduction = if late_move_count > LMR_LATE_THRESHOLD as i32 {
-            LMR_LATE_REDUCTION
-        } else {
-            LMR_REDUCTION
-        };
-        
-        assert_eq!(early_reduction, LMR_REDUCTION);
-        assert_eq!(late_reduction, LMR_LATE_REDUCTION);
+
+        // Reductions never exceed depth - 1 and never go negative
+        for depth in 1..MAX_PLY {
+            for move_number in 1..LMR_MAX_MOVES {
+                let reduction = tunables.lmr_reduction(depth, move_number);
+                assert!((0..depth).contains(&reduction));
+            }
+        }
+
+        // Reductions grow monotonically with both depth and move number
+        for depth in 1..MAX_PLY {
+            for move_number in 1..LMR_MAX_MOVES {
+                let reduction = tunables.lmr_reduction(depth, move_number);
+                if depth + 1 < MAX_PLY {
+                    assert!(tunables.lmr_reduction(depth + 1, move_number) >= reduction);
+                }
+                if move_number + 1 < LMR_MAX_MOVES {
+                    assert!(tunables.lmr_reduction(depth, move_number + 1) >= reduction);
+                }
+            }
+        }
+
+        assert!(tunables.lmr_reduction(20, 40) > tunables.lmr_reduction(4, 4));
     }
 
     #[test]
     fn test_alpha_beta_with_lmr_depth() {
-        let (mut board, mg, tt, mut search_params, mut search_info, mut thread_local_data, control_rx, report_tx) = create_test_search_refs();
-        
         // Set up a more complex position where LMR can be applied
-        board.fen_read(Some("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R b KQkq - 0 4")).unwrap();
+        let mut test = TestSearch::new("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R b KQkq - 0 4");
         
-        let mut refs = SearchRefs {
-            board: &mut board,
-            mg: &mg,
-            tt: &tt,
-            tt_enabled: true,
-            search_params: &mut search_params,
-            search_info: &mut search_info,
-            control_rx: &control_rx,
-            report_tx: &report_tx,
-            thread_local_data: &mut thread_local_data,
-        };
+        let mut refs = test.refs();
 
         let mut pv = Vec::new();
         
@@ -792,29 +1092,17 @@ mod tests {
 
     #[test]
     fn test_alpha_beta_move_count_threshold() {
-        let (mut board, mg, tt, mut search_params, mut search_info, mut thread_local_data, control_rx, report_tx) = create_test_search_refs();
-        
         // Use a position with many moves available
-        board.fen_read(Some("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R b KQkq - 0 4")).unwrap();
+        let mut test = TestSearch::new("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R b KQkq - 0 4");
         
         // Generate moves to see how many we have (before creating refs)
         let mut move_list = MoveList::new();
-        mg.generate_moves(&board, &mut move_list, MoveType::All);
+        test.mg.generate_moves(&test.board, &mut move_list, MoveType::All);
         
         // Should have enough moves to test LMR threshold
         assert!(move_list.len() >= LMR_MOVE_THRESHOLD);
         
-        let mut refs = SearchRefs {
-            board: &mut board,
-            mg: &mg,
-            tt: &tt,
-            tt_enabled: true,
-            search_params: &mut search_params,
-            search_info: &mut search_info,
-            control_rx: &control_rx,
-            report_tx: &report_tx,
-            thread_local_data: &mut thread_local_data,
-        };
+        let mut refs = test.refs();
         
         let mut pv = Vec::new();
         let _score = Search::alpha_beta(4, -INF, INF, &mut pv, &mut refs);
@@ -822,4 +1110,368 @@ mod tests {
         // Test passes if no panic occurs
         assert!(true);
     }
+
+    #[test]
+    fn test_futility_pruning_reduces_nodes() {
+        // White is a queen down with only quiet moves available.
+        let fen = "3qk3/8/8/8/8/8/PPP5/1K6 w - - 0 1";
+        let mut nodes = [0usize; 2];
+
+        for (i, futility_pruning) in [false, true].into_iter().enumerate() {
+            let mut test = TestSearch::new(fen).without_tt();
+            test.search_params.futility_pruning = futility_pruning;
+            test.search_info.allocated_time = 60_000;
+
+            let mut refs = test.refs();
+
+            let mut pv = Vec::new();
+            Search::alpha_beta(4, -INF, INF, &mut pv, &mut refs);
+            nodes[i] = refs.search_info.nodes;
+        }
+
+        assert!(nodes[1] < nodes[0], "futility pruning should search fewer nodes");
+    }
+
+    #[test]
+    fn test_every_tunable_has_its_own_value() {
+        for &(name, min, max) in SearchTunables::RANGES.iter() {
+            let mut tunables = SearchTunables::new();
+            let value = if tunables.get(name) == Some(max) { min } else { max };
+            assert!(tunables.set(name, value));
+
+            // Only the named tunable changed.
+            for &(other, _, _) in SearchTunables::RANGES.iter() {
+                let expected = if other == name { Some(value) } else { SearchTunables::new().get(other) };
+                assert_eq!(tunables.get(other), expected, "setting {name} changed {other}");
+            }
+        }
+
+        assert_eq!(SearchTunables::new().get("NoSuchTunable"), None);
+    }
+
+    #[test]
+    fn test_tuned_lmr_base_changes_applied_reduction() {
+        let mut tunables = SearchTunables::new();
+        let reduction = tunables.lmr_reduction(8, 20);
+        assert!(tunables.set("lmrbase", LMR_BASE + 100));
+        assert_eq!(tunables.lmr_reduction(8, 20), reduction + 1);
+        assert!(!tunables.set("NoSuchTunable", 1));
+
+        // The search takes the reduction from its parameters: reducing late
+        // moves by one more ply searches fewer nodes.
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R b KQkq - 0 4";
+        let mut nodes = [0usize; 2];
+
+        for (i, lmr_base) in [LMR_BASE, LMR_BASE + 100].into_iter().enumerate() {
+            let mut test = TestSearch::new(fen).without_tt();
+            test.search_params.tunables.set(SearchTunables::LMR_BASE, lmr_base);
+            test.search_info.allocated_time = 60_000;
+
+            let mut refs = test.refs();
+
+            let mut pv = Vec::new();
+            Search::alpha_beta(5, -INF, INF, &mut pv, &mut refs);
+            nodes[i] = refs.search_info.nodes;
+        }
+
+        assert!(nodes[1] < nodes[0], "nodes: {nodes:?}");
+    }
+
+    // Searches the position to depth 5 with the given LMR minimum depth,
+    // and returns the number of moves that were reduced.
+    fn lmr_search(lmr_min_depth: i8) -> usize {
+        let mut test = TestSearch::new("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").without_tt();
+        test.search_params.quiet = true;
+        test.search_params.lmr_min_depth = lmr_min_depth;
+        test.search_info.allocated_time = 60_000;
+
+        let mut refs = test.refs();
+
+        let mut pv = Vec::new();
+        Search::alpha_beta(5, -INF, INF, &mut pv, &mut refs);
+        refs.search_info.lmr_reductions
+    }
+
+    #[test]
+    fn test_lmr_min_depth_above_search_depth_disables_lmr() {
+        assert!(lmr_search(LMR_MIN_DEPTH) > 0, "LMR should reduce late moves");
+        assert_eq!(lmr_search(6), 0);
+    }
+
+    #[test]
+    fn test_singular_extension_fires_on_forced_recapture() {
+        // Both back ranks are weak. Whenever a rook check lands on the back
+        // rank, capturing it with the other rook is the only move that
+        // avoids mate.
+        let fen = "r5k1/5ppp/8/8/8/8/5PPP/3R2K1 b - - 0 1";
+        let mut test = TestSearch::new(fen);
+        test.search_params.quiet = true;
+
+        let mut refs = test.refs();
+
+        // Iterate up to the singular extension depth so the TT holds
+        // entries deep enough to verify against.
+        for depth in 1..=SINGULAR_MIN_DEPTH + 1 {
+            let mut pv = Vec::new();
+            Search::alpha_beta(depth, -INF, INF, &mut pv, &mut refs);
+            Search::flush_tt_batch(&mut refs);
+        }
+
+        assert!(refs.search_info.singular_extensions > 0, "singular extension should fire");
+    }
+
+    // Searches the knight exchange on d5 after white has just taken
+    // there, and returns the node count and the number of recaptures
+    // that were extended.
+    fn exchange_search(recapture_extension: bool) -> (usize, usize) {
+        let mut test = TestSearch::new("4k3/8/5n2/3p4/4P3/2N5/8/4K3 w - - 0 1");
+        let m = test.board.san_to_move("exd5", &test.mg).unwrap();
+        assert!(test.board.make(m, &test.mg));
+        test.search_params.quiet = true;
+        test.search_params.recapture_extension = recapture_extension;
+
+        let mut refs = test.refs();
+
+        let mut pv = Vec::new();
+        Search::alpha_beta(2, -INF, INF, &mut pv, &mut refs);
+        assert_eq!(refs.search_info.line_extensions, 0);
+
+        (refs.search_info.nodes, refs.search_info.recapture_extensions)
+    }
+
+    #[test]
+    fn test_recapture_extension_searches_exchange_deeper() {
+        // Nxd5 takes back on the square of exd5, and Nxd5 takes back again,
+        // so both recaptures are searched one ply deeper.
+        let (extended_nodes, extended) = exchange_search(true);
+        let (plain_nodes, plain) = exchange_search(false);
+
+        assert!(extended > 0, "recapture extension should fire");
+        assert_eq!(plain, 0);
+        assert!(extended_nodes > plain_nodes, "nodes: {extended_nodes} vs {plain_nodes}");
+    }
+
+    // Searches the position with the given limit on check extensions, and
+    // returns the node count and the most check extensions along a line.
+    fn check_extension_search(fen: &str, depth: i8, max_check_extensions: i8) -> (usize, i8) {
+        let mut test = TestSearch::new(fen);
+        test.search_params.quiet = true;
+        test.search_params.max_check_extensions = max_check_extensions;
+
+        let mut refs = test.refs();
+
+        let mut pv = Vec::new();
+        Search::alpha_beta(depth, -INF, INF, &mut pv, &mut refs);
+        let most = refs.search_info.check_extensions.iter().copied().max().unwrap_or(0);
+
+        (refs.search_info.nodes, most)
+    }
+
+    #[test]
+    fn test_check_extensions_are_limited_per_line() {
+        // Both kings are open to queen checks, so there are long lines of
+        // consecutive checks by either side.
+        let fen = "7k/8/8/3q4/8/8/8/Q6K w - - 0 1";
+        let (capped_nodes, capped_most) = check_extension_search(fen, 6, 4);
+        let (free_nodes, free_most) = check_extension_search(fen, 6, i8::MAX);
+
+        assert_eq!(capped_most, 4);
+        assert!(free_most > capped_most, "uncapped lines had {free_most} check extensions");
+        assert!(capped_nodes < free_nodes, "nodes: {capped_nodes} vs {free_nodes}");
+    }
+
+    #[test]
+    fn test_improving_flag_oscillating_eval() {
+        let mut search_info = SearchInfo::new();
+
+        // The evaluation swings between the two sides along a line; each
+        // ply is compared with the ply two steps back.
+        let evals = [10, 50, 20, 60, 10, -INF, 30, 40];
+        // In check at ply 5, so neither ply 5 nor ply 7 can be improving.
+        let expected = [false, false, true, true, false, false, true, false];
+
+        for (ply, (&eval, &improving)) in evals.iter().zip(expected.iter()).enumerate() {
+            assert_eq!(search_info.update_improving(ply as i8, eval), improving);
+        }
+    }
+
+    #[test]
+    fn test_sharp_analysis_marks_our_forced_follow_up() {
+        // Philidor's mate: after Qg8+ the rook must take, because the knight
+        // guards g8, and only Nf7 mates. Every other move loses the queen.
+        let mut test = TestSearch::new("5r1k/6pp/7N/3Q4/8/8/6PP/6K1 w - - 0 1");
+        test.search_params.quiet = true;
+
+        let mut refs = test.refs();
+
+        let mut pv = Vec::new();
+        Search::alpha_beta(4, -INF, INF, &mut pv, &mut refs);
+
+        let qg8 = refs
+            .search_info
+            .root_analysis
+            .iter()
+            .find(|a| a.mv.as_string() == "d5g8")
+            .unwrap();
+        let line: Vec<String> = qg8.reply_sequence.iter().map(|m| m.as_string()).collect();
+
+        assert_eq!(qg8.good_replies, 1);
+        assert!(qg8.our_forced);
+        assert_eq!(line[..2], ["f8g8", "h6f7"]);
+    }
+
+    #[test]
+    fn test_stopped_search_gives_no_reply_scores() {
+        let mut test = TestSearch::new("5r1k/6pp/7N/3Q4/8/8/6PP/6K1 w - - 0 1");
+        test.search_params.quiet = true;
+        test.search_info.terminate = SearchTerminate::Stop;
+
+        // Scores of only some of the moves would undercount the good ones.
+        let mut refs = test.refs();
+        assert!(Search::search_each_move(2, -INF, INF, true, &mut refs).is_none());
+    }
+
+    // Searches the position to depth 3 and returns the score.
+    fn fifty_move_score(fen: &str) -> i16 {
+        let mut test = TestSearch::new(fen);
+        test.search_params.quiet = true;
+
+        let mut refs = test.refs();
+
+        let mut pv = Vec::new();
+        Search::alpha_beta(3, -INF, INF, &mut pv, &mut refs)
+    }
+
+    // Searches the position after white's queen and black's king shuffle
+    // back to the start, as the node one ply below the root.
+    fn repeated_position_score(setup: impl Fn(&mut SearchParams)) -> i16 {
+        let mut test = TestSearch::new("k7/8/8/8/8/8/8/3QK3 w - - 0 1");
+        for san in ["Qd2", "Kb8", "Qd1", "Ka8"] {
+            let m = test.board.san_to_move(san, &test.mg).unwrap();
+            assert!(test.board.make(m, &test.mg));
+        }
+        test.search_params.quiet = true;
+        setup(&mut test.search_params);
+        test.search_info.ply = 1;
+
+        let mut refs = test.refs();
+
+        let mut pv = Vec::new();
+        Search::alpha_beta(3, -INF, INF, &mut pv, &mut refs)
+    }
+
+    #[test]
+    fn test_repetition_policy_penalises_winning_side() {
+        // A queen up, the twofold repetition costs twice the largest penalty.
+        let largest = RepetitionPolicy::new().penalties[0].1;
+        assert_eq!(repeated_position_score(|sp| sp.repetition = RepetitionPolicy::new()), DRAW + 2 * largest);
+
+        // Without penalties, the repetition is simply a draw.
+        assert_eq!(repeated_position_score(|sp| sp.repetition = RepetitionPolicy::scaled(0)), DRAW);
+    }
+
+    // Searches the start position to depth 2 and returns the numbers of the
+    // root moves reported as the current move.
+    fn current_move_reports(interval: u128) -> Vec<u8> {
+        let mut test = TestSearch::new(FEN_START_POSITION);
+        test.search_params.quiet = false;
+        test.search_params.curr_move_interval = interval;
+        test.search_info.timer_start();
+
+        let mut refs = test.refs();
+
+        let mut pv = Vec::new();
+        Search::alpha_beta(2, -INF, INF, &mut pv, &mut refs);
+
+        test.report_rx
+            .try_iter()
+            .filter_map(|info| match info {
+                Information::Search(SearchReport::SearchCurrentMove(current)) => {
+                    Some(current.curr_move_number)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_root_search_reports_current_move() {
+        // Without an interval, every root move is reported, with its
+        // 1-based number.
+        let numbers: Vec<u8> = (1..=20).collect();
+        assert_eq!(current_move_reports(0), numbers);
+
+        // The search ends long before the default interval has passed.
+        assert!(current_move_reports(MIN_TIME_CURR_MOVE).is_empty());
+    }
+
+    #[test]
+    fn test_root_avoids_threefold_when_winning() {
+        let mut test = TestSearch::new("k7/8/8/8/8/8/8/3QK3 w - - 0 1").without_tt();
+        for san in ["Qd2", "Kb8", "Qd1", "Ka8", "Qd2", "Kb8", "Qd1", "Ka8"] {
+            let m = test.board.san_to_move(san, &test.mg).unwrap();
+            assert!(test.board.make(m, &test.mg));
+        }
+        let repeating = test.board.san_to_move("Qd2", &test.mg).unwrap();
+        test.search_params.quiet = true;
+
+        let mut refs = test.refs();
+
+        // Qd2 repeats the position for the third time: a draw.
+        refs.search_params.search_moves = vec![repeating];
+        let mut pv = Vec::new();
+        assert_eq!(Search::alpha_beta(3, -INF, INF, &mut pv, &mut refs), DRAW);
+
+        // With all moves available, a queen up, the engine plays on.
+        refs.search_params.search_moves.clear();
+        let mut pv = Vec::new();
+        let score = Search::alpha_beta(3, -INF, INF, &mut pv, &mut refs);
+        assert!(score > PIECE_VALUES[Pieces::ROOK]);
+        assert_ne!(pv[0].get_move(), repeating.get_move());
+    }
+
+    #[test]
+    fn test_analyse_mode_scores_repetition_as_draw() {
+        // Contempt would make the draw look better for the side at ply 1.
+        let with_contempt = |sp: &mut SearchParams| sp.contempt = 50;
+        assert_ne!(repeated_position_score(with_contempt), DRAW);
+
+        // When analysing, neither the winning side's penalty nor contempt
+        // is applied.
+        let analysing = |sp: &mut SearchParams| {
+            sp.contempt = 50;
+            sp.analyse_mode = true;
+        };
+        assert_eq!(repeated_position_score(analysing), DRAW);
+    }
+
+    #[test]
+    fn test_dead_drawn_material_cuts_search() {
+        let mut test = TestSearch::new("4k3/8/8/8/8/8/8/4KN2 w - - 0 1");
+        test.search_params.quiet = true;
+
+        let mut refs = test.refs();
+
+        // Every move from the root reaches a KNvK position, which is a
+        // draw without being searched any further.
+        let mut pv = Vec::new();
+        let score = Search::alpha_beta(10, -INF, INF, &mut pv, &mut refs);
+        assert_eq!(score, DRAW);
+        let nodes = refs.thread_local_data.nodes_searched;
+        assert!(nodes < 100, "nodes: {nodes}");
+    }
+
+    #[test]
+    fn test_fifty_move_rule_draws_after_quiet_move() {
+        // A queen up, but without a pawn to move or anything to capture,
+        // every move reaches the 100th ply.
+        assert_eq!(fifty_move_score("k7/8/8/8/8/8/8/3QK3 w - - 99 120"), DRAW);
+    }
+
+    #[test]
+    fn test_fifty_move_rule_allows_mate_on_last_move() {
+        // Rh8 mates on the move reaching the 100th ply.
+        let score = fifty_move_score("k7/8/1K6/8/8/8/8/7R w - - 99 120");
+        assert_eq!(score, CHECKMATE - 1);
+    }
 }
@@ -18,7 +18,7 @@ The definitions here control the behaviour of:
 use crate::{
     board::{Board, defs::ZobristKey},
     defs::{MAX_PLY, NrOf, Sides},
-    engine::defs::{Information, SearchData, TT, LocalTTCache},
+    engine::defs::{Information, SearchData, TT, TTPrefetch, LocalTTCache},
     movegen::{
         defs::{Move, ShortMove},
         MoveGenerator,
@@ -26,7 +26,7 @@ use crate::{
 };
 use crossbeam_channel::{Receiver, Sender};
 use std::{
-    sync::{Arc, Mutex, RwLock},
+    sync::{atomic::AtomicBool, Arc, Mutex, OnceLock, RwLock},
     time::Instant,
 };
 
@@ -42,9 +42,14 @@ pub use super::time::OVERHEAD;
 pub const INF: i16 = 25_000;
 
 /// Window size for aspiration search optimisation. Starts with a narrow window
-/// around the previous iteration's score and widens if the search fails.
+/// around the previous iteration's score and widens if the search fails
+/// (UCI option AspirationWindow).
 pub const ASPIRATION_WINDOW: i16 = 50;
 
+/// Percentage by which a failed aspiration window grows before the same depth
+/// is searched again: 200 doubles it (UCI option AspirationGrowth).
+pub const ASPIRATION_GROWTH: i16 = 200;
+
 /// Base checkmate score. Actual mate scores are calculated as CHECKMATE - distance_to_mate
 /// to prefer shorter mates over longer ones.
 pub const CHECKMATE: i16 = 24_000;
@@ -62,9 +67,18 @@ pub const DRAW: i16 = 0;
 /// are considered roughly equivalent for tactical sequence analysis.
 pub const SHARP_MARGIN: i16 = 30;
 
-/// Maximum depth for analysing sharp tactical sequences to prevent excessive computation.
+/// Default maximum depth for analysing sharp tactical sequences to prevent
+/// excessive computation (UCI option SharpDepthCap).
 pub const SHARP_SEQUENCE_DEPTH_CAP: i8 = 3;
 
+/// Maximum number of sharp root moves searched again for their exact score
+/// when the search prefers sharp moves (UCI option SharpOnly).
+pub const SHARP_CANDIDATES: usize = 3;
+
+/// Drop in centipawns from the opponent's best to second best reply at
+/// which a forcing root move counts as fully sharp.
+pub const SHARPNESS_GAP_SCALE: i16 = 100;
+
 // =======================================================================
 // SEARCH TIMING AND STATISTICS
 // =======================================================================
@@ -89,7 +103,14 @@ pub const MIN_TIME_CURR_MOVE: u128 = 1_000;
 
 /// Maximum number of killer moves stored per ply. Killer moves are quiet moves
 /// that caused beta cutoffs and are likely to be good in similar positions.
-pub const MAX_KILLER_MOVES: usize = 2;
+pub const MAX_KILLER_MOVES: usize = 3;
+
+/// Default number of killer slots in use per ply (UCI option KillerSlots).
+pub const KILLER_SLOTS: usize = 2;
+
+/// Upper bound for history heuristic scores. Cutoff bonuses shrink as an entry approaches
+/// this value, so scores saturate instead of growing without limit.
+pub const MAX_HISTORY: u32 = 16_384;
 
 // =======================================================================
 // SEARCH PRUNING TECHNIQUES
@@ -99,24 +120,67 @@ pub const MAX_KILLER_MOVES: usize = 2;
 /// search 3 plies shallower to detect if the position is still good enough for a cutoff.
 pub const NULL_MOVE_REDUCTION: i8 = 3;
 
-/// Standard Late Move Reduction amount for early moves. Reduces search depth by 1 ply
-/// for moves that are likely to be inferior (conservative reduction).
-pub const LMR_REDUCTION: i8 = 1;
+/// Maximum remaining depth at which Reverse Futility Pruning (static null move)
+/// is attempted. Deeper nodes always get a real search.
+pub const RFP_MAX_DEPTH: i8 = 3;
+
+/// Per-ply margin for Reverse Futility Pruning. If the static evaluation minus
+/// this margin times the remaining depth still beats beta, the node is cut.
+pub const RFP_MARGIN: i16 = 120;
+
+/// Futility pruning margin per ply of remaining depth (1 or 2). A quiet move
+/// is skipped when the static evaluation plus this margin cannot reach alpha.
+pub const FUTILITY_MARGIN: i16 = 150;
+
+/// Amount taken off the futility margin when the static evaluation is not
+/// improving, so that such nodes are pruned more eagerly.
+pub const FUTILITY_NOT_IMPROVING: i16 = 50;
+
+/// Razoring margin at depth 1. If the static evaluation plus this margin is still
+/// below alpha, the node drops straight into quiescence search.
+pub const RAZOR_MARGIN: i16 = 300;
+
+/// Maximum remaining depth at which Late Move Pruning is applied.
+pub const LMP_MAX_DEPTH: i8 = 3;
+
+/// Number of legal moves searched at the given depth before Late Move Pruning
+/// starts skipping the remaining quiet moves. Grows quadratically with depth,
+/// and is halved when the static evaluation is not improving.
+pub fn late_move_count(depth: i8, improving: bool) -> usize {
+    let d = depth.max(0) as usize;
+    let count = 3 + d * d;
+    if improving {
+        count
+    } else {
+        count / 2
+    }
+}
 
 /// Move number threshold for starting Late Move Reduction. Only begin reducing moves
 /// from the 4th move onwards, as early moves are more likely to be important.
 pub const LMR_MOVE_THRESHOLD: u8 = 4;
 
-/// Move number threshold for applying more aggressive Late Move Reduction.
-/// Moves beyond the 8th position get larger reductions as they're less likely to be best.
-pub const LMR_LATE_THRESHOLD: u8 = 8;
+/// Number of move slots in the Late Move Reduction table. Later moves use the last slot.
+pub const LMR_MAX_MOVES: usize = 64;
+
+/// Late Move Reduction formula, in hundredths of a ply: the reduction is
+/// `LMR_BASE / 100 + ln(depth) * ln(move_number) * 100 / LMR_DIVISOR`.
+pub const LMR_BASE: i16 = 75;
+pub const LMR_DIVISOR: i16 = 225;
 
-/// Reduction amount for very late moves (beyond LMR_LATE_THRESHOLD).
-/// Conservative 1-ply reduction to avoid missing important defensive resources.
-pub const LMR_LATE_REDUCTION: i8 = 1;
+/// Logarithmic Late Move Reduction table: [depth][move_number] -> reduction in plies,
+/// for the default LMR_BASE and LMR_DIVISOR. Built once on first use.
+pub static LMR_TABLE: OnceLock<[[i8; LMR_MAX_MOVES]; MAX_PLY as usize]> = OnceLock::new();
+
+/// The Late Move Reduction formula, rounded to plies and capped at `depth - 1`.
+fn lmr_formula(base: i16, divisor: i16, depth: usize, move_number: usize) -> i8 {
+    let r = base as f64 / 100.0 + (depth as f64).ln() * (move_number as f64).ln() * 100.0 / divisor as f64;
+    (r.round() as i8).min(depth as i8 - 1)
+}
 
 /// Minimum depth required before applying Late Move Reduction. Only use LMR
-/// in deeper searches where the time savings are worthwhile.
+/// in deeper searches where the time savings are worthwhile (UCI option
+/// LmrMinDepth).
 pub const LMR_MIN_DEPTH: i8 = 4;
 
 /// Minimum depth for applying Multi-Cut pruning. This aggressive technique
@@ -132,10 +196,250 @@ pub const MULTICUT_CUTOFFS: u8 = 2;
 /// Maximum number of moves to try in Multi-Cut before giving up.
 pub const MULTICUT_MOVES: u8 = 4;
 
+/// Minimum remaining depth for singular extension. Below this depth the verification
+/// search costs more than the extension is worth.
+pub const SINGULAR_MIN_DEPTH: i8 = 8;
+
+/// Per-ply margin below the TT value used in the singular verification search. If every
+/// other move fails low against this lowered bound, the TT move is singular.
+pub const SINGULAR_MARGIN: i16 = 2;
+
+/// Safety margin for delta pruning in quiescence search. A capture is skipped if
+/// winning the captured piece plus this margin still can't raise the score to alpha.
+pub const DELTA_MARGIN: i16 = 200;
+
 /// Depth extension for recapture moves. Recaptures are tactically important
 /// and deserve extra search attention.
 pub const RECAPTURE_EXTENSION: i8 = 1;
 
+/// Maximum number of plies one line can be extended by singular and recapture
+/// extensions together. Once reached, neither extension is applied any more.
+pub const MAX_LINE_EXTENSIONS: i8 = 4;
+
+/// Default maximum number of check extensions along one line. Without a
+/// limit, a perpetual check keeps extending the search, and the node count
+/// explodes (UCI option MaxCheckExtensions).
+pub const MAX_CHECK_EXTENSIONS: i8 = 8;
+
+/// Repetition penalties by the static evaluation of the side to move, as
+/// (minimum evaluation, penalty) pairs from the highest threshold down. The
+/// further ahead, the more a repetition costs, so won positions aren't drawn.
+pub const REPETITION_PENALTIES: [(i16, i16); 3] = [(300, -150), (150, -75), (50, -25)];
+
+/// Repetition penalty in roughly equal positions, below the lowest threshold.
+pub const REPETITION_EQUAL_PENALTY: i16 = -10;
+
+/// Evaluation at or below which a repetition is not penalised: the losing side
+/// is happy to draw.
+pub const REPETITION_LOSING_THRESHOLD: i16 = -150;
+
+/// How a repetition in the search is scored, relative to the draw score.
+#[derive(PartialEq, Clone, Copy)]
+pub struct RepetitionPolicy {
+    pub penalties: [(i16, i16); 3],
+    pub equal_penalty: i16,
+    pub losing_threshold: i16,
+}
+
+impl RepetitionPolicy {
+    pub fn new() -> Self {
+        Self {
+            penalties: REPETITION_PENALTIES,
+            equal_penalty: REPETITION_EQUAL_PENALTY,
+            losing_threshold: REPETITION_LOSING_THRESHOLD,
+        }
+    }
+
+    /// The default policy with its penalties scaled to the given percentage.
+    /// At 0, repetitions score exactly as a draw.
+    pub fn scaled(percent: i16) -> Self {
+        let scale = |penalty: i16| (penalty as i32 * percent as i32 / 100) as i16;
+        let mut policy = Self::new();
+        for (_, penalty) in policy.penalties.iter_mut() {
+            *penalty = scale(*penalty);
+        }
+        policy.equal_penalty = scale(policy.equal_penalty);
+        policy
+    }
+
+    /// The penalty for a position repeated this many times, given its static
+    /// evaluation. Each further repetition adds the penalty once more.
+    pub fn penalty(&self, eval: i16, repetition_count: u8) -> i16 {
+        let penalty = if eval <= self.losing_threshold {
+            0
+        } else {
+            self.penalties
+                .iter()
+                .find(|(threshold, _)| eval >= *threshold)
+                .map_or(self.equal_penalty, |(_, penalty)| *penalty)
+        };
+
+        penalty * (repetition_count as i16 + 1)
+    }
+
+    /// True if repetitions are not penalised at all.
+    pub fn is_disabled(&self) -> bool {
+        self.equal_penalty == 0 && self.penalties.iter().all(|(_, penalty)| *penalty == 0)
+    }
+}
+
+impl Default for RepetitionPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// =======================================================================
+// SEARCH TUNABLES
+// =======================================================================
+
+/// Search constants that can be changed at runtime, so they can be tuned
+/// (with SPSA, for example). The search reads them from its SearchParams
+/// instead of the constants above, which are their defaults. With the
+/// "tune" feature, each one is a UCI option of the same name.
+#[derive(PartialEq, Clone, Copy)]
+pub struct SearchTunables {
+    pub null_move_reduction: i8,
+    pub lmr_move_threshold: u8,
+    pub lmr_base: i16,
+    pub lmr_divisor: i16,
+    pub rfp_margin: i16,
+    pub futility_margin: i16,
+    pub futility_not_improving: i16,
+    pub razor_margin: i16,
+    pub singular_margin: i16,
+    pub delta_margin: i16,
+}
+
+impl SearchTunables {
+    pub const NULL_MOVE_REDUCTION: &'static str = "NullMoveReduction";
+    pub const LMR_MOVE_THRESHOLD: &'static str = "LmrMoveThreshold";
+    pub const LMR_BASE: &'static str = "LmrBase";
+    pub const LMR_DIVISOR: &'static str = "LmrDivisor";
+    pub const RFP_MARGIN: &'static str = "RfpMargin";
+    pub const FUTILITY_MARGIN: &'static str = "FutilityMargin";
+    pub const FUTILITY_NOT_IMPROVING: &'static str = "FutilityNotImproving";
+    pub const RAZOR_MARGIN: &'static str = "RazorMargin";
+    pub const SINGULAR_MARGIN: &'static str = "SingularMargin";
+    pub const DELTA_MARGIN: &'static str = "DeltaMargin";
+
+    /// Name, minimum and maximum of each tunable.
+    pub const RANGES: [(&'static str, i16, i16); 10] = [
+        (Self::NULL_MOVE_REDUCTION, 1, 6),
+        (Self::LMR_MOVE_THRESHOLD, 1, 32),
+        (Self::LMR_BASE, 0, 300),
+        (Self::LMR_DIVISOR, 100, 600),
+        (Self::RFP_MARGIN, 0, 500),
+        (Self::FUTILITY_MARGIN, 0, 500),
+        (Self::FUTILITY_NOT_IMPROVING, 0, 200),
+        (Self::RAZOR_MARGIN, 0, 1000),
+        (Self::SINGULAR_MARGIN, 0, 20),
+        (Self::DELTA_MARGIN, 0, 1000),
+    ];
+
+    pub fn new() -> Self {
+        Self {
+            null_move_reduction: NULL_MOVE_REDUCTION,
+            lmr_move_threshold: LMR_MOVE_THRESHOLD,
+            lmr_base: LMR_BASE,
+            lmr_divisor: LMR_DIVISOR,
+            rfp_margin: RFP_MARGIN,
+            futility_margin: FUTILITY_MARGIN,
+            futility_not_improving: FUTILITY_NOT_IMPROVING,
+            razor_margin: RAZOR_MARGIN,
+            singular_margin: SINGULAR_MARGIN,
+            delta_margin: DELTA_MARGIN,
+        }
+    }
+
+    /// Returns the current value of the tunable with the given name (not
+    /// case sensitive), or None if there is no such tunable.
+    pub fn get(&self, name: &str) -> Option<i16> {
+        let value = match Self::RANGES.iter().find(|r| r.0.eq_ignore_ascii_case(name))?.0 {
+            Self::NULL_MOVE_REDUCTION => self.null_move_reduction as i16,
+            Self::LMR_MOVE_THRESHOLD => self.lmr_move_threshold as i16,
+            Self::LMR_BASE => self.lmr_base,
+            Self::LMR_DIVISOR => self.lmr_divisor,
+            Self::RFP_MARGIN => self.rfp_margin,
+            Self::FUTILITY_MARGIN => self.futility_margin,
+            Self::FUTILITY_NOT_IMPROVING => self.futility_not_improving,
+            Self::RAZOR_MARGIN => self.razor_margin,
+            Self::SINGULAR_MARGIN => self.singular_margin,
+            Self::DELTA_MARGIN => self.delta_margin,
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    /// Sets the tunable with the given name (not case sensitive), clamping
+    /// the value to its range. Returns false if there is no such tunable.
+    pub fn set(&mut self, name: &str, value: i16) -> bool {
+        let Some(&(name, min, max)) = Self::RANGES.iter().find(|r| r.0.eq_ignore_ascii_case(name)) else {
+            return false;
+        };
+
+        let v = value.clamp(min, max);
+        match name {
+            Self::NULL_MOVE_REDUCTION => self.null_move_reduction = v as i8,
+            Self::LMR_MOVE_THRESHOLD => self.lmr_move_threshold = v as u8,
+            Self::LMR_BASE => self.lmr_base = v,
+            Self::LMR_DIVISOR => self.lmr_divisor = v,
+            Self::RFP_MARGIN => self.rfp_margin = v,
+            Self::FUTILITY_MARGIN => self.futility_margin = v,
+            Self::FUTILITY_NOT_IMPROVING => self.futility_not_improving = v,
+            Self::RAZOR_MARGIN => self.razor_margin = v,
+            Self::SINGULAR_MARGIN => self.singular_margin = v,
+            Self::DELTA_MARGIN => self.delta_margin = v,
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Adaptive null move reduction. Starts at the null move reduction and grows
+    /// with the remaining depth and with the margin by which the static evaluation
+    /// beats beta (one ply per 200 centipawns, at most 3), so clearly won positions
+    /// are pruned harder.
+    pub fn null_move_reduction(&self, depth: i8, static_eval: i16, beta: i16) -> i8 {
+        let margin = (static_eval as i32 - beta as i32).max(0) / 200;
+        self.null_move_reduction + depth.max(0) / 6 + margin.min(3) as i8
+    }
+
+    /// Returns the Late Move Reduction for a move at the given depth and move
+    /// number. The default formula is looked up in LMR_TABLE; a tuned one is
+    /// calculated.
+    pub fn lmr_reduction(&self, depth: i8, move_number: usize) -> i8 {
+        let d = depth.clamp(0, MAX_PLY - 1) as usize;
+        let m = move_number.min(LMR_MAX_MOVES - 1);
+        if d == 0 || m == 0 {
+            return 0;
+        }
+
+        if self.lmr_base != LMR_BASE || self.lmr_divisor != LMR_DIVISOR {
+            return lmr_formula(self.lmr_base, self.lmr_divisor, d, m);
+        }
+
+        let table = LMR_TABLE.get_or_init(|| {
+            let mut table = [[0i8; LMR_MAX_MOVES]; MAX_PLY as usize];
+            for (d, row) in table.iter_mut().enumerate().skip(1) {
+                for (m, reduction) in row.iter_mut().enumerate().skip(1) {
+                    *reduction = lmr_formula(LMR_BASE, LMR_DIVISOR, d, m);
+                }
+            }
+            table
+        });
+
+        table[d][m]
+    }
+}
+
+impl Default for SearchTunables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // =======================================================================
 // TIME MANAGEMENT CONSTANTS
 // =======================================================================
@@ -150,22 +454,55 @@ pub const EMERGENCY_MAX_DEPTH: i8 = 8;
 /// Factor for reducing time allocation in emergency mode (50% of normal time).
 pub const EMERGENCY_TIME_FACTOR: f64 = 0.5;
 
+/// Upper bounds in milliseconds of the estimated game duration (clock
+/// plus TIME_CONTROL_MOVES increments) for bullet, blitz and rapid games.
+/// Longer games are classical.
+pub const BULLET_MAX_TIME: u128 = 180_000;
+pub const BLITZ_MAX_TIME: u128 = 900_000;
+pub const RAPID_MAX_TIME: u128 = 3_600_000;
+
+/// Number of moves assumed when estimating the game duration, so the
+/// increment is taken into account.
+pub const TIME_CONTROL_MOVES: u128 = 40;
+
+/// Number of iterations the root best move must stay the same before the
+/// search may stop early.
+pub const STABILITY_ITERATIONS: u8 = 4;
+
+/// Fraction of the allocated time that must be used before a stable best
+/// move stops the search early.
+pub const STABILITY_TIME_FRACTION: f64 = 0.3;
+
+/// Gaps in centipawns between the scores of the best and the second best
+/// root move, from which the best move is judged Poor, Acceptable, Good
+/// and Excellent. Smaller gaps are Critical.
+pub const QUALITY_POOR_GAP: i16 = 10;
+pub const QUALITY_ACCEPTABLE_GAP: i16 = 30;
+pub const QUALITY_GOOD_GAP: i16 = 100;
+pub const QUALITY_EXCELLENT_GAP: i16 = 200;
+
+/// Extra aspiration window given to helper threads in Lazy SMP, per thread
+/// ID. Slightly different windows make the helpers diverge from the main
+/// thread, so they fill the shared TT with different parts of the tree.
+pub const HELPER_ASPIRATION_STEP: i16 = 10;
+
+/// Added to each thread's score above the lowest score when the threads
+/// vote on the best move, so even the worst scoring thread has a vote.
+pub const VOTE_SCORE_OFFSET: i64 = 14;
+
 // =======================================================================
 // GAME PHASE DETECTION CONSTANTS
 // =======================================================================
 
-/// Ply threshold for considering a game to be in the opening phase.
-/// Used for adaptive time management and move selection strategies.
+/// Last ply of the opening. Used for adaptive time management.
 pub const OPENING_PLY_THRESHOLD: usize = 25;
 
-/// Ply threshold for transitioning from opening to early middlegame.
+/// Last ply of the early middlegame; after it, the late middlegame starts.
 pub const EARLY_MIDDLEGAME_PLY_THRESHOLD: usize = 30;
 
-/// Ply threshold for transitioning to late middlegame phase.
-pub const LATE_MIDDLEGAME_PLY_THRESHOLD: usize = 40;
-
-/// Piece count threshold for detecting endgame phase. When total pieces
-/// on board drop below this number, endgame time management kicks in.
+/// Piece count threshold for detecting endgame phase. When the pieces on
+/// the board, kings included and pawns not, number this many or fewer,
+/// the game is in the endgame, whatever the ply.
 pub const ENDGAME_PIECE_THRESHOLD: usize = 12;
 
 // =======================================================================
@@ -202,7 +539,7 @@ pub enum TimeControl {
 
 /// Quality assessment of the current move situation.
 /// Used to determine if extra time should be allocated.
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Copy, Clone, Debug)]
 pub enum MoveQuality {
     /// Clear best move identified, can move quickly
     Excellent,
@@ -239,6 +576,10 @@ pub struct TimeStats {
     /// Time usage statistics broken down by game phase
     /// Helps optimise phase-specific time allocation
     pub time_usage_by_phase: std::collections::HashMap<GamePhase, u128>,
+
+    /// Moving average of the search speed in nodes per second (0 if unknown)
+    /// Used to turn time allocations into node budgets
+    pub average_nps: usize,
     
     /// Timestamp of the last statistics update
     pub last_update: std::time::Instant,
@@ -254,6 +595,7 @@ impl TimeStats {
             time_losses: 0,
             average_time_per_move: 0,
             time_usage_by_phase: std::collections::HashMap::new(),
+            average_nps: 0,
             last_update: std::time::Instant::now(),
         }
     }
@@ -287,6 +629,16 @@ impl TimeStats {
         self.last_update = std::time::Instant::now();
     }
 
+    /// Updates the average search speed with the speed of a completed move,
+    /// using the same exponential moving average as the phase statistics.
+    pub fn update_nps(&mut self, nps: usize) {
+        self.average_nps = if self.average_nps == 0 {
+            nps
+        } else {
+            (self.average_nps + nps) / 2
+        };
+    }
+
     /// Calculates the success rate of time allocations as a percentage.
     /// Returns 0.0 if no moves have been tracked yet.
     /// 
@@ -311,6 +663,10 @@ pub type SearchResult = (Move, SearchTerminate);
 /// Unique identifier for search threads in multi-threaded search.
 pub type ThreadId = u32;
 
+/// Result of one search thread: best move, last completed depth and the
+/// score of that depth. Helper threads send this to the main thread.
+pub type ThreadResult = (Move, i8, i16);
+
 /// Killer moves storage: [ply][killer_slot] -> move
 /// Stores the best quiet moves that caused cutoffs at each ply level.
 type KillerMoves = [[ShortMove; MAX_KILLER_MOVES]; MAX_PLY as usize];
@@ -405,6 +761,10 @@ pub struct ThreadLocalData {
     /// Batch container for pending transposition table updates.
     /// Reduces write lock contention on the global TT.
     pub tt_batch: TTBatch,
+
+    /// Location of the transposition table's buckets, taken when the
+    /// search starts, so they can be prefetched without locking the TT.
+    pub tt_prefetch: TTPrefetch,
     
     /// Timestamp when the current search iteration began.
     /// Used for time management and search termination.
@@ -421,6 +781,15 @@ pub struct ThreadLocalData {
     /// Current search depth reached by this thread.
     /// Used for iterative deepening and depth-based termination.
     pub search_depth: i8,
+
+    /// Last depth this thread searched completely, and its score.
+    /// Used by the main thread to pick the best result of all threads.
+    pub completed_depth: i8,
+    pub best_score: i16,
+
+    /// Termination flag shared by all threads of a search. When one of
+    /// them sets it, the others stop at their next termination check.
+    pub terminated: Arc<AtomicBool>,
 }
 
 impl ThreadLocalData {
@@ -434,13 +803,23 @@ impl ThreadLocalData {
             thread_id,
             local_tt_cache: LocalTTCache::new(),
             tt_batch: TTBatch::new(),
+            tt_prefetch: TTPrefetch::default(),
             search_start_time: None,
             nodes_searched: 0,
             best_move_found: None,
             search_depth: 0,
+            completed_depth: 0,
+            best_score: 0,
+            terminated: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// Returns true for the main search thread, which reports to the GUI
+    /// and collects the results of the helper threads.
+    pub fn is_main_thread(&self) -> bool {
+        self.thread_id == 0
+    }
+
     /// Initialises the thread-local data for a new search iteration.
     /// Clears all caches and resets counters to prepare for fresh search.
     pub fn start_search(&mut self) {
@@ -448,6 +827,8 @@ impl ThreadLocalData {
         self.nodes_searched = 0;
         self.best_move_found = None;
         self.search_depth = 0;
+        self.completed_depth = 0;
+        self.best_score = 0;
         
         // Clear caches to avoid stale data from previous searches
         self.local_tt_cache.clear();
@@ -492,11 +873,13 @@ impl ThreadLocalData {
 #[derive(PartialEq, Clone)]
 pub enum SearchControl {
     /// Begin a new search with the specified parameters
-    Start(SearchParams),
+    Start(Box<SearchParams>),
     /// Stop the current search and return the best move found so far
     Stop,
     /// Terminate the search thread permanently
     Quit,
+    /// Report the persistent time management statistics to the engine
+    ReportTimeStats,
     /// No action required (placeholder value)
     Nothing,
 }
@@ -577,7 +960,7 @@ impl GameTime {
 
 /// Complete set of search parameters and configuration options.
 /// Contains all information needed to configure a search iteration.
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Clone)]
 pub struct SearchParams {
     /// Maximum depth to search (for depth-limited searches)
     pub depth: i8,
@@ -587,12 +970,57 @@ pub struct SearchParams {
     pub nodes: usize,
     /// Time control parameters (for game-time searches)
     pub game_time: GameTime,
+    /// Time control of the game, classified from the clock at its start
+    pub time_control: Option<TimeControl>,
     /// Search mode determining termination criteria
     pub search_mode: SearchMode,
     /// Whether to suppress output during search (for background analysis)
     pub quiet: bool,
+    /// Minimum time in milliseconds between reports of the current move
+    pub curr_move_interval: u128,
     /// Evaluation margin for sharp move analysis
     pub sharp_margin: i16,
+    /// Whether hopeless quiet moves near the horizon may be pruned
+    pub futility_pruning: bool,
+    /// Whether the search window is narrowed by the distance to mate
+    pub mate_distance_pruning: bool,
+    /// Whether quiescence search skips captures that lose material (SEE < 0)
+    pub see_pruning: bool,
+    /// Whether recaptures on the square of the previous capture are extended
+    pub recapture_extension: bool,
+    /// Whether the TT bucket of a position is prefetched right after its move is made
+    pub tt_prefetch: bool,
+    /// Number of best root moves to search and report (UCI MultiPV)
+    pub multi_pv: usize,
+    /// Root moves to restrict the search to (UCI searchmoves); empty means all
+    pub search_moves: Vec<Move>,
+    /// Time in milliseconds kept in reserve for GUI and network lag
+    pub move_overhead: u128,
+    /// Centipawns a draw is worth less than equal to the side to move at the root
+    pub contempt: i16,
+    /// Whether game time is turned into a node budget, using the historical speed
+    pub use_nodes_time: bool,
+    /// Whether root moves leaving the opponent one good reply are preferred
+    pub sharp_only: bool,
+    /// Maximum depth of the reply analysis behind each root move
+    pub sharp_depth_cap: i8,
+    /// Search constants, which can be changed for tuning
+    pub tunables: SearchTunables,
+    /// How repetitions are scored relative to a draw
+    pub repetition: RepetitionPolicy,
+    /// Maximum number of check extensions along one line
+    pub max_check_extensions: i8,
+    /// Whether the GUI is analysing (UCI_AnalyseMode): draws score exactly as
+    /// draws, and the search doesn't stop early on a stable best move
+    pub analyse_mode: bool,
+    /// Number of killer moves kept per ply, up to MAX_KILLER_MOVES
+    pub killer_slots: usize,
+    /// Minimum remaining depth at which Late Move Reduction is applied
+    pub lmr_min_depth: i8,
+    /// Initial aspiration window around the previous score, in centipawns
+    pub aspiration_window: i16,
+    /// Percentage by which a failed aspiration window grows
+    pub aspiration_growth: i16,
 }
 
 impl SearchParams {
@@ -604,9 +1032,31 @@ impl SearchParams {
             move_time: 0,
             nodes: 0,
             game_time: GameTime::new(0, 0, 0, 0, None),
+            time_control: None,
             search_mode: SearchMode::Nothing,
             quiet: false,
+            curr_move_interval: MIN_TIME_CURR_MOVE,
             sharp_margin: SHARP_MARGIN,
+            futility_pruning: true,
+            mate_distance_pruning: true,
+            see_pruning: true,
+            recapture_extension: true,
+            tt_prefetch: true,
+            multi_pv: 1,
+            search_moves: Vec::new(),
+            move_overhead: OVERHEAD,
+            contempt: 0,
+            use_nodes_time: false,
+            sharp_only: false,
+            sharp_depth_cap: SHARP_SEQUENCE_DEPTH_CAP,
+            tunables: SearchTunables::new(),
+            repetition: RepetitionPolicy::new(),
+            max_check_extensions: MAX_CHECK_EXTENSIONS,
+            analyse_mode: false,
+            killer_slots: KILLER_SLOTS,
+            lmr_min_depth: LMR_MIN_DEPTH,
+            aspiration_window: ASPIRATION_WINDOW,
+            aspiration_growth: ASPIRATION_GROWTH,
         }
     }
 
@@ -652,16 +1102,54 @@ pub struct SearchInfo {
     /// Counter moves table: [side][piece][square] -> move
     /// Stores best replies to opponent moves for move ordering
     pub counter_moves: [[[ShortMove; NrOf::SQUARES]; NrOf::PIECE_TYPES]; Sides::BOTH],
+
+    /// Counter move history: [side][piece][square] -> score
+    /// Confidence in the counter move stored in the same slot of `counter_moves`,
+    /// grown each time it causes a beta cutoff after that opponent move
+    pub counter_move_history: [[[i32; NrOf::SQUARES]; NrOf::PIECE_TYPES]; Sides::BOTH],
     
     /// Timestamp of last current move report to GUI
     pub last_curr_move_sent: u128,
     
     /// Time allocated for the current move in milliseconds
     pub allocated_time: u128,
+
+    /// Nodes allocated for the current move, when game time is node-based
+    pub node_budget: Option<usize>,
     
     /// Current search termination status
     pub terminate: SearchTerminate,
     
+    /// Move excluded from the search at each ply: [ply] -> move
+    /// Set during a singular extension verification search; empty (0) otherwise
+    pub excluded_moves: [ShortMove; MAX_PLY as usize],
+
+    /// Check extensions along the current line, up to and including each
+    /// ply: [ply] -> count. Each node derives its entry from the ply before,
+    /// so the count starts again for every root move.
+    pub check_extensions: [i8; MAX_PLY as usize],
+
+    /// Number of singular extensions applied during this search
+    pub singular_extensions: usize,
+
+    /// Number of recapture extensions applied during this search
+    pub recapture_extensions: usize,
+
+    /// Number of moves searched with a Late Move Reduction during this search
+    pub lmr_reductions: usize,
+
+    /// Plies by which the current line has been extended (singular and
+    /// recapture extensions), bounded by MAX_LINE_EXTENSIONS
+    pub line_extensions: i8,
+
+    /// Static evaluation at each ply of the current line: [ply] -> eval
+    /// Used for the "improving" heuristic; -INF when in check (unknown)
+    pub static_evals: Vec<i16>,
+
+    /// Root moves skipped while searching further MultiPV lines
+    /// Holds the first move of each line already found in this iteration
+    pub root_excluded: Vec<ShortMove>,
+    
     /// Analysis of all legal moves at the root position
     pub root_analysis: Vec<RootMoveAnalysis>,
     
@@ -680,6 +1168,18 @@ pub struct SearchInfo {
     
     /// Maximum search depth allowed (may be limited by time pressure)
     pub max_depth: i8,
+
+    /// Time control of the game, classified when a search on the clock
+    /// starts (Rapid otherwise)
+    pub time_control: TimeControl,
+
+    /// Number of consecutive completed iterations with the same best move
+    pub best_move_stability: u8,
+
+    /// How far the best root move scores above the second best one, in the
+    /// last completed iteration. Gaps outside the quality gaps are cut off
+    /// at the nearest one.
+    pub second_best_gap: Option<i16>,
     
     /// Comprehensive time management statistics and tracking
     pub time_stats: TimeStats,
@@ -698,15 +1198,28 @@ impl SearchInfo {
             killer_moves: [[ShortMove::new(0); MAX_KILLER_MOVES]; MAX_PLY as usize],
             history_heuristic: [[[0u32; NrOf::SQUARES]; NrOf::PIECE_TYPES]; Sides::BOTH],
             counter_moves: [[[ShortMove::new(0); NrOf::SQUARES]; NrOf::PIECE_TYPES]; Sides::BOTH],
+            counter_move_history: [[[0i32; NrOf::SQUARES]; NrOf::PIECE_TYPES]; Sides::BOTH],
             last_stats_sent: 0,
             last_curr_move_sent: 0,
             allocated_time: 0,
+            node_budget: None,
             terminate: SearchTerminate::Nothing,
+            excluded_moves: [ShortMove::new(0); MAX_PLY as usize],
+            check_extensions: [0; MAX_PLY as usize],
+            singular_extensions: 0,
+            recapture_extensions: 0,
+            lmr_reductions: 0,
+            line_extensions: 0,
+            static_evals: vec![-INF; MAX_PLY as usize],
+            root_excluded: Vec::new(),
             root_analysis: Vec::new(),
             local_tt_cache: LocalTTCache::new(),
             tt_batch: TTBatch::new(),
             emergency_mode: false,
             max_depth: 0,
+            time_control: TimeControl::Rapid,
+            best_move_stability: 0,
+            second_best_gap: None,
             time_stats: TimeStats::new(),
         }
     }
@@ -730,6 +1243,19 @@ impl SearchInfo {
         }
     }
 
+    /// Stores the static evaluation for the given ply and returns whether it
+    /// is improving: higher than the evaluation two plies earlier, when the
+    /// same side was to move. Unknown evaluations never count as improving.
+    pub fn update_improving(&mut self, ply: i8, static_eval: i16) -> bool {
+        let ply = ply as usize;
+        self.static_evals[ply] = static_eval;
+
+        ply >= 2
+            && static_eval != -INF
+            && self.static_evals[ply - 2] != -INF
+            && static_eval > self.static_evals[ply - 2]
+    }
+
     /// Checks if the search has been interrupted by external command.
     /// 
     /// # Returns
@@ -753,6 +1279,18 @@ impl SearchInfo {
 // SEARCH REPORTING STRUCTURES
 // =======================================================================
 
+/// Relation of a reported score to the true score of the position.
+/// Scores from a failed aspiration window are only bounds.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum ScoreBound {
+    /// The score is exact
+    Exact,
+    /// The search failed high: the true score is at least this
+    Lower,
+    /// The search failed low: the true score is at most this
+    Upper,
+}
+
 /// Complete search results summary for GUI reporting.
 /// Contains all information typically sent in UCI "info" messages.
 #[derive(PartialEq, Clone)]
@@ -775,6 +1313,10 @@ pub struct SearchSummary {
     pub hash_full: u16,
     /// Principal variation (best line of play found)
     pub pv: Vec<Move>,
+    /// Rank of this line among the root moves (1 = best), for MultiPV
+    pub multipv: usize,
+    /// Whether the score is exact or a bound from a failed window
+    pub bound: ScoreBound,
 }
 
 impl SearchSummary {
@@ -784,12 +1326,44 @@ impl SearchSummary {
     /// # Returns
     /// Space-separated string of moves in algebraic notation
     pub fn pv_as_string(&self) -> String {
-        let mut pv = String::from("");
-        for next_move in self.pv.iter() {
-            let m = format!(" {}", next_move.as_string());
-            pv.push_str(&m[..]);
-        }
-        pv
+        self.pv
+            .iter()
+            .map(|m| m.as_string())
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+/// Sharpness of one root move: how many good replies the opponent has,
+/// and the forced line if there is only one. Reported after each depth.
+#[derive(PartialEq, Clone)]
+pub struct SharpInfo {
+    /// The root move
+    pub mv: Move,
+    /// Number of good replies available to the opponent
+    pub good_replies: usize,
+    /// Forced sequence following the root move
+    pub reply_sequence: Vec<Move>,
+}
+
+impl SharpInfo {
+    /// Formats the sharpness information, with the root move heading the line.
+    ///
+    /// # Returns
+    /// String such as "sharp move e2e4 replies 1 line e2e4 e7e5 g1f3"
+    pub fn as_string(&self) -> String {
+        let line = std::iter::once(&self.mv)
+            .chain(self.reply_sequence.iter())
+            .map(|m| m.as_string())
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        format!(
+            "sharp move {} replies {} line {}",
+            self.mv.as_string(),
+            self.good_replies,
+            line
+        )
     }
 }
 
@@ -861,10 +1435,30 @@ pub struct RootMoveAnalysis {
     pub good_replies: usize,
     /// Best reply found (if forced/limited options)
     pub reply: Option<Move>,
+    /// How much worse the opponent's second best reply is than the best one
+    pub reply_gap: i16,
+    /// Whether each of our follow-ups in the forced line is the only good one
+    pub our_forced: bool,
     /// Sequence of moves in sharp tactical lines
     pub reply_sequence: Vec<Move>,
 }
 
+/// Result of analysing the opponent's replies to a move, and our follow-ups
+/// while the replies are forced.
+#[derive(Default)]
+pub struct SharpSequence {
+    /// Number of good replies available to the opponent
+    pub good_replies: usize,
+    /// The only good reply, or else the best one
+    pub reply: Option<Move>,
+    /// How much worse the second best reply is than the best one
+    pub reply_gap: i16,
+    /// Whether each of our moves in the sequence is the only good one
+    pub our_forced: bool,
+    /// The forced reply followed by alternating moves of ours and theirs
+    pub sequence: Vec<Move>,
+}
+
 // =======================================================================
 // SEARCH CONTEXT STRUCTURE
 // =======================================================================
@@ -901,8 +1495,9 @@ pub struct SearchRefs<'a> {
 /// Used for communication between search logic and the main engine.
 #[derive(PartialEq, Clone)]
 pub enum SearchReport {
-    /// Search completed with the best move found
-    Finished(Move),
+    /// Search completed with the best move found, and the expected reply
+    /// to ponder on, if known
+    Finished(Move, Option<Move>),
     /// Comprehensive search results summary
     SearchSummary(SearchSummary),
     /// Information about current move being searched
@@ -911,4 +1506,8 @@ pub enum SearchReport {
     SearchStats(SearchStats),
     /// Arbitrary information string for debugging/logging
     InfoString(String),
+    /// Time management statistics, as requested by the engine
+    TimeStats(TimeStats),
+    /// Sharpness of a root move after a completed depth
+    SharpInfo(SharpInfo),
 }
\ No newline at end of file
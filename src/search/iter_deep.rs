@@ -22,25 +22,47 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 use super::{
-    defs::{SearchMode, SearchRefs, SearchResult, INF, ASPIRATION_WINDOW},
+    defs::{
+        RootMoveAnalysis, ScoreBound, SearchMode, SearchRefs, SearchResult, SharpInfo,
+        HELPER_ASPIRATION_STEP, INF, QUALITY_EXCELLENT_GAP, QUALITY_POOR_GAP,
+        SHARP_CANDIDATES,
+    },
     ErrFatal, Information, Search, SearchReport, SearchSummary,
 };
-use crate::{defs::MAX_PLY, movegen::defs::Move};
+use crate::{
+    defs::MAX_PLY,
+    movegen::defs::{Move, MoveList, MoveType},
+};
+use std::cmp::Reverse;
 
 // Actual search routines.
 impl Search {
     pub fn iterative_deepening(refs: &mut SearchRefs) -> SearchResult {
-        let mut depth = 1;
+        let is_main = refs.thread_local_data.is_main_thread();
+        // Lazy SMP: helper threads with an odd ID start one ply deeper, so
+        // not all threads search the same depth at the same time.
+        let mut depth = 1 + (refs.thread_local_data.thread_id % 2) as i8;
         let mut best_move = Move::new(0);
         let mut root_pv: Vec<Move> = Vec::new();
         let mut stop = false;
         let mut prev_eval: i16 = 0;
+        let mut previous_best = Move::new(0);
         let is_game_time = refs.search_params.is_game_time();
+        let mut base_allocation = 0;
+
+        // With a TT of size 0 ("Hash 0"), the search does without it.
+        if refs.tt_enabled {
+            let tt = refs.tt.read().expect(ErrFatal::LOCK);
+            refs.tt_enabled = tt.is_enabled();
+            refs.thread_local_data.tt_prefetch = tt.prefetcher();
+        }
 
         // Initialize thread-local data for this search
         refs.thread_local_data.start_search();
 
         if is_game_time {
+            refs.search_info.time_control = Search::game_time_control(refs);
+
             // Apply emergency time management first
             Search::emergency_time_management(refs);
             
@@ -49,11 +71,23 @@ impl Search {
             let factor = Search::dynamic_time_factor(refs);
 
             if time_slice > 0 {
-                refs.search_info.allocated_time = (time_slice as f64 * factor).round() as u128;
+                base_allocation = (time_slice as f64 * factor).round() as u128;
+                refs.search_info.allocated_time = base_allocation;
+                refs.search_info.node_budget =
+                    Search::node_budget(refs, refs.search_info.allocated_time);
             } else {
                 refs.search_params.search_mode = SearchMode::Depth;
                 refs.search_params.depth = 1;
             }
+
+            // With only one legal move there is nothing to think about. A
+            // depth 1 search still confirms it, so the GUI gets an info line.
+            if Search::legal_root_moves(refs) == 1 {
+                refs.search_info.max_depth = 1;
+            }
+        } else {
+            // Without a clock, the search mode alone limits the depth.
+            refs.search_info.max_depth = MAX_PLY;
         }
 
         refs.search_info.timer_start();
@@ -63,29 +97,12 @@ impl Search {
         
         while (depth <= refs.search_info.max_depth) && (depth <= refs.search_params.depth) && !stop {
             refs.search_info.depth = depth;
+            refs.search_info.seldepth = 0;
             refs.thread_local_data.search_depth = depth;
             refs.search_info.root_analysis.clear();
+            Search::age_history_heuristic(refs);
 
-            let mut alpha = if depth > 1 {
-                prev_eval - ASPIRATION_WINDOW
-            } else {
-                -INF
-            };
-            let mut beta = if depth > 1 {
-                prev_eval + ASPIRATION_WINDOW
-            } else {
-                INF
-            };
-
-            root_pv.clear();
-            let mut eval = Search::alpha_beta(depth, alpha, beta, &mut root_pv, refs);
-
-            if (eval <= alpha) || (eval >= beta) {
-                alpha = -INF;
-                beta = INF;
-                root_pv.clear();
-                eval = Search::alpha_beta(depth, alpha, beta, &mut root_pv, refs);
-            }
+            let (mut eval, re_searches) = Search::aspiration_search(depth, prev_eval, &mut root_pv, refs);
             prev_eval = eval;
 
             // Always update best_move if we have a valid PV, even if interrupted
@@ -108,118 +125,58 @@ impl Search {
             let interrupted = refs.search_info.interrupted();
             
             if !interrupted {
-                let elapsed = refs.search_info.timer_elapsed();
-                let nodes = refs.search_info.nodes;
-                let hash_full = refs.tt.read().expect(ErrFatal::LOCK).hash_full();
-
-                let forced_lines: Vec<(Move, Vec<Move>)> = refs
-                    .search_info
-                    .root_analysis
-                    .iter()
-                    .filter(|a| a.good_replies == 1)
-                    .map(|a| (a.mv, a.reply_sequence.clone()))
-                    .collect();
-
-                let pv_to_send = root_pv.clone();
-
-                // Only send results if we have a meaningful PV or this is depth 1
-                if !pv_to_send.is_empty() || depth == 1 {
-                    let summary = SearchSummary {
-                        depth,
-                        seldepth: refs.search_info.seldepth,
-                        time: elapsed,
-                        cp: eval,
-                        mate: 0,
-                        nodes,
-                        nps: Search::nodes_per_second(nodes, elapsed),
-                        hash_full,
-                        pv: pv_to_send,
-                    };
-
-                    let report = SearchReport::SearchSummary(summary);
-                    let information = Information::Search(report);
-                    refs.report_tx.send(information).expect(ErrFatal::CHANNEL);
+                if refs.search_params.sharp_only {
+                    if let Some((sharp_eval, sharp_pv)) =
+                        Search::search_sharp_move(depth, eval, best_move, refs)
+                    {
+                        eval = sharp_eval;
+                        root_pv = sharp_pv;
+                        best_move = root_pv[0];
+                        refs.thread_local_data.update_best_move(best_move);
+                    }
                 }
 
-                // Enhanced sharp move logging
-                if !refs.search_info.root_analysis.is_empty() {
-                    // Check if the best move is a sharp line
-                    let best_move_analysis = refs.search_info.root_analysis
-                        .iter()
-                        .find(|a| a.mv == best_move);
-                    
-                    if let Some(best_analysis) = best_move_analysis {
-                        if best_analysis.good_replies == 1 && !best_analysis.reply_sequence.is_empty() {
-                            // The best move is a sharp line - log it with top alternatives
-                            let mut sorted_analysis = refs.search_info.root_analysis.clone();
-                            sorted_analysis.sort_by(|a, b| b.eval.cmp(&a.eval));
-                            
-                            let sequence_str = best_analysis.reply_sequence
-                                .iter()
-                                .map(|m| m.as_string())
-                                .collect::<Vec<String>>()
-                                .join(" ");
-                            
-                            let mut msg = format!(
-                                "Sharp line chosen: {} (eval: {}) -> {}", 
-                                best_move.as_string(), 
-                                best_analysis.eval, 
-                                sequence_str
-                            );
-                            
-                            // Add top 3 alternatives (excluding the best move)
-                            let alternatives: Vec<_> = sorted_analysis
-                                .iter()
-                                .filter(|a| a.mv != best_move)
-                                .take(3)
-                                .collect();
-                            
-                            if !alternatives.is_empty() {
-                                msg.push_str(" | Alternatives: ");
-                                let alt_strs: Vec<String> = alternatives
-                                    .iter()
-                                    .map(|a| format!("{} ({})", a.mv.as_string(), a.eval))
-                                    .collect();
-                                msg.push_str(&alt_strs.join(", "));
-                            }
-                            
-                            let report = SearchReport::InfoString(msg);
-                            let information = Information::Search(report);
-                            refs.report_tx.send(information).expect(ErrFatal::CHANNEL);
-                        }
-                    }
-                    
-                    // Also log any other sharp lines that weren't chosen (legacy behaviour)
-                    let other_sharp_lines: Vec<_> = forced_lines
-                        .iter()
-                        .filter(|(mv, _)| *mv != best_move)
-                        .collect();
-                    
-                    if !other_sharp_lines.is_empty() {
-                        let mut parts: Vec<String> = Vec::new();
-                        for (mv, seq) in other_sharp_lines.iter() {
-                            let seq_str = seq
-                                .iter()
-                                .map(|m| m.as_string())
-                                .collect::<Vec<String>>()
-                                .join(" ");
-                            parts.push(format!("{} -> {}", mv.as_string(), seq_str));
-                        }
-                        let msg = format!("Other sharp lines available: {}", parts.join(" | "));
-                        let report = SearchReport::InfoString(msg);
-                        let information = Information::Search(report);
-                        refs.report_tx.send(information).expect(ErrFatal::CHANNEL);
+                let same_best = root_pv
+                    .first()
+                    .is_some_and(|mv| mv.get_move() == previous_best.get_move());
+                if depth > 1 && same_best {
+                    refs.search_info.best_move_stability =
+                        refs.search_info.best_move_stability.saturating_add(1);
+                } else {
+                    refs.search_info.best_move_stability = 0;
+                }
+                if let Some(&mv) = root_pv.first() {
+                    previous_best = mv;
+                }
+
+                // Only the main thread reports to the GUI.
+                if is_main {
+                    Search::report_iteration(depth, eval, re_searches, best_move, &root_pv, refs);
+                }
+
+                // Hard decisions get more time, clear ones less.
+                if base_allocation > 0 && !Search::out_of_time(refs) {
+                    if let Some(gap) = Search::search_second_best_gap(depth, eval, best_move, refs) {
+                        refs.search_info.second_best_gap = Some(gap);
+                        let allocated = Search::quality_based_time_allocation(refs, base_allocation);
+                        refs.search_info.allocated_time = allocated;
+                        refs.search_info.node_budget = Search::node_budget(refs, allocated);
                     }
                 }
 
+                // Publish this iteration's TT entries to the other threads.
+                Search::flush_tt_batch(refs);
+                refs.thread_local_data.completed_depth = depth;
+                refs.thread_local_data.best_score = eval;
+
                 depth += 1;
             }
 
-            let time_up = if is_game_time {
-                refs.search_info.timer_elapsed() > refs.search_info.allocated_time
-            } else {
-                false
-            };
+            let time_up = match refs.search_info.node_budget {
+                _ if !is_game_time => false,
+                Some(budget) => refs.search_info.nodes > budget,
+                None => refs.search_info.timer_elapsed() > refs.search_info.allocated_time,
+            } || Search::should_terminate_early(refs);
 
             // Stop if interrupted or if we failed to complete this iteration meaningfully
             stop = interrupted || time_up || (root_pv.is_empty() && depth > 1);
@@ -229,7 +186,7 @@ impl Search {
         Search::flush_tt_batch(refs);
 
         // Update time statistics
-        if is_game_time {
+        if is_game_time && is_main {
             let time_used = refs.search_info.timer_elapsed();
             // Success is determined by whether we found a valid move, not by time usage
             let success = best_move.get_move() != 0 && !refs.search_info.interrupted();
@@ -243,9 +200,14 @@ impl Search {
         }
 
         // Final fallback: if we still don't have a valid move, generate moves and use the first legal one
+        if best_move.get_move() == 0 && !refs.search_params.search_moves.is_empty() {
+            best_move = refs.search_params.search_moves[0];
+            refs.thread_local_data.update_best_move(best_move);
+        }
+
         if best_move.get_move() == 0 {
-            let mut move_list = crate::movegen::defs::MoveList::new();
-            refs.mg.generate_moves(refs.board, &mut move_list, crate::movegen::defs::MoveType::All);
+            let mut move_list = MoveList::new();
+            refs.mg.generate_moves(refs.board, &mut move_list, MoveType::All);
             
             for i in 0..move_list.len() {
                 let mv = move_list.get_move(i);
@@ -260,4 +222,609 @@ impl Search {
 
         (best_move, refs.search_info.terminate)
     }
+
+    // SharpOnly: the best root move that leaves the opponent exactly one
+    // good reply is played instead of the best move, if it scores within
+    // the sharp margin of it. Apart from the best move's, the scores in the
+    // root analysis are only bounds, so each candidate is searched again on
+    // its own for its exact score. Returns that score and the line.
+    fn search_sharp_move(
+        depth: i8,
+        eval: i16,
+        best_move: Move,
+        refs: &mut SearchRefs,
+    ) -> Option<(i16, Vec<Move>)> {
+        let threshold = eval.saturating_sub(refs.search_params.sharp_margin);
+        let mut analysis: Vec<&RootMoveAnalysis> = refs
+            .search_info
+            .root_analysis
+            .iter()
+            .filter(|a| a.good_replies == 1 && a.eval >= threshold)
+            .collect();
+
+        if analysis.iter().any(|a| a.mv.get_move() == best_move.get_move()) {
+            return None;
+        }
+
+        analysis.sort_by_key(|a| Reverse(a.eval));
+        let candidates: Vec<Move> = analysis.iter().take(SHARP_CANDIDATES).map(|a| a.mv).collect();
+        let root_moves = Search::legal_root_move_list(refs);
+
+        for candidate in candidates {
+            // Searching a single root move works like a MultiPV line: all
+            // other moves are excluded.
+            refs.search_info.root_excluded = root_moves
+                .iter()
+                .filter(|m| m.get_move() != candidate.get_move())
+                .map(|m| m.to_short_move())
+                .collect();
+
+            let mut pv: Vec<Move> = Vec::new();
+            let score = Search::alpha_beta(depth, -INF, INF, &mut pv, refs);
+            refs.search_info.root_excluded.clear();
+
+            if refs.search_info.interrupted() {
+                return None;
+            }
+            if score >= threshold && !pv.is_empty() {
+                return Some((score, pv));
+            }
+        }
+
+        None
+    }
+
+    // Finds how far the best move scores above the second best one. The
+    // other root moves only have upper bounds from the main search, so the
+    // root is searched again without the best move, like the second line
+    // in MultiPV. Its window spans the quality gaps, so a score outside it
+    // still tells the quality. Returns None with fewer than two root moves,
+    // or if the search runs out of time.
+    fn search_second_best_gap(
+        depth: i8,
+        eval: i16,
+        best_move: Move,
+        refs: &mut SearchRefs,
+    ) -> Option<i16> {
+        if Search::legal_root_moves(refs) < 2 {
+            return None;
+        }
+
+        let alpha = eval.saturating_sub(QUALITY_EXCELLENT_GAP);
+        let beta = eval.saturating_sub(QUALITY_POOR_GAP) + 1;
+
+        refs.search_info.root_excluded.push(best_move.to_short_move());
+        let mut pv: Vec<Move> = Vec::new();
+        let score = Search::alpha_beta(depth, alpha, beta, &mut pv, refs);
+        refs.search_info.root_excluded.clear();
+
+        if Search::time_up(refs) {
+            return None;
+        }
+
+        Some(eval.saturating_sub(score))
+    }
+
+    // Reports a completed iteration: the main line, further MultiPV lines,
+    // aspiration re-searches and the sharp moves found at the root.
+    fn report_iteration(
+        depth: i8,
+        eval: i16,
+        re_searches: usize,
+        best_move: Move,
+        root_pv: &[Move],
+        refs: &mut SearchRefs,
+    ) {
+        // Only send results if we have a meaningful PV or this is depth 1
+        if !root_pv.is_empty() || depth == 1 {
+            let pv = Search::reported_pv(root_pv, refs);
+            Search::send_summary(depth, eval, pv, 1, ScoreBound::Exact, refs);
+        }
+
+        // Search and report the next best lines if MultiPV is set.
+        if refs.search_params.multi_pv > 1 && !root_pv.is_empty() {
+            Search::search_extra_pv_lines(depth, root_pv[0], refs);
+        }
+
+        // Report aspiration window re-searches for window tuning
+        if re_searches > 0 {
+            let msg = format!("Aspiration re-searches at depth {depth}: {re_searches}");
+            let report = SearchReport::InfoString(msg);
+            let information = Information::Search(report);
+            refs.report_tx.send(information).expect(ErrFatal::CHANNEL);
+        }
+
+        // Enhanced sharp move logging
+        if !refs.search_info.root_analysis.is_empty() {
+            // Check if the best move is a sharp line
+            let best_move_analysis = refs.search_info.root_analysis
+                .iter()
+                .find(|a| a.mv == best_move);
+            
+            if let Some(best_analysis) = best_move_analysis {
+                if best_analysis.good_replies == 1 && !best_analysis.reply_sequence.is_empty() {
+                    // The best move is a sharp line - log it with top alternatives
+                    let mut sorted_analysis = refs.search_info.root_analysis.clone();
+                    sorted_analysis.sort_by(|a, b| b.eval.cmp(&a.eval));
+                    
+                    let sequence_str = best_analysis.reply_sequence
+                        .iter()
+                        .map(|m| m.as_string())
+                        .collect::<Vec<String>>()
+                        .join(" ");
+                    
+                    let mut msg = format!(
+                        "Sharp line chosen: {} (eval: {}) -> {}", 
+                        best_move.as_string(), 
+                        best_analysis.eval, 
+                        sequence_str
+                    );
+                    
+                    // Add top 3 alternatives (excluding the best move)
+                    let alternatives: Vec<_> = sorted_analysis
+                        .iter()
+                        .filter(|a| a.mv != best_move)
+                        .take(3)
+                        .collect();
+                    
+                    if !alternatives.is_empty() {
+                        msg.push_str(" | Alternatives: ");
+                        let alt_strs: Vec<String> = alternatives
+                            .iter()
+                            .map(|a| format!("{} ({})", a.mv.as_string(), a.eval))
+                            .collect();
+                        msg.push_str(&alt_strs.join(", "));
+                    }
+                    
+                    let report = SearchReport::InfoString(msg);
+                    let information = Information::Search(report);
+                    refs.report_tx.send(information).expect(ErrFatal::CHANNEL);
+                }
+            }
+        }
+
+        // Report every root move leaving the opponent a single good reply
+        // with its forced line, so the GUI shows which moves are forcing.
+        let sharp_moves: Vec<SharpInfo> = refs
+            .search_info
+            .root_analysis
+            .iter()
+            .filter(|a| a.good_replies == 1 && !a.reply_sequence.is_empty())
+            .map(|a| SharpInfo {
+                mv: a.mv,
+                good_replies: a.good_replies,
+                reply_sequence: a.reply_sequence.clone(),
+            })
+            .collect();
+
+        for info in sharp_moves {
+            let information = Information::Search(SearchReport::SharpInfo(info));
+            refs.report_tx.send(information).expect(ErrFatal::CHANNEL);
+        }
+
+        // Summarize how tactical the position is in a single number.
+        if !refs.search_info.root_analysis.is_empty() {
+            let sharpness = Search::position_sharpness(refs);
+            let msg = format!("sharpness {sharpness:.2}");
+            let information = Information::Search(SearchReport::InfoString(msg));
+            refs.report_tx.send(information).expect(ErrFatal::CHANNEL);
+        }
+    }
+
+    // Aspiration window: the first two depths are searched with a full
+    // window. After that, search a narrow window around the previous score
+    // and widen it exponentially on a fail-low or fail-high, re-searching
+    // the same depth. The initial window and its growth are UCI options.
+    // Each failed window is reported as a bound. Helper threads start with
+    // a slightly wider window than the main thread. Returns the score and
+    // the number of re-searches.
+    fn aspiration_search(
+        depth: i8,
+        prev_eval: i16,
+        root_pv: &mut Vec<Move>,
+        refs: &mut SearchRefs,
+    ) -> (i16, usize) {
+        let helper_step = HELPER_ASPIRATION_STEP
+            .saturating_mul(refs.thread_local_data.thread_id.min(16) as i16);
+        let growth = refs.search_params.aspiration_growth as i32;
+        let grow = |window: i16| (window as i32 * growth / 100).min(INF as i32) as i16;
+        let mut window = refs.search_params.aspiration_window + helper_step;
+        let (mut alpha, mut beta) = if depth > 2 {
+            ((prev_eval - window).max(-INF), (prev_eval + window).min(INF))
+        } else {
+            (-INF, INF)
+        };
+        let mut re_searches = 0;
+
+        loop {
+            root_pv.clear();
+            let eval = Search::alpha_beta(depth, alpha, beta, root_pv, refs);
+
+            let full_window = alpha <= -INF && beta >= INF;
+            if refs.search_info.interrupted() || full_window {
+                return (eval, re_searches);
+            }
+
+            let bound = if eval <= alpha {
+                window = grow(window);
+                alpha = alpha.saturating_sub(window).max(-INF);
+                ScoreBound::Upper
+            } else if eval >= beta {
+                window = grow(window);
+                beta = beta.saturating_add(window).min(INF);
+                ScoreBound::Lower
+            } else {
+                return (eval, re_searches);
+            };
+
+            if refs.thread_local_data.is_main_thread() {
+                Search::send_summary(depth, eval, root_pv.clone(), 1, bound, refs);
+            }
+            re_searches += 1;
+        }
+    }
+
+    // The PV collected during the search can be cut short, for example by
+    // TT cutoffs along the line. If the PV walked from the TT starts with
+    // the same move and is longer, that one is reported instead.
+    fn reported_pv(root_pv: &[Move], refs: &mut SearchRefs) -> Vec<Move> {
+        if !refs.tt_enabled || root_pv.is_empty() {
+            return root_pv.to_vec();
+        }
+
+        Search::flush_tt_batch(refs);
+        let tt_pv = Search::extract_pv_from_tt(refs.board, refs.mg, refs.tt, MAX_PLY as usize);
+        let same_start = tt_pv.first().map(|m| m.get_move()) == Some(root_pv[0].get_move());
+
+        if same_start && tt_pv.len() > root_pv.len() {
+            tt_pv
+        } else {
+            root_pv.to_vec()
+        }
+    }
+
+    // Sends the result of one search line to the GUI.
+    fn send_summary(
+        depth: i8,
+        eval: i16,
+        pv: Vec<Move>,
+        multipv: usize,
+        bound: ScoreBound,
+        refs: &mut SearchRefs,
+    ) {
+        let elapsed = refs.search_info.timer_elapsed();
+        let nodes = refs.search_info.nodes;
+        let hash_full = refs.tt.read().expect(ErrFatal::LOCK).hashfull_permille();
+
+        let summary = SearchSummary {
+            depth,
+            seldepth: refs.search_info.seldepth,
+            time: elapsed,
+            cp: eval,
+            mate: Search::moves_to_mate(eval),
+            nodes,
+            nps: Search::nodes_per_second(nodes, elapsed),
+            hash_full,
+            pv,
+            multipv,
+            bound,
+        };
+
+        let report = SearchReport::SearchSummary(summary);
+        let information = Information::Search(report);
+        refs.report_tx.send(information).expect(ErrFatal::CHANNEL);
+    }
+
+    // MultiPV: after the best line at this depth is found, search the root
+    // again with a full window for each further line, skipping the moves
+    // that head the lines found so far. Each line is reported with its rank.
+    fn search_extra_pv_lines(depth: i8, best_move: Move, refs: &mut SearchRefs) {
+        let lines = refs.search_params.multi_pv.min(Search::legal_root_moves(refs));
+
+        refs.search_info.root_excluded.push(best_move.to_short_move());
+
+        for multipv in 2..=lines {
+            let mut pv: Vec<Move> = Vec::new();
+            let eval = Search::alpha_beta(depth, -INF, INF, &mut pv, refs);
+
+            if refs.search_info.interrupted() || pv.is_empty() {
+                break;
+            }
+
+            refs.search_info.root_excluded.push(pv[0].to_short_move());
+            Search::send_summary(depth, eval, pv, multipv, ScoreBound::Exact, refs);
+        }
+
+        refs.search_info.root_excluded.clear();
+    }
+
+    // Counts the legal moves in the root position, or the moves given
+    // with "go searchmoves" (which are legal).
+    fn legal_root_moves(refs: &mut SearchRefs) -> usize {
+        if !refs.search_params.search_moves.is_empty() {
+            return refs.search_params.search_moves.len();
+        }
+
+        Search::legal_root_move_list(refs).len()
+    }
+
+    // Returns the legal moves in the root position.
+    fn legal_root_move_list(refs: &mut SearchRefs) -> Vec<Move> {
+        let mut move_list = MoveList::new();
+        let mut legal_moves = Vec::new();
+
+        refs.mg.generate_moves(refs.board, &mut move_list, MoveType::All);
+        for i in 0..move_list.len() {
+            let mv = move_list.get_move(i);
+            if refs.board.make(mv, refs.mg) {
+                refs.board.unmake();
+                legal_moves.push(mv);
+            }
+        }
+
+        legal_moves
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        board::Board,
+        defs::FEN_START_POSITION,
+        movegen::MoveGenerator,
+        search::{defs::SHARP_MARGIN, test_utils::TestSearch},
+    };
+
+    #[test]
+    fn test_failed_aspiration_window_reports_bound() {
+        let mut test = TestSearch::new(FEN_START_POSITION);
+        test.search_params.quiet = true;
+
+        let mut refs = test.refs();
+
+        // The start position is nowhere near a 500 cp advantage, so a
+        // window around that score fails low.
+        let mut pv = Vec::new();
+        let (_, re_searches) = Search::aspiration_search(3, 500, &mut pv, &mut refs);
+
+        let bounds: Vec<ScoreBound> = test.report_rx
+            .try_iter()
+            .filter_map(|info| match info {
+                Information::Search(SearchReport::SearchSummary(s)) => Some(s.bound),
+                _ => None,
+            })
+            .collect();
+
+        assert!(re_searches > 0);
+        assert_eq!(bounds.len(), re_searches);
+        assert!(bounds.iter().all(|&b| b == ScoreBound::Upper));
+    }
+
+    // Searches the position to the given depth with the given aspiration
+    // window, and adds up the re-searches reported in the info output.
+    fn reported_re_searches(fen: &str, depth: i8, window: i16) -> usize {
+        let mut test = TestSearch::new(fen);
+        test.search_params.search_mode = SearchMode::Depth;
+        test.search_params.depth = depth;
+        test.search_params.aspiration_window = window;
+
+        let mut refs = test.refs();
+
+        Search::iterative_deepening(&mut refs);
+
+        test.report_rx
+            .try_iter()
+            .filter_map(|info| match info {
+                Information::Search(SearchReport::InfoString(msg)) => msg
+                    .strip_prefix("Aspiration re-searches at depth ")
+                    .and_then(|rest| rest.split(": ").nth(1))
+                    .and_then(|n| n.parse::<usize>().ok()),
+                _ => None,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_narrow_aspiration_window_re_searches_more() {
+        let fen = "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1";
+        let narrow = reported_re_searches(fen, 5, 10);
+        let wide = reported_re_searches(fen, 5, 500);
+
+        assert!(narrow > wide, "window 10 re-searched {narrow} times, window 500 {wide} times");
+    }
+
+    #[test]
+    fn test_seldepth_includes_quiescence() {
+        // Pieces on both sides bear on e5, so capture sequences run deep.
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+        let mut test = TestSearch::to_depth(fen, 3);
+
+        let mut refs = test.refs();
+
+        Search::iterative_deepening(&mut refs);
+
+        let summaries = test.summaries();
+
+        assert_eq!(summaries.last().map(|s| s.depth), Some(3));
+        assert!(summaries.iter().all(|s| s.seldepth > s.depth));
+    }
+
+    // Asserts that every move of the line is legal in the position reached
+    // by the moves before it.
+    fn assert_legal_line(fen: &str, line: &[Move], mg: &MoveGenerator) {
+        let mut replay = Board::new();
+        replay.fen_read(Some(fen)).unwrap();
+        for m in line {
+            let mut move_list = MoveList::new();
+            mg.generate_moves(&replay, &mut move_list, MoveType::All);
+            let found = (0..move_list.len()).any(|i| move_list.get_move(i).get_move() == m.get_move());
+            assert!(found, "{} is not a move", m.as_string());
+            assert!(replay.make(*m, mg), "{} is not legal", m.as_string());
+        }
+    }
+
+    #[test]
+    fn test_pv_from_tt_is_legal_from_root() {
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+        let mut test = TestSearch::to_depth(fen, 6);
+
+        let mut refs = test.refs();
+
+        let (best_move, _) = Search::iterative_deepening(&mut refs);
+        Search::flush_tt_batch(&mut refs);
+        let pv = Search::extract_pv_from_tt(refs.board, refs.mg, refs.tt, MAX_PLY as usize);
+
+        // The walk leaves the board as it was.
+        assert_eq!(test.board.to_fen(), fen);
+        assert!(pv.len() > 1);
+        assert_eq!(pv[0].get_move(), best_move.get_move());
+
+        assert_legal_line(fen, &pv, &test.mg);
+
+        // The reported main line is at least as long as the TT walk.
+        let last = test.summaries().pop().unwrap();
+        assert_eq!(last.pv[0].get_move(), pv[0].get_move());
+        assert!(last.pv.len() >= pv.len());
+    }
+
+    // Searches the position to depth 5 and returns the best move, the score
+    // of the last reported line and the number of good replies to the move.
+    fn sharp_only_search(fen: &str, sharp_only: bool) -> (Move, i16, usize) {
+        let mut test = TestSearch::to_depth(fen, 5);
+        test.search_params.sharp_only = sharp_only;
+
+        let mut refs = test.refs();
+
+        let (best_move, _) = Search::iterative_deepening(&mut refs);
+        let good_replies = refs
+            .search_info
+            .root_analysis
+            .iter()
+            .find(|a| a.mv.get_move() == best_move.get_move())
+            .map_or(0, |a| a.good_replies);
+        let cp = test.summaries().last().unwrap().cp;
+
+        (best_move, cp, good_replies)
+    }
+
+    #[test]
+    fn test_sharp_only_prefers_sharp_move() {
+        // The queen has checks that leave the king one good escape, and
+        // checks that leave it two.
+        let fen = "7k/8/8/4n3/8/5Q2/8/K7 w - - 0 1";
+
+        let (best_move, best_cp, best_replies) = sharp_only_search(fen, false);
+        let (sharp_move, sharp_cp, sharp_replies) = sharp_only_search(fen, true);
+
+        // The best move leaves the king more than one escape, so SharpOnly
+        // gives up a little of its score for a check that leaves one.
+        assert_ne!(best_replies, 1);
+        assert_eq!(sharp_replies, 1);
+        assert_ne!(sharp_move.get_move(), best_move.get_move(), "{}", best_move.as_string());
+        assert!(sharp_cp < best_cp && sharp_cp >= best_cp - SHARP_MARGIN, "{sharp_cp} vs {best_cp}");
+    }
+
+    #[test]
+    fn test_sharp_info_lists_forced_sequence() {
+        let fen = "8/7k/8/5n2/3Q4/8/8/K7 w - - 0 1";
+        let mut test = TestSearch::to_depth(fen, 5);
+
+        let mut refs = test.refs();
+
+        Search::iterative_deepening(&mut refs);
+
+        let reports: Vec<SharpInfo> = test
+            .report_rx
+            .try_iter()
+            .filter_map(|info| match info {
+                Information::Search(SearchReport::SharpInfo(s)) => Some(s),
+                _ => None,
+            })
+            .collect();
+        assert!(!reports.is_empty());
+
+        // Each reported line is legal, and starts with the root move.
+        for info in &reports {
+            let line = info.as_string();
+            let moves = line.split(" line ").nth(1).unwrap();
+            assert_eq!(info.good_replies, 1);
+            assert!(moves.starts_with(&info.mv.as_string()), "{line}");
+
+            let played: Vec<Move> =
+                std::iter::once(info.mv).chain(info.reply_sequence.iter().copied()).collect();
+            assert_legal_line(fen, &played, &test.mg);
+        }
+
+        // The last depth reports the forced line of every sharp root move.
+        let sharp = test.search_info.root_analysis.iter().filter(|a| a.good_replies == 1);
+        for a in sharp.filter(|a| !a.reply_sequence.is_empty()) {
+            let listed = reports.iter().any(|info| {
+                let line = info.reply_sequence.iter().map(|m| m.get_move());
+                info.mv.get_move() == a.mv.get_move()
+                    && line.eq(a.reply_sequence.iter().map(|m| m.get_move()))
+            });
+            assert!(listed, "{} is not reported", a.mv.as_string());
+        }
+    }
+
+    // Searches the position to depth 6 with the given sharp depth cap and
+    // returns the length of the longest forced line behind a root move.
+    fn longest_sharp_line(fen: &str, sharp_depth_cap: i8) -> usize {
+        let mut test = TestSearch::to_depth(fen, 6);
+        test.search_params.sharp_depth_cap = sharp_depth_cap;
+        // With recaptures extended, Qg6 is seen to mate in two within the
+        // search depth, after which every root move looks the same.
+        test.search_params.recapture_extension = false;
+
+        let mut refs = test.refs();
+
+        Search::iterative_deepening(&mut refs);
+        refs.search_info
+            .root_analysis
+            .iter()
+            .map(|a| a.reply_sequence.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_sharp_depth_cap_lengthens_forced_lines() {
+        // White's pieces bear on the black king, so some lines stay forced
+        // for more than one reply.
+        let fen = "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1";
+
+        let short = longest_sharp_line(fen, 3);
+        let long = longest_sharp_line(fen, 5);
+
+        assert!(long > short, "cap 5 gave {long} moves, cap 3 gave {short}");
+    }
+
+    // Searches the position to depth 5 and returns its sharpness.
+    fn sharpness_after_search(fen: &str) -> f64 {
+        let mut test = TestSearch::to_depth(fen, 5);
+        // See longest_sharp_line(): keep the tactical position unresolved.
+        test.search_params.recapture_extension = false;
+
+        let mut refs = test.refs();
+
+        Search::iterative_deepening(&mut refs);
+        Search::position_sharpness(&refs)
+    }
+
+    #[test]
+    fn test_sharpness_contrasts_quiet_and_tactical() {
+        // In the start position no move forces anything. In the
+        // middlegame, White's pieces bear on the black king, and many
+        // moves leave Black a single defence.
+        let quiet = sharpness_after_search(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        );
+        let tactical = sharpness_after_search(
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1",
+        );
+
+        assert!(quiet < 0.1, "quiet position scored {quiet}");
+        assert!(tactical > 0.2, "tactical position scored {tactical}");
+    }
 }
@@ -22,19 +22,30 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 use super::{
-    defs::{SearchTerminate, CHECK_TERMINATION, SEND_STATS},
+    defs::{SearchTerminate, CHECKMATE, CHECK_TERMINATION, SEND_STATS},
     Search, SearchRefs,
 };
 use crate::{
+    board::defs::Pieces,
     defs::MAX_PLY,
-    evaluation,
+    evaluation::{self, defs::exchange_value},
     movegen::defs::{Move, MoveList, MoveType, ShortMove},
 };
 
 impl Search {
-    pub fn quiescence(mut alpha: i16, beta: i16, pv: &mut Vec<Move>, refs: &mut SearchRefs) -> i16 {
-        // We created a new node which we'll search, so count it.
+    // Quiescence search. The qs_ply counts the plies since the main search
+    // ended; the first one also tries quiet moves that give check.
+    pub fn quiescence(
+        mut alpha: i16,
+        beta: i16,
+        qs_ply: i8,
+        pv: &mut Vec<Move>,
+        refs: &mut SearchRefs,
+    ) -> i16 {
+        // We created a new node which we'll search, so count it, and
+        // update seldepth if we're searching deeper than requested.
         refs.search_info.nodes += 1;
+        refs.search_info.seldepth = refs.search_info.seldepth.max(refs.search_info.ply);
 
         // No intermediate stats updates if quiet.
         let quiet = refs.search_params.quiet;
@@ -54,20 +65,30 @@ impl Search {
             return evaluation::evaluate_position(refs.board, refs.mg);
         }
 
+        // When in check, we can't stand pat: all evasions must be tried.
+        let is_check = refs.mg.square_attacked(
+            refs.board,
+            refs.board.opponent(),
+            refs.board.king_square(refs.board.us()),
+        );
+
         // Do a stand-pat here: Check how we're doing, even before we make
         // a move. If the evaluation score is larger than beta, then we're
         // already so bad we don't need to search any further. Just return
         // the beta score.
-        let eval_score = evaluation::evaluate_position(refs.board, refs.mg);
-        if eval_score >= beta {
-            return beta;
-        }
+        let mut stand_pat = -CHECKMATE;
+        if !is_check {
+            stand_pat = evaluation::evaluate_position(refs.board, refs.mg);
+            if stand_pat >= beta {
+                return beta;
+            }
 
-        // If the evaluation score is bigger than alpha, then we can
-        // improve our position. So set alpha to this score and keep
-        // searching until there are no more captures.
-        if eval_score > alpha {
-            alpha = eval_score
+            // If the evaluation score is bigger than alpha, then we can
+            // improve our position. So set alpha to this score and keep
+            // searching until there are no more captures.
+            if stand_pat > alpha {
+                alpha = stand_pat
+            }
         }
 
         // Stand-pat is done. Start searching the captures in our position.
@@ -76,10 +97,11 @@ impl Search {
         // the recursion, or until there are no more captures available.
         // Then the function will return after looping the move list.
 
-        // Generate only capture moves.
+        // Generate only captures and promotions, or all evasions when in check.
         let mut move_list = MoveList::new();
-        let mtc = MoveType::Capture;
+        let mtc = if is_check { MoveType::All } else { MoveType::Capture };
         refs.mg.generate_moves(refs.board, &mut move_list, mtc);
+        let mut legal_moves_found = 0;
 
         // Do move scoring, so the best move will be searched first.
         Search::score_moves(&mut move_list, ShortMove::new(0), refs);
@@ -96,6 +118,31 @@ impl Search {
             Search::pick_move(&mut move_list, i);
 
             let current_move = move_list.get_move(i);
+
+            // Delta pruning: skip the capture if even winning the captured
+            // piece (plus a safety margin) can't raise the score to alpha.
+            // Promotions are never delta-pruned.
+            if !is_check && current_move.promoted() == Pieces::NONE {
+                let captured = if current_move.en_passant() {
+                    Pieces::PAWN
+                } else {
+                    current_move.captured()
+                };
+
+                if stand_pat + exchange_value(captured) + refs.search_params.tunables.delta_margin <= alpha {
+                    continue;
+                }
+            }
+
+            // SEE pruning: skip captures that lose material in the exchange
+            // on the target square. Never prune evasions when in check.
+            if refs.search_params.see_pruning
+                && !is_check
+                && Search::see(refs.board, refs.mg, current_move) < 0
+            {
+                continue;
+            }
+
             let is_legal = refs.board.make(current_move, refs.mg);
 
             // If not legal, skip the move and the rest of the function.
@@ -103,44 +150,171 @@ impl Search {
                 continue;
             }
 
-            // Move is legal; increase the ply count.
-            refs.search_info.ply += 1;
+            legal_moves_found += 1;
 
-            // Update seldepth if we're searching deeper than requested.
-            if refs.search_info.ply > refs.search_info.seldepth {
-                refs.search_info.seldepth = refs.search_info.ply;
+            if Search::quiescence_move(current_move, &mut alpha, beta, qs_ply, pv, refs) {
+                return beta;
             }
+        }
 
-            // Create a PV for this node.
-            let mut node_pv: Vec<Move> = Vec::new();
+        // At the first ply, also try quiet moves that give check, so a
+        // check winning material (such as a fork) is not missed at the
+        // horizon. Deeper plies don't, to keep the search from exploding.
+        if qs_ply == 0 && !is_check {
+            let mut check_list = MoveList::new();
+            refs.mg.generate_moves(refs.board, &mut check_list, MoveType::Quiet);
 
-            // The position is not yet quiet. Go one ply deeper.
-            let eval_score = -Search::quiescence(-beta, -alpha, &mut node_pv, refs);
+            for i in 0..check_list.len() {
+                let current_move = check_list.get_move(i);
 
-            // Take back the move, and decrease ply accordingly.
-            refs.board.unmake();
-            refs.search_info.ply -= 1;
+                // Don't try checks that just lose the checking piece.
+                if refs.search_params.see_pruning && Search::see(refs.board, refs.mg, current_move) < 0 {
+                    continue;
+                }
 
-            // If we are worse than beta (the opponent), then stop
-            // searching, because we can't improve anymore.
-            if eval_score >= beta {
-                return beta;
-            }
+                if !refs.board.make(current_move, refs.mg) {
+                    continue;
+                }
 
-            // We found a better move for us.
-            if eval_score > alpha {
-                // Save our better evaluation score.
-                alpha = eval_score;
+                let gives_check = refs.mg.square_attacked(
+                    refs.board,
+                    refs.board.opponent(),
+                    refs.board.king_square(refs.board.us()),
+                );
+                if !gives_check {
+                    refs.board.unmake();
+                    continue;
+                }
 
-                // Update the Principal Variation.
-                pv.clear();
-                pv.push(current_move);
-                pv.append(&mut node_pv);
+                if Search::quiescence_move(current_move, &mut alpha, beta, qs_ply, pv, refs) {
+                    return beta;
+                }
             }
         }
 
+        // In check without any legal evasion: we have been checkmated.
+        if is_check && legal_moves_found == 0 {
+            return -CHECKMATE + refs.search_info.ply as i16;
+        }
+
         // We have traversed the entire move list and found the best score for us,
         // so we return this.
         alpha
     }
+
+    // Searches a move that has already been made on the board, and takes
+    // it back. Raises alpha and updates the PV if the move is better.
+    // Returns true on a beta cutoff.
+    fn quiescence_move(
+        current_move: Move,
+        alpha: &mut i16,
+        beta: i16,
+        qs_ply: i8,
+        pv: &mut Vec<Move>,
+        refs: &mut SearchRefs,
+    ) -> bool {
+        // Move is legal; increase the ply count.
+        refs.search_info.ply += 1;
+
+        // Create a PV for this node.
+        let mut node_pv: Vec<Move> = Vec::new();
+
+        // The position is not yet quiet. Go one ply deeper.
+        let eval_score = -Search::quiescence(-beta, -*alpha, qs_ply + 1, &mut node_pv, refs);
+
+        // Take back the move, and decrease ply accordingly.
+        refs.board.unmake();
+        refs.search_info.ply -= 1;
+
+        // If we are worse than beta (the opponent), then stop
+        // searching, because we can't improve anymore.
+        if eval_score >= beta {
+            return true;
+        }
+
+        // We found a better move for us.
+        if eval_score > *alpha {
+            // Save our better evaluation score.
+            *alpha = eval_score;
+
+            // Update the Principal Variation.
+            pv.clear();
+            pv.push(current_move);
+            pv.append(&mut node_pv);
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{defs::INF, test_utils::TestSearch};
+
+    // Runs quiescence search on the position and returns the node count.
+    fn quiescence_nodes(fen: &str, alpha: i16, beta: i16, see_pruning: bool) -> usize {
+        let mut test = TestSearch::new(fen).without_tt();
+        test.search_params.see_pruning = see_pruning;
+
+        let mut refs = test.refs();
+
+        let mut pv = Vec::new();
+        Search::quiescence(alpha, beta, 0, &mut pv, &mut refs);
+        refs.search_info.nodes
+    }
+
+    #[test]
+    fn see_pruning_skips_losing_captures() {
+        // The queen and rooks can each take a pawn, but all of them are defended.
+        let fen = "7k/1p3p2/2p1p3/3p4/8/8/8/2RQR2K w - - 0 1";
+        let pruned = quiescence_nodes(fen, -INF, INF, true);
+        let unpruned = quiescence_nodes(fen, -INF, INF, false);
+
+        assert!(pruned * 2 < unpruned, "SEE pruning should search far fewer nodes");
+    }
+
+    #[test]
+    fn delta_pruning_skips_hopeless_captures() {
+        // Winning a free pawn can't lift a rook endgame to alpha = 2000.
+        let fen = "4k3/8/8/3p4/8/8/8/3RK3 w - - 0 1";
+        assert_eq!(quiescence_nodes(fen, 2_000, 2_001, true), 1);
+
+        // With alpha in reach, the capture is searched.
+        assert!(quiescence_nodes(fen, -INF, INF, true) > 1);
+    }
+
+    #[test]
+    fn delta_pruning_never_skips_promotions() {
+        // Capturing the knight on a8 also promotes the pawn.
+        let fen = "n3k3/1P6/8/8/8/8/8/4K3 w - - 0 1";
+        assert!(quiescence_nodes(fen, 2_000, 2_001, true) > 1);
+    }
+
+    #[test]
+    fn quiescence_tries_evasions_when_in_check() {
+        // Back rank mate: in check without evasions, quiescence must see the mate.
+        let mut test = TestSearch::new("3R2k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").without_tt();
+
+        let mut refs = test.refs();
+
+        let mut pv = Vec::new();
+        let score = Search::quiescence(-INF, INF, 0, &mut pv, &mut refs);
+        assert_eq!(score, -CHECKMATE);
+    }
+
+    #[test]
+    fn quiescence_finds_quiet_checking_fork() {
+        // Nc7+ forks the king and the rook on a8. It isn't a capture, so
+        // only the quiet checks at the first ply find it.
+        let mut test = TestSearch::new("r3k3/8/8/1N6/8/8/8/6K1 w - - 0 1").without_tt();
+
+        let mut refs = test.refs();
+
+        let mut pv = Vec::new();
+        let score = Search::quiescence(-INF, INF, 0, &mut pv, &mut refs);
+        assert!(score > 0, "the fork wins the rook, got {score}");
+        assert_eq!(pv[0].as_string(), "b5c7");
+        assert_eq!(pv[2].as_string(), "c7a8");
+    }
 }
@@ -0,0 +1,131 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// Static Exchange Evaluation (SEE) for moves. Plain captures go to
+// Board::see(); en passant and promotions set up the first capture here,
+// and the exchange is resolved by Board::see_exchange().
+
+use super::Search;
+use crate::{
+    board::{
+        defs::{Pieces, BB_SQUARES},
+        Board,
+    },
+    defs::Sides,
+    evaluation::defs::exchange_value,
+    movegen::{defs::Move, MoveGenerator},
+};
+
+impl Search {
+    // Resolves the sequence of captures on the target square of the given
+    // move, with both sides always recapturing with their least valuable
+    // attacker and being allowed to stop when it doesn't pay off. Returns
+    // the material balance of the exchange in centipawns for the side
+    // making the move: positive if it wins material, negative if it loses.
+    pub fn see(board: &Board, mg: &MoveGenerator, m: Move) -> i16 {
+        if !m.en_passant() && m.promoted() == Pieces::NONE {
+            return board.see(m.from(), m.to(), mg);
+        }
+
+        let to = m.to();
+        let mut occupancy = board.occupancy();
+
+        // The piece standing on the target square after the move, and
+        // thus the next piece to be captured.
+        let mut on_square = m.piece();
+
+        // En passant captures a pawn that is not on the target square.
+        let captured = if m.en_passant() {
+            let ep_square = if board.us() == Sides::WHITE { to - 8 } else { to + 8 };
+            occupancy &= !BB_SQUARES[ep_square];
+            Pieces::PAWN
+        } else {
+            m.captured()
+        };
+
+        let mut gain = exchange_value(captured);
+        if m.promoted() != Pieces::NONE {
+            gain += exchange_value(m.promoted()) - exchange_value(Pieces::PAWN);
+            on_square = m.promoted();
+        }
+
+        board.see_exchange(m.from(), to, gain, on_square, occupancy, mg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        defs::Square,
+        evaluation::defs::PIECE_VALUES,
+        movegen::defs::{MoveList, MoveType},
+    };
+
+    // Finds the move from/to the given squares in the position.
+    fn find_move(board: &Board, mg: &MoveGenerator, from: Square, to: Square) -> Move {
+        let mut ml = MoveList::new();
+        mg.generate_moves(board, &mut ml, MoveType::All);
+        (0..ml.len())
+            .map(|i| ml.get_move(i))
+            .find(|m| m.from() == from && m.to() == to)
+            .expect("move should be generated")
+    }
+
+    fn see_of(fen: &str, from: Square, to: Square) -> i16 {
+        let mut board = Board::new();
+        let mg = MoveGenerator::new();
+        board.fen_read(Some(fen)).unwrap();
+        let m = find_move(&board, &mg, from, to);
+        Search::see(&board, &mg, m)
+    }
+
+    #[test]
+    fn see_winning_capture() {
+        // Pawn takes an undefended knight on d5.
+        let see = see_of("4k3/8/8/3n4/4P3/8/8/4K3 w - - 0 1", 28, 35);
+        assert_eq!(see, PIECE_VALUES[Pieces::KNIGHT]);
+    }
+
+    #[test]
+    fn see_equal_trade() {
+        // Knight takes a knight defended by a pawn: knight for knight.
+        let see = see_of("4k3/8/2p5/3n4/8/4N3/8/4K3 w - - 0 1", 20, 35);
+        assert_eq!(see, 0);
+    }
+
+    #[test]
+    fn see_losing_capture() {
+        // Queen takes a pawn defended by a pawn.
+        let see = see_of("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1", 3, 35);
+        assert_eq!(see, PIECE_VALUES[Pieces::PAWN] - PIECE_VALUES[Pieces::QUEEN]);
+    }
+
+    #[test]
+    fn see_xray_recapture() {
+        // Rook takes a pawn defended by a rook, backed up by a second rook
+        // behind the first: the x-ray makes the capture win a pawn.
+        let see = see_of("3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1", 11, 35);
+        assert_eq!(see, PIECE_VALUES[Pieces::PAWN]);
+    }
+}
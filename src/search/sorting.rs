@@ -24,7 +24,7 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 // Move sorting routines.
 
 use super::{
-    defs::{SearchRefs, MAX_KILLER_MOVES},
+    defs::SearchRefs,
     Search,
 };
 use crate::{board::defs::Pieces, defs::NrOf, movegen::defs::MoveList, movegen::defs::ShortMove};
@@ -32,7 +32,7 @@ use crate::{board::defs::Pieces, defs::NrOf, movegen::defs::MoveList, movegen::d
 const MVV_LVA_OFFSET: u32 = u32::MAX - 256;
 const TTMOVE_SORT_VALUE: u32 = 60;
 const KILLER_VALUE: u32 = 10;
-const COUNTER_VALUE: u32 = 15;
+const QUIET_OFFSET: u32 = 256;
 
 // MVV_VLA[victim][attacker]
 pub const MVV_LVA: [[u16; NrOf::PIECE_TYPES + 1]; NrOf::PIECE_TYPES + 1] = [
@@ -51,42 +51,56 @@ impl Search {
             let m = ml.get_mut_move(i);
             let mut value: u32 = 0;
 
-            // Sort order priority is: TT Move first, then captures, then
-            // quiet moves that are in the list of killer moves.
+            // Sort order priority is: TT Move first, then winning and equal
+            // captures, then quiet moves that are in the list of killer
+            // moves, then other quiet moves, and losing captures last.
             if m.get_move() == tt_move.get_move() {
                 value = MVV_LVA_OFFSET + TTMOVE_SORT_VALUE;
             } else if m.captured() != Pieces::NONE {
-                // Order captures higher than MVV_LVA_OFFSET
-                value = MVV_LVA_OFFSET + MVV_LVA[m.captured()][m.piece()] as u32;
+                let mvv_lva = MVV_LVA[m.captured()][m.piece()] as u32;
+                if Search::see(refs.board, refs.mg, *m) >= 0 {
+                    // Order winning captures higher than MVV_LVA_OFFSET
+                    value = MVV_LVA_OFFSET + mvv_lva;
+                } else {
+                    // Order losing captures below QUIET_OFFSET
+                    value = mvv_lva;
+                }
             } else {
                 let ply = refs.search_info.ply as usize;
                 let mut n = 0;
-                while n < MAX_KILLER_MOVES && value == 0 {
+                while n < refs.search_params.killer_slots && value == 0 {
                     let killer = refs.search_info.killer_moves[ply][n];
                     if m.get_move() == killer.get_move() {
-                        // Order killers below MVV_LVA_OFFSET
-                        value = MVV_LVA_OFFSET - ((i as u32 + 1) * KILLER_VALUE);
+                        // Order killers below MVV_LVA_OFFSET, the most
+                        // recent one first.
+                        value = MVV_LVA_OFFSET - ((n as u32 + 1) * KILLER_VALUE);
                     }
                     n += 1;
                 }
-
-                if value == 0 && refs.board.history.len() > 0 {
-                    let prev = refs.board.history.get_ref(refs.board.history.len() - 1).next_move;
-                    let cm = refs.search_info.counter_moves[refs.board.us()][prev.piece()][prev.to()];
-                    if m.get_move() == cm.get_move() {
-                        value = MVV_LVA_OFFSET - ((i as u32 + 1) * COUNTER_VALUE);
-                    }
-                }
             }
 
-            
-            // If still not sorted, try to sort by history heuristic.
+            // If still not sorted, sort by history heuristic, blended with
+            // the counter move history if this move is the counter move to
+            // the opponent's previous move.
             if value == 0 {
+                let us = refs.board.us();
                 let piece = m.piece();
                 let to = m.to();
-                value = refs.search_info.history_heuristic[refs.board.us()][piece][to];
+                let mut counter_score: u32 = 0;
+
+                if let Some(prev) = Search::previous_move(refs.board) {
+                    let cm = refs.search_info.counter_moves[us][prev.piece()][prev.to()];
+                    if m.get_move() == cm.get_move() {
+                        let cmh = refs.search_info.counter_move_history[us][prev.piece()][prev.to()];
+                        counter_score = cmh.max(0) as u32;
+                    }
+                }
+
+                value = QUIET_OFFSET
+                    .saturating_add(refs.search_info.history_heuristic[us][piece][to])
+                    .saturating_add(counter_score);
             }
-            
+
 
             m.set_sort_score(value);
         }
@@ -107,43 +121,23 @@ impl Search {
 mod tests {
     use super::*;
     use crate::{
-        board::Board,
-        engine::defs::{Information, SearchData, TT},
-        movegen::{MoveGenerator, defs::{MoveList, MoveType}},
-        search::defs::{SearchControl, SearchInfo, SearchParams, SearchRefs, ThreadLocalData},
+        defs::FEN_START_POSITION,
+        movegen::defs::{MoveList, MoveType},
+        search::{defs::MAX_HISTORY, test_utils::TestSearch},
     };
-    use crossbeam_channel::unbounded;
-    use std::sync::{Arc, RwLock};
 
     #[test]
     fn history_heuristic_affects_scoring() {
-        let mut board = Board::new();
-        let mg = Arc::new(MoveGenerator::new());
-        let tt: Arc<RwLock<TT<SearchData>>> = Arc::new(RwLock::new(TT::new(0)));
-        let (_ct, crx) = unbounded::<SearchControl>();
-        let (rtx, _rrx) = unbounded::<Information>();
-        let mut sp = SearchParams::new();
-        let mut si = SearchInfo::new();
-
-        board.fen_read(None).unwrap();
+        let mut test = TestSearch::new(FEN_START_POSITION).without_tt();
+
         let mut ml = MoveList::new();
-        mg.generate_moves(&mut board, &mut ml, MoveType::All);
+        test.mg.generate_moves(&test.board, &mut ml, MoveType::All);
 
         assert!(ml.len() > 1);
         let mv0 = ml.get_move(0);
-        let side = board.us();
-
-        let refs = SearchRefs {
-            board: &mut board,
-            mg: &mg,
-            tt: &tt,
-            tt_enabled: false,
-            search_params: &mut sp,
-            search_info: &mut si,
-            control_rx: &crx,
-            report_tx: &rtx,
-            thread_local_data: &mut ThreadLocalData::new(0),
-        };
+        let side = test.board.us();
+
+        let refs = test.refs();
 
         refs.search_info.history_heuristic[side][mv0.piece()][mv0.to()] = 500;
 
@@ -152,4 +146,108 @@ mod tests {
 
         assert_eq!(ml.get_move(0).get_move(), mv0.get_move());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn counter_move_history_orders_reply_earlier() {
+        let mut test = TestSearch::new(FEN_START_POSITION).without_tt();
+
+        // Play 1. e4, so black's replies are preceded by an opponent move.
+        let mut ml = MoveList::new();
+        test.mg.generate_moves(&test.board, &mut ml, MoveType::All);
+        let e4 = (0..ml.len())
+            .map(|i| ml.get_move(i))
+            .find(|m| m.as_string() == "e2e4")
+            .unwrap();
+        assert!(test.board.make(e4, &test.mg));
+
+        let mut ml = MoveList::new();
+        test.mg.generate_moves(&test.board, &mut ml, MoveType::All);
+        let reply = (0..ml.len())
+            .map(|i| ml.get_move(i))
+            .find(|m| m.as_string() == "c7c5")
+            .unwrap();
+        let other = (0..ml.len())
+            .map(|i| ml.get_move(i))
+            .find(|m| m.as_string() == "e7e5")
+            .unwrap();
+        let side = test.board.us();
+
+        let mut refs = test.refs();
+
+        // The other move has a better plain history score...
+        refs.search_info.history_heuristic[side][other.piece()][other.to()] = 50;
+
+        // ...but the reply repeatedly caused cutoffs after 1. e4.
+        for _ in 0..5 {
+            Search::update_counter_move_history(e4, reply, 4, &mut refs);
+            Search::store_counter_move(e4, reply, &mut refs);
+        }
+        assert_eq!(refs.search_info.counter_move_history[side][e4.piece()][e4.to()], 16 * 5);
+
+        Search::score_moves(&mut ml, ShortMove::new(0), &refs);
+        Search::pick_move(&mut ml, 0);
+
+        assert_eq!(ml.get_move(0).get_move(), reply.get_move());
+    }
+
+    #[test]
+    fn history_heuristic_stays_bounded() {
+        let mut test = TestSearch::new(FEN_START_POSITION).without_tt();
+
+        let mut ml = MoveList::new();
+        test.mg.generate_moves(&test.board, &mut ml, MoveType::All);
+        let mv = ml.get_move(0);
+        let side = test.board.us();
+
+        let mut refs = test.refs();
+
+        for _ in 0..100_000 {
+            Search::update_history_heuristic(mv, 100, &mut refs);
+        }
+
+        let score = refs.search_info.history_heuristic[side][mv.piece()][mv.to()];
+        assert!(score <= MAX_HISTORY);
+        assert!(score > MAX_HISTORY / 2);
+
+        Search::age_history_heuristic(&mut refs);
+        let aged = refs.search_info.history_heuristic[side][mv.piece()][mv.to()];
+        assert_eq!(aged, score / 2);
+    }
+
+    // Stores three killers at the root of the start position, and returns
+    // the first three moves picked, in order.
+    fn order_with_killer_slots(slots: usize) -> Vec<String> {
+        let mut test = TestSearch::new(FEN_START_POSITION).without_tt();
+        test.search_params.killer_slots = slots;
+
+        let mut ml = MoveList::new();
+        test.mg.generate_moves(&test.board, &mut ml, MoveType::All);
+        let find = |s: &str| (0..ml.len()).map(|i| ml.get_move(i)).find(|m| m.as_string() == s).unwrap();
+        let killers = [find("a2a3"), find("b2b3"), find("c2c3")];
+
+        let mut refs = test.refs();
+
+        for killer in killers {
+            Search::store_killer_move(killer, &mut refs);
+        }
+
+        Search::score_moves(&mut ml, ShortMove::new(0), &refs);
+        (0..3)
+            .map(|i| {
+                Search::pick_move(&mut ml, i);
+                ml.get_move(i).as_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn third_killer_slot_is_respected_in_ordering() {
+        // With three slots, all killers come first, the most recent one first.
+        assert_eq!(order_with_killer_slots(3), ["c2c3", "b2b3", "a2a3"]);
+
+        // With the default two slots, the oldest killer has been pushed out.
+        let order = order_with_killer_slots(2);
+        assert_eq!(order[..2], ["c2c3", "b2b3"]);
+        assert_ne!(order[2], "a2a3");
+    }
+}
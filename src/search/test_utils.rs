@@ -0,0 +1,124 @@
+/* =======================================================================
+Rustic is a chess playing engine.
+Copyright (C) 2019-2024, Marcel Vanthoor
+https://rustic-chess.org/
+
+Rustic is written in the Rust programming language. It is an original
+work, not derived from any engine that came before it. However, it does
+use a lot of concepts which are well-known and are in use by most if not
+all classical alpha/beta-based chess engines.
+
+Rustic is free software: you can redistribute it and/or modify it under
+the terms of the GNU General Public License version 3 as published by
+the Free Software Foundation.
+
+Rustic is distributed in the hope that it will be useful, but WITHOUT
+ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License
+for more details.
+
+You should have received a copy of the GNU General Public License along
+with this program.  If not, see <http://www.gnu.org/licenses/>.
+======================================================================= */
+
+// Shared setup for the search tests. A TestSearch owns everything a
+// search needs, and hands it out as SearchRefs.
+
+use super::defs::{
+    SearchControl, SearchInfo, SearchMode, SearchParams, SearchRefs, SearchReport, SearchSummary,
+    ThreadLocalData,
+};
+use crate::{
+    board::Board,
+    engine::defs::{Information, SearchData, TT},
+    movegen::MoveGenerator,
+};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::sync::{Arc, RwLock};
+
+// TT size in MB for test searches.
+const TEST_TT_SIZE: usize = 32;
+
+pub struct TestSearch {
+    pub board: Board,
+    pub mg: Arc<MoveGenerator>,
+    pub tt: Arc<RwLock<TT<SearchData>>>,
+    pub tt_enabled: bool,
+    pub search_params: SearchParams,
+    pub search_info: SearchInfo,
+    pub thread_local_data: ThreadLocalData,
+    _control_tx: Sender<SearchControl>,
+    pub control_rx: Receiver<SearchControl>,
+    pub report_tx: Sender<Information>,
+    pub report_rx: Receiver<Information>,
+}
+
+impl TestSearch {
+    // Sets up the position with default search parameters and a TT.
+    pub fn new(fen: &str) -> Self {
+        let mut board = Board::new();
+        board.fen_read(Some(fen)).expect("Valid FEN");
+        let (control_tx, control_rx) = unbounded::<SearchControl>();
+        let (report_tx, report_rx) = unbounded::<Information>();
+
+        Self {
+            board,
+            mg: Arc::new(MoveGenerator::new()),
+            tt: Arc::new(RwLock::new(TT::new(TEST_TT_SIZE))),
+            tt_enabled: true,
+            search_params: SearchParams::new(),
+            search_info: SearchInfo::new(),
+            thread_local_data: ThreadLocalData::new(0),
+            _control_tx: control_tx,
+            control_rx,
+            report_tx,
+            report_rx,
+        }
+    }
+
+    // Sets up a quiet search of the position to the given depth.
+    pub fn to_depth(fen: &str, depth: i8) -> Self {
+        let mut test = Self::new(fen);
+        test.search_params.search_mode = SearchMode::Depth;
+        test.search_params.depth = depth;
+        test.search_params.quiet = true;
+        test
+    }
+
+    // Replaces the TT with one of the given size. Size 0 disables it.
+    pub fn with_tt(mut self, megabytes: usize) -> Self {
+        self.tt = Arc::new(RwLock::new(TT::new(megabytes)));
+        self
+    }
+
+    // Searches without a TT, as the tests of single search parts do.
+    pub fn without_tt(mut self) -> Self {
+        self.tt_enabled = false;
+        self.with_tt(0)
+    }
+
+    pub fn refs(&mut self) -> SearchRefs<'_> {
+        SearchRefs {
+            board: &mut self.board,
+            mg: &self.mg,
+            tt: &self.tt,
+            tt_enabled: self.tt_enabled,
+            search_params: &mut self.search_params,
+            search_info: &mut self.search_info,
+            control_rx: &self.control_rx,
+            report_tx: &self.report_tx,
+            thread_local_data: &mut self.thread_local_data,
+        }
+    }
+
+    // Returns the search summaries reported since the last call.
+    pub fn summaries(&self) -> Vec<SearchSummary> {
+        self.report_rx
+            .try_iter()
+            .filter_map(|info| match info {
+                Information::Search(SearchReport::SearchSummary(s)) => Some(s),
+                _ => None,
+            })
+            .collect()
+    }
+}
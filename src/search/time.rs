@@ -22,15 +22,19 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
 use super::{defs::SearchRefs, Search};
-use crate::defs::Sides;
+use crate::{board::Board, defs::{Side, Sides}};
 use super::defs::{
-    GamePhase, TimeControl, MoveQuality,
-    OPENING_PLY_THRESHOLD, EARLY_MIDDLEGAME_PLY_THRESHOLD, LATE_MIDDLEGAME_PLY_THRESHOLD, ENDGAME_PIECE_THRESHOLD,
-    EMERGENCY_TIME_THRESHOLD, EMERGENCY_MAX_DEPTH, EMERGENCY_TIME_FACTOR
+    GamePhase, GameTime, TimeControl, MoveQuality,
+    OPENING_PLY_THRESHOLD, EARLY_MIDDLEGAME_PLY_THRESHOLD, ENDGAME_PIECE_THRESHOLD,
+    EMERGENCY_TIME_THRESHOLD, EMERGENCY_MAX_DEPTH, EMERGENCY_TIME_FACTOR,
+    STABILITY_ITERATIONS, STABILITY_TIME_FRACTION, BULLET_MAX_TIME, BLITZ_MAX_TIME, RAPID_MAX_TIME,
+    TIME_CONTROL_MOVES, QUALITY_POOR_GAP, QUALITY_ACCEPTABLE_GAP, QUALITY_GOOD_GAP,
+    QUALITY_EXCELLENT_GAP,
 };
 use crate::defs::MAX_PLY;
 
-pub const OVERHEAD: i128 = 50; // msecs
+pub const OVERHEAD: u128 = 50; // msecs, default for the Move Overhead option
+pub const DEFAULT_NPS: usize = 1_000_000; // Assumed speed without any history
 const CRITICAL_TIME: u128 = 1_000; // msecs
 const OK_TIME: u128 = CRITICAL_TIME * 5; // msecs
 
@@ -39,6 +43,8 @@ impl Search {
     pub fn out_of_time(refs: &mut SearchRefs) -> bool {
         let elapsed = refs.search_info.timer_elapsed();
         let allocated = refs.search_info.allocated_time;
+        let gt = &refs.search_params.game_time;
+        let clock = if refs.board.us() == Sides::WHITE { gt.wtime } else { gt.btime };
 
         // Calculate a factor with which it is allowed to overshoot the
         // allocated search time. The more time the engine has, the larger
@@ -50,11 +56,48 @@ impl Search {
             _ => 1.0,                                      // This case shouldn't happen.
         };
 
-        elapsed >= (overshoot_factor * allocated as f64).round() as u128
+        // Whatever the overshoot, never search so long that the move
+        // overhead doesn't fit on the clock anymore.
+        let hard_limit = clock.saturating_sub(refs.search_params.move_overhead);
+
+        // With a node budget, the nodes replace the allocated time. Only
+        // the clock itself is still watched, so the engine can't flag.
+        if let Some(budget) = refs.search_info.node_budget {
+            return refs.search_info.nodes >= budget || elapsed >= hard_limit;
+        }
+
+        let limit = ((overshoot_factor * allocated as f64).round() as u128).min(hard_limit);
+
+        elapsed >= limit
+    }
+
+    // Determine if the search can stop before the allocated time is used
+    // up, because the best move has not changed for several iterations.
+    // Some of the time must have been used, or a move that only looks
+    // good at low depths would be played too easily. When analysing, the
+    // search always uses all of its time.
+    pub fn should_terminate_early(refs: &SearchRefs) -> bool {
+        if !refs.search_params.is_game_time()
+            || refs.search_params.analyse_mode
+            || refs.search_info.best_move_stability < STABILITY_ITERATIONS
+        {
+            return false;
+        }
+
+        match refs.search_info.node_budget {
+            Some(budget) => {
+                refs.search_info.nodes as f64 >= budget as f64 * STABILITY_TIME_FRACTION
+            }
+            None => {
+                let allocated = refs.search_info.allocated_time as f64;
+                refs.search_info.timer_elapsed() as f64 >= allocated * STABILITY_TIME_FRACTION
+            }
+        }
     }
 
     pub fn time_up(refs: &mut SearchRefs) -> bool {
-        Search::out_of_time(refs) || refs.search_info.interrupted()
+        let out_of_time = refs.search_params.is_game_time() && Search::out_of_time(refs);
+        out_of_time || refs.search_info.interrupted()
     }
 
     // Calculates the time the engine allocates for searching a single
@@ -67,13 +110,14 @@ impl Search {
         let clock = if white { gt.wtime } else { gt.btime };
         let increment = if white { gt.winc } else { gt.binc } as i128;
         let base_time = ((clock as f64) / (mtg as f64)).round() as i128;
-        let time_slice = base_time + increment - OVERHEAD;
+        let overhead = refs.search_params.move_overhead as i128;
+        let time_slice = base_time + increment - overhead;
 
         // Make sure we're never sending less than 0 msecs of available time.
         if time_slice > 0 {
             // Just send the calculated slice.
             time_slice as u128
-        } else if (base_time + increment) > (OVERHEAD / 5) {
+        } else if (base_time + increment) > (overhead / 5) {
             // Don't substract GUI lag protection (overhead) if this leads
             // to a negative time allocation.
             (base_time + increment) as u128
@@ -83,9 +127,28 @@ impl Search {
         }
     }
 
+    // Converts the time allocated for a move into a node budget, when the
+    // engine is set to use node-based time. The budget is based on the
+    // search speed measured in earlier moves, so it is reproducible on a
+    // busy machine where the wall-clock speed varies.
+    pub fn node_budget(refs: &SearchRefs, allocated_time: u128) -> Option<usize> {
+        if !refs.search_params.use_nodes_time {
+            return None;
+        }
+
+        let nps = match refs.search_info.time_stats.average_nps {
+            0 => DEFAULT_NPS,
+            x => x,
+        };
+
+        Some((allocated_time * nps as u128 / 1000) as usize)
+    }
+
     // Determine a factor for how much of the available time for a move
     // should actually be used. The idea is to spend more time when there
     // is plenty on the clock and reduce thinking time in critical stages.
+    // In fast games a time loss is more likely, so less is used, and in
+    // slow games more.
     pub fn dynamic_time_factor(refs: &SearchRefs) -> f64 {
         let gt = &refs.search_params.game_time;
         let white = refs.board.us() == Sides::WHITE;
@@ -97,77 +160,76 @@ impl Search {
         let max_clock = 120_000_f64; // cap at two minutes
         let capped = if clock > max_clock { max_clock } else { clock };
 
-        base + (capped / max_clock) * max_add
+        let time_control_factor = match refs.search_info.time_control {
+            TimeControl::Bullet => 0.8,
+            TimeControl::Blitz => 0.9,
+            TimeControl::Rapid => 1.0,
+            TimeControl::Classical => 1.15,
+        };
+
+        (base + (capped / max_clock) * max_add) * time_control_factor
     }
 
-    // Adaptive moves-to-go based on game phase
+    // Estimates the number of moves still to go in the game from its
+    // phase, when the GUI doesn't send it.
     pub fn adaptive_moves_to_go(refs: &SearchRefs) -> usize {
         if let Some(mtg) = refs.search_params.game_time.moves_to_go {
             return mtg;
         }
-        
-        let ply = refs.board.history.len();
-        let piece_count = refs.board.piece_count();
-        
-        // Adaptive estimation based on game phase
-        // This is incredibly basic and needs to be improved, but should be good enough 
-        if ply <= OPENING_PLY_THRESHOLD {
-            40  // Opening: more moves expected
-        } else if ply <= EARLY_MIDDLEGAME_PLY_THRESHOLD {
-            if piece_count >= 20 {
-                30  // Early middlegame
-            } else if piece_count >= 10 {
-                25  // Late middlegame
-            } else {
-                15  // Late game with pieces
-            }
-        } else {
-            if piece_count >= 10 {
-                15  // Late game with pieces
-            } else {
-                10  // Endgame
-            }
+
+        match Search::classify_game_phase(refs.board) {
+            GamePhase::Opening => 40,
+            GamePhase::EarlyMiddlegame => 25,
+            GamePhase::LateMiddlegame => 15,
+            GamePhase::Endgame => 10,
         }
     }
 
-    // Determine game phase based on ply and piece count
-    pub fn determine_game_phase(refs: &SearchRefs) -> GamePhase {
-        let ply = refs.board.history.len();
-        let piece_count = refs.board.piece_count();
-        
-        if ply <= OPENING_PLY_THRESHOLD {
+    // Classifies the phase of the game. Once only a few pieces are left,
+    // the game is in the endgame, whatever the move number. Before that,
+    // the ply decides. It is taken from the move counter instead of the
+    // history, so a position set up from a FEN is classified as well.
+    pub fn classify_game_phase(board: &Board) -> GamePhase {
+        let black_to_move = (board.us() == Sides::BLACK) as usize;
+        let ply = (board.game_state.fullmove_number as usize).saturating_sub(1) * 2 + black_to_move;
+
+        if board.piece_count() <= ENDGAME_PIECE_THRESHOLD {
+            GamePhase::Endgame
+        } else if ply <= OPENING_PLY_THRESHOLD {
             GamePhase::Opening
         } else if ply <= EARLY_MIDDLEGAME_PLY_THRESHOLD {
             GamePhase::EarlyMiddlegame
-        } else if ply <= LATE_MIDDLEGAME_PLY_THRESHOLD {
-            GamePhase::LateMiddlegame
-        } else if piece_count <= ENDGAME_PIECE_THRESHOLD {
-            GamePhase::Endgame
         } else {
             GamePhase::LateMiddlegame
         }
     }
 
-    // Classify time control based on total time available
-    // This does not include increment time so isn't perfect
-    pub fn classify_time_control(refs: &SearchRefs) -> TimeControl {
-        let gt = &refs.search_params.game_time;
-        let white = refs.board.us() == Sides::WHITE;
-        let clock = if white { gt.wtime } else { gt.btime };
-        let increment = if white { gt.winc } else { gt.binc };
-        let total_time = clock + (increment * 40); // Estimate impact of increment by assuming a minimum of 40 moves
-        
-        if total_time < 60 {
-            TimeControl::Bullet     // < 1 minute
-        } else if total_time < 300 {
-            TimeControl::Blitz      // 5 minutes
-        } else if total_time < 1800 {
-            TimeControl::Rapid      // 30 minutes
-        } else {
-            TimeControl::Classical  // > 30 minutes
+    // Classifies the time control from the clock and the increment of the
+    // given side. As for online play, the duration of the game is estimated
+    // as the clock plus the increments of TIME_CONTROL_MOVES moves. This
+    // is meant for the clock at the start of the game.
+    pub fn classify_time_control(game_time: &GameTime, side: Side) -> TimeControl {
+        let white = side == Sides::WHITE;
+        let clock = if white { game_time.wtime } else { game_time.btime };
+        let increment = if white { game_time.winc } else { game_time.binc };
+        let duration = clock + increment * TIME_CONTROL_MOVES;
+
+        match duration {
+            d if d < BULLET_MAX_TIME => TimeControl::Bullet,
+            d if d < BLITZ_MAX_TIME => TimeControl::Blitz,
+            d if d < RAPID_MAX_TIME => TimeControl::Rapid,
+            _ => TimeControl::Classical,
         }
     }
 
+    // The time control of the game. The engine classifies it at the start
+    // of the game; without that, it is classified from the current clock.
+    pub fn game_time_control(refs: &SearchRefs) -> TimeControl {
+        refs.search_params.time_control.unwrap_or_else(|| {
+            Search::classify_time_control(&refs.search_params.game_time, refs.board.us())
+        })
+    }
+
     // Emergency time management
     pub fn emergency_time_management(refs: &mut SearchRefs) -> bool {
         let gt = &refs.search_params.game_time;
@@ -187,36 +249,32 @@ impl Search {
         }
     }
 
-    // Assess move quality based on root analysis
+    // Assesses how clear the choice of the best move is, from how far it
+    // scores above the second best root move in the last completed
+    // iteration. Near-equal top moves are a hard decision, and a best
+    // move far ahead of the others a clear one.
     pub fn assess_move_quality(refs: &SearchRefs) -> MoveQuality {
-        if refs.search_info.root_analysis.is_empty() {
-            return MoveQuality::Acceptable;
+        let info = &refs.search_info;
+
+        // The only legal move.
+        if info.root_analysis.len() == 1 {
+            return MoveQuality::Excellent;
         }
-        
-        let analysis = &refs.search_info.root_analysis;
-        let best_eval = analysis[0].eval;
-        let second_eval = if analysis.len() > 1 { analysis[1].eval } else { best_eval };
-        let eval_diff = (best_eval - second_eval).abs();
-        
-        // Check if we're in check
-        let in_check = refs.board.in_check();
-        
-        match (eval_diff, in_check) {
-            (0..30, false) => MoveQuality::Acceptable,  // Close evaluation
-            (30..100, false) => MoveQuality::Good,      // Clear advantage
-            (100.., false) => MoveQuality::Excellent,   // Large advantage
-            (0..50, true) => MoveQuality::Critical,     // In check, close evaluation
-            (50.., true) => MoveQuality::Poor,          // In check, large difference
-            _ => MoveQuality::Acceptable,
+
+        match info.second_best_gap {
+            None => MoveQuality::Acceptable,
+            Some(gap) if gap < QUALITY_POOR_GAP => MoveQuality::Critical,
+            Some(gap) if gap < QUALITY_ACCEPTABLE_GAP => MoveQuality::Poor,
+            Some(gap) if gap < QUALITY_GOOD_GAP => MoveQuality::Acceptable,
+            Some(gap) if gap < QUALITY_EXCELLENT_GAP => MoveQuality::Good,
+            Some(_) => MoveQuality::Excellent,
         }
     }
 
-    // Quality-based time allocation
-    pub fn quality_based_time_allocation(refs: &SearchRefs) -> u128 {
-        let base_time = Search::calculate_time_slice(refs);
-        let move_quality = Search::assess_move_quality(refs);
-        
-        match move_quality {
+    // Scales the time allocated for the move by the quality of the move
+    // found so far: hard decisions get more time, clear ones less.
+    pub fn quality_based_time_allocation(refs: &SearchRefs, base_time: u128) -> u128 {
+        match Search::assess_move_quality(refs) {
             MoveQuality::Excellent => base_time * 80 / 100,  // 20% less time
             MoveQuality::Good => base_time * 90 / 100,       // 10% less time
             MoveQuality::Acceptable => base_time,            // Normal time
@@ -225,49 +283,34 @@ impl Search {
         }
     }
 
-    // Time control specific allocation
-    pub fn time_control_specific_allocation(refs: &SearchRefs) -> u128 {
-        let base_time = Search::calculate_time_slice(refs);
-        let time_control = Search::classify_time_control(refs);
-        
-        match time_control {
-            TimeControl::Bullet => base_time * 90 / 100,     // 10% less time
-            TimeControl::Blitz => base_time * 95 / 100,      // 5% less time
-            TimeControl::Rapid => base_time,                 // Normal time
-            TimeControl::Classical => base_time * 100 / 100, // 0% more time
-        }
-    }
-
-    // Enhanced time slice calculation with all improvements
+    // Time slice for the move, before the search starts. In emergency
+    // mode, only part of it is used.
     pub fn calculate_enhanced_time_slice(refs: &SearchRefs) -> u128 {
         let base_time = Search::calculate_time_slice(refs);
-        
-        // Apply emergency time management
+
         if refs.search_info.emergency_mode {
-            return (base_time as f64 * EMERGENCY_TIME_FACTOR) as u128;
+            (base_time as f64 * EMERGENCY_TIME_FACTOR) as u128
+        } else {
+            base_time
         }
-        
-        // Apply quality-based allocation
-        let quality_time = Search::quality_based_time_allocation(refs);
-        
-        // Apply time control specific allocation
-        let control_time = Search::time_control_specific_allocation(refs);
-        
-        // Use the more conservative of the two
-        std::cmp::min(quality_time, control_time)
     }
 
     // Update time statistics
     pub fn update_time_statistics(refs: &mut SearchRefs, time_used: u128, success: bool) {
-        let phase = Search::determine_game_phase(refs);
+        let phase = Search::classify_game_phase(refs.board);
         refs.search_info.time_stats.update(time_used, success, phase);
+
+        if time_used > 0 {
+            let nps = Search::nodes_per_second(refs.search_info.nodes, time_used);
+            refs.search_info.time_stats.update_nps(nps);
+        }
     }
 
     // Display time management statistics
     pub fn display_time_statistics(refs: &SearchRefs) -> String {
         let stats = &refs.search_info.time_stats;
-        let phase = Search::determine_game_phase(refs);
-        let time_control = Search::classify_time_control(refs);
+        let phase = Search::classify_game_phase(refs.board);
+        let time_control = refs.search_info.time_control;
         let emergency = refs.search_info.emergency_mode;
         
         format!(
@@ -282,3 +325,150 @@ impl Search {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{defs::SearchMode, test_utils::TestSearch};
+
+    // Sets up the position with both sides on the given clock, and runs f
+    // on the search refs.
+    fn with_clock<T>(fen: &str, time: u128, inc: u128, f: impl FnOnce(&mut SearchRefs) -> T) -> T {
+        let mut test = TestSearch::new(fen).without_tt();
+        test.search_params.search_mode = SearchMode::GameTime;
+        test.search_params.game_time.wtime = time;
+        test.search_params.game_time.btime = time;
+        test.search_params.game_time.winc = inc;
+        test.search_params.game_time.binc = inc;
+
+        let mut refs = test.refs();
+
+        f(&mut refs)
+    }
+
+    fn phase(fen: &str) -> GamePhase {
+        let mut board = Board::new();
+        board.fen_read(Some(fen)).unwrap();
+        Search::classify_game_phase(&board)
+    }
+
+    #[test]
+    fn test_classify_game_phase() {
+        // Move 1, and black's 13th move: ply 25.
+        assert_eq!(
+            phase("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            GamePhase::Opening
+        );
+        assert_eq!(
+            phase("r1bq1rk1/pp2bppp/2n1pn2/3p4/3P4/2NBPN2/PP3PPP/R2Q1RK1 b - - 0 13"),
+            GamePhase::Opening
+        );
+
+        // The same pieces on later moves.
+        assert_eq!(
+            phase("r1bq1rk1/pp2bppp/2n1pn2/3p4/3P4/2NBPN2/PP3PPP/R2Q1RK1 w - - 0 15"),
+            GamePhase::EarlyMiddlegame
+        );
+        assert_eq!(
+            phase("r1bq1rk1/pp2bppp/2n1pn2/3p4/3P4/2NBPN2/PP3PPP/R2Q1RK1 w - - 0 30"),
+            GamePhase::LateMiddlegame
+        );
+
+        // With only a few pieces left, the ply doesn't matter.
+        assert_eq!(phase("8/5pk1/6p1/8/8/6P1/5PK1/8 w - - 0 1"), GamePhase::Endgame);
+        assert_eq!(
+            phase("r3k3/pp3ppp/2n5/8/8/2N5/PP3PPP/R3K3 w - - 0 40"),
+            GamePhase::Endgame
+        );
+    }
+
+    #[test]
+    fn test_time_control_shapes_time_factor() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let classify = |refs: &mut SearchRefs| {
+            refs.search_info.time_control = Search::game_time_control(refs);
+            (refs.search_info.time_control, Search::dynamic_time_factor(refs))
+        };
+
+        let (bullet, bullet_factor) = with_clock(fen, 60_000, 0, classify);
+        let (rapid, rapid_factor) = with_clock(fen, 1_800_000, 0, classify);
+        assert_eq!(bullet, TimeControl::Bullet);
+        assert_eq!(rapid, TimeControl::Rapid);
+        assert!(bullet_factor < rapid_factor);
+
+        // The increment counts: 3+2 is blitz, and 90+30 classical.
+        assert_eq!(with_clock(fen, 180_000, 2_000, classify).0, TimeControl::Blitz);
+        assert_eq!(with_clock(fen, 5_400_000, 30_000, classify).0, TimeControl::Classical);
+
+        // With the same clock, a faster time control uses less of it.
+        let factor = |tc| {
+            with_clock(fen, 60_000, 0, |refs| {
+                refs.search_info.time_control = tc;
+                Search::dynamic_time_factor(refs)
+            })
+        };
+        assert!(factor(TimeControl::Bullet) < factor(TimeControl::Blitz));
+        assert!(factor(TimeControl::Rapid) < factor(TimeControl::Classical));
+    }
+
+    #[test]
+    fn test_time_control_kept_from_game_start() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+        // Ten seconds left on the clock looks like bullet...
+        let now = with_clock(fen, 10_000, 0, |refs| Search::game_time_control(refs));
+        assert_eq!(now, TimeControl::Bullet);
+
+        // ...but late in a 30 minute game, it is still a rapid game.
+        let game_start = GameTime::new(1_800_000, 1_800_000, 0, 0, None);
+        let rapid = Search::classify_time_control(&game_start, Sides::WHITE);
+        let kept = with_clock(fen, 10_000, 0, |refs| {
+            refs.search_params.time_control = Some(rapid);
+            Search::game_time_control(refs)
+        });
+        assert_eq!(kept, TimeControl::Rapid);
+    }
+
+    // Searches the position on a one minute clock, and allocates time for
+    // the move as judged from the last iteration. The time is counted in
+    // nodes at a fixed speed, so the search always ends at the same depth.
+    fn allocation_after_search(fen: &str, base_time: u128) -> (MoveQuality, u128) {
+        let mut test = TestSearch::new(fen);
+        test.search_params.search_mode = SearchMode::GameTime;
+        test.search_params.game_time.wtime = 60_000;
+        test.search_params.game_time.btime = 60_000;
+        test.search_params.quiet = true;
+        test.search_params.use_nodes_time = true;
+        test.search_info.time_stats.average_nps = 50_000;
+
+        let mut refs = test.refs();
+        Search::iterative_deepening(&mut refs);
+
+        (
+            Search::assess_move_quality(&refs),
+            Search::quality_based_time_allocation(&refs, base_time),
+        )
+    }
+
+    #[test]
+    fn test_critical_moves_get_more_time() {
+        // Either knight can take the queen, and both moves score nearly
+        // the same. The back rank mate is far ahead of any other move.
+        let (critical, critical_time) =
+            allocation_after_search("4k3/8/8/3q4/8/2N1N3/8/4K3 w - - 0 1", 1000);
+        let (clear, clear_time) =
+            allocation_after_search("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", 1000);
+
+        assert_eq!(critical, MoveQuality::Critical);
+        assert_eq!(clear, MoveQuality::Excellent);
+        assert!(critical_time > 1000);
+        assert!(clear_time < 1000);
+
+        // Without an iteration to judge from, the time is left alone.
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let before_search = with_clock(fen, 300_000, 0, |refs| {
+            (Search::assess_move_quality(refs), Search::quality_based_time_allocation(refs, 1000))
+        });
+        assert_eq!(before_search, (MoveQuality::Acceptable, 1000));
+    }
+}
@@ -24,19 +24,24 @@ with this program.  If not, see <http://www.gnu.org/licenses/>.
 use super::{
     defs::{
         SearchControl, SearchCurrentMove, SearchMode, SearchRefs, SearchReport, SearchStats,
-        SearchTerminate, MAX_KILLER_MOVES, MIN_TIME_CURR_MOVE, MIN_TIME_STATS,
+        SearchTerminate, CHECKMATE, CHECKMATE_THRESHOLD, DRAW, MAX_HISTORY,
+        MIN_TIME_STATS, SHARPNESS_GAP_SCALE,
     },
     Search,
 };
 use crate::{
-    board::{defs::Pieces, Board},
+    board::{
+        defs::{Pieces, BB_DARK_SQUARES, BB_LIGHT_SQUARES},
+        Board,
+    },
     defs::{Sides, MAX_MOVE_RULE},
-    engine::defs::{ErrFatal, Information},
-    movegen::defs::Move,
+    engine::defs::{ErrFatal, Information, SearchData, TT},
+    movegen::{
+        defs::{Move, MoveList, MoveType, ShortMove},
+        MoveGenerator,
+    },
 };
-
-const DARK_SQUARES: u64 = 0xAA55_AA55_AA55_AA55;
-const LIGHT_SQUARES: u64 = 0x55AA_55AA_55AA_55AA;
+use std::sync::{atomic::Ordering, RwLock};
 
 impl Search {
     // This function calculates the number of nodes per second.
@@ -49,13 +54,47 @@ impl Search {
         nps
     }
 
+    // Converts a mate score into the number of moves to mate. Returns 0 if
+    // the score is not a mate score.
+    pub fn moves_to_mate(eval: i16) -> u8 {
+        if eval.abs() >= CHECKMATE_THRESHOLD && eval.abs() < CHECKMATE {
+            let ply = CHECKMATE - eval.abs();
+            ((ply + 1) / 2) as u8
+        } else {
+            0
+        }
+    }
+
+    // Summarizes how forcing the root position is, from 0.0 to 1.0. Each
+    // root move that leaves the opponent exactly one good reply counts in
+    // proportion to how much worse the second best reply is; the total is
+    // divided by the number of root moves, so moves without any good reply
+    // count as not sharp at all.
+    pub fn position_sharpness(refs: &SearchRefs) -> f64 {
+        let root_analysis = &refs.search_info.root_analysis;
+        if root_analysis.is_empty() {
+            return 0f64;
+        }
+
+        let sharpness: f64 = root_analysis
+            .iter()
+            .filter(|a| a.good_replies == 1)
+            .map(|a| {
+                let gap = a.reply_gap.min(SHARPNESS_GAP_SCALE);
+                gap as f64 / SHARPNESS_GAP_SCALE as f64
+            })
+            .sum();
+
+        sharpness / root_analysis.len() as f64
+    }
+
     // Send intermediate statistics to GUI.
     pub fn send_stats_to_gui(refs: &mut SearchRefs) {
         let elapsed = refs.search_info.timer_elapsed();
         let last_stats = refs.search_info.last_stats_sent;
 
         if elapsed >= last_stats + MIN_TIME_STATS {
-            let hash_full = refs.tt.read().expect(ErrFatal::LOCK).hash_full();
+            let hash_full = refs.tt.read().expect(ErrFatal::LOCK).hashfull_permille();
             let msecs = refs.search_info.timer_elapsed();
             let nps = Search::nodes_per_second(refs.search_info.nodes, msecs);
             let stats = SearchStats::new(msecs, refs.search_info.nodes, nps, hash_full);
@@ -72,7 +111,7 @@ impl Search {
         let elapsed = refs.search_info.timer_elapsed();
         let lcm = refs.search_info.last_curr_move_sent;
 
-        if elapsed >= lcm + MIN_TIME_CURR_MOVE {
+        if elapsed >= lcm + refs.search_params.curr_move_interval {
             let scm = SearchCurrentMove::new(current_move, count);
             let scm_report = SearchReport::SearchCurrentMove(scm);
             let information = Information::Search(scm_report);
@@ -82,6 +121,17 @@ impl Search {
         }
     }
 
+    // Send the time statistics to the engine when asked during a search.
+    // All threads share them, so only the first thread reports.
+    fn report_time_stats(refs: &mut SearchRefs) {
+        if refs.thread_local_data.thread_id == 0 {
+            let stats = refs.search_info.time_stats.clone();
+            let information = Information::Search(SearchReport::TimeStats(stats));
+
+            refs.report_tx.send(information).expect(ErrFatal::CHANNEL);
+        }
+    }
+
     // This function checks termination conditions and sets the termination
     // flag if this is required.
     pub fn check_termination(refs: &mut SearchRefs) {
@@ -90,9 +140,18 @@ impl Search {
         match cmd {
             SearchControl::Stop => refs.search_info.terminate = SearchTerminate::Stop,
             SearchControl::Quit => refs.search_info.terminate = SearchTerminate::Quit,
+            SearchControl::ReportTimeStats => Search::report_time_stats(refs),
             SearchControl::Start(_) | SearchControl::Nothing => (),
         };
 
+        // Stop if another thread of this search has ended it.
+        if refs.thread_local_data.terminated.load(Ordering::Relaxed) {
+            refs.search_info.terminate = SearchTerminate::Stop;
+        }
+
+        // Publish pending TT updates, so the other threads can use them.
+        Search::flush_tt_batch(refs);
+
         // Terminate search if certain conditions are met.
         let search_mode = refs.search_params.search_mode;
         match search_mode {
@@ -130,33 +189,45 @@ impl Search {
             || is_max_move_rule
     }
 
-    // Detects position repetitions in the game's history.
-    pub fn is_repetition(board: &Board) -> u8 {
-        let mut count = 0;
-        let mut stop = false;
-        let mut i = board.history.len() - 1;
-
-        // Search the history list.
-        while i != 0 && !stop {
-            let historic = board.history.get_ref(i);
-
-            // If the historic zobrist key is equal to the one of the board
-            // passed into the function, then we found a repetition.
-            if historic.zobrist_key == board.game_state.zobrist_key {
-                count += 1;
+    // Returns true if the side to move has at least one legal move.
+    pub fn has_legal_move(refs: &mut SearchRefs) -> bool {
+        let mut move_list = MoveList::new();
+        refs.mg.generate_moves(refs.board, &mut move_list, MoveType::All);
+
+        for i in 0..move_list.len() {
+            if refs.board.make(move_list.get_move(i), refs.mg) {
+                refs.board.unmake();
+                return true;
             }
+        }
+        false
+    }
 
-            // If the historic HMC is 0, it indicates that this position
-            // was created by a capture or pawn move. We don't have to
-            // search further back, because before this, we can't ever
-            // repeat. After all, the capture or pawn move can't be
-            // reverted or repeated.
-            stop = historic.halfmove_clock == 0;
+    // Returns the score of a draw for the side to move. With positive
+    // contempt, the side to move at the root thinks a draw is worse than
+    // equal, so it avoids draws; the opponent (at odd plies) sees the same
+    // draw as better than equal. Negative contempt makes the engine seek
+    // draws instead. Analysis wants objective scores, so there a draw is
+    // always just a draw.
+    pub fn draw_score(refs: &SearchRefs) -> i16 {
+        if refs.search_params.analyse_mode {
+            return DRAW;
+        }
 
-            // Search backwards.
-            i -= 1;
+        let contempt = refs.search_params.contempt;
+
+        if refs.search_info.ply % 2 == 0 {
+            DRAW - contempt
+        } else {
+            DRAW + contempt
         }
-        count
+    }
+
+    // Detects position repetitions in the game's history. Returns how
+    // often the current position occurred before: 1 for a twofold and 2
+    // for a threefold repetition.
+    pub fn is_repetition(board: &Board) -> u8 {
+        board.repetition_count()
     }
 
     /// Apply all pending TT updates in batch to reduce lock contention
@@ -195,8 +266,8 @@ impl Search {
         let w_b_bb = refs.board.get_pieces(Pieces::BISHOP, Sides::WHITE);
         let b_b_bb = refs.board.get_pieces(Pieces::BISHOP, Sides::BLACK);
         // ...or two bishops for one side on opposite-colored squares.
-        let w_b = (w_b_bb & DARK_SQUARES != 0) && (w_b_bb & LIGHT_SQUARES != 0);
-        let b_b = (b_b_bb & DARK_SQUARES != 0) && (b_b_bb & LIGHT_SQUARES != 0);
+        let w_b = (w_b_bb & BB_DARK_SQUARES != 0) && (w_b_bb & BB_LIGHT_SQUARES != 0);
+        let b_b = (b_b_bb & BB_DARK_SQUARES != 0) && (b_b_bb & BB_LIGHT_SQUARES != 0);
         // ... or a bishop+knight for at least one side.
         let w_bn =
             refs.board.get_pieces(Pieces::BISHOP, Sides::WHITE).count_ones() > 0 &&
@@ -214,10 +285,12 @@ impl Search {
 // Killer moves and history heuristics.
 impl Search {
     // This function stores a move in the list of killer moves. Normally we
-    // store two killer moves per ply. By checking that the move we want to
-    // store is not the same as the first killer move in the list, we make sure
-    // that both moves are always different. It is possible to store three or
-    // more killer moves, but experience shows that checking for ALL of them to
+    // store two killer moves per ply; the KillerSlots option allows from one
+    // up to MAX_KILLER_MOVES. By checking that the move we want to store is
+    // not the same as the first killer move in the list, we make sure that
+    // the same move is never stored twice in a row. With two slots, both
+    // moves are therefore always different. With more slots, an older
+    // duplicate can remain further down the list: checking ALL of them to
     // be unique costs more time than the extra killer moves could save.
     pub fn store_killer_move(current_move: Move, refs: &mut SearchRefs) {
         const FIRST: usize = 0;
@@ -227,7 +300,7 @@ impl Search {
         // First killer must not be the same as the move being stored.
         if first_killer.get_move() != current_move.get_move() {
             // Shift all the moves one index upward...
-            for i in (1..MAX_KILLER_MOVES).rev() {
+            for i in (1..refs.search_params.killer_slots).rev() {
                 let n = i;
                 let previous = refs.search_info.killer_moves[ply][n - 1];
                 refs.search_info.killer_moves[ply][n] = previous;
@@ -238,13 +311,25 @@ impl Search {
         }
     }
 
+    // Adds a depth based bonus to the history score of a quiet move. The
+    // bonus is scaled down by how close the entry already is to MAX_HISTORY
+    // ("history gravity"), so the score approaches the maximum but never
+    // exceeds it.
     pub fn update_history_heuristic(current_move: Move, depth: i8, refs: &mut SearchRefs) {
         let side = refs.board.us();
         let piece = current_move.piece();
         let to = current_move.to();
-        let inc = (depth as u32).saturating_mul(depth as u32);
+        let bonus = (depth as u32 * depth as u32).min(MAX_HISTORY);
         let entry = &mut refs.search_info.history_heuristic[side][piece][to];
-        *entry = entry.saturating_add(inc);
+        *entry += bonus - *entry * bonus / MAX_HISTORY;
+    }
+
+    // Halves all history scores, so moves that were good in earlier
+    // iterations slowly lose their priority. Called once per iteration.
+    pub fn age_history_heuristic(refs: &mut SearchRefs) {
+        for entry in refs.search_info.history_heuristic.iter_mut().flatten().flatten() {
+            *entry /= 2;
+        }
     }
 
     pub fn store_counter_move(prev: Move, reply: Move, refs: &mut SearchRefs) {
@@ -253,4 +338,221 @@ impl Search {
         let to = prev.to();
         refs.search_info.counter_moves[side][piece][to] = reply.to_short_move();
     }
+
+    // Grows the counter move history score for the reply to the previous
+    // move. If the reply is not the counter move currently stored for the
+    // previous move, it starts over. Call this before store_counter_move().
+    pub fn update_counter_move_history(prev: Move, reply: Move, depth: i8, refs: &mut SearchRefs) {
+        let side = refs.board.us();
+        let piece = prev.piece();
+        let to = prev.to();
+        let bonus = (depth as i32) * (depth as i32);
+        let counter_move = refs.search_info.counter_moves[side][piece][to];
+        let entry = &mut refs.search_info.counter_move_history[side][piece][to];
+
+        if counter_move.get_move() == reply.to_short_move().get_move() {
+            *entry = entry.saturating_add(bonus);
+        } else {
+            *entry = bonus;
+        }
+    }
+
+    // Returns the move that led to the current position, if there is one.
+    // A null move is not a real move, so it does not count.
+    pub fn previous_move(board: &Board) -> Option<Move> {
+        if board.history.is_empty() {
+            return None;
+        }
+
+        let prev = board.history.get_ref(board.history.len() - 1).next_move;
+        if prev.get_move() == 0 {
+            None
+        } else {
+            Some(prev)
+        }
+    }
+
+    // Walks the best moves stored in the TT from the current position, and
+    // returns them as the principal variation. The walk stops at a position
+    // without a TT move, at a TT move that is not legal (which can happen
+    // after a key collision), when a position repeats, or after max_len
+    // moves. The board is returned in the position it was given in.
+    pub fn extract_pv_from_tt(
+        board: &mut Board,
+        mg: &MoveGenerator,
+        tt: &RwLock<TT<SearchData>>,
+        max_len: usize,
+    ) -> Vec<Move> {
+        let mut pv: Vec<Move> = Vec::new();
+        let mut keys = vec![board.game_state.zobrist_key];
+
+        while pv.len() < max_len {
+            let tt_move = match tt.read().expect(ErrFatal::LOCK).probe(board.game_state.zobrist_key) {
+                Some(data) if data.best_move().get_move() != 0 => data.best_move(),
+                _ => break,
+            };
+
+            match Search::legal_move_from_short(board, mg, tt_move) {
+                Some(m) => {
+                    board.make(m, mg);
+                    pv.push(m);
+                }
+                None => break,
+            }
+
+            if keys.contains(&board.game_state.zobrist_key) {
+                break;
+            }
+            keys.push(board.game_state.zobrist_key);
+        }
+
+        for _ in 0..pv.len() {
+            board.unmake();
+        }
+
+        pv
+    }
+
+    // Returns the expected reply to the best move, for the GUI to ponder
+    // on: the second move of the principal variation in the TT.
+    pub fn ponder_move(
+        board: &mut Board,
+        mg: &MoveGenerator,
+        tt: &RwLock<TT<SearchData>>,
+        best_move: Move,
+    ) -> Option<Move> {
+        if best_move.get_move() == 0 || !board.make(best_move, mg) {
+            return None;
+        }
+
+        let reply = Search::extract_pv_from_tt(board, mg, tt, 1).first().copied();
+        board.unmake();
+
+        reply
+    }
+
+    // Returns the legal move in the position that matches the short move.
+    fn legal_move_from_short(board: &mut Board, mg: &MoveGenerator, short_move: ShortMove) -> Option<Move> {
+        let mut move_list = MoveList::new();
+        mg.generate_moves(board, &mut move_list, MoveType::All);
+
+        (0..move_list.len())
+            .map(|i| move_list.get_move(i))
+            .filter(|m| m.to_short_move().get_move() == short_move.get_move())
+            .find(|&m| {
+                let legal = board.make(m, mg);
+                if legal {
+                    board.unmake();
+                }
+                legal
+            })
+    }
+
+    // Updates the move ordering tables after a quiet move caused a beta
+    // cutoff: killer moves, history heuristic, and counter moves.
+    pub fn update_quiet_cutoff(current_move: Move, depth: i8, refs: &mut SearchRefs) {
+        Search::store_killer_move(current_move, refs);
+        Search::update_history_heuristic(current_move, depth, refs);
+
+        if let Some(prev) = Search::previous_move(refs.board) {
+            Search::update_counter_move_history(prev, current_move, depth, refs);
+            Search::store_counter_move(prev, current_move, refs);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        defs::FEN_START_POSITION,
+        movegen::MoveGenerator,
+        search::{defs::RootMoveAnalysis, test_utils::TestSearch},
+    };
+
+    // Plays the moves, given in long algebraic notation, on the board.
+    fn play(board: &mut Board, mg: &MoveGenerator, moves: &[&str]) {
+        for m in moves {
+            let mut move_list = MoveList::new();
+            mg.generate_moves(board, &mut move_list, MoveType::All);
+            let mv = (0..move_list.len())
+                .map(|i| move_list.get_move(i))
+                .find(|x| x.as_string() == *m)
+                .unwrap();
+            assert!(board.make(mv, mg));
+        }
+    }
+
+    const KNIGHT_SHUFFLE: [&str; 4] = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+    #[test]
+    fn test_repetition_twofold() {
+        let mut board = Board::new();
+        board.fen_read(None).unwrap();
+        let mg = MoveGenerator::new();
+
+        play(&mut board, &mg, &KNIGHT_SHUFFLE[..2]);
+        assert_eq!(Search::is_repetition(&board), 0);
+
+        play(&mut board, &mg, &KNIGHT_SHUFFLE[2..]);
+        assert_eq!(Search::is_repetition(&board), 1);
+
+        // The search threads see the game's positions as well.
+        assert_eq!(Search::is_repetition(&board.clone_for_search()), 1);
+    }
+
+    #[test]
+    fn test_repetition_threefold() {
+        let mut board = Board::new();
+        board.fen_read(None).unwrap();
+        let mg = MoveGenerator::new();
+
+        play(&mut board, &mg, &KNIGHT_SHUFFLE);
+        play(&mut board, &mg, &KNIGHT_SHUFFLE);
+
+        assert_eq!(Search::is_repetition(&board), 2);
+    }
+
+    #[test]
+    fn test_repetition_resets_after_pawn_move() {
+        let mut board = Board::new();
+        board.fen_read(None).unwrap();
+        let mg = MoveGenerator::new();
+
+        play(&mut board, &mg, &KNIGHT_SHUFFLE);
+        play(&mut board, &mg, &["e2e3", "e7e6"]);
+        assert_eq!(Search::is_repetition(&board), 0);
+
+        // The positions before the pawn moves can't come back, so this is
+        // the first repetition, not the second.
+        play(&mut board, &mg, &KNIGHT_SHUFFLE);
+        assert_eq!(Search::is_repetition(&board), 1);
+    }
+
+    fn analysis(good_replies: usize, reply_gap: i16) -> RootMoveAnalysis {
+        RootMoveAnalysis {
+            mv: Move::new(0),
+            eval: 0,
+            good_replies,
+            reply: None,
+            reply_gap,
+            our_forced: false,
+            reply_sequence: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_sharpness_counts_moves_without_good_replies() {
+        let mut test = TestSearch::new(FEN_START_POSITION);
+        test.search_info.root_analysis = vec![
+            analysis(1, SHARPNESS_GAP_SCALE / 2),
+            analysis(1, SHARPNESS_GAP_SCALE * 2),
+            analysis(3, SHARPNESS_GAP_SCALE),
+            analysis(0, 0),
+        ];
+
+        // Half sharp and fully sharp, over four root moves.
+        let refs = test.refs();
+        assert_eq!(Search::position_sharpness(&refs), 1.5 / 4.0);
+    }
 }